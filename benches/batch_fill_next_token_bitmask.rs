@@ -0,0 +1,112 @@
+//! Measures `BatchGrammarMatcher::batch_fill_next_token_bitmask` at batch size 32 with a
+//! synthetic 128k-token vocabulary, serial (`max_threads = 1`) versus the thread pool.
+//!
+//! Run with `cargo bench --bench batch_fill_next_token_bitmask`.
+
+use std::time::Instant;
+
+use xgrammar::{
+    BatchGrammarMatcher, DLDataType, DLDataTypeCode, DLDevice, DLDeviceType,
+    DLTensor, GrammarCompiler, GrammarMatcher, TokenizerInfo, VocabType,
+    allocate_token_bitmask, c_void, get_bitmask_shape,
+};
+
+const BATCH_SIZE: usize = 32;
+const VOCAB_SIZE: usize = 128_000;
+const ITERATIONS: usize = 20;
+
+fn synthetic_vocab() -> Vec<String> {
+    let pieces = [
+        "{", "}", "[", "]", ":", ",", "\"", " ", "true", "false", "null", "0",
+        "1", "-", ".", "e",
+    ];
+    let mut vocab: Vec<String> = vec!["</s>".to_string()];
+    vocab.extend(pieces.iter().map(|s| s.to_string()));
+    let mut i = 0usize;
+    while vocab.len() < VOCAB_SIZE {
+        let piece = pieces[i % pieces.len()];
+        vocab.push(format!("{piece}tok{i}"));
+        i += 1;
+    }
+    vocab
+}
+
+fn run(
+    max_threads: i32,
+    matchers: &[GrammarMatcher],
+    bitmask: &mut [i32],
+) -> f64 {
+    let (_, bitmask_size) = get_bitmask_shape(BATCH_SIZE, VOCAB_SIZE);
+    let mut shape = [BATCH_SIZE as i64, bitmask_size as i64];
+    let mut strides = [bitmask_size as i64, 1];
+    let mut tensor = unsafe {
+        DLTensor::new(
+            bitmask.as_mut_ptr() as *mut c_void,
+            DLDevice {
+                device_type: DLDeviceType::kDLCPU,
+                device_id: 0,
+            },
+            2,
+            DLDataType {
+                code: DLDataTypeCode::kDLInt as u8,
+                bits: 32,
+                lanes: 1,
+            },
+            shape.as_mut_ptr(),
+            strides.as_mut_ptr(),
+            0,
+        )
+    };
+
+    let mut batch_matcher = BatchGrammarMatcher::new(max_threads).unwrap();
+    // Warm up once so the first-call allocations are not measured.
+    batch_matcher.batch_fill_next_token_bitmask(
+        matchers,
+        &mut tensor,
+        None,
+        false,
+    );
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        batch_matcher.batch_fill_next_token_bitmask(
+            matchers,
+            &mut tensor,
+            None,
+            false,
+        );
+    }
+    start.elapsed().as_secs_f64() * 1000.0 / ITERATIONS as f64
+}
+
+fn main() {
+    let vocab = synthetic_vocab();
+    let tokenizer_info = TokenizerInfo::new(
+        &vocab,
+        VocabType::RAW,
+        &Some(vec![0].into()),
+        false,
+    )
+    .unwrap();
+    let mut compiler =
+        GrammarCompiler::new(&tokenizer_info, 8, false, -1).unwrap();
+    let compiled_grammar = compiler.compile_builtin_json_grammar().unwrap();
+
+    let matchers: Vec<GrammarMatcher> = (0..BATCH_SIZE)
+        .map(|_| {
+            let mut matcher =
+                GrammarMatcher::new(&compiled_grammar, None, true, -1).unwrap();
+            // Move into a string context, where most tokens are uncertain.
+            assert!(matcher.accept_string("{\"", false));
+            matcher
+        })
+        .collect();
+
+    let mut bitmask = allocate_token_bitmask(BATCH_SIZE, VOCAB_SIZE);
+    let serial_ms = run(1, &matchers, &mut bitmask);
+    let parallel_ms = run(-1, &matchers, &mut bitmask);
+    println!(
+        "batch {BATCH_SIZE}, vocab {VOCAB_SIZE}: serial {serial_ms:.3} ms, \
+         parallel {parallel_ms:.3} ms, speedup {:.2}x",
+        serial_ms / parallel_ms
+    );
+}
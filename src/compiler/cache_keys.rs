@@ -0,0 +1,98 @@
+//! The entries of the grammar-level cache of a `GrammarCompiler`, see
+//! `GrammarCompiler::cache_entries`.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use super::CacheEntryInfo;
+
+/// Hash the input of a cache entry, e.g. the regex, or the JSON schema with its format
+/// options.
+pub(super) fn hash_cache_key(key: &impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The hashed keys and sizes of the grammars in the C++ cache, which does not expose its
+/// entries.
+///
+/// The C++ cache is an LRU cache. Compiling a cached grammar makes it the most recently used,
+/// and inserting a new grammar first evicts the least recently used ones while the size of the
+/// others exceeds the limit. Replaying these steps on the compile calls keeps the same entries
+/// as the cache, so they are bounded by its limit and pruned when it evicts.
+pub(super) struct CacheKeys {
+    // The limit of the grammar-level cache, `None` if it is unlimited.
+    limit_bytes: Option<usize>,
+    // The last use and the size of each entry, by kind and key hash.
+    entries: HashMap<(&'static str, u64), (u64, usize)>,
+    // The entries by last use, the least recently used first.
+    by_last_use: BTreeMap<u64, (&'static str, u64)>,
+    size_bytes: usize,
+    clock: u64,
+}
+
+impl CacheKeys {
+    /// The entries of a compiler created with `cache_limit_bytes`. The grammar-level cache
+    /// gets two thirds of the limit, and the rule-level cache the rest.
+    pub(super) fn new(cache_limit_bytes: isize) -> Self {
+        Self {
+            limit_bytes: usize::try_from(cache_limit_bytes)
+                .ok()
+                .map(|limit| limit / 3 * 2),
+            entries: HashMap::new(),
+            by_last_use: BTreeMap::new(),
+            size_bytes: 0,
+            clock: 0,
+        }
+    }
+
+    /// Record a compile call of the grammar of `kind` and `key_hash`, of `memory_size_bytes`.
+    pub(super) fn visit(
+        &mut self,
+        kind: &'static str,
+        key_hash: u64,
+        memory_size_bytes: usize,
+    ) {
+        self.clock += 1;
+        if let Some((last_use, _)) = self.entries.get_mut(&(kind, key_hash)) {
+            self.by_last_use.remove(last_use);
+            *last_use = self.clock;
+            self.by_last_use.insert(self.clock, (kind, key_hash));
+            return;
+        }
+        if let Some(limit_bytes) = self.limit_bytes {
+            while self.size_bytes > limit_bytes {
+                let Some((_, key)) = self.by_last_use.pop_first() else {
+                    break;
+                };
+                let (_, size) =
+                    self.entries.remove(&key).expect("listed by last use");
+                self.size_bytes -= size;
+            }
+        }
+        self.entries.insert((kind, key_hash), (self.clock, memory_size_bytes));
+        self.by_last_use.insert(self.clock, (kind, key_hash));
+        self.size_bytes += memory_size_bytes;
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.entries.clear();
+        self.by_last_use.clear();
+        self.size_bytes = 0;
+    }
+
+    /// The entries, the least recently used first.
+    pub(super) fn entries(&self) -> Vec<CacheEntryInfo> {
+        self.by_last_use
+            .values()
+            .map(|&(kind, key_hash)| CacheEntryInfo {
+                kind,
+                key_hash,
+                memory_size_bytes: self.entries[&(kind, key_hash)].1,
+            })
+            .collect()
+    }
+}
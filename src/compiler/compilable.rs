@@ -0,0 +1,66 @@
+use crate::{CompiledGrammar, Grammar, GrammarCompiler};
+
+/// An input of `GrammarCompiler::compile`: a grammar to compile, or a grammar that is
+/// already compiled. It lets generic code accept either without matching on an enum at every
+/// call site.
+pub trait Compilable {
+    /// Compile the input with `compiler`, or return it if it is already compiled.
+    ///
+    /// # Parameters
+    ///
+    /// - `compiler`: The compiler, whose tokenizer info and cache are used.
+    ///
+    /// # Returns
+    ///
+    /// The compiled grammar.
+    ///
+    /// # Errors
+    ///
+    /// When the compilation fails.
+    fn compile(
+        self,
+        compiler: &mut GrammarCompiler,
+    ) -> Result<CompiledGrammar, String>;
+}
+
+/// Compiles the grammar with `GrammarCompiler::compile_grammar`.
+impl Compilable for &Grammar {
+    fn compile(
+        self,
+        compiler: &mut GrammarCompiler,
+    ) -> Result<CompiledGrammar, String> {
+        compiler.compile_grammar(self)
+    }
+}
+
+/// Returns a clone of the compiled grammar, which shares its data, and does not update
+/// `last_compile_stats` or the cache.
+///
+/// The compiled grammar must have been compiled for the tokenizer of the compiler: the same
+/// tokenizer info or a clone of it, or one with the same metadata and decoded vocabulary.
+/// Otherwise its token masks do not fit the vocabulary of the compiler, and an error is
+/// returned.
+impl Compilable for &CompiledGrammar {
+    fn compile(
+        self,
+        compiler: &mut GrammarCompiler,
+    ) -> Result<CompiledGrammar, String> {
+        let tokenizer_info = self.tokenizer_info();
+        let expected = compiler.tokenizer_info();
+        if tokenizer_info.vocab_size() != expected.vocab_size() {
+            return Err(format!(
+                "The grammar is compiled for a vocabulary of size {}, but the compiler's \
+                 vocabulary has size {}",
+                tokenizer_info.vocab_size(),
+                expected.vocab_size()
+            ));
+        }
+        if !tokenizer_info.is_same_tokenizer(expected) {
+            return Err(
+                "The grammar is compiled for a different tokenizer than the compiler's"
+                    .to_string(),
+            );
+        }
+        Ok(self.clone())
+    }
+}
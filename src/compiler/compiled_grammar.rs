@@ -2,6 +2,28 @@ use std::pin::Pin;
 
 use crate::{CxxUniquePtr, DeserializeError, Grammar, TokenizerInfo, ffi};
 
+/// The memory usage of a compiled grammar by component, returned by
+/// `CompiledGrammar::memory_breakdown`. All sizes are approximate and in bytes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryBreakdown {
+    /// The rules and expressions of the grammar.
+    pub grammar_bytes: usize,
+    /// The finite automata built for the rules of the grammar.
+    pub automaton_bytes: usize,
+    /// The adaptive token masks precomputed for the parser states of the grammar.
+    pub token_mask_cache_bytes: usize,
+    /// The part of `token_mask_cache_bytes` used by the states of each rule, as
+    /// `(rule name, bytes)` in rule id order. Rules with no cached state have 0 bytes.
+    pub token_mask_cache_bytes_per_rule: Vec<(String, usize)>,
+}
+
+impl MemoryBreakdown {
+    /// The total memory usage, equal to `CompiledGrammar::memory_size_bytes`.
+    pub fn total_bytes(&self) -> usize {
+        self.grammar_bytes + self.automaton_bytes + self.token_mask_cache_bytes
+    }
+}
+
 /// This is the primary object to store compiled grammar.
 ///
 /// A `CompiledGrammar` can be used to construct `GrammarMatcher` to generate token masks
@@ -10,6 +32,22 @@ use crate::{CxxUniquePtr, DeserializeError, Grammar, TokenizerInfo, ffi};
 /// # Notes
 ///
 /// Do not construct this class directly, instead use `GrammarCompiler` to construct the object.
+///
+/// # Token masks
+///
+/// The compiler precomputes the adaptive token mask of every parser state of the grammar.
+/// Filling a bitmask combines the masks of the current states and checks the tokens they
+/// leave uncertain; nothing is computed lazily on the first decode step. This cost is paid at compile time, and grows with the number of parser states and the
+/// vocabulary size: `GrammarCompiler::last_compile_stats` reports the compile time and the
+/// number of states, and `memory_breakdown` the memory of the masks.
+///
+/// # Sharing
+///
+/// A compiled grammar is immutable once compiled, so it is `Send` and `Sync`. Wrap it in an
+/// `Arc<CompiledGrammar>` to share one compilation between threads, e.g. request handlers
+/// each creating their own `GrammarMatcher` from it. Dropping the last owner frees the
+/// grammar, unless a `GrammarMatcher` created from it is still alive: the matcher keeps the
+/// C++ compiled grammar it was created from.
 pub struct CompiledGrammar {
     inner: CxxUniquePtr<ffi::CompiledGrammar>,
 }
@@ -49,6 +87,30 @@ impl CompiledGrammar {
         sz
     }
 
+    /// The memory usage of the compiled grammar by component, to find out what makes a
+    /// grammar large. The adaptive token mask cache usually dominates; its split by rule
+    /// points to the parts of the grammar (e.g. the schema properties) whose masks are the
+    /// most expensive.
+    pub fn memory_breakdown(&self) -> MemoryBreakdown {
+        let mut rule_names = ffi::new_string_vector();
+        let sizes = ffi::compiled_grammar_memory_breakdown(
+            self.ffi_ref(),
+            rule_names.pin_mut(),
+        );
+        let sizes: Vec<usize> =
+            sizes.iter().map(|&size| size as usize).collect();
+        MemoryBreakdown {
+            grammar_bytes: sizes[0],
+            automaton_bytes: sizes[1],
+            token_mask_cache_bytes: sizes[2],
+            token_mask_cache_bytes_per_rule: rule_names
+                .iter()
+                .map(|name| name.to_string_lossy().into_owned())
+                .zip(sizes[3..].iter().copied())
+                .collect(),
+        }
+    }
+
     /// Serialize the compiled grammar to a JSON string. It will serialize the compiled grammar
     /// without the tokenizer info, since the tokenizer info is shared by multiple compiled
     /// grammars.
@@ -66,6 +128,16 @@ impl CompiledGrammar {
         ffi::compiled_grammar_serialize_json(inner_ref).to_string()
     }
 
+    /// Serialize the compiled grammar to an indented JSON string, for reading and diffing. The string
+    /// deserializes to the same compiled grammar as the one from `serialize_json`.
+    ///
+    /// # Returns
+    ///
+    /// The indented JSON string.
+    pub fn serialize_json_pretty(&self) -> String {
+        crate::utils::pretty_json(self.serialize_json())
+    }
+
     /// Deserialize the compiled grammar from a JSON string and associate it with the specified
     /// tokenizer info.
     ///
@@ -128,6 +200,36 @@ impl CompiledGrammar {
     }
 }
 
+/// Clones share the compiled data, which is not modified after compilation, so cloning is
+/// cheap and does not copy the token masks.
+impl Clone for CompiledGrammar {
+    fn clone(&self) -> Self {
+        Self::from_unique_ptr(ffi::compiled_grammar_clone(self.ffi_ref()))
+    }
+}
+
+/// Formats the EBNF of the grammar, the vocabulary size of the tokenizer info, and the memory
+/// usage, e.g. for logging a cached or deserialized compiled grammar.
+impl std::fmt::Debug for CompiledGrammar {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        let ebnf = self.grammar().to_string_ebnf();
+        f.debug_struct("CompiledGrammar")
+            .field("grammar", &format_args!("{}", ebnf.trim_end()))
+            .field("vocab_size", &self.tokenizer_info().vocab_size())
+            .field("memory_size_bytes", &self.memory_size_bytes())
+            .finish()
+    }
+}
+
 impl Drop for CompiledGrammar {
     fn drop(&mut self) {}
 }
+
+// SAFETY: The C++ compiled grammar is a reference-counted handle to data that is not modified
+// after compilation, and the methods taking `&self` only read it or copy the handle, whose
+// reference count is atomic. The batch matcher reads it from several threads at once.
+unsafe impl Send for CompiledGrammar {}
+unsafe impl Sync for CompiledGrammar {}
@@ -1,9 +1,22 @@
+use std::{
+    cmp::Reverse,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::cache_keys::{CacheKeys, hash_cache_key};
 use crate::{
-    CxxUniquePtr,
-    compiler::CompiledGrammar,
+    CxxUniquePtr, JsonSchemaError,
+    compiler::{Compilable, CompiledGrammar},
     ffi,
-    grammar::{self, StructuralTagItem},
+    grammar::{self, JsonSchemaOptions, RegexMatchMode, StructuralTagItem},
     tokenizer_info::TokenizerInfo,
+    utils::bytes_as_c_char_ptr,
 };
 
 /// The compiler for grammars.
@@ -11,8 +24,63 @@ use crate::{
 /// It is associated with a certain tokenizer info, and compiles grammars into `CompiledGrammar`
 /// with the tokenizer info. It allows parallel compilation with multiple threads, and has a cache
 /// to store the compilation result, avoiding compiling the same grammar multiple times.
+///
+/// The compiler keeps no threads of its own. With `max_threads > 1`, each compile call starts a
+/// pool of up to `max_threads` threads and joins it before returning. With `max_threads <= 1`,
+/// compile calls run entirely on the calling thread, so many compilers can be created inside
+/// another thread pool (e.g. rayon) without oversubscribing it.
 pub struct GrammarCompiler {
     inner: CxxUniquePtr<ffi::GrammarCompiler>,
+    // The tokenizer info the grammars are compiled with.
+    tokenizer_info: TokenizerInfo,
+    max_threads: i32,
+    cache_enabled: bool,
+    last_compile_stats: Option<CompileStats>,
+    // The grammars in the cache. See `cache_entries`.
+    cache_keys: CacheKeys,
+    // The threads of `compile_json_schema_with_timeout` that are still compiling.
+    running_timeout_compiles: Arc<AtomicUsize>,
+}
+
+/// The maximum number of compilations abandoned by `compile_json_schema_with_timeout` that
+/// can keep running for one compiler.
+const MAX_ABANDONED_COMPILES: usize = 4;
+
+/// Statistics of a successful compilation, see `GrammarCompiler::last_compile_stats`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CompileStats {
+    /// The wall-clock time of the compile call, including the conversion of the input (JSON
+    /// schema, regex, ...) to a grammar.
+    pub compile_time: Duration,
+    /// The number of parser states whose token mask was precomputed.
+    pub num_states: usize,
+    /// Whether the compile call left the size of the cache unchanged, as a cache hit does. The
+    /// C++ cache does not report hits and misses, so this is the observable signal: a miss
+    /// usually grows the cache, but not when it evicts entries of the same total size, or when
+    /// the compiled grammar does not fit in `cache_limit_bytes`. Always `false` when the cache
+    /// is disabled.
+    pub cache_size_unchanged: bool,
+}
+
+/// A grammar compiled into the cache of a `GrammarCompiler`, see
+/// `GrammarCompiler::cache_entries`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CacheEntryInfo {
+    /// What the entry was compiled from: `"grammar"`, `"json_schema"`, `"regex"`,
+    /// `"structural_tag"` or `"builtin_json"`.
+    pub kind: &'static str,
+    /// A hash of the input of the entry, e.g. of the regex, of the EBNF of the grammar, or of
+    /// the JSON schema with its format options. Equal inputs of the same kind have equal
+    /// hashes within a process.
+    pub key_hash: u64,
+    /// The approximate memory usage of the compiled grammar in bytes, as counted by the cache.
+    pub memory_size_bytes: usize,
+}
+
+/// The state captured at the start of a compile call.
+struct CompileStart {
+    instant: Instant,
+    cache_size_bytes: i64,
 }
 
 impl GrammarCompiler {
@@ -21,7 +89,8 @@ impl GrammarCompiler {
     /// # Parameters
     ///
     /// - `tokenizer_info`: The tokenizer info.
-    /// - `max_threads`: The maximum number of threads used to compile the grammar.
+    /// - `max_threads`: The maximum number of threads used to compile the grammar. `0` and `1`
+    ///   compile on the calling thread without starting any thread.
     /// - `cache_enabled`: Whether to enable the cache.
     /// - `cache_limit_bytes`: The maximum memory usage for the cache in bytes.
     ///   Note that the actual memory usage may slightly exceed this value.
@@ -50,12 +119,68 @@ impl GrammarCompiler {
         }
         Ok(Self {
             inner,
+            tokenizer_info: tokenizer_info.clone(),
+            max_threads,
+            cache_enabled,
+            last_compile_stats: None,
+            cache_keys: CacheKeys::new(cache_limit_bytes),
+            running_timeout_compiles: Arc::default(),
         })
     }
 
+    pub(crate) fn tokenizer_info(&self) -> &TokenizerInfo {
+        &self.tokenizer_info
+    }
+
+    /// The statistics of the last successful compile call. Failed compilations do not
+    /// update them.
+    ///
+    /// # Returns
+    ///
+    /// The statistics, or `None` if nothing has been compiled yet.
+    pub fn last_compile_stats(&self) -> Option<CompileStats> {
+        self.last_compile_stats
+    }
+
+    fn start_compile(&self) -> CompileStart {
+        CompileStart {
+            instant: Instant::now(),
+            cache_size_bytes: self.get_cache_size_bytes(),
+        }
+    }
+
+    fn finish_compile(
+        &mut self,
+        start: CompileStart,
+        kind: &'static str,
+        key: impl FnOnce() -> u64,
+        compiled_grammar: CompiledGrammar,
+    ) -> CompiledGrammar {
+        let cache_size_unchanged = self.cache_enabled
+            && self.get_cache_size_bytes() == start.cache_size_bytes;
+        self.last_compile_stats = Some(CompileStats {
+            compile_time: start.instant.elapsed(),
+            num_states: ffi::compiled_grammar_num_cached_states(
+                compiled_grammar.ffi_ref(),
+            ),
+            cache_size_unchanged,
+        });
+        if self.cache_enabled {
+            self.cache_keys.visit(
+                kind,
+                key(),
+                compiled_grammar.memory_size_bytes(),
+            );
+        }
+        compiled_grammar
+    }
+
     /// Get `CompiledGrammar` from the specified JSON schema and format. The indent
     /// and separators parameters follow the same convention as in `json.dumps()`.
     ///
+    /// This is the canonical signature, and it mirrors `Grammar::from_json_schema`. Use
+    /// `compile_json_schema_default_whitespace` to leave `max_whitespace_cnt` unlimited.
+    ///
     /// # Parameters
     ///
     /// - `schema`: The schema string.
@@ -80,7 +205,10 @@ impl GrammarCompiler {
     ///
     /// # Errors
     ///
-    /// Returns an error if the JSON schema is invalid or compilation fails.
+    /// The errors of `Grammar::from_json_schema`, e.g. `JsonSchemaError::Invalid` if the
+    /// JSON schema is invalid or compilation fails, or `JsonSchemaError::Unsupported` if it
+    /// uses a form of `contains`, `additionalProperties`, `multipleOf` or exclusive bounds
+    /// that the grammar cannot enforce.
     pub fn compile_json_schema(
         &mut self,
         schema: &str,
@@ -89,8 +217,38 @@ impl GrammarCompiler {
         separators: Option<(impl AsRef<str>, impl AsRef<str>)>,
         strict_mode: bool,
         max_whitespace_cnt: Option<i32>,
-    ) -> Result<CompiledGrammar, String> {
-        cxx::let_cxx_string!(schema_cxx = schema);
+    ) -> Result<CompiledGrammar, JsonSchemaError> {
+        let prepared =
+            grammar::json_schema_prepare::prepare_json_schema(schema)?;
+        if prepared.rewrites_ebnf() {
+            // The converted grammar is rewritten, see `Grammar::from_json_schema`, so it is
+            // compiled as a grammar.
+            let grammar = grammar::Grammar::from_prepared_json_schema(
+                schema,
+                &prepared,
+                any_whitespace,
+                indent,
+                separators,
+                strict_mode,
+                max_whitespace_cnt,
+                false,
+            )?;
+            return self
+                .compile_grammar(&grammar)
+                .map_err(JsonSchemaError::Other);
+        }
+        let start = self.start_compile();
+        let cache_key = json_schema_cache_key(
+            schema,
+            any_whitespace,
+            indent,
+            separators
+                .as_ref()
+                .map(|(comma, colon)| (comma.as_ref(), colon.as_ref())),
+            strict_mode,
+            max_whitespace_cnt,
+        );
+        cxx::let_cxx_string!(schema_cxx = prepared.schema(schema));
         let has_indent = indent.is_some();
         let indent_i32: i32 = indent.unwrap_or(0);
         let has_separators = separators.is_some();
@@ -120,9 +278,357 @@ impl GrammarCompiler {
             )
         };
         if unique_ptr.is_null() {
-            return Err(error_out_cxx.to_string());
+            return Err(JsonSchemaError::Invalid(error_out_cxx.to_string()));
+        }
+        Ok(self.finish_compile(
+            start,
+            "json_schema",
+            || cache_key,
+            CompiledGrammar::from_unique_ptr(unique_ptr),
+        ))
+    }
+
+    /// Get `CompiledGrammar` from the specified JSON schema, with the format parameters of
+    /// `compile_json_schema` and the schema rewrites in `options`, see
+    /// `Grammar::from_json_schema_with_options`. The rewritten schema is compiled and cached
+    /// like `compile_json_schema`, or with `trailing_whitespace`, the grammar is compiled and
+    /// cached like `compile_grammar`.
+    ///
+    /// # Parameters
+    ///
+    /// - `schema`: The schema string.
+    /// - `options`: The format parameters and the rewrites, see `JsonSchemaOptions`.
+    ///
+    /// # Returns
+    ///
+    /// The compiled grammar.
+    ///
+    /// # Errors
+    ///
+    /// The errors of `Grammar::from_json_schema_with_options`.
+    pub fn compile_json_schema_with_options(
+        &mut self,
+        schema: &str,
+        options: &JsonSchemaOptions,
+    ) -> Result<CompiledGrammar, JsonSchemaError> {
+        if options.trailing_whitespace {
+            // The converted grammar is extended, so it is compiled as a grammar.
+            let grammar = grammar::Grammar::from_json_schema_with_options(
+                schema, options,
+            )?;
+            return self
+                .compile_grammar(&grammar)
+                .map_err(JsonSchemaError::Other);
+        }
+        if options.print_converted_ebnf {
+            // The compiler converts the schema itself without printing, so the conversion is
+            // repeated here only for its output.
+            grammar::Grammar::from_json_schema_with_options(schema, options)?;
         }
-        Ok(CompiledGrammar::from_unique_ptr(unique_ptr))
+        let rewritten = options.rewrite_schema(schema)?;
+        self.compile_json_schema(
+            rewritten.as_deref().unwrap_or(schema),
+            options.any_whitespace,
+            options.indent,
+            options.separators(),
+            options.strict_mode,
+            options.max_whitespace_cnt,
+        )
+    }
+
+    /// Get `CompiledGrammar` from the specified JSON schema and format, without limiting
+    /// the number of whitespace characters. Equivalent to `compile_json_schema` with
+    /// `max_whitespace_cnt` set to `None`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the JSON schema is invalid or compilation fails.
+    pub fn compile_json_schema_default_whitespace(
+        &mut self,
+        schema: &str,
+        any_whitespace: bool,
+        indent: Option<i32>,
+        separators: Option<(impl AsRef<str>, impl AsRef<str>)>,
+        strict_mode: bool,
+    ) -> Result<CompiledGrammar, JsonSchemaError> {
+        self.compile_json_schema(
+            schema,
+            any_whitespace,
+            indent,
+            separators,
+            strict_mode,
+            None,
+        )
+    }
+
+    /// Variant of `compile_json_schema` that gives up after `timeout`, e.g. to bound the time
+    /// spent on untrusted schemas. Combine it with `set_max_recursion_depth` to also bound
+    /// the nesting of the schemas.
+    ///
+    /// The schema is compiled on a new thread, through a second handle to this compiler that
+    /// shares its cache. The compilation cannot be interrupted, so on timeout it is abandoned,
+    /// not aborted: the thread keeps running until the compilation ends, then drops the
+    /// result and exits. Until then it keeps using CPU (and the compiler threads when
+    /// `max_threads > 1`) and keeps the cache alive, even if this compiler is dropped.
+    ///
+    /// An abandoned compilation still populates the shared cache when it ends, like any other
+    /// compile call, so a later call with the same schema can be served from the cache. It is
+    /// not listed by `cache_entries`, and does not update `last_compile_stats`.
+    ///
+    /// To bound the CPU spent on abandoned compilations, e.g. when an adversary keeps sending
+    /// slow schemas, at most 4 of them can be running for one compiler. While that many are
+    /// running, the call fails without starting a new one.
+    ///
+    /// # Parameters
+    ///
+    /// - `schema`, `any_whitespace`, `indent`, `separators`, `strict_mode`,
+    ///   `max_whitespace_cnt`: See `compile_json_schema`.
+    /// - `timeout`: The time budget of the compilation.
+    ///
+    /// # Returns
+    ///
+    /// The compiled grammar.
+    ///
+    /// # Errors
+    ///
+    /// - `JsonSchemaError::Timeout` when the compilation does not end within `timeout`.
+    /// - When too many compilations abandoned by earlier timeouts are still running.
+    /// - The errors of `compile_json_schema`.
+    pub fn compile_json_schema_with_timeout(
+        &mut self,
+        schema: &str,
+        any_whitespace: bool,
+        indent: Option<i32>,
+        separators: Option<(impl AsRef<str>, impl AsRef<str>)>,
+        strict_mode: bool,
+        max_whitespace_cnt: Option<i32>,
+        timeout: Duration,
+    ) -> Result<CompiledGrammar, JsonSchemaError> {
+        let running = Arc::clone(&self.running_timeout_compiles);
+        // Only this compiler starts these threads, and `&mut self` serializes the calls, so
+        // the count cannot grow between the check and the increment.
+        let num_running = running.load(Ordering::Acquire);
+        if num_running >= MAX_ABANDONED_COMPILES {
+            return Err(JsonSchemaError::Other(format!(
+                "{} compilations abandoned by earlier timeouts are still running",
+                num_running
+            )));
+        }
+        running.fetch_add(1, Ordering::AcqRel);
+        let worker = SharedCompiler(self.share());
+        let schema = schema.to_string();
+        let separators = separators.map(|(comma, colon)| {
+            (comma.as_ref().to_string(), colon.as_ref().to_string())
+        });
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut worker = worker.into_inner();
+            let result = {
+                let _running = RunningCompile(running);
+                worker.compile_json_schema(
+                    &schema,
+                    any_whitespace,
+                    indent,
+                    separators,
+                    strict_mode,
+                    max_whitespace_cnt,
+                )
+            };
+            // The receiver is gone if the compilation timed out.
+            let _ = sender.send((
+                result,
+                worker.last_compile_stats,
+                worker.cache_keys.entries(),
+            ));
+        });
+        let (result, stats, cache_entries) =
+            match receiver.recv_timeout(timeout) {
+                Ok(received) => received,
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    return Err(JsonSchemaError::Timeout);
+                },
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    return Err(JsonSchemaError::Other(
+                        "The compile thread panicked".to_string(),
+                    ));
+                },
+            };
+        let compiled_grammar = result?;
+        if let Some(stats) = stats {
+            self.last_compile_stats = Some(stats);
+            for entry in cache_entries {
+                self.cache_keys.visit(
+                    entry.kind,
+                    entry.key_hash,
+                    entry.memory_size_bytes,
+                );
+            }
+        }
+        Ok(compiled_grammar)
+    }
+
+    // A second compiler over the same C++ compiler, with bookkeeping of its own.
+    fn share(&self) -> Self {
+        Self {
+            inner: ffi::grammar_compiler_share(
+                self.inner.as_ref().expect("GrammarCompiler inner is null"),
+            ),
+            tokenizer_info: self.tokenizer_info.clone(),
+            max_threads: self.max_threads,
+            cache_enabled: self.cache_enabled,
+            last_compile_stats: None,
+            cache_keys: CacheKeys::new(-1),
+            running_timeout_compiles: Arc::default(),
+        }
+    }
+
+    /// Compile several JSON schemas with the same format options, in parallel on up to
+    /// `max_threads` threads of the compiler. This is faster than calling
+    /// `compile_json_schema` for each schema, e.g. to warm up the cache at startup. With
+    /// `max_threads <= 1` the schemas are compiled one after another on the calling thread.
+    ///
+    /// Each schema is compiled like `compile_json_schema`, and the options have the same
+    /// meaning. The batch does not update `last_compile_stats`.
+    ///
+    /// # Parameters
+    ///
+    /// - `schemas`: The schema strings.
+    /// - `any_whitespace`, `indent`, `separators`, `strict_mode`, `max_whitespace_cnt`: The
+    ///   format options, see `compile_json_schema`.
+    ///
+    /// # Returns
+    ///
+    /// The result of each schema, in the order of `schemas`. A schema that fails to compile
+    /// gets its own error and does not affect the others.
+    pub fn compile_json_schema_batch(
+        &mut self,
+        schemas: &[&str],
+        any_whitespace: bool,
+        indent: Option<i32>,
+        separators: Option<(impl AsRef<str>, impl AsRef<str>)>,
+        strict_mode: bool,
+        max_whitespace_cnt: Option<i32>,
+    ) -> Vec<Result<CompiledGrammar, JsonSchemaError>> {
+        let prepared: Vec<_> = schemas
+            .iter()
+            .map(|&schema| {
+                grammar::json_schema_prepare::prepare_json_schema(schema)
+            })
+            .collect();
+        // Only the schemas that passed the checks and whose converted grammar is not
+        // rewritten are compiled in the batch. The others are compiled one by one after it,
+        // see `compile_json_schema`.
+        let mut cxx_schemas = ffi::new_string_vector();
+        {
+            let mut cxx_vec_pin = cxx_schemas.pin_mut();
+            ffi::string_vec_reserve(cxx_vec_pin.as_mut(), schemas.len());
+            for (&schema, prepared) in schemas.iter().zip(&prepared) {
+                let Ok(prepared) = prepared else {
+                    continue;
+                };
+                if prepared.rewrites_ebnf() {
+                    continue;
+                }
+                let bytes = prepared.schema(schema).as_bytes();
+                unsafe {
+                    ffi::string_vec_push_bytes(
+                        cxx_vec_pin.as_mut(),
+                        bytes_as_c_char_ptr(bytes),
+                        bytes.len(),
+                    );
+                }
+            }
+        }
+        let (sep_comma, sep_colon) = match &separators {
+            Some((comma, colon)) => {
+                (comma.as_ref().to_string(), colon.as_ref().to_string())
+            },
+            None => (String::new(), String::new()),
+        };
+        cxx::let_cxx_string!(sep_comma_cxx = sep_comma.as_str());
+        cxx::let_cxx_string!(sep_colon_cxx = sep_colon.as_str());
+
+        let mut errors = ffi::new_string_vector();
+        let mut compiled = ffi::compiler_compile_json_schema_batch(
+            self.inner.as_mut().expect("GrammarCompiler inner is null"),
+            &cxx_schemas,
+            any_whitespace,
+            indent.is_some(),
+            indent.unwrap_or(0),
+            separators.is_some(),
+            &sep_comma_cxx,
+            &sep_colon_cxx,
+            strict_mode,
+            max_whitespace_cnt.is_some(),
+            max_whitespace_cnt.unwrap_or(0),
+            self.max_threads,
+            errors.pin_mut(),
+        );
+        let separators = separators
+            .as_ref()
+            .map(|(comma, colon)| (comma.as_ref(), colon.as_ref()));
+        let last_compile_stats = self.last_compile_stats.take();
+        let mut index = 0;
+        let results = schemas
+            .iter()
+            .zip(prepared)
+            .map(|(&schema, prepared)| {
+                let prepared = prepared?;
+                if prepared.rewrites_ebnf() {
+                    let grammar = grammar::Grammar::from_prepared_json_schema(
+                        schema,
+                        &prepared,
+                        any_whitespace,
+                        indent,
+                        separators,
+                        strict_mode,
+                        max_whitespace_cnt,
+                        false,
+                    )?;
+                    return self
+                        .compile_grammar(&grammar)
+                        .map_err(JsonSchemaError::Other);
+                }
+                let Some(error) =
+                    errors.get(index).filter(|_| index < compiled.len())
+                else {
+                    return Err(JsonSchemaError::Other(
+                        "The batch compilation returned no result for the schema"
+                            .to_string(),
+                    ));
+                };
+                let result = if error.is_empty() {
+                    let compiled_grammar = CompiledGrammar::from_unique_ptr(
+                        ffi::compiled_grammar_vec_take(
+                            compiled.pin_mut(),
+                            index,
+                        ),
+                    );
+                    if self.cache_enabled {
+                        let key = json_schema_cache_key(
+                            schema,
+                            any_whitespace,
+                            indent,
+                            separators,
+                            strict_mode,
+                            max_whitespace_cnt,
+                        );
+                        self.cache_keys.visit(
+                            "json_schema",
+                            key,
+                            compiled_grammar.memory_size_bytes(),
+                        );
+                    }
+                    Ok(compiled_grammar)
+                } else {
+                    Err(JsonSchemaError::Invalid(error.to_string()))
+                };
+                index += 1;
+                result
+            })
+            .collect();
+        self.last_compile_stats = last_compile_stats;
+        results
     }
 
     /// Get `CompiledGrammar` from the standard JSON.
@@ -137,6 +643,7 @@ impl GrammarCompiler {
     pub fn compile_builtin_json_grammar(
         &mut self
     ) -> Result<CompiledGrammar, String> {
+        let start = self.start_compile();
         cxx::let_cxx_string!(error_out_cxx = "");
         let unique_ptr = unsafe {
             ffi::compiler_compile_builtin_json(
@@ -147,7 +654,12 @@ impl GrammarCompiler {
         if unique_ptr.is_null() {
             return Err(error_out_cxx.to_string());
         }
-        Ok(CompiledGrammar::from_unique_ptr(unique_ptr))
+        Ok(self.finish_compile(
+            start,
+            "builtin_json",
+            || hash_cache_key(&()),
+            CompiledGrammar::from_unique_ptr(unique_ptr),
+        ))
     }
 
     /// Get `CompiledGrammar` from the specified regex.
@@ -167,6 +679,7 @@ impl GrammarCompiler {
         &mut self,
         regex: &str,
     ) -> Result<CompiledGrammar, String> {
+        let start = self.start_compile();
         cxx::let_cxx_string!(regex_cxx = regex);
         cxx::let_cxx_string!(error_out_cxx = "");
         let unique_ptr = unsafe {
@@ -179,7 +692,72 @@ impl GrammarCompiler {
         if unique_ptr.is_null() {
             return Err(error_out_cxx.to_string());
         }
-        Ok(CompiledGrammar::from_unique_ptr(unique_ptr))
+        Ok(self.finish_compile(
+            start,
+            "regex",
+            || hash_cache_key(&regex),
+            CompiledGrammar::from_unique_ptr(unique_ptr),
+        ))
+    }
+
+    /// Get `CompiledGrammar` from the specified regex, with the debugging options of
+    /// `Grammar::from_regex`. The result and the caching are the same as `compile_regex`.
+    ///
+    /// # Parameters
+    ///
+    /// - `regex`: The regex string.
+    /// - `print_converted_ebnf`: This method will convert the regex pattern to EBNF first.
+    ///   If this is true, the converted EBNF string will be printed. For debugging purposes.
+    ///
+    /// # Returns
+    ///
+    /// The compiled grammar.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the regex is invalid or compilation fails.
+    pub fn compile_regex_with_options(
+        &mut self,
+        regex: &str,
+        print_converted_ebnf: bool,
+    ) -> Result<CompiledGrammar, String> {
+        if print_converted_ebnf {
+            // The compiler converts the regex itself without printing, so the conversion is
+            // repeated here only for its output.
+            grammar::Grammar::from_regex(regex, true)?;
+        }
+        self.compile_regex(regex)
+    }
+
+    /// Get `CompiledGrammar` from the specified regex, matching either the whole output or a
+    /// prefix of it, see `Grammar::from_regex_with_match_mode`. With
+    /// `RegexMatchMode::FullMatch`, this is the same as `compile_regex`.
+    ///
+    /// # Parameters
+    ///
+    /// - `regex`: The regex string.
+    /// - `match_mode`: Whether the regex matches the whole output or a prefix of it.
+    ///
+    /// # Returns
+    ///
+    /// The compiled grammar.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the regex is invalid or compilation fails.
+    pub fn compile_regex_with_match_mode(
+        &mut self,
+        regex: &str,
+        match_mode: RegexMatchMode,
+    ) -> Result<CompiledGrammar, String> {
+        match match_mode {
+            RegexMatchMode::FullMatch => self.compile_regex(regex),
+            RegexMatchMode::Prefix => self.compile_grammar(
+                &grammar::Grammar::from_regex_with_match_mode(
+                    regex, match_mode, false,
+                )?,
+            ),
+        }
     }
 
     /// Compile a grammar from a structural tag. See the Structural Tag Usage in XGrammar
@@ -232,7 +810,74 @@ impl GrammarCompiler {
             "format": format_obj,
         })
         .to_string();
+        self.compile_structural_tag_json(&structural_tag_json)
+    }
+
+    /// Compile a grammar from structural tags like [`Self::compile_structural_tag`], with the
+    /// triggers derived from the `begin` of each tag, for the common case where each tag is
+    /// triggered by its own begin.
+    ///
+    /// Tags with the same begin share one trigger. A tag may match only one trigger, so when a
+    /// begin starts with another begin, e.g. `<call>` and `<call><urgent>`, only the shorter
+    /// one becomes a trigger, and the output after it continues with either tag. Begins that
+    /// only share a prefix without one starting with the other, e.g. `<function=f` and
+    /// `<function=g`, are separate triggers.
+    ///
+    /// # Parameters
+    ///
+    /// - `tags`: The structural tags.
+    ///
+    /// # Returns
+    ///
+    /// The compiled grammar from the structural tag.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the structural tag is invalid, e.g. a tag has an empty begin, or
+    /// compilation fails.
+    pub fn compile_structural_tag_auto_triggers(
+        &mut self,
+        tags: &[StructuralTagItem],
+    ) -> Result<CompiledGrammar, String> {
+        let mut begins: Vec<&str> =
+            tags.iter().map(|tag| tag.begin.as_str()).collect();
+        begins.sort_unstable_by_key(|begin| (begin.len(), *begin));
+        begins.dedup();
+        let triggers: Vec<&str> = begins
+            .iter()
+            .enumerate()
+            .filter(|(i, begin)| {
+                !begins[..*i].iter().any(|shorter| {
+                    shorter.len() < begin.len() && begin.starts_with(shorter)
+                })
+            })
+            .map(|(_, begin)| *begin)
+            .collect();
+        self.compile_structural_tag(tags, &triggers)
+    }
 
+    /// Compile a grammar from a complete structural tag JSON, the format accepted by
+    /// [`crate::Grammar::from_structural_tag`]. Unlike [`Self::compile_structural_tag`], every
+    /// format of the structural tag can be used, not only triggered tags with JSON schema
+    /// contents.
+    ///
+    /// # Parameters
+    ///
+    /// - `structural_tag_json`: The structural tag as a JSON string.
+    ///
+    /// # Returns
+    ///
+    /// The compiled grammar from the structural tag.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the structural tag is not valid JSON, is invalid, or compilation
+    /// fails.
+    pub fn compile_structural_tag_json(
+        &mut self,
+        structural_tag_json: &str,
+    ) -> Result<CompiledGrammar, String> {
+        let start = self.start_compile();
         cxx::let_cxx_string!(structural_tag_str = structural_tag_json);
         cxx::let_cxx_string!(error_out_cxx = "");
         let unique_ptr = unsafe {
@@ -245,7 +890,33 @@ impl GrammarCompiler {
         if unique_ptr.is_null() {
             return Err(error_out_cxx.to_string());
         }
-        Ok(CompiledGrammar::from_unique_ptr(unique_ptr))
+        Ok(self.finish_compile(
+            start,
+            "structural_tag",
+            || hash_cache_key(&structural_tag_json),
+            CompiledGrammar::from_unique_ptr(unique_ptr),
+        ))
+    }
+
+    /// Compile a grammar, or pass through a grammar that is already compiled, e.g. in generic
+    /// code that holds either. See `Compilable`.
+    ///
+    /// # Parameters
+    ///
+    /// - `input`: A `&Grammar` to compile or a `&CompiledGrammar` to return.
+    ///
+    /// # Returns
+    ///
+    /// The compiled grammar.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the grammar is invalid or compilation fails.
+    pub fn compile(
+        &mut self,
+        input: impl Compilable,
+    ) -> Result<CompiledGrammar, String> {
+        input.compile(self)
     }
 
     /// Compile a grammar object.
@@ -265,6 +936,7 @@ impl GrammarCompiler {
         &mut self,
         grammar: &grammar::Grammar,
     ) -> Result<CompiledGrammar, String> {
+        let start = self.start_compile();
         cxx::let_cxx_string!(error_out_cxx = "");
         let unique_ptr = unsafe {
             ffi::compiler_compile_grammar_or_error(
@@ -276,7 +948,12 @@ impl GrammarCompiler {
         if unique_ptr.is_null() {
             return Err(error_out_cxx.to_string());
         }
-        Ok(CompiledGrammar::from_unique_ptr(unique_ptr))
+        Ok(self.finish_compile(
+            start,
+            "grammar",
+            || hash_cache_key(&grammar.to_string()),
+            CompiledGrammar::from_unique_ptr(unique_ptr),
+        ))
     }
 
     /// Compile a grammar from an EBNF string. The string should follow the format described in
@@ -303,12 +980,55 @@ impl GrammarCompiler {
         self.compile_grammar(&grammar)
     }
 
+    /// Compile a grammar from an EBNF string whose root rule is named `root`. Same as
+    /// `compile_grammar_from_ebnf(ebnf_string, "root")`.
+    ///
+    /// # Parameters
+    ///
+    /// - `ebnf_string`: The grammar string in EBNF format.
+    ///
+    /// # Returns
+    ///
+    /// The compiled grammar.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the EBNF string is invalid, has no `root` rule, or compilation fails.
+    pub fn compile_ebnf(
+        &mut self,
+        ebnf_string: &str,
+    ) -> Result<CompiledGrammar, String> {
+        self.compile_grammar_from_ebnf(ebnf_string, "root")
+    }
+
     /// Clear all cached compiled grammars.
     pub fn clear_cache(&mut self) {
         self.inner
             .as_mut()
             .expect("GrammarCompiler inner is null")
             .ClearCache();
+        self.cache_keys.clear();
+    }
+
+    /// The grammars in the cache, largest first, e.g. to see which grammars weigh on
+    /// `cache_limit_bytes`.
+    ///
+    /// The C++ cache does not expose its entries, so the compile calls of this compiler replay
+    /// its LRU policy on the hashed inputs and sizes of the grammars: entries are dropped when
+    /// the cache evicts them under `cache_limit_bytes`, and by `clear_cache`. The grammars
+    /// compiled through other handles to the same cache are not listed, and when several
+    /// threads insert at once the cache may evict in a different order. The sizes do not add
+    /// up to `get_cache_size_bytes`, which also counts the token masks shared between grammars
+    /// at the rule level.
+    ///
+    /// # Returns
+    ///
+    /// The entries sorted by descending `memory_size_bytes`, the least recently used first
+    /// among equal sizes. Empty if the cache is disabled.
+    pub fn cache_entries(&self) -> Vec<CacheEntryInfo> {
+        let mut entries = self.cache_keys.entries();
+        entries.sort_by_key(|entry| Reverse(entry.memory_size_bytes));
+        entries
     }
 
     /// The approximate memory usage of the cache in bytes.
@@ -332,6 +1052,49 @@ impl GrammarCompiler {
     }
 }
 
+// The key hash of a JSON schema in `cache_entries`: of the schema and its format options.
+fn json_schema_cache_key(
+    schema: &str,
+    any_whitespace: bool,
+    indent: Option<i32>,
+    separators: Option<(&str, &str)>,
+    strict_mode: bool,
+    max_whitespace_cnt: Option<i32>,
+) -> u64 {
+    hash_cache_key(&(
+        schema,
+        any_whitespace,
+        indent,
+        separators,
+        strict_mode,
+        max_whitespace_cnt,
+    ))
+}
+
+// A compiler moved to the thread of `compile_json_schema_with_timeout`.
+struct SharedCompiler(GrammarCompiler);
+
+// SAFETY: the C++ compiler is thread-safe, and this handle is only used by the thread it is
+// moved to.
+unsafe impl Send for SharedCompiler {}
+
+impl SharedCompiler {
+    // Taking `self` makes the closure capture the whole `Send` wrapper.
+    fn into_inner(self) -> GrammarCompiler {
+        self.0
+    }
+}
+
+// Counts a thread of `compile_json_schema_with_timeout` as running until it is dropped, even
+// if the compilation panics.
+struct RunningCompile(Arc<AtomicUsize>);
+
+impl Drop for RunningCompile {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
 impl Drop for GrammarCompiler {
     fn drop(&mut self) {}
 }
@@ -1,7 +1,10 @@
 //! Compiling grammar for efficient token mask generation.
 
+mod cache_keys;
+pub mod compilable;
 pub mod compiled_grammar;
 pub mod grammar_compiler;
 
-pub use compiled_grammar::CompiledGrammar;
-pub use grammar_compiler::GrammarCompiler;
+pub use compilable::Compilable;
+pub use compiled_grammar::{CompiledGrammar, MemoryBreakdown};
+pub use grammar_compiler::{CacheEntryInfo, CompileStats, GrammarCompiler};
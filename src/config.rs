@@ -1,9 +1,18 @@
-use crate::ffi::{
-    GetMaxRecursionDepth as FFIGetMaxRecursionDepth,
-    GetSerializationVersion as FFIGetSerializationVersion,
-    SetMaxRecursionDepth as FFISetMaxRecursionDepth,
+use std::sync::Mutex;
+
+use crate::{
+    DeserializeError,
+    ffi::{
+        GetMaxRecursionDepth as FFIGetMaxRecursionDepth,
+        GetSerializationVersion as FFIGetSerializationVersion,
+        SetMaxRecursionDepth as FFISetMaxRecursionDepth,
+    },
 };
 
+/// Serializes the writes of the maximum recursion depth done through this crate, so that
+/// `swap_max_recursion_depth` reads and replaces the value in one step.
+static MAX_RECURSION_DEPTH_LOCK: Mutex<()> = Mutex::new(());
+
 /// Get the serialization version number. The current version is "v13".
 ///
 /// Returns
@@ -14,6 +23,39 @@ pub fn get_serialization_version() -> String {
     FFIGetSerializationVersion().to_string()
 }
 
+/// Get the serialization version embedded in the `__VERSION__` field of a JSON string produced
+/// by `serialize_json` of `Grammar`, `CompiledGrammar` or `TokenizerInfo`.
+///
+/// Serialized objects are not migrated between versions: when the embedded version differs
+/// from `get_serialization_version()`, `deserialize_json` fails with
+/// `DeserializeError::VersionMismatch`. Checking the version first lets a cache of
+/// serialized grammars tell stale entries apart and rebuild them from their source.
+///
+/// # Parameters
+///
+/// - `json_string`: The serialized JSON string.
+///
+/// # Returns
+///
+/// The embedded serialization version, e.g. "v13".
+///
+/// # Errors
+///
+/// - `DeserializeError::InvalidJson` when the string is not valid JSON.
+/// - `DeserializeError::Format` when it has no string `__VERSION__` field.
+pub fn get_serialized_version(
+    json_string: &str
+) -> Result<String, DeserializeError> {
+    let value: serde_json::Value = serde_json::from_str(json_string)
+        .map_err(|e| DeserializeError::InvalidJson(e.to_string()))?;
+    match value.get("__VERSION__") {
+        Some(serde_json::Value::String(version)) => Ok(version.clone()),
+        _ => Err(DeserializeError::Format(
+            "The JSON string has no string \"__VERSION__\" field".to_string(),
+        )),
+    }
+}
+
 /// Get the maximum allowed recursion depth. The depth is shared per process.
 ///
 /// The maximum recursion depth is determined in the following order:
@@ -38,5 +80,32 @@ pub fn get_max_recursion_depth() -> i32 {
 /// max_recursion_depth : int
 ///     The maximum allowed recursion depth.
 pub fn set_max_recursion_depth(max_recursion_depth: i32) {
+    let _guard = MAX_RECURSION_DEPTH_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
     FFISetMaxRecursionDepth(max_recursion_depth)
 }
+
+/// Set the maximum allowed recursion depth and return the previous one, e.g. to restore it
+/// after a compilation that needs a different limit.
+///
+/// The depth is a global setting shared by every thread of the process, so changing it
+/// affects all the compilations and matchers running at the same time. Reading and replacing
+/// the value is atomic with respect to the other calls of `set_max_recursion_depth` and
+/// `swap_max_recursion_depth`.
+///
+/// # Parameters
+///
+/// - `max_recursion_depth`: The maximum allowed recursion depth.
+///
+/// # Returns
+///
+/// The maximum recursion depth before the call.
+pub fn swap_max_recursion_depth(max_recursion_depth: i32) -> i32 {
+    let _guard = MAX_RECURSION_DEPTH_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let previous = FFIGetMaxRecursionDepth();
+    FFISetMaxRecursionDepth(max_recursion_depth);
+    previous
+}
@@ -0,0 +1,230 @@
+#ifndef XGRAMMAR_RS_CXX_UTILS_BINARY_JSON_H_
+#define XGRAMMAR_RS_CXX_UTILS_BINARY_JSON_H_
+
+#include <picojson.h>
+
+#include <cstdint>
+#include <cstring>
+#include <stdexcept>
+#include <string>
+#include <vector>
+
+namespace cxx_utils {
+
+// A compact binary encoding of the picojson values produced by xgrammar's reflection-based
+// serializer. It stores the same tree as the JSON serialization, so the deserialization
+// checks (version, format) are shared, but skips text formatting and parsing.
+//
+// Layout: a 4-byte magic, a format version byte, then the value. Each value starts with a
+// tag byte. Integers and lengths are LEB128 varints (integers zigzag-encoded), doubles are
+// 8 little-endian bytes, and strings and containers are length-prefixed.
+namespace binary_json {
+
+constexpr uint8_t kFormatVersion = 1;
+
+enum Tag : uint8_t {
+  kNull = 0,
+  kFalse = 1,
+  kTrue = 2,
+  kInt64 = 3,
+  kDouble = 4,
+  kString = 5,
+  kArray = 6,
+  kObject = 7,
+};
+
+inline void write_varint(std::vector<uint8_t>* out, uint64_t value) {
+  while (value >= 0x80) {
+    out->push_back(static_cast<uint8_t>(value | 0x80));
+    value >>= 7;
+  }
+  out->push_back(static_cast<uint8_t>(value));
+}
+
+inline void write_u64(std::vector<uint8_t>* out, uint64_t value) {
+  for (int i = 0; i < 8; ++i) {
+    out->push_back(static_cast<uint8_t>(value >> (8 * i)));
+  }
+}
+
+inline void write_string(std::vector<uint8_t>* out, const std::string& value) {
+  write_varint(out, value.size());
+  out->insert(out->end(), value.begin(), value.end());
+}
+
+inline void write_value(std::vector<uint8_t>* out, const picojson::value& value) {
+  if (value.is<picojson::null>()) {
+    out->push_back(kNull);
+  } else if (value.is<bool>()) {
+    out->push_back(value.get<bool>() ? kTrue : kFalse);
+  } else if (value.is<int64_t>()) {
+    out->push_back(kInt64);
+    int64_t number = value.get<int64_t>();
+    uint64_t zigzag = (static_cast<uint64_t>(number) << 1) ^ static_cast<uint64_t>(number >> 63);
+    write_varint(out, zigzag);
+  } else if (value.is<double>()) {
+    out->push_back(kDouble);
+    double number = value.get<double>();
+    uint64_t bits;
+    std::memcpy(&bits, &number, sizeof(bits));
+    write_u64(out, bits);
+  } else if (value.is<std::string>()) {
+    out->push_back(kString);
+    write_string(out, value.get<std::string>());
+  } else if (value.is<picojson::array>()) {
+    const auto& array = value.get<picojson::array>();
+    out->push_back(kArray);
+    write_varint(out, array.size());
+    for (const auto& item : array) {
+      write_value(out, item);
+    }
+  } else {
+    const auto& object = value.get<picojson::object>();
+    out->push_back(kObject);
+    write_varint(out, object.size());
+    for (const auto& [key, item] : object) {
+      write_string(out, key);
+      write_value(out, item);
+    }
+  }
+}
+
+class Reader {
+ public:
+  Reader(const uint8_t* data, size_t len) : data_(data), len_(len) {}
+
+  bool AtEnd() const { return pos_ == len_; }
+
+  const uint8_t* Take(size_t count) {
+    if (len_ - pos_ < count) {
+      throw std::runtime_error("unexpected end of the binary data");
+    }
+    const uint8_t* result = data_ + pos_;
+    pos_ += count;
+    return result;
+  }
+
+  uint8_t ReadU8() { return *Take(1); }
+
+  uint64_t ReadVarint() {
+    uint64_t value = 0;
+    for (int shift = 0; shift < 64; shift += 7) {
+      uint8_t byte = ReadU8();
+      value |= static_cast<uint64_t>(byte & 0x7f) << shift;
+      if ((byte & 0x80) == 0) {
+        return value;
+      }
+    }
+    throw std::runtime_error("invalid varint in the binary data");
+  }
+
+  // Reads a length and checks that at least that many bytes remain, as every element takes
+  // at least one byte.
+  size_t ReadLength() {
+    uint64_t size = ReadVarint();
+    if (size > len_ - pos_) {
+      throw std::runtime_error("unexpected end of the binary data");
+    }
+    return static_cast<size_t>(size);
+  }
+
+  uint64_t ReadU64() {
+    const uint8_t* bytes = Take(8);
+    uint64_t value = 0;
+    for (int i = 0; i < 8; ++i) {
+      value |= static_cast<uint64_t>(bytes[i]) << (8 * i);
+    }
+    return value;
+  }
+
+  std::string ReadString() {
+    size_t size = ReadLength();
+    const uint8_t* bytes = Take(size);
+    return std::string(reinterpret_cast<const char*>(bytes), size);
+  }
+
+  picojson::value ReadValue() {
+    switch (ReadU8()) {
+      case kNull:
+        return picojson::value();
+      case kFalse:
+        return picojson::value(false);
+      case kTrue:
+        return picojson::value(true);
+      case kInt64: {
+        uint64_t zigzag = ReadVarint();
+        return picojson::value(static_cast<int64_t>((zigzag >> 1) ^ (~(zigzag & 1) + 1)));
+      }
+      case kDouble: {
+        uint64_t bits = ReadU64();
+        double number;
+        std::memcpy(&number, &bits, sizeof(number));
+        return picojson::value(number);
+      }
+      case kString:
+        return picojson::value(ReadString());
+      case kArray: {
+        size_t size = ReadLength();
+        picojson::array array;
+        array.reserve(size);
+        for (size_t i = 0; i < size; ++i) {
+          array.push_back(ReadValue());
+        }
+        return picojson::value(std::move(array));
+      }
+      case kObject: {
+        size_t size = ReadLength();
+        picojson::object object;
+        for (size_t i = 0; i < size; ++i) {
+          std::string key = ReadString();
+          object[key] = ReadValue();
+        }
+        return picojson::value(std::move(object));
+      }
+      default:
+        throw std::runtime_error("invalid value tag in the binary data");
+    }
+  }
+
+ private:
+  const uint8_t* data_;
+  size_t len_;
+  size_t pos_ = 0;
+};
+
+// Encodes `value` with the given 4-byte magic.
+inline std::vector<uint8_t> encode(const char (&magic)[5], const picojson::value& value) {
+  std::vector<uint8_t> out(magic, magic + 4);
+  out.push_back(kFormatVersion);
+  write_value(&out, value);
+  return out;
+}
+
+// Decodes data produced by `encode` with the same magic. Throws std::runtime_error if the
+// data is not in that format.
+inline picojson::value decode(const char (&magic)[5], const uint8_t* data, size_t len) {
+  Reader reader(data, len);
+  if (len < 4 || std::memcmp(reader.Take(4), magic, 4) != 0) {
+    throw std::runtime_error(
+        "the data does not start with the magic \"" + std::string(magic) + "\""
+    );
+  }
+  uint8_t version = reader.ReadU8();
+  if (version != kFormatVersion) {
+    throw std::runtime_error(
+        "unsupported binary format version " + std::to_string(version) + ", expected " +
+        std::to_string(kFormatVersion)
+    );
+  }
+  picojson::value value = reader.ReadValue();
+  if (!reader.AtEnd()) {
+    throw std::runtime_error("trailing bytes after the binary data");
+  }
+  return value;
+}
+
+} // namespace binary_json
+
+} // namespace cxx_utils
+
+#endif // XGRAMMAR_RS_CXX_UTILS_BINARY_JSON_H_
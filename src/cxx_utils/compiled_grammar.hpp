@@ -1,11 +1,16 @@
 #ifndef XGRAMMAR_RS_CXX_UTILS_COMPILED_GRAMMAR_H_
 #define XGRAMMAR_RS_CXX_UTILS_COMPILED_GRAMMAR_H_
 
+#include <cstddef>
 #include <memory>
 #include <string>
 #include <variant>
+#include <vector>
 
 #include "xgrammar/xgrammar.h"
+#include "cpp/compiled_grammar_impl.h"
+#include "cpp/grammar_impl.h"
+#include "cpp/support/memory_size.h"
 
 #include "common.hpp"
 
@@ -44,12 +49,59 @@ compiled_grammar_get_tokenizer_info(const xgrammar::CompiledGrammar& self) {
   return make_unique(self.GetTokenizerInfo());
 }
 
+inline std::unique_ptr<xgrammar::CompiledGrammar> compiled_grammar_clone(
+    const xgrammar::CompiledGrammar& self
+) {
+  return std::make_unique<xgrammar::CompiledGrammar>(self);
+}
+
 inline std::unique_ptr<std::string> compiled_grammar_serialize_json(
     const xgrammar::CompiledGrammar& self
 ) {
   return make_unique(self.SerializeJSON());
 }
 
+// The number of parser states whose adaptive token mask was precomputed.
+inline size_t compiled_grammar_num_cached_states(
+    const xgrammar::CompiledGrammar& self
+) {
+  return self.ImplPtr()->adaptive_token_mask_cache.size();
+}
+
+// The memory usage of the components of the compiled grammar, in bytes: the rules and
+// expressions of the grammar, its FSMs, and the adaptive token mask cache, followed by the part
+// of the cache used by the masks of each rule. The rule names are written to `rule_names_out`.
+inline std::unique_ptr<std::vector<uint64_t>> compiled_grammar_memory_breakdown(
+    const xgrammar::CompiledGrammar& self,
+    std::vector<std::string>& rule_names_out
+) {
+  // Unqualified calls, so that the overloads of the xgrammar types are found by ADL.
+  using xgrammar::MemorySize;
+  const auto& impl = *self.ImplPtr();
+  const auto& grammar = *impl.grammar.ImplPtr();
+  uint64_t fsm_bytes = MemorySize(grammar.complete_fsm) + MemorySize(grammar.per_rule_fsms);
+  uint64_t grammar_bytes = MemorySize(grammar) - fsm_bytes;
+
+  auto result = std::make_unique<std::vector<uint64_t>>();
+  result->reserve(3 + grammar.NumRules());
+  result->push_back(grammar_bytes);
+  result->push_back(fsm_bytes);
+  result->push_back(MemorySize(impl.adaptive_token_mask_cache));
+  result->resize(3 + grammar.NumRules(), 0);
+  // Each cache entry takes the size of the entry plus the heap memory of the mask, the same as
+  // in the total.
+  for (const auto& entry : impl.adaptive_token_mask_cache) {
+    (*result)[3 + entry.first.rule_id] += sizeof(entry) + MemorySize(entry.second);
+  }
+
+  rule_names_out.clear();
+  rule_names_out.reserve(grammar.NumRules());
+  for (int32_t i = 0; i < grammar.NumRules(); ++i) {
+    rule_names_out.push_back(grammar.GetRule(i).name);
+  }
+  return result;
+}
+
 } // namespace cxx_utils
 
 #endif // XGRAMMAR_RS_CXX_UTILS_COMPILED_GRAMMAR_H_
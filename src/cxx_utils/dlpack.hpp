@@ -32,6 +32,37 @@ std::unique_ptr<DLTensor> make_tensor(
   );
 }
 
+// Field accessors so that Rust can check a tensor before handing it to
+// XGrammar.
+
+inline bool tensor_has_data(const DLTensor& tensor) {
+  return tensor.data != nullptr;
+}
+
+inline int32_t tensor_device_type(const DLTensor& tensor) {
+  return static_cast<int32_t>(tensor.device.device_type);
+}
+
+inline DLDataType tensor_dtype(const DLTensor& tensor) { return tensor.dtype; }
+
+inline int32_t tensor_ndim(const DLTensor& tensor) { return tensor.ndim; }
+
+inline int64_t tensor_shape(const DLTensor& tensor, int32_t dim) {
+  return tensor.shape[dim];
+}
+
+inline bool tensor_has_strides(const DLTensor& tensor) {
+  return tensor.strides != nullptr;
+}
+
+inline int64_t tensor_stride(const DLTensor& tensor, int32_t dim) {
+  return tensor.strides[dim];
+}
+
+inline uint64_t tensor_byte_offset(const DLTensor& tensor) {
+  return tensor.byte_offset;
+}
+
 } // namespace cxx_utils
 
 #endif // XGRAMMAR_RS_CXX_UTILS_DLPACK_H_
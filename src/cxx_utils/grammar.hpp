@@ -2,8 +2,10 @@
 #define XGRAMMAR_RS_CXX_UTILS_GRAMMAR_H_
 
 #include <memory>
+#include <algorithm>
 #include <cstdint>
 #include <exception>
+#include <functional>
 #include <optional>
 #include <string>
 #include <utility>
@@ -11,6 +13,7 @@
 
 #include "xgrammar/grammar.h"
 #include "xgrammar/tokenizer_info.h"
+#include "cpp/grammar_impl.h"
 
 #include "common.hpp"
 
@@ -229,6 +232,205 @@ inline std::unique_ptr<std::string> grammar_serialize_json(
   return make_unique(self.SerializeJSON());
 }
 
+// Walks the rules and expressions of `Grammar::Impl` directly: the expression
+// types, the operand layout of `kRuleRef`, `kRepeat`, `kSequence`, `kChoices`
+// and `kTagDispatch` (tag/rule pairs followed by
+// `kTagDispatchExtraParameter` trailing parameters) and
+// `lookahead_assertion_id` are internal to xgrammar and must be revisited
+// whenever the submodule is updated.
+//
+// Rules not reachable from the root rule are not an error, they are listed in
+// `unreachable_out`.
+inline bool grammar_validate(
+    const xgrammar::Grammar& self,
+    std::vector<std::string>& unreachable_out,
+    std::string* error_out
+) {
+  using GrammarExprType = xgrammar::Grammar::Impl::GrammarExprType;
+  try {
+    unreachable_out.clear();
+    if (error_out) {
+      error_out->clear();
+    }
+    const auto* impl = self.ImplPtr();
+    if (impl == nullptr || impl->NumRules() == 0) {
+      if (error_out) {
+        *error_out = "grammar is empty: it does not define any rule";
+      }
+      return false;
+    }
+    int32_t num_rules = impl->NumRules();
+    int32_t num_exprs = impl->NumGrammarExprs();
+    int32_t root_rule_id = impl->GetRootRuleId();
+    if (root_rule_id < 0 || root_rule_id >= num_rules) {
+      if (error_out) {
+        *error_out = "root rule (id " + std::to_string(root_rule_id) +
+                     ") is not defined in the grammar";
+      }
+      return false;
+    }
+    std::vector<std::vector<int32_t>> rule_refs(num_rules);
+    for (int32_t rule_id = 0; rule_id < num_rules; ++rule_id) {
+      const auto& rule = impl->GetRule(rule_id);
+      std::vector<int32_t> pending = {rule.body_expr_id};
+      if (rule.lookahead_assertion_id != -1) {
+        pending.push_back(rule.lookahead_assertion_id);
+      }
+      while (!pending.empty()) {
+        int32_t expr_id = pending.back();
+        pending.pop_back();
+        if (expr_id < 0 || expr_id >= num_exprs) {
+          if (error_out) {
+            *error_out = "rule `" + rule.name +
+                         "` refers to an undefined expression (id " +
+                         std::to_string(expr_id) + ")";
+          }
+          return false;
+        }
+        auto expr = impl->GetGrammarExpr(expr_id);
+        std::vector<int32_t> referenced_rule_ids;
+        switch (expr.type) {
+          case GrammarExprType::kRuleRef:
+          case GrammarExprType::kRepeat:
+            referenced_rule_ids.push_back(expr[0]);
+            break;
+          case GrammarExprType::kSequence:
+          case GrammarExprType::kChoices:
+            pending.insert(pending.end(), expr.begin(), expr.end());
+            break;
+          case GrammarExprType::kTagDispatch:
+            for (int32_t i = 0;
+                 i + 1 < expr.size() -
+                             xgrammar::Grammar::Impl::TagDispatch::
+                                 kTagDispatchExtraParameter;
+                 i += 2) {
+              referenced_rule_ids.push_back(expr[i + 1]);
+            }
+            break;
+          default:
+            break;
+        }
+        for (int32_t referenced_rule_id : referenced_rule_ids) {
+          if (referenced_rule_id < 0 || referenced_rule_id >= num_rules) {
+            if (error_out) {
+              *error_out = "rule `" + rule.name +
+                           "` references an undefined rule (id " +
+                           std::to_string(referenced_rule_id) + ")";
+            }
+            return false;
+          }
+          rule_refs[rule_id].push_back(referenced_rule_id);
+        }
+      }
+    }
+    std::vector<bool> reachable(num_rules, false);
+    std::vector<int32_t> pending_rules = {root_rule_id};
+    reachable[root_rule_id] = true;
+    while (!pending_rules.empty()) {
+      int32_t rule_id = pending_rules.back();
+      pending_rules.pop_back();
+      for (int32_t referenced_rule_id : rule_refs[rule_id]) {
+        if (!reachable[referenced_rule_id]) {
+          reachable[referenced_rule_id] = true;
+          pending_rules.push_back(referenced_rule_id);
+        }
+      }
+    }
+    for (int32_t rule_id = 0; rule_id < num_rules; ++rule_id) {
+      if (!reachable[rule_id]) {
+        unreachable_out.push_back(impl->GetRule(rule_id).name);
+      }
+    }
+    return true;
+  } catch (const std::exception& e) {
+    if (error_out) {
+      *error_out = e.what();
+    }
+    return false;
+  } catch (...) {
+    if (error_out) {
+      *error_out = "unknown C++ exception";
+    }
+    return false;
+  }
+}
+
+inline int32_t grammar_num_rules(const xgrammar::Grammar& self) {
+  const auto* impl = self.ImplPtr();
+  return impl == nullptr ? 0 : impl->NumRules();
+}
+
+inline std::unique_ptr<std::vector<std::string>> grammar_rule_names(
+    const xgrammar::Grammar& self
+) {
+  auto names = std::make_unique<std::vector<std::string>>();
+  const auto* impl = self.ImplPtr();
+  if (impl == nullptr) {
+    return names;
+  }
+  names->reserve(impl->NumRules());
+  for (int32_t rule_id = 0; rule_id < impl->NumRules(); ++rule_id) {
+    names->push_back(impl->GetRule(rule_id).name);
+  }
+  return names;
+}
+
+// Whether the language of the grammar is empty, i.e. the root rule cannot derive any finite
+// string. Computes the set of productive rules (rules deriving some finite string) as a
+// fixpoint. Lookahead assertions are ignored, so the answer is conservative for them.
+inline bool grammar_is_empty_language(const xgrammar::Grammar& self) {
+  using GrammarExprType = xgrammar::Grammar::Impl::GrammarExprType;
+  const auto* impl = self.ImplPtr();
+  if (impl == nullptr || impl->NumRules() == 0) {
+    return true;
+  }
+  int32_t num_rules = impl->NumRules();
+  int32_t num_exprs = impl->NumGrammarExprs();
+  std::vector<bool> productive(num_rules, false);
+
+  auto is_rule_productive = [&](int32_t rule_id) {
+    return rule_id >= 0 && rule_id < num_rules && productive[rule_id];
+  };
+  std::function<bool(int32_t)> is_expr_productive = [&](int32_t expr_id) {
+    if (expr_id < 0 || expr_id >= num_exprs) {
+      return false;
+    }
+    auto expr = impl->GetGrammarExpr(expr_id);
+    switch (expr.type) {
+      case GrammarExprType::kCharacterClass:
+        // A positive class without ranges matches no character.
+        return expr[0] != 0 || expr.size() > 1;
+      case GrammarExprType::kToken:
+        return expr.size() > 0;
+      case GrammarExprType::kRuleRef:
+        return is_rule_productive(expr[0]);
+      case GrammarExprType::kRepeat:
+        return expr[1] == 0 || is_rule_productive(expr[0]);
+      case GrammarExprType::kSequence:
+        return std::all_of(expr.begin(), expr.end(), is_expr_productive);
+      case GrammarExprType::kChoices:
+        return std::any_of(expr.begin(), expr.end(), is_expr_productive);
+      default:
+        // Byte strings, the empty string, starred classes and tag dispatches always
+        // derive some string.
+        return true;
+    }
+  };
+
+  bool changed = true;
+  while (changed) {
+    changed = false;
+    for (int32_t rule_id = 0; rule_id < num_rules; ++rule_id) {
+      if (!productive[rule_id] &&
+          is_expr_productive(impl->GetRule(rule_id).body_expr_id)) {
+        productive[rule_id] = true;
+        changed = true;
+      }
+    }
+  }
+  return !is_rule_productive(impl->GetRootRuleId());
+}
+
 } // namespace cxx_utils
 
 #endif // XGRAMMAR_RS_CXX_UTILS_GRAMMAR_H_
@@ -8,10 +8,12 @@
 #include <utility>
 #include <vector>
 #include <cstdio>
+#include <algorithm>
 
 #include "xgrammar/xgrammar.h"
 
 #include "common.hpp"
+#include "cpp/support/thread_pool.h"
 
 namespace cxx_utils {
 
@@ -46,6 +48,14 @@ inline std::unique_ptr<xgrammar::GrammarCompiler> make_grammar_compiler(
   }
 }
 
+// A second handle to `compiler`. The handles share the cache and the compilation settings,
+// and the compiler lives until both are dropped.
+inline std::unique_ptr<xgrammar::GrammarCompiler> grammar_compiler_share(
+    const xgrammar::GrammarCompiler& compiler
+) {
+  return std::make_unique<xgrammar::GrammarCompiler>(compiler);
+}
+
 inline std::unique_ptr<xgrammar::CompiledGrammar> compiler_compile_json_schema(
     xgrammar::GrammarCompiler& compiler,
     const std::string& schema,
@@ -96,6 +106,81 @@ inline std::unique_ptr<xgrammar::CompiledGrammar> compiler_compile_json_schema(
   }
 }
 
+// Compiles the schemas on up to `max_threads` threads. Entry i of the result is null and
+// errors_out[i] holds the message if schema i fails to compile; errors_out[i] is empty
+// otherwise.
+inline std::unique_ptr<std::vector<xgrammar::CompiledGrammar>>
+compiler_compile_json_schema_batch(
+    xgrammar::GrammarCompiler& compiler,
+    const std::vector<std::string>& schemas,
+    bool any_whitespace,
+    bool has_indent,
+    int32_t indent,
+    bool has_separators,
+    const std::string& separator_comma,
+    const std::string& separator_colon,
+    bool strict_mode,
+    bool has_max_whitespace_cnt,
+    int32_t max_whitespace_cnt,
+    int32_t max_threads,
+    std::vector<std::string>& errors_out
+) {
+  std::optional<int> indent_opt =
+      has_indent ? std::optional<int>(indent) : std::nullopt;
+  std::optional<std::pair<std::string, std::string>> sep_opt =
+      has_separators ? std::optional<std::pair<std::string, std::string>>(
+                           std::make_pair(separator_comma, separator_colon)
+                       )
+                     : std::nullopt;
+  std::optional<int> max_whitespace_cnt_opt =
+      has_max_whitespace_cnt ? std::optional<int>(max_whitespace_cnt)
+                             : std::nullopt;
+
+  auto results = std::make_unique<std::vector<xgrammar::CompiledGrammar>>(
+      schemas.size(), xgrammar::CompiledGrammar(xgrammar::NullObj{})
+  );
+  errors_out.assign(schemas.size(), std::string());
+  auto compile_one = [&](size_t i) {
+    try {
+      (*results)[i] = compiler.CompileJSONSchema(
+          schemas[i],
+          any_whitespace,
+          indent_opt,
+          sep_opt,
+          strict_mode,
+          max_whitespace_cnt_opt
+      );
+    } catch (const std::exception& e) {
+      errors_out[i] = e.what();
+    } catch (...) {
+      errors_out[i] = "unknown C++ exception";
+    }
+  };
+
+  size_t num_threads = std::min(
+      static_cast<size_t>(std::max(max_threads, 1)), schemas.size()
+  );
+  if (num_threads <= 1) {
+    for (size_t i = 0; i < schemas.size(); ++i) {
+      compile_one(i);
+    }
+  } else {
+    xgrammar::ThreadPool thread_pool(num_threads);
+    for (size_t i = 0; i < schemas.size(); ++i) {
+      thread_pool.Execute([&compile_one, i]() { compile_one(i); });
+    }
+    thread_pool.Join();
+  }
+  return results;
+}
+
+inline std::unique_ptr<xgrammar::CompiledGrammar> compiled_grammar_vec_take(
+    std::vector<xgrammar::CompiledGrammar>& vec,
+    size_t index
+) {
+  return std::make_unique<xgrammar::CompiledGrammar>(std::move(vec[index]));
+}
+
 inline std::unique_ptr<xgrammar::CompiledGrammar> compiler_compile_builtin_json(
     xgrammar::GrammarCompiler& compiler,
     std::string* error_out
@@ -1,11 +1,13 @@
 #ifndef XGRAMMAR_RS_CXX_UTILS_MATCHER_H_
 #define XGRAMMAR_RS_CXX_UTILS_MATCHER_H_
 
+#include <bitset>
 #include <cstddef>
 #include <cstdint>
 #include <exception>
 #include <optional>
 #include <vector>
+#include <stdexcept>
 #include <string>
 
 #include "xgrammar/xgrammar.h"
@@ -67,6 +69,9 @@ make_batch_grammar_matcher(int32_t max_threads, std::string* error_out) {
     if (error_out) {
       error_out->clear();
     }
+    if (max_threads < 0) {
+      return std::make_unique<xgrammar::BatchGrammarMatcher>(std::string("auto"));
+    }
     return std::make_unique<xgrammar::BatchGrammarMatcher>(max_threads);
   } catch (const std::exception& e) {
     if (error_out) {
@@ -187,6 +192,30 @@ inline std::unique_ptr<std::string> grammar_matcher_find_jump_forward_string(
   return make_unique(self.FindJumpForwardString());
 }
 
+inline bool grammar_matcher_rollback(
+    xgrammar::GrammarMatcher& self,
+    int32_t num_tokens,
+    std::string* error_out
+) {
+  try {
+    if (error_out) {
+      error_out->clear();
+    }
+    self.Rollback(num_tokens);
+    return true;
+  } catch (const std::exception& e) {
+    if (error_out) {
+      *error_out = e.what();
+    }
+    return false;
+  } catch (...) {
+    if (error_out) {
+      *error_out = "unknown C++ exception";
+    }
+    return false;
+  }
+}
+
 inline std::unique_ptr<std::string> grammar_matcher_debug_print_internal_state(
     const xgrammar::GrammarMatcher& self
 ) {
@@ -202,6 +231,209 @@ inline bool grammar_matcher_fill_next_token_bitmask(
   return self.FillNextTokenBitmask(next_token_bitmask, next, debug_print);
 }
 
+// Fills row `index` of the bitmask like grammar_matcher_fill_next_token_bitmask,
+// but reports C++ exceptions in `error_out` instead of aborting. The tensor is
+// checked on the Rust side. Returns whether the bitmask was filled.
+inline bool grammar_matcher_try_fill_next_token_bitmask(
+    xgrammar::GrammarMatcher& self,
+    DLTensor* bitmask,
+    int32_t index,
+    bool debug_print,
+    bool& need_apply,
+    std::string* error_out
+) {
+  try {
+    if (error_out) {
+      error_out->clear();
+    }
+    need_apply = self.FillNextTokenBitmask(bitmask, index, debug_print);
+    return true;
+  } catch (const std::exception& e) {
+    if (error_out) {
+      *error_out = e.what();
+    }
+  } catch (...) {
+    if (error_out) {
+      *error_out = "unknown C++ exception";
+    }
+  }
+  return false;
+}
+
+// Returns row `index` of an int32 bitmask of shape (ceil(vocab_size / 32),) or
+// (batch_size, ceil(vocab_size / 32)). Throws when the shape or the index do not
+// match, or when the bitmask is not compact: like `check_bitmask` on the Rust
+// side, a byte offset or strides other than the contiguous ones are rejected.
+inline uint32_t* token_bitmask_row(
+    const DLTensor* bitmask, int32_t index, int32_t vocab_size
+) {
+  if (bitmask == nullptr || bitmask->data == nullptr) {
+    throw std::invalid_argument("The bitmask tensor has no data");
+  }
+  if (bitmask->dtype.code != kDLInt || bitmask->dtype.bits != 32 ||
+      bitmask->dtype.lanes != 1) {
+    throw std::invalid_argument("The bitmask must be int32");
+  }
+  int64_t row_size = (static_cast<int64_t>(vocab_size) + 31) / 32;
+  int32_t ndim = bitmask->ndim;
+  if (ndim != 1 && ndim != 2) {
+    throw std::invalid_argument(
+        "The bitmask must have 1 or 2 dimensions, but has " +
+        std::to_string(ndim)
+    );
+  }
+  if (bitmask->shape[ndim - 1] != row_size) {
+    throw std::invalid_argument(
+        "The bitmask rows must have " + std::to_string(row_size) +
+        " elements for vocab size " + std::to_string(vocab_size) + ", but have " +
+        std::to_string(bitmask->shape[ndim - 1])
+    );
+  }
+  int64_t num_rows = ndim == 1 ? 1 : bitmask->shape[0];
+  if (index < 0 || index >= num_rows) {
+    throw std::invalid_argument(
+        "The index " + std::to_string(index) + " is out of the " +
+        std::to_string(num_rows) + " rows of the bitmask"
+    );
+  }
+  if (bitmask->byte_offset != 0) {
+    throw std::invalid_argument(
+        "The bitmask must have no byte offset, but has " +
+        std::to_string(bitmask->byte_offset)
+    );
+  }
+  if (bitmask->strides != nullptr &&
+      (bitmask->strides[ndim - 1] != 1 ||
+       (ndim == 2 && bitmask->strides[0] != row_size))) {
+    throw std::invalid_argument("The bitmask must be contiguous");
+  }
+  return static_cast<uint32_t*>(bitmask->data) + index * row_size;
+}
+
+// Clears the bits of `token_ids` in row `index` of the bitmask, and sets
+// `changed` to whether any of the bits was set. Returns false with the error in
+// `error_out`, leaving the bitmask unchanged, when the bitmask is invalid (see
+// token_bitmask_row) or a token id is out of [0, vocab_size).
+inline bool token_bitmask_clear_tokens(
+    DLTensor* bitmask,
+    int32_t index,
+    int32_t vocab_size,
+    const int32_t* token_ids_ptr,
+    size_t token_ids_len,
+    bool& changed,
+    std::string* error_out
+) {
+  try {
+    if (error_out) {
+      error_out->clear();
+    }
+    uint32_t* row = token_bitmask_row(bitmask, index, vocab_size);
+    for (size_t i = 0; i < token_ids_len; ++i) {
+      if (token_ids_ptr[i] < 0 || token_ids_ptr[i] >= vocab_size) {
+        throw std::out_of_range(
+            "The token id " + std::to_string(token_ids_ptr[i]) +
+            " is out of the vocabulary of size " + std::to_string(vocab_size)
+        );
+      }
+    }
+    changed = false;
+    for (size_t i = 0; i < token_ids_len; ++i) {
+      uint32_t bit = 1u << (token_ids_ptr[i] % 32);
+      uint32_t& block = row[token_ids_ptr[i] / 32];
+      changed |= (block & bit) != 0;
+      block &= ~bit;
+    }
+    return true;
+  } catch (const std::exception& e) {
+    if (error_out) {
+      *error_out = e.what();
+    }
+  } catch (...) {
+    if (error_out) {
+      *error_out = "unknown C++ exception";
+    }
+  }
+  return false;
+}
+
+// Sets `count` to the number of tokens set in row `index` of the bitmask; the
+// bits past the vocabulary in the last block are not tokens. Returns false with
+// the error in `error_out` when the bitmask is invalid (see token_bitmask_row).
+inline bool token_bitmask_count_tokens(
+    const DLTensor* bitmask,
+    int32_t index,
+    int32_t vocab_size,
+    size_t& count,
+    std::string* error_out
+) {
+  try {
+    if (error_out) {
+      error_out->clear();
+    }
+    const uint32_t* row = token_bitmask_row(bitmask, index, vocab_size);
+    count = 0;
+    int32_t full_blocks = vocab_size / 32;
+    for (int32_t i = 0; i < full_blocks; ++i) {
+      count += std::bitset<32>(row[i]).count();
+    }
+    if (vocab_size % 32 != 0) {
+      uint32_t valid = (1u << (vocab_size % 32)) - 1;
+      count += std::bitset<32>(row[full_blocks] & valid).count();
+    }
+    return true;
+  } catch (const std::exception& e) {
+    if (error_out) {
+      *error_out = e.what();
+    }
+  } catch (...) {
+    if (error_out) {
+      *error_out = "unknown C++ exception";
+    }
+  }
+  return false;
+}
+
+// Returns the ids of the tokens set in row `index` of the bitmask, in increasing
+// order; the bits past the vocabulary in the last block are not tokens. Returns
+// nullptr with the error in `error_out` when the bitmask is invalid (see
+// token_bitmask_row).
+inline std::unique_ptr<std::vector<int32_t>> token_bitmask_accepted_tokens(
+    const DLTensor* bitmask,
+    int32_t index,
+    int32_t vocab_size,
+    std::string* error_out
+) {
+  try {
+    if (error_out) {
+      error_out->clear();
+    }
+    const uint32_t* row = token_bitmask_row(bitmask, index, vocab_size);
+    auto result = std::make_unique<std::vector<int32_t>>();
+    for (int32_t block = 0; block * 32 < vocab_size; ++block) {
+      uint32_t bits = row[block];
+      for (int32_t bit = 0; bits != 0; ++bit, bits >>= 1) {
+        int32_t token_id = block * 32 + bit;
+        if (token_id >= vocab_size) {
+          break;
+        }
+        if (bits & 1u) {
+          result->push_back(token_id);
+        }
+      }
+    }
+    return result;
+  } catch (const std::exception& e) {
+    if (error_out) {
+      *error_out = e.what();
+    }
+  } catch (...) {
+    if (error_out) {
+      *error_out = "unknown C++ exception";
+    }
+  }
+  return nullptr;
+}
+
 inline std::unique_ptr<xgrammar::GrammarMatcher> grammar_matcher_fork(
     const xgrammar::GrammarMatcher& self
 ) {
@@ -3,6 +3,7 @@
 #include <string>
 #include <vector>
 #include <exception>
+#include <stdexcept>
 #include <optional>
 #include <algorithm>
 #include <memory>
@@ -104,6 +105,84 @@ inline std::unique_ptr<std::vector<int32_t>> get_masked_tokens_from_bitmask(
   }
 }
 
+// Returns the rejected token ids of every row as `[len_0, ids_0..., len_1, ids_1...]`, or
+// nullptr with error_out set when the bitmask does not match `batch_size`.
+inline std::unique_ptr<std::vector<int32_t>> get_masked_tokens_all_rows(
+    const DLTensor* bitmask,
+    int32_t vocab_size,
+    int32_t batch_size,
+    std::string* error_out
+) {
+  try {
+    if (error_out) {
+      error_out->clear();
+    }
+    if (bitmask->device.device_type != kDLCPU) {
+      throw std::invalid_argument("bitmask must be on CPU");
+    }
+    if (bitmask->dtype.code != kDLInt || bitmask->dtype.bits != 32 ||
+        bitmask->dtype.lanes != 1) {
+      throw std::invalid_argument("bitmask must be an int32 tensor");
+    }
+    if (bitmask->ndim != 1 && bitmask->ndim != 2) {
+      throw std::invalid_argument(
+          "bitmask must be a 1D or 2D tensor, but got " +
+          std::to_string(bitmask->ndim) + " dimensions"
+      );
+    }
+    if (vocab_size < 0 || batch_size < 0) {
+      throw std::invalid_argument("vocab_size and batch_size must be non-negative");
+    }
+    int64_t num_rows = bitmask->ndim == 2 ? bitmask->shape[0] : 1;
+    if (num_rows != batch_size) {
+      throw std::invalid_argument(
+          "bitmask has " + std::to_string(num_rows) + " rows, but batch_size is " +
+          std::to_string(batch_size)
+      );
+    }
+    int64_t row_words = bitmask->shape[bitmask->ndim - 1];
+    int64_t required_words = (static_cast<int64_t>(vocab_size) + 31) / 32;
+    if (row_words < required_words) {
+      throw std::invalid_argument(
+          "bitmask rows have " + std::to_string(row_words) + " int32 words, but vocab_size " +
+          std::to_string(vocab_size) + " needs " + std::to_string(required_words)
+      );
+    }
+    if (bitmask->strides && bitmask->strides[bitmask->ndim - 1] != 1) {
+      throw std::invalid_argument("bitmask rows must be contiguous");
+    }
+    int64_t row_stride =
+        bitmask->ndim == 2 && bitmask->strides ? bitmask->strides[0] : row_words;
+
+    const auto* data = reinterpret_cast<const uint32_t*>(
+        static_cast<const char*>(bitmask->data) + bitmask->byte_offset
+    );
+    std::vector<int32_t> output;
+    for (int64_t row = 0; row < num_rows; ++row) {
+      const uint32_t* row_data = data + row * row_stride;
+      size_t len_pos = output.size();
+      output.push_back(0);
+      for (int32_t id = 0; id < vocab_size; ++id) {
+        if (((row_data[id / 32] >> (id % 32)) & 1) == 0) {
+          output.push_back(id);
+        }
+      }
+      output[len_pos] = static_cast<int32_t>(output.size() - len_pos - 1);
+    }
+    return make_unique(std::move(output));
+  } catch (const std::exception& e) {
+    if (error_out) {
+      *error_out = e.what();
+    }
+    return nullptr;
+  } catch (...) {
+    if (error_out) {
+      *error_out = "unknown C++ exception";
+    }
+    return nullptr;
+  }
+}
+
 struct SingleTokenResult {
   bool is_single;
   int32_t token_id;
@@ -10,7 +10,10 @@
 #include <vector>
 
 #include "xgrammar/xgrammar.h"
+#include "cpp/support/json_serializer.h"
+#include "cpp/tokenizer_info_impl.h"
 
+#include "binary_json.hpp"
 #include "common.hpp"
 
 namespace cxx_utils {
@@ -69,11 +72,41 @@ inline std::unique_ptr<xgrammar::TokenizerInfo> make_tokenizer_info(
 inline std::unique_ptr<xgrammar::TokenizerInfo>
 tokenizer_info_from_vocab_and_metadata(
     const std::vector<std::string>& encoded_vocab,
-    const std::string& metadata
+    const std::string& metadata,
+    std::string* error_out
 ) {
-  return make_unique(
-      xgrammar::TokenizerInfo::FromVocabAndMetadata(encoded_vocab, metadata)
-  );
+  try {
+    if (error_out) {
+      error_out->clear();
+    }
+    return make_unique(
+        xgrammar::TokenizerInfo::FromVocabAndMetadata(encoded_vocab, metadata)
+    );
+  } catch (const std::exception& e) {
+    if (error_out) {
+      *error_out = e.what();
+    }
+    return nullptr;
+  } catch (...) {
+    if (error_out) {
+      *error_out = "unknown C++ exception";
+    }
+    return nullptr;
+  }
+}
+
+// Copies share the underlying implementation, so this does not copy the vocabulary.
+inline std::unique_ptr<xgrammar::TokenizerInfo> tokenizer_info_clone(
+    const xgrammar::TokenizerInfo& self
+) {
+  return std::make_unique<xgrammar::TokenizerInfo>(self);
+}
+
+inline bool tokenizer_info_shares_impl(
+    const xgrammar::TokenizerInfo& self,
+    const xgrammar::TokenizerInfo& other
+) {
+  return self.ImplPtr() == other.ImplPtr();
 }
 
 inline std::unique_ptr<std::string> tokenizer_info_serialize_json(
@@ -126,6 +159,72 @@ tokenizer_info_deserialize_json_or_error(
   }
 }
 
+constexpr char kTokenizerInfoMagic[5] = "XGTI";
+
+inline std::unique_ptr<std::vector<uint8_t>> tokenizer_info_serialize_bytes(
+    const xgrammar::TokenizerInfo& self
+) {
+  picojson::value value = xgrammar::AutoSerializeJSONValue(self);
+  xgrammar::SerializeVersion::Apply(&value.get<picojson::object>());
+  return make_unique(binary_json::encode(kTokenizerInfoMagic, value));
+}
+
+inline std::unique_ptr<xgrammar::TokenizerInfo>
+tokenizer_info_deserialize_bytes_or_error(
+    const uint8_t* data,
+    size_t len,
+    int32_t* error_kind,
+    std::string* error_out
+) {
+  auto set_error = [&](const xgrammar::SerializationError& err) {
+    if (error_out) {
+      std::visit([&](const auto& e) { *error_out = e.what(); }, err);
+    }
+    if (error_kind) {
+      *error_kind = serialization_error_kind(err);
+    }
+  };
+  try {
+    picojson::value value;
+    try {
+      value = binary_json::decode(kTokenizerInfoMagic, data, len);
+    } catch (const std::runtime_error& e) {
+      set_error(xgrammar::ConstructDeserializeError(e.what(), "TokenizerInfo"));
+      return nullptr;
+    }
+    if (!value.is<picojson::object>()) {
+      set_error(xgrammar::ConstructDeserializeError("expect an object", "TokenizerInfo"));
+      return nullptr;
+    }
+    if (auto err = xgrammar::SerializeVersion::Check(value.get<picojson::object>())) {
+      set_error(*err);
+      return nullptr;
+    }
+    xgrammar::TokenizerInfo tokenizer_info{xgrammar::NullObj()};
+    if (auto err = xgrammar::AutoDeserializeJSONValue(&tokenizer_info, value, "TokenizerInfo")) {
+      set_error(*err);
+      return nullptr;
+    }
+    return make_unique(std::move(tokenizer_info));
+  } catch (const std::exception& e) {
+    if (error_out) {
+      *error_out = e.what();
+    }
+    if (error_kind) {
+      *error_kind = 0;
+    }
+    return nullptr;
+  } catch (...) {
+    if (error_out) {
+      *error_out = "unknown C++ exception";
+    }
+    if (error_kind) {
+      *error_kind = 0;
+    }
+    return nullptr;
+  }
+}
+
 inline bool detect_metadata_from_hf(
     const std::string& backend_str,
     std::string* metadata_out,
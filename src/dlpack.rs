@@ -1,4 +1,12 @@
-use crate::{DLDataType, c_void, ffi, utils::tie_enum_with_ffi};
+//! DLPack interop: the device and data type descriptors, and constructors for the tensors
+//! passed to XGrammar.
+
+use std::ops::{Deref, DerefMut};
+
+use crate::{
+    CxxUniquePtr, DLDataType, DLTensor, c_void, ffi, get_bitmask_shape,
+    utils::tie_enum_with_ffi,
+};
 
 /// DLPack data type code enum (`DLDataTypeCode`)
 #[derive(PartialEq, Eq, Clone, Debug, Hash)]
@@ -121,3 +129,187 @@ impl ffi::DLTensor {
         }
     }
 }
+
+/// The CPU device, the device of the bitmasks that the matchers fill.
+pub fn cpu_device() -> DLDevice {
+    DLDevice {
+        device_type: DLDeviceType::kDLCPU,
+        device_id: 0,
+    }
+}
+
+/// The CUDA device with the given ordinal.
+pub fn cuda_device(ordinal: i32) -> DLDevice {
+    DLDevice {
+        device_type: DLDeviceType::kDLCUDA,
+        device_id: ordinal,
+    }
+}
+
+/// The data type of token bitmasks, int32, as returned by `GetBitmaskDLType`.
+pub fn bitmask_dtype() -> DLDataType {
+    ffi::GetBitmaskDLType()
+}
+
+/// A `DLTensor` built by `DLTensorBuilder`. It owns the shape and strides arrays the tensor
+/// points to, but not the data.
+///
+/// It dereferences to `CxxUniquePtr<DLTensor>`, so it can be passed wherever a tensor is
+/// expected. Do not copy the raw `DLTensor` out of it: its shape and strides pointers dangle
+/// once the value is dropped.
+pub struct OwnedDLTensor {
+    tensor: CxxUniquePtr<DLTensor>,
+    // Boxed so that the pointers stored in the tensor stay valid when this struct moves.
+    _shape: Box<[i64]>,
+    _strides: Box<[i64]>,
+}
+
+impl Deref for OwnedDLTensor {
+    type Target = CxxUniquePtr<DLTensor>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tensor
+    }
+}
+
+impl DerefMut for OwnedDLTensor {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.tensor
+    }
+}
+
+/// Builder of a `DLTensor` over memory owned elsewhere, e.g. a bitmask allocated on a GPU.
+///
+/// The device defaults to `cpu_device()`, the data type to `bitmask_dtype()`, the strides to
+/// the compact row-major strides of the shape, and the byte offset to 0.
+///
+/// Note that the matcher functions taking a bitmask tensor, such as
+/// `fill_next_token_bitmask`, require a CPU tensor.
+#[derive(Clone, Debug)]
+pub struct DLTensorBuilder {
+    data: *mut c_void,
+    shape: Vec<i64>,
+    strides: Option<Vec<i64>>,
+    device: DLDevice,
+    dtype: DLDataType,
+    byte_offset: u64,
+}
+
+impl DLTensorBuilder {
+    /// Start building a tensor of the given shape over `data`.
+    pub fn new(
+        data: *mut c_void,
+        shape: &[i64],
+    ) -> Self {
+        Self {
+            data,
+            shape: shape.to_vec(),
+            strides: None,
+            device: cpu_device(),
+            dtype: bitmask_dtype(),
+            byte_offset: 0,
+        }
+    }
+
+    /// Start building a token bitmask tensor of shape (batch_size, ceil(vocab_size / 32))
+    /// over `data`.
+    pub fn bitmask(
+        data: *mut c_void,
+        batch_size: usize,
+        vocab_size: usize,
+    ) -> Self {
+        let (batch_size, bitmask_size) =
+            get_bitmask_shape(batch_size, vocab_size);
+        Self::new(data, &[batch_size as i64, bitmask_size as i64])
+    }
+
+    /// Set the device the data lives on, e.g. `cuda_device(0)`.
+    pub fn device(
+        mut self,
+        device: DLDevice,
+    ) -> Self {
+        self.device = device;
+        self
+    }
+
+    /// Set the data type of the elements.
+    pub fn dtype(
+        mut self,
+        dtype: DLDataType,
+    ) -> Self {
+        self.dtype = dtype;
+        self
+    }
+
+    /// Set the strides, in elements, one per dimension.
+    pub fn strides(
+        mut self,
+        strides: &[i64],
+    ) -> Self {
+        self.strides = Some(strides.to_vec());
+        self
+    }
+
+    /// Set the offset in bytes from `data` to the first element.
+    pub fn byte_offset(
+        mut self,
+        byte_offset: u64,
+    ) -> Self {
+        self.byte_offset = byte_offset;
+        self
+    }
+
+    /// Build the tensor.
+    ///
+    /// # Returns
+    ///
+    /// The tensor, owning its shape and strides.
+    ///
+    /// # Errors
+    ///
+    /// When the number of strides differs from the number of dimensions.
+    ///
+    /// # Safety
+    ///
+    /// `data` must point to memory on the tensor's device that holds the elements described by
+    /// the shape, strides, data type and byte offset, and stays valid as long as the tensor is
+    /// used.
+    pub unsafe fn build(self) -> Result<OwnedDLTensor, String> {
+        let strides = match self.strides {
+            Some(strides) if strides.len() != self.shape.len() => {
+                return Err(format!(
+                    "The tensor has {} dimensions, but {} strides",
+                    self.shape.len(),
+                    strides.len()
+                ));
+            },
+            Some(strides) => strides,
+            None => {
+                let mut strides = vec![1i64; self.shape.len()];
+                for i in (1..self.shape.len()).rev() {
+                    strides[i - 1] = strides[i] * self.shape[i];
+                }
+                strides
+            },
+        };
+        let mut shape = self.shape.into_boxed_slice();
+        let mut strides = strides.into_boxed_slice();
+        // SAFETY: the data is up to the caller; shape and strides are owned by the result.
+        let tensor = unsafe {
+            DLTensor::new(
+                self.data,
+                self.device,
+                shape.len() as i32,
+                self.dtype,
+                shape.as_mut_ptr(),
+                strides.as_mut_ptr(),
+                self.byte_offset,
+            )
+        };
+        Ok(OwnedDLTensor {
+            tensor,
+            _shape: shape,
+            _strides: strides,
+        })
+    }
+}
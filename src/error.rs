@@ -1,4 +1,5 @@
-//! Typed error categories for XGrammar's deserialization and structural-tag entry points.
+//! Typed error categories for XGrammar's deserialization, structural-tag, JSON schema and
+//! token-acceptance entry points.
 
 use std::fmt;
 
@@ -97,3 +98,93 @@ impl fmt::Display for StructuralTagError {
 }
 
 impl std::error::Error for StructuralTagError {}
+
+/// Error returned when checking, converting or compiling a JSON schema fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JsonSchemaError {
+    /// The schema was not valid JSON.
+    InvalidJson(String),
+    /// The schema has bounds no instance can satisfy, such as `minLength` greater than
+    /// `maxLength`. `detail` names the keywords, their values and the JSON pointer of the
+    /// subschema.
+    ContradictorySchema {
+        detail: String,
+    },
+    /// A `pattern`, e.g. in `propertyNames`, or a `patternProperties` key is not a valid
+    /// regex. `detail` is the parse error, with the JSON pointer of the keyword.
+    InvalidSchemaRegex {
+        pattern: String,
+        detail: String,
+    },
+    /// The schema uses a keyword in a way the grammar cannot enforce, such as `contains` with
+    /// a subschema. The message names the keyword and the JSON pointer of the subschema.
+    Unsupported(String),
+    /// The schema is valid JSON but not a valid JSON schema, e.g. a keyword has a value of the
+    /// wrong type, and was rejected by the rewrites or by the converter.
+    Invalid(String),
+    /// The compilation did not end within the time budget of
+    /// `GrammarCompiler::compile_json_schema_with_timeout`.
+    Timeout,
+    /// An unexpected error not covered by the categories above.
+    Other(String),
+}
+
+impl fmt::Display for JsonSchemaError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Self::InvalidJson(message) => f.write_str(message),
+            Self::ContradictorySchema {
+                detail,
+            } => write!(f, "Contradictory JSON schema: {}", detail),
+            Self::InvalidSchemaRegex {
+                pattern,
+                detail,
+            } => write!(
+                f,
+                "Invalid regex {:?} in the JSON schema: {}",
+                pattern, detail
+            ),
+            Self::Unsupported(message)
+            | Self::Invalid(message)
+            | Self::Other(message) => f.write_str(message),
+            Self::Timeout => f.write_str("timeout"),
+        }
+    }
+}
+
+impl std::error::Error for JsonSchemaError {}
+
+/// Reason why `GrammarMatcher::try_accept_token` rejected a token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcceptRejectReason {
+    /// The token id is not in `[0, vocab_size)`.
+    OutOfRange,
+    /// The token is a special token, which the matcher never accepts.
+    SpecialToken,
+    /// The token does not match the grammar. A stop token is also rejected this way when the
+    /// grammar cannot terminate yet.
+    GrammarMismatch,
+    /// The matcher has already terminated after accepting a stop token.
+    AlreadyTerminated,
+}
+
+impl fmt::Display for AcceptRejectReason {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        f.write_str(match self {
+            Self::OutOfRange => "the token id is out of range",
+            Self::SpecialToken => "the token is a special token",
+            Self::GrammarMismatch => "the token does not match the grammar",
+            Self::AlreadyTerminated => {
+                "the matcher has terminated after accepting a stop token"
+            },
+        })
+    }
+}
+
+impl std::error::Error for AcceptRejectReason {}
@@ -0,0 +1,234 @@
+//! The named parts of a grammar built with `Grammar::concat_with_markers`.
+
+use crate::{
+    CompiledGrammar, GrammarCompiler, GrammarMatcher, TokenizerInfo, VocabType,
+};
+
+use super::Grammar;
+
+/// The names of the parts of a grammar built with [`Grammar::concat_with_markers`], to
+/// locate the parts in a string the grammar matched, e.g. the prefix and the JSON body of a
+/// tool call.
+///
+/// The part grammars are compiled once with an empty vocabulary when the markers are
+/// created. A [`PartTracker`] then records where each part ends while the string is
+/// matched, in a single pass.
+pub struct Markers {
+    names: Vec<String>,
+    parts: Vec<CompiledGrammar>,
+}
+
+impl Markers {
+    pub(super) fn new(parts: &[(&str, &Grammar)]) -> Result<Self, String> {
+        let tokenizer_info =
+            TokenizerInfo::new::<&str>(&[], VocabType::RAW, &None, false)?;
+        let mut compiler = GrammarCompiler::new(&tokenizer_info, 1, false, -1)?;
+        Ok(Self {
+            names: parts.iter().map(|&(name, _)| name.to_string()).collect(),
+            parts: parts
+                .iter()
+                .map(|&(_, grammar)| compiler.compile_grammar(grammar))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// The names of the parts, in order.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Create a tracker that records the part boundaries of the bytes it accepts. Feed it
+    /// the same input as the matcher of the concatenated grammar, e.g. the decoded tokens,
+    /// and query [`PartTracker::part_ends`] once the matcher completes.
+    ///
+    /// # Errors
+    ///
+    /// When a matcher for a part cannot be created.
+    pub fn tracker(&self) -> Result<PartTracker<'_>, String> {
+        let initial = self
+            .parts
+            .iter()
+            .map(|part| GrammarMatcher::new(part, None, true, 0))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut tracker = PartTracker {
+            names: &self.names,
+            initial,
+            splits: Vec::new(),
+            len: 0,
+        };
+        tracker.start_part(0, Vec::new());
+        Ok(tracker)
+    }
+
+    /// Locate the end of each part in `matched`, a complete string matched by the
+    /// concatenated grammar. Shorthand for feeding `matched` to a [`Self::tracker`].
+    ///
+    /// # Parameters
+    ///
+    /// - `matched`: The string matched by the concatenated grammar.
+    ///
+    /// # Returns
+    ///
+    /// The name of each part with the byte offset in `matched` where the part ends, in
+    /// order, as in [`PartTracker::part_ends`].
+    ///
+    /// # Errors
+    ///
+    /// When the concatenated grammar does not match the whole of `matched`.
+    pub fn part_ends(
+        &self,
+        matched: &str,
+    ) -> Result<Vec<(&str, usize)>, String> {
+        let mut tracker = self.tracker()?;
+        if !tracker.accept_bytes(matched.as_bytes()) {
+            return Err(format!(
+                "The string does not match the parts, rejected at byte {}",
+                tracker.len - 1
+            ));
+        }
+        tracker.part_ends()
+    }
+}
+
+/// Records where each part of a grammar built with [`Grammar::concat_with_markers`] ends
+/// while bytes are matched, created by [`Markers::tracker`].
+///
+/// The tracker follows every way of splitting the accepted bytes into parts that is still
+/// viable, with one matcher per part being matched and offset where it started. Each
+/// accepted byte advances these matchers by one step, so a string is tracked in a single
+/// pass; when the part boundaries are unambiguous, as for a literal prefix followed by a
+/// JSON body, only one or two splits are live at a time.
+pub struct PartTracker<'a> {
+    names: &'a [String],
+    // A matcher in the initial state for each part, forked to start matching the part.
+    initial: Vec<GrammarMatcher>,
+    splits: Vec<Split>,
+    len: usize,
+}
+
+// One way to split the accepted bytes: the part being matched, the ends of the parts
+// before it, and the matcher of the part from its start.
+struct Split {
+    part: usize,
+    ends: Vec<usize>,
+    matcher: GrammarMatcher,
+}
+
+impl Split {
+    fn start(&self) -> usize {
+        self.ends.last().copied().unwrap_or(0)
+    }
+}
+
+impl<'a> PartTracker<'a> {
+    /// Accept the next bytes of the matched string.
+    ///
+    /// # Returns
+    ///
+    /// Whether the accepted bytes are still a prefix of a string of the concatenated
+    /// grammar. After `false`, no split is viable and `part_ends` fails.
+    pub fn accept_bytes(
+        &mut self,
+        bytes: &[u8],
+    ) -> bool {
+        for &byte in bytes {
+            if self.splits.is_empty() {
+                return false;
+            }
+            self.splits
+                .retain_mut(|split| split.matcher.accept_bytes(&[byte], false));
+            self.len += 1;
+            let next: Vec<(usize, Vec<usize>)> = self
+                .splits
+                .iter()
+                .filter(|split| {
+                    split.part + 1 < self.initial.len()
+                        && split.matcher.is_completed()
+                })
+                .map(|split| {
+                    let mut ends = split.ends.clone();
+                    ends.push(self.len);
+                    (split.part + 1, ends)
+                })
+                .collect();
+            for (part, ends) in next {
+                self.start_part(part, ends);
+            }
+        }
+        !self.splits.is_empty()
+    }
+
+    /// The end of each part in the bytes accepted so far, which must form a complete string
+    /// of the concatenated grammar.
+    ///
+    /// When a part could end at several offsets, the earliest one after which the
+    /// following parts match the rest of the bytes is used.
+    ///
+    /// # Returns
+    ///
+    /// The name of each part with the byte offset where the part ends, in order. The last
+    /// part ends at the number of accepted bytes.
+    ///
+    /// # Errors
+    ///
+    /// When the accepted bytes are not a complete string of the concatenated grammar.
+    pub fn part_ends(&self) -> Result<Vec<(&'a str, usize)>, String> {
+        let last = self.initial.len() - 1;
+        let ends = self
+            .splits
+            .iter()
+            .filter(|split| split.part == last && split.matcher.is_completed())
+            .map(|split| &split.ends)
+            .min()
+            .ok_or_else(|| {
+                format!(
+                    "The {} accepted bytes do not complete the parts",
+                    self.len
+                )
+            })?;
+        Ok(self
+            .names
+            .iter()
+            .map(String::as_str)
+            .zip(ends.iter().copied().chain([self.len]))
+            .collect())
+    }
+
+    // Start matching `part` at the current offset, after parts ending at `ends`, and the
+    // following parts as well while the started part can be empty. A part started at the
+    // same offset by another split is matched once, keeping the earliest ends.
+    fn start_part(
+        &mut self,
+        part: usize,
+        ends: Vec<usize>,
+    ) {
+        let existing = self
+            .splits
+            .iter_mut()
+            .find(|split| split.part == part && split.start() == self.len);
+        let completed = match existing {
+            Some(split) => {
+                if ends >= split.ends {
+                    return;
+                }
+                split.ends = ends.clone();
+                split.matcher.is_completed()
+            },
+            None => {
+                let matcher = self.initial[part].fork();
+                let completed = matcher.is_completed();
+                self.splits.push(Split {
+                    part,
+                    ends: ends.clone(),
+                    matcher,
+                });
+                completed
+            },
+        };
+        if completed && part + 1 < self.initial.len() {
+            let mut ends = ends;
+            ends.push(self.len);
+            self.start_part(part + 1, ends);
+        }
+    }
+}
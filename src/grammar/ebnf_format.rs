@@ -0,0 +1,197 @@
+//! Pretty-printing of the EBNF text produced by `Grammar::to_string_ebnf`.
+
+/// Options of `Grammar::to_ebnf_pretty`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EbnfFormatOptions {
+    /// The preferred maximum line width. Lines are broken between the elements of a
+    /// sequence; a single element longer than the width is kept on its own line.
+    pub max_line_width: usize,
+    /// Whether to put each alternative of a rule with several alternatives on its own line.
+    pub alternatives_on_separate_lines: bool,
+}
+
+impl Default for EbnfFormatOptions {
+    fn default() -> Self {
+        Self {
+            max_line_width: 80,
+            alternatives_on_separate_lines: true,
+        }
+    }
+}
+
+const INDENT: &str = "    ";
+const ALTERNATIVE_PREFIX: &str = "  | ";
+
+/// Reformat EBNF text with one rule per line. The result parses to the same grammar.
+pub(crate) fn format_ebnf(
+    ebnf: &str,
+    options: &EbnfFormatOptions,
+) -> String {
+    let mut output = String::with_capacity(ebnf.len() * 2);
+    for line in ebnf.lines() {
+        match line.split_once(" ::= ") {
+            Some((name, body)) => format_rule(name, body, options, &mut output),
+            None => output.push_str(line),
+        }
+        output.push('\n');
+    }
+    output
+}
+
+fn format_rule(
+    name: &str,
+    body: &str,
+    options: &EbnfFormatOptions,
+    output: &mut String,
+) {
+    let mut elements = split_top_level(body, ' ');
+    let lookahead = match elements.last() {
+        Some(last) if elements.len() > 1 && last.starts_with("(=") => {
+            elements.pop()
+        },
+        _ => None,
+    };
+
+    let alternatives = match elements.as_slice() {
+        [group] => split_top_level(strip_parens(group), '|'),
+        _ => vec![],
+    };
+
+    let head = format!("{} ::= ", name);
+    if alternatives.len() > 1 && options.alternatives_on_separate_lines {
+        output.push_str(&head);
+        output.push_str("(\n");
+        for (i, alternative) in alternatives.iter().enumerate() {
+            let prefix = if i == 0 {
+                INDENT
+            } else {
+                ALTERNATIVE_PREFIX
+            };
+            push_wrapped(
+                output,
+                prefix,
+                &split_top_level(strip_parens(alternative), ' '),
+                options.max_line_width,
+            );
+            output.push('\n');
+        }
+        output.push(')');
+        if let Some(lookahead) = lookahead {
+            output.push(' ');
+            output.push_str(lookahead);
+        }
+    } else {
+        if alternatives.len() == 1 {
+            elements = split_top_level(alternatives[0], ' ');
+        }
+        elements.extend(lookahead);
+        push_wrapped(output, &head, &elements, options.max_line_width);
+    }
+}
+
+/// Remove redundant parentheses around the whole of `text`, e.g. `((a b))` -> `a b`.
+fn strip_parens(mut text: &str) -> &str {
+    while text.starts_with('(')
+        && !text.starts_with("(=")
+        && matching_paren_end(text) == Some(text.len() - 1)
+    {
+        text = text[1..text.len() - 1].trim();
+    }
+    text
+}
+
+/// Append `elements` separated by spaces after `prefix`, starting a new line indented by
+/// `INDENT` whenever the next element would exceed `max_line_width`.
+fn push_wrapped(
+    output: &mut String,
+    prefix: &str,
+    elements: &[&str],
+    max_line_width: usize,
+) {
+    output.push_str(prefix);
+    let mut line_width = prefix.chars().count();
+    let mut line_is_empty = true;
+    for element in elements {
+        let width = element.chars().count();
+        if !line_is_empty {
+            if line_width + 1 + width > max_line_width {
+                output.push('\n');
+                output.push_str(INDENT);
+                line_width = INDENT.len();
+            } else {
+                output.push(' ');
+                line_width += 1;
+            }
+        }
+        output.push_str(element);
+        line_width += width;
+        line_is_empty = false;
+    }
+}
+
+/// Split `text` at occurrences of `separator` that are outside of parentheses, repetition
+/// ranges, string literals and character classes. The parts are trimmed and empty parts are dropped.
+fn split_top_level(
+    text: &str,
+    separator: char,
+) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut in_class = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if in_string || in_class => escaped = true,
+            '"' if !in_class => in_string = !in_string,
+            '[' if !in_string => in_class = true,
+            ']' if in_class => in_class = false,
+            '(' | '{' if !in_string && !in_class => depth += 1,
+            ')' | '}' if !in_string && !in_class => {
+                depth = depth.saturating_sub(1)
+            },
+            _ if c == separator && depth == 0 && !in_string && !in_class => {
+                parts.push(text[start..i].trim());
+                start = i + c.len_utf8();
+            },
+            _ => {},
+        }
+    }
+    parts.push(text[start..].trim());
+    parts.retain(|part| !part.is_empty());
+    parts
+}
+
+/// The byte index of the parenthesis that closes the one at the start of `text`.
+fn matching_paren_end(text: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut in_class = false;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if in_string || in_class => escaped = true,
+            '"' if !in_class => in_string = !in_string,
+            '[' if !in_string => in_class = true,
+            ']' if in_class => in_class = false,
+            '(' if !in_string && !in_class => depth += 1,
+            ')' if !in_string && !in_class => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            },
+            _ => {},
+        }
+    }
+    None
+}
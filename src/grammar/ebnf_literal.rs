@@ -0,0 +1,84 @@
+//! Building EBNF expressions that match given text, for the grammars constructed from Rust
+//! strings such as `Grammar::literal_ci`.
+
+/// The EBNF string literal matching `text` exactly, e.g. `"a\"b"` for `a"b`.
+pub(crate) fn string_literal(text: &str) -> String {
+    let mut literal = String::with_capacity(text.len() + 2);
+    literal.push('"');
+    for c in text.chars() {
+        push_escaped(c, &['"'], &mut literal);
+    }
+    literal.push('"');
+    literal
+}
+
+/// An EBNF sequence matching `text` with every letter in either case. Runs of characters
+/// without case are kept as string literals; each cased character becomes a character class
+/// of its lowercase, uppercase and original forms, e.g. `[tT] [rR] "-" [uU]`.
+///
+/// Only case mappings to a single character are used, so a character whose other case has a
+/// different length, such as `ß` (uppercase `SS`), only matches as written.
+pub(crate) fn case_insensitive_literal(text: &str) -> String {
+    let mut elements = Vec::new();
+    let mut run = String::new();
+    for c in text.chars() {
+        let variants = case_variants(c);
+        if variants.len() == 1 {
+            run.push(c);
+            continue;
+        }
+        if !run.is_empty() {
+            elements.push(string_literal(&run));
+            run.clear();
+        }
+        let mut class = String::from("[");
+        for variant in variants {
+            push_escaped(variant, &['[', ']', '^', '-'], &mut class);
+        }
+        class.push(']');
+        elements.push(class);
+    }
+    if !run.is_empty() || elements.is_empty() {
+        elements.push(string_literal(&run));
+    }
+    elements.join(" ")
+}
+
+// `c` followed by its lowercase and uppercase forms that are single characters different
+// from the forms before them.
+fn case_variants(c: char) -> Vec<char> {
+    let mut variants = vec![c];
+    for mapped in [single_char(c.to_lowercase()), single_char(c.to_uppercase())]
+        .into_iter()
+        .flatten()
+    {
+        if !variants.contains(&mapped) {
+            variants.push(mapped);
+        }
+    }
+    variants
+}
+
+fn single_char(mut chars: impl Iterator<Item = char>) -> Option<char> {
+    let c = chars.next()?;
+    chars.next().is_none().then_some(c)
+}
+
+// Push `c`, escaping the backslash, the `special` characters and the control characters,
+// which are all below U+00A0.
+fn push_escaped(
+    c: char,
+    special: &[char],
+    out: &mut String,
+) {
+    if c == '\\' || special.contains(&c) {
+        out.push('\\');
+        out.push(c);
+    } else if c.is_control() {
+        // `\u` takes exactly four digits, while `\x` would also consume the hex digits that
+        // follow.
+        out.push_str(&format!("\\u{:04x}", c as u32));
+    } else {
+        out.push(c);
+    }
+}
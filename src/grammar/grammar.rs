@@ -1,7 +1,35 @@
+use std::{borrow::Borrow, collections::HashMap};
+
+use super::{
+    EbnfFormatOptions, JsonSchemaOptions, Markers,
+    ebnf_format::format_ebnf,
+    ebnf_literal,
+    json_schema_formats::apply_custom_formats,
+    json_schema_prepare::{
+        PreparedJsonSchema, check_contradictions, check_keywords,
+        check_regexes, prepare_json_schema,
+    },
+    regex_flavor,
+    regex_flavor::{RegexFlavor, RegexMatchMode},
+};
 use crate::{
-    CxxUniquePtr, DeserializeError, StructuralTagError, TokenizerInfo, ffi,
+    CxxUniquePtr, DeserializeError, JsonSchemaError, StructuralTagError,
+    TokenizerInfo, ffi,
 };
 
+// The builtin JSON grammar without whitespace between the tokens: the same values, strings
+// and numbers, with the top-level value an object or an array.
+const COMPACT_JSON_EBNF: &str = r#"
+root ::= object | array
+value ::= object | array | string | number | "true" | "false" | "null"
+object ::= "{" (member ("," member)*)? "}"
+member ::= string ":" value
+array ::= "[" (value ("," value)*)? "]"
+string ::= "\"" ([^"\\\x00-\x1F] | "\\" escape)* "\""
+escape ::= ["\\/bfnrt] | "u" [A-Fa-f0-9] [A-Fa-f0-9] [A-Fa-f0-9] [A-Fa-f0-9]
+number ::= "-"? ("0" | [1-9] [0-9]*) ("." [0-9]+)? ([eE] [+\-]? [0-9]+)?
+"#;
+
 /// This class represents a grammar object in XGrammar, and can be used later in the
 /// grammar-guided generation.
 ///
@@ -24,6 +52,24 @@ impl core::fmt::Display for Grammar {
     }
 }
 
+/// Parse an EBNF grammar whose root rule is named `root`. Use `Grammar::from_ebnf` for a
+/// custom root rule name.
+impl core::str::FromStr for Grammar {
+    type Err = String;
+
+    fn from_str(ebnf_string: &str) -> Result<Self, Self::Err> {
+        Self::from_ebnf(ebnf_string, "root")
+    }
+}
+
+impl TryFrom<&str> for Grammar {
+    type Error = String;
+
+    fn try_from(ebnf_string: &str) -> Result<Self, Self::Error> {
+        ebnf_string.parse()
+    }
+}
+
 impl Grammar {
     /// Print the BNF grammar to a string, in EBNF format.
     ///
@@ -34,6 +80,108 @@ impl Grammar {
         ffi::grammar_to_string(&self.inner).to_string()
     }
 
+    /// Print the grammar in EBNF format, laid out for reading. Each rule starts on a new
+    /// line, long rules are wrapped, and optionally each alternative gets its own line. The
+    /// result parses to the same grammar as `to_string_ebnf`.
+    ///
+    /// # Parameters
+    ///
+    /// - `options`: The formatting options. See `EbnfFormatOptions`.
+    ///
+    /// # Returns
+    ///
+    /// The formatted grammar string.
+    pub fn to_ebnf_pretty(
+        &self,
+        options: &EbnfFormatOptions,
+    ) -> String {
+        format_ebnf(&self.to_string_ebnf(), options)
+    }
+
+    /// Run cheap structural checks on the grammar without compiling it. The grammar must define
+    /// at least one rule, the root rule must exist, and every rule reference must point to a
+    /// defined rule.
+    ///
+    /// This is much cheaper than `GrammarCompiler::compile_grammar`, which also builds the token
+    /// mask cache, so it is suited for linting user-provided grammars at submission time. It is
+    /// mostly useful for grammars that did not go through the EBNF parser, e.g. ones obtained
+    /// from `deserialize_json`. Rules not reachable from the root rule are not an error, see
+    /// [`Grammar::unreachable_rules`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the first problem found, naming the offending rule.
+    pub fn validate(&self) -> Result<(), String> {
+        self.unreachable_rules().map(|_| ())
+    }
+
+    /// The rules that cannot be reached from the root rule, e.g. to warn about them when
+    /// linting a grammar. They do not affect the language of the grammar.
+    ///
+    /// # Returns
+    ///
+    /// The names of the unreachable rules, in rule id order.
+    ///
+    /// # Errors
+    ///
+    /// The errors of [`Grammar::validate`], when the grammar is malformed.
+    pub fn unreachable_rules(&self) -> Result<Vec<String>, String> {
+        let mut unreachable = ffi::new_string_vector();
+        cxx::let_cxx_string!(error_out_cxx = "");
+        let is_valid = unsafe {
+            ffi::grammar_validate(
+                self.ffi_ref(),
+                unreachable.pin_mut(),
+                error_out_cxx.as_mut().get_unchecked_mut(),
+            )
+        };
+        if !is_valid {
+            return Err(error_out_cxx.to_string());
+        }
+        Ok(unreachable
+            .iter()
+            .map(|name| name.to_string_lossy().into_owned())
+            .collect())
+    }
+
+    /// Check whether the language of the grammar is empty, i.e. the grammar accepts no string
+    /// at all. This happens e.g. when a rule references itself without a base case:
+    /// `root ::= "a" root`. A matcher of such a grammar rejects every token.
+    ///
+    /// Lookahead assertions are not taken into account, so a grammar whose lookaheads can
+    /// never be satisfied is still reported as non-empty.
+    ///
+    /// # Returns
+    ///
+    /// Whether the grammar accepts no string.
+    pub fn is_empty_language(&self) -> bool {
+        ffi::grammar_is_empty_language(self.ffi_ref())
+    }
+
+    /// The number of rules of the grammar, including the rules the parser and the JSON
+    /// schema, regex and structural tag converters generate for sub-expressions. Useful to
+    /// reject pathological grammars before compiling them.
+    ///
+    /// # Returns
+    ///
+    /// The number of rules.
+    pub fn rule_count(&self) -> usize {
+        ffi::grammar_num_rules(self.ffi_ref()) as usize
+    }
+
+    /// The names of the rules of the grammar, in rule id order, as they appear in the EBNF
+    /// form of the grammar.
+    ///
+    /// # Returns
+    ///
+    /// The rule names; their number is [`Self::rule_count`].
+    pub fn rule_names(&self) -> Vec<String> {
+        ffi::grammar_rule_names(self.ffi_ref())
+            .iter()
+            .map(|name| name.to_string_lossy().into_owned())
+            .collect()
+    }
+
     /// Construct a grammar from EBNF string. The EBNF string should follow the format
     /// in <https://github.com/ggerganov/llama.cpp/blob/master/grammars/README.md>.
     ///
@@ -49,7 +197,60 @@ impl Grammar {
         ebnf_string: &str,
         root_rule_name: &str,
     ) -> Result<Self, String> {
-        cxx::let_cxx_string!(ebnf_cxx = ebnf_string);
+        Self::from_ebnf_bytes(ebnf_string.as_bytes(), root_rule_name)
+    }
+
+    /// Construct a grammar from EBNF without naming its root rule, e.g. to import GBNF files
+    /// whose start symbol is not called `root`. The root is the rule named `root` if the
+    /// grammar defines one, and the first defined rule otherwise.
+    ///
+    /// # Parameters
+    ///
+    /// - `ebnf_string`: The grammar string in EBNF format.
+    ///
+    /// # Returns
+    ///
+    /// The grammar and the name of the rule chosen as its root. The grammar itself names its
+    /// root rule `root`, like every grammar from `from_ebnf`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the EBNF string defines no rule, is invalid, or parsing fails.
+    pub fn from_ebnf_auto_root(
+        ebnf_string: &str
+    ) -> Result<(Self, String), String> {
+        let mut rule_names = defined_rule_names(ebnf_string);
+        let root_rule_name =
+            match rule_names.clone().find(|&name| name == "root") {
+                Some(root) => root,
+                None => rule_names
+                    .next()
+                    .ok_or("The EBNF string does not define any rule")?,
+            };
+        let grammar = Self::from_ebnf(ebnf_string, root_rule_name)?;
+        Ok((grammar, root_rule_name.to_string()))
+    }
+
+    /// Construct a grammar from EBNF given as raw bytes, e.g. a grammar read from a binary
+    /// buffer. The bytes are passed to the parser without a UTF-8 check on the Rust side; see
+    /// `from_ebnf` for the format.
+    ///
+    /// Bytes that are not valid UTF-8 must still be written as escapes in the grammar text,
+    /// e.g. `"\xff"` or `[\x80-\xff]`: the parser rejects invalid UTF-8 sequences.
+    ///
+    /// # Parameters
+    ///
+    /// - `ebnf`: The grammar in EBNF format.
+    /// - `root_rule_name`: The name of the root rule in the grammar.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the EBNF is invalid or parsing fails.
+    pub fn from_ebnf_bytes(
+        ebnf: &[u8],
+        root_rule_name: &str,
+    ) -> Result<Self, String> {
+        cxx::let_cxx_string!(ebnf_cxx = ebnf);
         cxx::let_cxx_string!(root_rule_name_cxx = root_rule_name);
         cxx::let_cxx_string!(error_out_cxx = "");
         let ffi_ptr = unsafe {
@@ -104,9 +305,53 @@ impl Grammar {
     ///
     /// The constructed grammar.
     ///
+    /// # Array `contains`
+    ///
+    /// The `contains`, `minContains` and `maxContains` keywords are supported when they only
+    /// bound the array length, i.e. when `contains` matches any item (`true` or `{}`), or
+    /// when `minContains` is 0 and there is no `maxContains`. Otherwise the schema is
+    /// rejected, as the grammar cannot count the items matching a subschema.
+    ///
+    /// # Object `additionalProperties`
+    ///
+    /// `additionalProperties` may be a schema, e.g. `{"type": "integer"}` for typed maps.
+    /// The values of the properties not listed in `properties` must then match it, in strict
+    /// and non-strict mode alike; the additional properties are generated after the listed
+    /// ones. A schema-valued `additionalProperties` is rejected together with
+    /// `patternProperties` or `propertyNames`, where the grammar only constrains the property
+    /// names and would accept any value.
+    ///
+    /// # Integer `multipleOf`
+    ///
+    /// `multipleOf` is supported on `"type": "integer"` schemas when it is a positive integer.
+    /// With both a lower and an upper bound (`minimum`, `maximum` or their exclusive forms),
+    /// the multiples between them are enumerated, up to 1024 of them. With at most one bound,
+    /// the grammar matches the multiples in decimal, for a `multipleOf` up to 100 or a power
+    /// of 10, and the bound may only exclude the negative or positive multiples, e.g.
+    /// `"minimum": 0`. Other uses of `multipleOf` are rejected rather than ignored.
+    ///
+    /// # Exclusive bounds
+    ///
+    /// `exclusiveMinimum` and `exclusiveMaximum` are exact on `"type": "integer"` schemas,
+    /// where they are the inclusive bounds one step inside, e.g. `"exclusiveMinimum": 0` is
+    /// `"minimum": 1`. On `"type": "number"` schemas the grammar cannot exclude the bound
+    /// itself, so an exclusive bound is rejected unless an inclusive bound on the same side is
+    /// stricter, e.g. `"minimum": 1` with `"exclusiveMinimum": 0`.
+    ///
     /// # Errors
     ///
-    /// When converting the JSON schema fails, with details about the parsing error.
+    /// - `JsonSchemaError::Invalid` when converting the JSON schema fails, with details about
+    ///   the parsing error.
+    /// - `JsonSchemaError::ContradictorySchema` when a subschema has bounds no instance can
+    ///   satisfy, as returned by [`Grammar::check_json_schema_contradictions`], or when no
+    ///   multiple of a `multipleOf` is between the bounds.
+    /// - `JsonSchemaError::InvalidSchemaRegex` when a `pattern` or a `patternProperties` key
+    ///   is not a valid regex, as returned by [`Grammar::check_json_schema_regexes`].
+    /// - `JsonSchemaError::Unsupported` when the schema uses `multipleOf` in a way the grammar
+    ///   cannot represent, when a number schema has an exclusive bound the grammar cannot
+    ///   enforce, when the schema uses an unsupported form of `contains`, or when a
+    ///   schema-valued `additionalProperties` is combined with `patternProperties` or
+    ///   `propertyNames`.
     pub fn from_json_schema(
         schema: &str,
         any_whitespace: bool,
@@ -115,8 +360,89 @@ impl Grammar {
         strict_mode: bool,
         max_whitespace_cnt: Option<i32>,
         print_converted_ebnf: bool,
-    ) -> Result<Self, String> {
-        cxx::let_cxx_string!(schema_cxx = schema);
+    ) -> Result<Self, JsonSchemaError> {
+        Self::from_json_schema_with_options(
+            schema,
+            &JsonSchemaOptions {
+                any_whitespace,
+                indent,
+                separators: separators.map(|(comma, colon)| {
+                    (comma.as_ref().to_string(), colon.as_ref().to_string())
+                }),
+                strict_mode,
+                max_whitespace_cnt,
+                print_converted_ebnf,
+                ..JsonSchemaOptions::default()
+            },
+        )
+    }
+
+    /// Construct a grammar from JSON schema, with the format parameters of
+    /// [`Grammar::from_json_schema`] and the schema rewrites in `options`, e.g. to resolve
+    /// external `$ref`s, to map custom `format`s to regexes or to allow trailing whitespace.
+    ///
+    /// The EBNF of the result, from [`Grammar::to_string_ebnf`], includes the rewrites done
+    /// before and after the conversion, e.g. for `multipleOf`, unlike
+    /// `testing::json_schema_to_ebnf`. It can be patched, e.g. where the converter handles a
+    /// rule imperfectly, and recompiled with [`Grammar::from_ebnf`] and the root rule `root`.
+    ///
+    /// # Parameters
+    ///
+    /// - `schema`: The schema string.
+    /// - `options`: The format parameters and the rewrites, see [`JsonSchemaOptions`].
+    ///
+    /// # Returns
+    ///
+    /// The constructed grammar.
+    ///
+    /// # Errors
+    ///
+    /// - The errors of [`Grammar::from_json_schema`].
+    /// - `JsonSchemaError::Invalid` when a `$ref` base is not in `options.refs`. The error
+    ///   lists every unresolved `$ref`.
+    /// - `JsonSchemaError::InvalidJson` when a document of `options.refs` is not valid JSON.
+    /// - `JsonSchemaError::Invalid` when a regex of `options.formats` is invalid.
+    /// - `JsonSchemaError::Invalid` with `options.force_defaults`, when a
+    ///   `default` contradicts the `const` or `enum` of its subschema.
+    /// - `JsonSchemaError::Invalid` when `options.max_whitespace_cnt` is not positive.
+    pub fn from_json_schema_with_options(
+        schema: &str,
+        options: &JsonSchemaOptions,
+    ) -> Result<Self, JsonSchemaError> {
+        let rewritten = options.rewrite_schema(schema)?;
+        let schema = rewritten.as_deref().unwrap_or(schema);
+        let prepared = prepare_json_schema(schema)?;
+        let grammar = Self::from_prepared_json_schema(
+            schema,
+            &prepared,
+            options.any_whitespace,
+            options.indent,
+            options.separators(),
+            options.strict_mode,
+            options.max_whitespace_cnt,
+            options.print_converted_ebnf,
+        )?;
+        if !options.trailing_whitespace {
+            return Ok(grammar);
+        }
+        let trailing_whitespace =
+            Self::trailing_whitespace(options.max_whitespace_cnt)
+                .map_err(JsonSchemaError::Invalid)?;
+        Ok(Self::concat(&[grammar, trailing_whitespace]))
+    }
+
+    // `from_json_schema` for a schema already prepared with `prepare_json_schema`.
+    pub(crate) fn from_prepared_json_schema(
+        schema: &str,
+        prepared: &PreparedJsonSchema,
+        any_whitespace: bool,
+        indent: Option<i32>,
+        separators: Option<(impl AsRef<str>, impl AsRef<str>)>,
+        strict_mode: bool,
+        max_whitespace_cnt: Option<i32>,
+        print_converted_ebnf: bool,
+    ) -> Result<Self, JsonSchemaError> {
+        cxx::let_cxx_string!(schema_cxx = prepared.schema(schema));
         let has_indent = indent.is_some();
         let indent_i32: i32 = indent.unwrap_or(0) as i32;
         let has_separators = separators.is_some();
@@ -154,11 +480,146 @@ impl Grammar {
             )
         };
         if ffi_ptr.is_null() {
-            return Err(error_out_cxx.to_string());
+            return Err(JsonSchemaError::Invalid(error_out_cxx.to_string()));
         }
-        Ok(Self {
+        let grammar = Self {
             inner: ffi_ptr,
-        })
+        };
+        if prepared.rewrites_ebnf() {
+            Self::from_ebnf(
+                &prepared.rewrite_ebnf(&grammar.to_string_ebnf()),
+                "root",
+            )
+            .map_err(JsonSchemaError::Other)
+        } else {
+            Ok(grammar)
+        }
+    }
+
+    /// Check a JSON schema for bounds that no instance can satisfy, which machine-generated
+    /// schemas often contain. [`Grammar::from_json_schema`] runs this check before the
+    /// conversion, and fails with the returned error.
+    ///
+    /// The contradictions detected in any subschema are `minItems` greater than `maxItems`,
+    /// `minItems` greater than the number of `prefixItems` when `items` is `false`,
+    /// `minLength` greater than `maxLength`, `minimum` greater than `maximum`, and
+    /// `minProperties` greater than `maxProperties`.
+    ///
+    /// # Parameters
+    ///
+    /// - `schema`: The schema string.
+    ///
+    /// # Errors
+    ///
+    /// - `JsonSchemaError::InvalidJson` when the schema is not valid JSON.
+    /// - `JsonSchemaError::ContradictorySchema` for the first contradiction found, with the
+    ///   keywords, their values and the JSON pointer of the subschema.
+    pub fn check_json_schema_contradictions(
+        schema: &str
+    ) -> Result<(), JsonSchemaError> {
+        check_contradictions(schema)
+    }
+
+    /// Check that the regexes of a JSON schema are valid: the `pattern` of every subschema,
+    /// e.g. of `propertyNames`, and the keys of every `patternProperties`.
+    /// [`Grammar::from_json_schema`] runs this check before the conversion, and fails with
+    /// the returned error, which names the pattern and where it is.
+    ///
+    /// # Parameters
+    ///
+    /// - `schema`: The schema string.
+    ///
+    /// # Errors
+    ///
+    /// - `JsonSchemaError::InvalidJson` when the schema is not valid JSON.
+    /// - `JsonSchemaError::InvalidSchemaRegex` for the first invalid regex found, with the
+    ///   pattern, the parse error and the JSON pointer of the keyword.
+    pub fn check_json_schema_regexes(
+        schema: &str
+    ) -> Result<(), JsonSchemaError> {
+        check_regexes(schema)
+    }
+
+    /// Check a JSON schema for keywords that no JSON schema draft defines, e.g. the typo
+    /// `porperties` for `properties`. The converter ignores unknown keywords, so such a typo
+    /// silently makes the grammar more permissive than intended. This is a separate pass over
+    /// the parsed schema, and [`Grammar::from_json_schema`] does not run it.
+    ///
+    /// Every subschema is checked, at the positions where JSON schema expects one, e.g. the
+    /// values of `properties`, `items` or `anyOf`. The keys of `properties`, `$defs` and the
+    /// other keywords mapping names to subschemas are names, so they are not checked.
+    /// Keywords starting with `x-` are extensions and are allowed.
+    ///
+    /// # Parameters
+    ///
+    /// - `schema`: The schema string.
+    ///
+    /// # Errors
+    ///
+    /// One message per unknown keyword, with the keyword and the JSON pointer of its
+    /// subschema, or the parse error when the schema is not valid JSON.
+    pub fn validate_schema_keywords(schema: &str) -> Result<(), Vec<String>> {
+        check_keywords(schema)
+    }
+
+    /// List the `format` values of a JSON schema that are neither standard nor in
+    /// `formats`, see [`JsonSchemaOptions::formats`]. The converter ignores them, and
+    /// converts their strings as plain strings.
+    ///
+    /// # Parameters
+    ///
+    /// - `schema`: The schema string.
+    /// - `formats`: The custom formats, as in [`JsonSchemaOptions::formats`].
+    ///
+    /// # Returns
+    ///
+    /// The unknown formats, sorted and without duplicates.
+    ///
+    /// # Errors
+    ///
+    /// `JsonSchemaError::InvalidJson` when the schema is not valid JSON.
+    pub fn unknown_json_schema_formats(
+        schema: &str,
+        formats: &HashMap<String, String>,
+    ) -> Result<Vec<String>, JsonSchemaError> {
+        Ok(apply_custom_formats(schema, formats)?.1)
+    }
+
+    /// Construct a grammar from the JSON schema of a Rust type deriving
+    /// `schemars::JsonSchema`. The schema is generated with `schemars::schema_for!`, so the
+    /// grammar follows the type as it evolves. Requires the `schemars` feature.
+    ///
+    /// # Parameters
+    ///
+    /// - `T`: The type whose JSON schema is used.
+    /// - The remaining parameters are the same as in [`Grammar::from_json_schema`].
+    ///
+    /// # Returns
+    ///
+    /// The constructed grammar.
+    ///
+    /// # Errors
+    ///
+    /// When converting the generated JSON schema fails.
+    #[cfg(feature = "schemars")]
+    pub fn from_schemars<T: schemars::JsonSchema>(
+        any_whitespace: bool,
+        indent: Option<i32>,
+        separators: Option<(impl AsRef<str>, impl AsRef<str>)>,
+        strict_mode: bool,
+        max_whitespace_cnt: Option<i32>,
+        print_converted_ebnf: bool,
+    ) -> Result<Self, JsonSchemaError> {
+        let schema = schemars::schema_for!(T);
+        Self::from_json_schema(
+            &schema.as_value().to_string(),
+            any_whitespace,
+            indent,
+            separators,
+            strict_mode,
+            max_whitespace_cnt,
+            print_converted_ebnf,
+        )
     }
 
     /// Create a grammar from a regular expression string.
@@ -197,6 +658,76 @@ impl Grammar {
         })
     }
 
+    /// Create a grammar from a regular expression string of the given dialect.
+    ///
+    /// The converter implements ECMAScript regexes. For the other flavors, the pattern is
+    /// first rewritten to that dialect: named groups become non-capturing groups, the `\A`,
+    /// `\z` and `\Z` anchors at the ends of the pattern become `^` and `$`, and `\x{...}`
+    /// escapes become `\u{...}`. In every flavor, `\d`, `\w` and `\s` match the ASCII
+    /// classes described in `RegexFlavor`.
+    ///
+    /// # Parameters
+    ///
+    /// - `regex_string`: The regular expression pattern to create the grammar from.
+    /// - `flavor`: The dialect of `regex_string`.
+    /// - `print_converted_ebnf`: This method will convert the regex pattern to EBNF first.
+    ///   If this is true, the converted EBNF string will be printed. For debugging purposes.
+    ///
+    /// # Returns
+    ///
+    /// The constructed grammar from the regex pattern.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the regex pattern is invalid or parsing fails, or if it uses a
+    /// construct of its flavor that has no ECMAScript equivalent (e.g. inline flags, atomic
+    /// groups or possessive quantifiers); the message names the construct.
+    pub fn from_regex_with_flavor(
+        regex_string: &str,
+        flavor: RegexFlavor,
+        print_converted_ebnf: bool,
+    ) -> Result<Self, String> {
+        let regex = regex_flavor::normalize_regex(regex_string, flavor)?;
+        Self::from_regex(&regex, print_converted_ebnf)
+    }
+
+    /// Create a grammar from a regular expression string that matches either the whole output
+    /// or a prefix of it.
+    ///
+    /// With `RegexMatchMode::FullMatch`, this is the same as [`Grammar::from_regex`]. With
+    /// `RegexMatchMode::Prefix`, the grammar is the regex followed by any text, so it accepts
+    /// every output that starts with a match of the regex.
+    ///
+    /// # Parameters
+    ///
+    /// - `regex_string`: The regular expression pattern to create the grammar from.
+    /// - `match_mode`: Whether the regex matches the whole output or a prefix of it.
+    /// - `print_converted_ebnf`: This method will convert the regex pattern to EBNF first.
+    ///   If this is true, the converted EBNF string will be printed. For debugging purposes.
+    ///
+    /// # Returns
+    ///
+    /// The constructed grammar from the regex pattern.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the regex pattern is invalid or parsing fails.
+    pub fn from_regex_with_match_mode(
+        regex_string: &str,
+        match_mode: RegexMatchMode,
+        print_converted_ebnf: bool,
+    ) -> Result<Self, String> {
+        let grammar = Self::from_regex(regex_string, print_converted_ebnf)?;
+        match match_mode {
+            RegexMatchMode::FullMatch => Ok(grammar),
+            RegexMatchMode::Prefix => {
+                let any_text = Self::from_ebnf("root ::= [^]*", "root")
+                    .expect("the any-text grammar is valid");
+                Ok(Self::concat(&[grammar, any_text]))
+            },
+        }
+    }
+
     /// Create a grammar from a structural tag. See the Structural Tag Usage in XGrammar
     /// documentation for its usage.
     ///
@@ -256,6 +787,31 @@ impl Grammar {
         })
     }
 
+    /// Create a grammar that matches `text` case-insensitively, e.g. `TRUE`, `true` and
+    /// `True` for `"true"`, without spelling out the character classes by hand.
+    ///
+    /// Letters are matched in their lowercase and uppercase forms, including non-ASCII
+    /// letters such as `é`/`É`. Only case mappings to a single character are used: a
+    /// character whose other case has a different length, such as `ß` (uppercase `SS`), only
+    /// matches as written. Characters without case are matched exactly.
+    ///
+    /// # Parameters
+    ///
+    /// - `text`: The text to match.
+    ///
+    /// # Returns
+    ///
+    /// The grammar matching `text` in any letter case. Combine it with other grammars with
+    /// `concat` or `union`.
+    pub fn literal_ci(text: &str) -> Self {
+        let ebnf = format!(
+            "root ::= {}",
+            ebnf_literal::case_insensitive_literal(text)
+        );
+        Self::from_ebnf(&ebnf, "root")
+            .expect("the case-insensitive literal is valid EBNF")
+    }
+
     /// Get the grammar of standard JSON. This is compatible with the official JSON grammar
     /// specification in <https://www.json.org/json-en.html>.
     ///
@@ -269,6 +825,72 @@ impl Grammar {
         }
     }
 
+    /// Get the grammar of standard JSON, optionally without whitespace between the tokens.
+    ///
+    /// With `allow_whitespace`, this is [`Grammar::builtin_json_grammar`]. Without it, the
+    /// grammar only accepts compact JSON such as `{"a":[1,2]}`, e.g. for outputs where every
+    /// byte counts. Whitespace inside strings is allowed either way. Like the builtin
+    /// grammar, the top-level value must be an object or an array.
+    ///
+    /// # Parameters
+    ///
+    /// - `allow_whitespace`: Whether whitespace is allowed between the tokens of the JSON.
+    ///
+    /// # Returns
+    ///
+    /// The JSON grammar.
+    pub fn builtin_json_grammar_with_options(allow_whitespace: bool) -> Self {
+        if allow_whitespace {
+            return Self::builtin_json_grammar();
+        }
+        Self::from_ebnf(COMPACT_JSON_EBNF, "root")
+            .expect("the compact JSON grammar is valid EBNF")
+    }
+
+    /// Get the grammar of standard JSON, optionally allowing whitespace after the JSON value.
+    ///
+    /// By default, as in [`Grammar::builtin_json_grammar`], the grammar ends with the closing
+    /// bracket of the value. With `trailing_whitespace_allowed`, any number of spaces,
+    /// newlines, carriage returns and tabs may follow it before the stop token.
+    ///
+    /// # Parameters
+    ///
+    /// - `trailing_whitespace_allowed`: Whether whitespace may follow the JSON value.
+    ///
+    /// # Returns
+    ///
+    /// The JSON grammar.
+    pub fn builtin_json_grammar_with_trailing_whitespace(
+        trailing_whitespace_allowed: bool
+    ) -> Self {
+        let grammar = Self::builtin_json_grammar();
+        if !trailing_whitespace_allowed {
+            return grammar;
+        }
+        let trailing_whitespace = Self::trailing_whitespace(None)
+            .expect("the unbounded trailing whitespace grammar is valid EBNF");
+        Self::concat(&[grammar, trailing_whitespace])
+    }
+
+    // The whitespace that may follow a JSON value, at most `max_whitespace_cnt` characters.
+    fn trailing_whitespace(
+        max_whitespace_cnt: Option<i32>
+    ) -> Result<Self, String> {
+        let ebnf = match max_whitespace_cnt {
+            Some(max_whitespace_cnt) if max_whitespace_cnt <= 0 => {
+                return Err(format!(
+                    "max_whitespace_cnt must be positive, but is {}",
+                    max_whitespace_cnt
+                ));
+            },
+            Some(max_whitespace_cnt) => {
+                format!(r"root ::= [ \n\t\r]{{0,{}}}", max_whitespace_cnt)
+            },
+            None => r"root ::= [ \n\t\r]*".to_string(),
+        };
+        Self::from_ebnf(&ebnf, "root")
+    }
+
     /// Create a grammar that matches the concatenation of the grammars in the list. That is
     /// equivalent to using the `+` operator to concatenate the grammars in the list.
     ///
@@ -281,17 +903,9 @@ impl Grammar {
     /// The concatenation of the grammars.
     pub fn concat(grammars: &[Grammar]) -> Self {
         assert!(!grammars.is_empty(), "concat requires at least one grammar");
-        let mut vec = ffi::new_grammar_vector();
-        {
-            let mut vec_pin = vec.pin_mut();
-            ffi::grammar_vec_reserve(vec_pin.as_mut(), grammars.len());
-            for grammar in grammars {
-                ffi::grammar_vec_push(vec_pin.as_mut(), grammar.ffi_ref());
-            }
-        }
-        let ffi_ptr = ffi::grammar_concat(vec.as_ref().unwrap());
+        let vec = Self::grammar_vector_from_iter(grammars).unwrap();
         Self {
-            inner: ffi_ptr,
+            inner: ffi::grammar_concat(vec.as_ref().unwrap()),
         }
     }
 
@@ -307,18 +921,147 @@ impl Grammar {
     /// The union of the grammars.
     pub fn union(grammars: &[Grammar]) -> Self {
         assert!(!grammars.is_empty(), "union requires at least one grammar");
+        let vec = Self::grammar_vector_from_iter(grammars).unwrap();
+        Self {
+            inner: ffi::grammar_union(vec.as_ref().unwrap()),
+        }
+    }
+
+    /// Like [`Grammar::concat`], but takes references, so grammars stored elsewhere can be
+    /// combined without collecting them into a slice of owned grammars first.
+    ///
+    /// # Parameters
+    ///
+    /// - `grammars`: The grammars to create the concatenation of.
+    ///
+    /// # Returns
+    ///
+    /// The concatenation of the grammars.
+    pub fn concat_refs(grammars: &[&Grammar]) -> Self {
+        assert!(!grammars.is_empty(), "concat requires at least one grammar");
+        let vec =
+            Self::grammar_vector_from_iter(grammars.iter().copied()).unwrap();
+        Self {
+            inner: ffi::grammar_concat(vec.as_ref().unwrap()),
+        }
+    }
+
+    /// Like [`Grammar::concat_refs`], with a name for each part, to locate the parts in a
+    /// string the concatenation matched, e.g. where a prefix ends and a JSON body starts.
+    ///
+    /// # Parameters
+    ///
+    /// - `parts`: The name and grammar of each part, in order.
+    ///
+    /// # Returns
+    ///
+    /// The concatenation of the grammars, and the markers locating the parts while matching
+    /// with [`Markers::tracker`], or in a matched string with [`Markers::part_ends`].
+    ///
+    /// # Errors
+    ///
+    /// - When `parts` is empty.
+    /// - When a part grammar fails to compile for the markers.
+    pub fn concat_with_markers(
+        parts: &[(&str, &Grammar)]
+    ) -> Result<(Self, Markers), String> {
+        if parts.is_empty() {
+            return Err("concat requires at least one grammar".to_string());
+        }
+        let markers = Markers::new(parts)?;
+        let grammars: Vec<&Grammar> =
+            parts.iter().map(|&(_, grammar)| grammar).collect();
+        Ok((Self::concat_refs(&grammars), markers))
+    }
+
+    /// Like [`Grammar::union`], but takes references, so grammars stored elsewhere can be
+    /// combined without collecting them into a slice of owned grammars first.
+    ///
+    /// # Parameters
+    ///
+    /// - `grammars`: The grammars to create the union of.
+    ///
+    /// # Returns
+    ///
+    /// The union of the grammars.
+    pub fn union_refs(grammars: &[&Grammar]) -> Self {
+        assert!(!grammars.is_empty(), "union requires at least one grammar");
+        let vec =
+            Self::grammar_vector_from_iter(grammars.iter().copied()).unwrap();
+        Self {
+            inner: ffi::grammar_union(vec.as_ref().unwrap()),
+        }
+    }
+
+    /// Like [`Grammar::concat`], but accepts any iterator of grammars and returns an error
+    /// instead of panicking when it is empty.
+    ///
+    /// # Parameters
+    ///
+    /// - `grammars`: The grammars to create the concatenation of.
+    ///
+    /// # Returns
+    ///
+    /// The concatenation of the grammars.
+    ///
+    /// # Errors
+    ///
+    /// - When `grammars` yields no grammar.
+    pub fn try_concat(
+        grammars: impl IntoIterator<Item = Grammar>
+    ) -> Result<Self, String> {
+        let vec = Self::grammar_vector_from_iter(grammars)?;
+        let ffi_ptr = ffi::grammar_concat(vec.as_ref().unwrap());
+        Ok(Self {
+            inner: ffi_ptr,
+        })
+    }
+
+    /// Like [`Grammar::union`], but accepts any iterator of grammars and returns an error
+    /// instead of panicking when it is empty.
+    ///
+    /// # Parameters
+    ///
+    /// - `grammars`: The grammars to create the union of.
+    ///
+    /// # Returns
+    ///
+    /// The union of the grammars.
+    ///
+    /// # Errors
+    ///
+    /// - When `grammars` yields no grammar.
+    pub fn try_union(
+        grammars: impl IntoIterator<Item = Grammar>
+    ) -> Result<Self, String> {
+        let vec = Self::grammar_vector_from_iter(grammars)?;
+        let ffi_ptr = ffi::grammar_union(vec.as_ref().unwrap());
+        Ok(Self {
+            inner: ffi_ptr,
+        })
+    }
+
+    fn grammar_vector_from_iter<G: Borrow<Grammar>>(
+        grammars: impl IntoIterator<Item = G>
+    ) -> Result<cxx::UniquePtr<cxx::CxxVector<ffi::Grammar>>, String> {
+        let grammars = grammars.into_iter();
         let mut vec = ffi::new_grammar_vector();
+        let mut count = 0usize;
         {
             let mut vec_pin = vec.pin_mut();
-            ffi::grammar_vec_reserve(vec_pin.as_mut(), grammars.len());
-            for g in grammars {
-                ffi::grammar_vec_push(vec_pin.as_mut(), g.ffi_ref());
+            ffi::grammar_vec_reserve(vec_pin.as_mut(), grammars.size_hint().0);
+            for grammar in grammars {
+                ffi::grammar_vec_push(
+                    vec_pin.as_mut(),
+                    grammar.borrow().ffi_ref(),
+                );
+                count += 1;
             }
         }
-        let ffi_ptr = ffi::grammar_union(vec.as_ref().unwrap());
-        Self {
-            inner: ffi_ptr,
+        if count == 0 {
+            return Err("requires at least one grammar".to_string());
         }
+        Ok(vec)
     }
 
     /// Serialize the grammar to a JSON string.
@@ -333,6 +1076,16 @@ impl Grammar {
         .to_string()
     }
 
+    /// Serialize the grammar to an indented JSON string, for reading and diffing. The string
+    /// deserializes to the same grammar as the one from `serialize_json`.
+    ///
+    /// # Returns
+    ///
+    /// The indented JSON string.
+    pub fn serialize_json_pretty(&self) -> String {
+        crate::utils::pretty_json(self.serialize_json())
+    }
+
     /// Deserialize a grammar from a JSON string.
     ///
     /// # Parameters
@@ -383,6 +1136,27 @@ impl Grammar {
     }
 }
 
+// The names of the rules defined in an EBNF string, in order: the identifiers that start a
+// line and are followed by `::=`, as in `name ::= ...`.
+fn defined_rule_names(ebnf: &str) -> impl Iterator<Item = &str> + Clone {
+    ebnf.lines().filter_map(|line| {
+        let line = line.trim_start();
+        let name_len = line
+            .char_indices()
+            .find(|&(i, c)| {
+                !(c.is_ascii_alphabetic()
+                    || c == '_'
+                    || c == '-'
+                    || c == '.'
+                    || (i > 0 && c.is_ascii_digit()))
+            })
+            .map_or(line.len(), |(i, _)| i);
+        let (name, rest) = line.split_at(name_len);
+        (!name.is_empty() && rest.trim_start().starts_with("::="))
+            .then_some(name)
+    })
+}
+
 impl Drop for Grammar {
     fn drop(&mut self) {}
 }
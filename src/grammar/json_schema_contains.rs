@@ -0,0 +1,80 @@
+//! The `contains`, `minContains` and `maxContains` array keywords of JSON schemas.
+//!
+//! The converter ignores these keywords, so a grammar would accept arrays without the required
+//! items. The cases that only bound the array length are rewritten to `minItems` and
+//! `maxItems`, and the other ones are rejected.
+
+use serde_json::{Map, Value};
+
+use crate::JsonSchemaError;
+
+/// Rewrite the `contains` keyword of the array subschema `obj`, if the grammar can enforce
+/// it:
+///
+/// - `contains` matching any item (`true` or `{}`) bounds the array length, and becomes
+///   `minItems: minContains` (default 1) and `maxItems: maxContains`.
+/// - `contains` with `minContains: 0` and no `maxContains` is always satisfied, and is
+///   removed.
+///
+/// # Returns
+///
+/// Whether `obj` changed, i.e. whether it has a `contains`.
+///
+/// # Errors
+///
+/// - When the `contains` keyword requires or limits the items matching a subschema.
+pub(super) fn rewrite_contains(
+    obj: &mut Map<String, Value>,
+    path: &str,
+) -> Result<bool, JsonSchemaError> {
+    if !obj.contains_key("contains") {
+        return Ok(false);
+    }
+    let count = |keyword: &str| -> Result<Option<u64>, JsonSchemaError> {
+        match obj.get(keyword) {
+            None => Ok(None),
+            Some(value) => value.as_u64().map(Some).ok_or_else(|| {
+                JsonSchemaError::Invalid(format!(
+                    "Invalid JSON schema at \"{}\": {} must be a non-negative integer",
+                    path, keyword
+                ))
+            }),
+        }
+    };
+    let min_contains = count("minContains")?.unwrap_or(1);
+    let max_contains = count("maxContains")?;
+
+    let matches_any = match &obj["contains"] {
+        Value::Bool(matches) => *matches,
+        Value::Object(contains) => contains.is_empty(),
+        _ => false,
+    };
+    if matches_any {
+        let min_items = count("minItems")?.unwrap_or(0).max(min_contains);
+        let max_items = match (count("maxItems")?, max_contains) {
+            (Some(max_items), Some(max_contains)) => {
+                Some(max_items.min(max_contains))
+            },
+            (max_items, max_contains) => max_items.or(max_contains),
+        };
+        obj.insert("minItems".to_string(), min_items.into());
+        if let Some(max_items) = max_items {
+            obj.insert("maxItems".to_string(), max_items.into());
+        }
+    } else if min_contains > 0 || max_contains.is_some() {
+        return Err(JsonSchemaError::Unsupported(format!(
+            "Unsupported JSON schema keyword at \"{}/contains\": the grammar cannot require \
+             arrays to contain {} item(s) matching a subschema. Only \"contains\" matching \
+             any item, or with \"minContains\": 0 and no \"maxContains\", is supported",
+            path,
+            match max_contains {
+                Some(max) => format!("between {} and {}", min_contains, max),
+                None => format!("at least {}", min_contains),
+            }
+        )));
+    }
+    for keyword in ["contains", "minContains", "maxContains"] {
+        obj.remove(keyword);
+    }
+    Ok(true)
+}
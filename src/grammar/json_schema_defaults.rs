@@ -0,0 +1,65 @@
+//! Pinning JSON schema properties to their `default` values.
+//!
+//! The converter ignores `default`. With forced defaults, every subschema with a `default` is
+//! narrowed to `const: <default>`, so the grammar only accepts the default value there.
+
+use serde_json::{Map, Value};
+
+use crate::JsonSchemaError;
+
+use super::json_schema_prepare::walk_mut;
+
+/// Rewrite every subschema of `schema` that has a `default` to also have `const` set to that
+/// default. The other keywords are kept, and `required` is not changed: an optional property
+/// with a default may still be omitted, but when present it must equal the default.
+///
+/// # Returns
+///
+/// The rewritten schema, or `None` if no subschema has a `default`. A schema that is not valid
+/// JSON is also returned as `None`, and left for `Grammar::from_json_schema` to reject.
+///
+/// # Errors
+///
+/// - When a `default` contradicts the `const` or `enum` of its subschema.
+pub(crate) fn force_defaults(
+    schema: &str
+) -> Result<Option<String>, JsonSchemaError> {
+    if !schema.contains("\"default\"") {
+        return Ok(None);
+    }
+    let Ok(mut root) = serde_json::from_str::<Value>(schema) else {
+        return Ok(None);
+    };
+    let mut changed = false;
+    walk_mut(&mut root, &mut String::new(), &mut |obj, path| {
+        if obj.contains_key("default") {
+            pin_default(obj, path)?;
+            changed = true;
+        }
+        Ok::<(), JsonSchemaError>(())
+    })?;
+    Ok(changed.then(|| root.to_string()))
+}
+
+fn pin_default(
+    obj: &mut Map<String, Value>,
+    path: &str,
+) -> Result<(), JsonSchemaError> {
+    let default = obj["default"].clone();
+    let contradiction = |keyword: &str| {
+        JsonSchemaError::Invalid(format!(
+            "Invalid JSON schema at \"{}\": the default {} is not allowed by \"{}\"",
+            path, default, keyword
+        ))
+    };
+    if obj.get("const").is_some_and(|value| *value != default) {
+        return Err(contradiction("const"));
+    }
+    if let Some(Value::Array(values)) = obj.get("enum") {
+        if !values.contains(&default) {
+            return Err(contradiction("enum"));
+        }
+    }
+    obj.insert("const".to_string(), default);
+    Ok(())
+}
@@ -0,0 +1,88 @@
+//! The `exclusiveMinimum` and `exclusiveMaximum` keywords of numeric JSON schemas.
+//!
+//! The converter replaces `minimum` with `exclusiveMinimum` when both are given, even when the
+//! exclusive bound is the looser one, and treats the exclusive bounds of `"type": "number"`
+//! schemas as inclusive. Integer exclusive bounds are rewritten to the equivalent inclusive
+//! ones. A number exclusive bound that a stricter inclusive bound does not make redundant is
+//! rejected.
+
+use serde_json::{Map, Value};
+
+use crate::JsonSchemaError;
+
+/// Rewrite the exclusive bounds of the numeric subschema `obj`:
+///
+/// - In an integer schema, `exclusiveMinimum: n` becomes `minimum: n + 1` and
+///   `exclusiveMaximum: n` becomes `maximum: n - 1`, keeping the stricter bound when the
+///   inclusive one is also given.
+/// - In a number schema, an exclusive bound is removed when the inclusive bound on the same
+///   side is stricter.
+///
+/// Bounds that are not integers in an integer schema, or that overflow, are kept, and the
+/// converter reports them.
+///
+/// # Returns
+///
+/// Whether `obj` changed.
+///
+/// # Errors
+///
+/// - When a number schema has an exclusive bound the grammar cannot enforce, as it would
+///   accept the bound itself.
+pub(super) fn rewrite_exclusive_bounds(
+    obj: &mut Map<String, Value>,
+    path: &str,
+) -> Result<bool, JsonSchemaError> {
+    let has_type = |name: &str| match obj.get("type") {
+        Some(Value::String(t)) => t == name,
+        Some(Value::Array(types)) => types.iter().any(|t| t == name),
+        _ => false,
+    };
+    let is_number = has_type("number");
+    let is_integer = has_type("integer");
+    let mut changed = false;
+    for (exclusive_keyword, keyword, step) in [
+        ("exclusiveMinimum", "minimum", 1),
+        ("exclusiveMaximum", "maximum", -1),
+    ] {
+        let Some(exclusive) =
+            obj.get(exclusive_keyword).and_then(Value::as_f64)
+        else {
+            continue;
+        };
+        let inclusive = obj.get(keyword).and_then(Value::as_f64);
+        // Whether the inclusive bound excludes the exclusive bound and everything beyond it.
+        let redundant = inclusive.is_some_and(|inclusive| {
+            (inclusive - exclusive) * step as f64 > 0.0
+        });
+        if is_number {
+            if !redundant {
+                return Err(JsonSchemaError::Unsupported(format!(
+                    "Unsupported JSON schema at \"{}\": \"{}\" on a number schema cannot be \
+                     enforced, as the grammar would also accept {}. Use \"{}\" or \
+                     \"type\": \"integer\" instead",
+                    path, exclusive_keyword, obj[exclusive_keyword], keyword
+                )));
+            }
+            obj.remove(exclusive_keyword);
+            changed = true;
+        } else if is_integer {
+            if !redundant {
+                let bound = obj[exclusive_keyword]
+                    .as_i64()
+                    .and_then(|exclusive| exclusive.checked_add(step));
+                match bound {
+                    Some(bound)
+                        if obj.get(keyword).is_none_or(Value::is_i64) =>
+                    {
+                        obj.insert(keyword.to_string(), bound.into());
+                    },
+                    _ => continue,
+                }
+            }
+            obj.remove(exclusive_keyword);
+            changed = true;
+        }
+    }
+    Ok(changed)
+}
@@ -0,0 +1,80 @@
+//! Custom `format` keywords for the JSON schema converter.
+//!
+//! The converter maps the standard formats to regexes itself and ignores the other ones. A
+//! custom format is supported by rewriting it to the equivalent `pattern` before conversion.
+
+use std::collections::{BTreeSet, HashMap};
+
+use serde_json::Value;
+
+use crate::JsonSchemaError;
+
+/// The `format` values the JSON schema converter maps to a regex.
+pub(crate) const STANDARD_FORMATS: &[&str] = &[
+    "email",
+    "date",
+    "time",
+    "date-time",
+    "duration",
+    "ipv4",
+    "ipv6",
+    "hostname",
+    "uuid",
+    "uri",
+    "uri-reference",
+    "uri-template",
+    "json-pointer",
+    "relative-json-pointer",
+];
+
+/// Replace every `"format": <name>` of `schema` with `name` in `formats` by
+/// `"pattern": formats[name]`. A custom format takes precedence over a standard format of the
+/// same name, and over a `pattern` next to it.
+///
+/// # Returns
+///
+/// The rewritten schema, and the formats that are neither custom nor standard, which the
+/// converter treats as plain strings.
+pub(crate) fn apply_custom_formats(
+    schema: &str,
+    formats: &HashMap<String, String>,
+) -> Result<(String, Vec<String>), JsonSchemaError> {
+    let mut root: Value = serde_json::from_str(schema).map_err(|e| {
+        JsonSchemaError::InvalidJson(format!("Invalid JSON schema: {}", e))
+    })?;
+    let mut unknown = BTreeSet::new();
+    rewrite(&mut root, formats, &mut unknown);
+    Ok((root.to_string(), unknown.into_iter().collect()))
+}
+
+fn rewrite(
+    value: &mut Value,
+    formats: &HashMap<String, String>,
+    unknown: &mut BTreeSet<String>,
+) {
+    match value {
+        Value::Object(obj) => {
+            if let Some(Value::String(format)) = obj.get("format") {
+                if let Some(pattern) = formats.get(format) {
+                    let pattern = Value::String(pattern.clone());
+                    obj.remove("format");
+                    obj.insert("pattern".to_string(), pattern);
+                } else if !STANDARD_FORMATS.contains(&format.as_str()) {
+                    unknown.insert(format.clone());
+                }
+            }
+            for (name, child) in obj.iter_mut() {
+                // Enum and const values are data, not subschemas.
+                if name != "enum" && name != "const" {
+                    rewrite(child, formats, unknown);
+                }
+            }
+        },
+        Value::Array(items) => {
+            for item in items {
+                rewrite(item, formats, unknown);
+            }
+        },
+        _ => {},
+    }
+}
@@ -0,0 +1,261 @@
+//! The `multipleOf` keyword of integer JSON schemas.
+//!
+//! The converter ignores `multipleOf`, so a grammar would accept any integer. An integer
+//! schema bounded on both sides is rewritten to the `enum` of its multiples. Otherwise the
+//! schema is replaced by a placeholder `const` string, and after conversion the placeholder is
+//! substituted in the EBNF by rules matching the multiples in decimal.
+
+use serde_json::{Map, Value};
+
+use crate::JsonSchemaError;
+
+/// The largest number of multiples a bounded range is rewritten to an `enum` of.
+const MAX_ENUM_MULTIPLES: f64 = 1024.0;
+
+/// The largest `multipleOf` matched by a grammar, other than powers of 10. The grammar has
+/// one rule per remainder.
+const MAX_REMAINDER_RULES: u64 = 100;
+
+/// The placeholders of the `multipleOf` keywords of a schema, see `rewrite_multiple_of`.
+#[derive(Default)]
+pub(super) struct MultipleOfPlaceholders {
+    // The placeholder names, each with the EBNF rules of its multiples. The placeholder
+    // `const` string and the rule matching the multiples have the same name.
+    placeholders: Vec<(String, String)>,
+}
+
+impl MultipleOfPlaceholders {
+    /// Whether the rewritten schema has no placeholder, i.e. the converted grammar already
+    /// enforces its `multipleOf` keywords.
+    pub(super) fn is_empty(&self) -> bool {
+        self.placeholders.is_empty()
+    }
+
+    /// Substitute the placeholders in `ebnf`, the EBNF of the converted schema.
+    ///
+    /// # Returns
+    ///
+    /// The EBNF of the grammar enforcing `multipleOf`.
+    pub(super) fn substitute(
+        &self,
+        ebnf: &str,
+    ) -> String {
+        let mut ebnf = ebnf.to_string();
+        for (name, rules) in &self.placeholders {
+            // The `const` is converted to the JSON string, which may be merged into a longer
+            // literal, so the literal is split around it.
+            ebnf = ebnf.replace(
+                &format!("\\\"{}\\\"", name),
+                &format!("\" {} \"", name),
+            );
+            ebnf.push('\n');
+            ebnf.push_str(rules);
+        }
+        ebnf
+    }
+}
+
+/// Rewrite the `multipleOf` keyword of the subschema `obj`, see the module documentation. A
+/// `multipleOf` of 1 is removed. The placeholder replacing an unbounded integer schema is
+/// added to `placeholders`.
+///
+/// # Returns
+///
+/// Whether `obj` changed, i.e. whether it has a `multipleOf`.
+///
+/// # Errors
+///
+/// - When `multipleOf` is not a positive integer, or its schema is not an integer schema.
+/// - When the grammar cannot represent the multiples: a one-sided bound beyond the first
+///   multiple, more than 1024 multiples between two bounds, or an unbounded `multipleOf`
+///   greater than 100 that is not a power of 10.
+pub(super) fn rewrite_multiple_of(
+    obj: &mut Map<String, Value>,
+    path: &str,
+    placeholders: &mut MultipleOfPlaceholders,
+) -> Result<bool, JsonSchemaError> {
+    if !obj.contains_key("multipleOf") {
+        return Ok(false);
+    }
+    if let Some(replacement) =
+        rewrite_integer_schema(obj, path, &mut placeholders.placeholders)?
+    {
+        *obj = replacement;
+    }
+    Ok(true)
+}
+
+/// Rewrite the integer schema `obj` with a `multipleOf`. Returns the schema replacing it, or
+/// `None` if `obj` is kept, with `multipleOf` removed.
+fn rewrite_integer_schema(
+    obj: &mut Map<String, Value>,
+    path: &str,
+    placeholders: &mut Vec<(String, String)>,
+) -> Result<Option<Map<String, Value>>, JsonSchemaError> {
+    let unsupported = |reason: String| {
+        Err(JsonSchemaError::Unsupported(format!(
+            "Unsupported JSON schema at \"{}\": {}",
+            path, reason
+        )))
+    };
+    let multiple_of = match obj["multipleOf"].as_f64() {
+        Some(k) if k >= 1.0 && k.fract() == 0.0 && k < 2f64.powi(53) => k,
+        _ => {
+            return unsupported(format!(
+                "\"multipleOf\" must be a positive integer, got {}",
+                obj["multipleOf"]
+            ));
+        },
+    };
+    if obj.get("type") != Some(&Value::from("integer")) {
+        return unsupported(
+            "\"multipleOf\" is only supported with \"type\": \"integer\""
+                .to_string(),
+        );
+    }
+    if multiple_of == 1.0 {
+        obj.remove("multipleOf");
+        return Ok(None);
+    }
+
+    // The smallest and largest allowed integers.
+    let bound = |keyword: &str| obj.get(keyword).and_then(Value::as_f64);
+    let low = match (bound("minimum"), bound("exclusiveMinimum")) {
+        (Some(min), Some(exclusive)) => {
+            Some(min.ceil().max(exclusive.floor() + 1.0))
+        },
+        (Some(min), None) => Some(min.ceil()),
+        (None, Some(exclusive)) => Some(exclusive.floor() + 1.0),
+        (None, None) => None,
+    };
+    let high = match (bound("maximum"), bound("exclusiveMaximum")) {
+        (Some(max), Some(exclusive)) => {
+            Some(max.floor().min(exclusive.ceil() - 1.0))
+        },
+        (Some(max), None) => Some(max.floor()),
+        (None, Some(exclusive)) => Some(exclusive.ceil() - 1.0),
+        (None, None) => None,
+    };
+
+    if let (Some(low), Some(high)) = (low, high) {
+        let first = (low / multiple_of).ceil();
+        let last = (high / multiple_of).floor();
+        if first > last {
+            return Err(JsonSchemaError::ContradictorySchema {
+                detail: format!(
+                    "no multiple of {} is between {} and {} (at \"{}\")",
+                    multiple_of, low, high, path
+                ),
+            });
+        }
+        if last - first + 1.0 > MAX_ENUM_MULTIPLES {
+            return unsupported(format!(
+                "more than {} multiples of {} between the bounds",
+                MAX_ENUM_MULTIPLES, multiple_of
+            ));
+        }
+        let multiples: Vec<Value> = (first as i64..=last as i64)
+            .map(|i| Value::from(i * multiple_of as i64))
+            .collect();
+        let mut replacement = Map::new();
+        replacement.insert("enum".to_string(), Value::Array(multiples));
+        return Ok(Some(replacement));
+    }
+
+    // With one bound, only the sign of the multiples can be restricted.
+    let (mut negative, mut zero, mut positive) = (true, true, true);
+    if let Some(low) = low {
+        if low <= -multiple_of || low > multiple_of {
+            return unsupported(format!(
+                "a lower bound of {} with \"multipleOf\" {} is only supported with an upper \
+                 bound",
+                low, multiple_of
+            ));
+        }
+        negative = false;
+        zero = low <= 0.0;
+    }
+    if let Some(high) = high {
+        if high >= multiple_of || high < -multiple_of {
+            return unsupported(format!(
+                "an upper bound of {} with \"multipleOf\" {} is only supported with a lower \
+                 bound",
+                high, multiple_of
+            ));
+        }
+        positive = false;
+        zero = high >= 0.0;
+    }
+    let multiple_of = multiple_of as u64;
+    let is_power_of_10 = {
+        let mut k = multiple_of;
+        while k % 10 == 0 {
+            k /= 10;
+        }
+        k == 1
+    };
+    if !is_power_of_10 && multiple_of > MAX_REMAINDER_RULES {
+        return unsupported(format!(
+            "\"multipleOf\" {} without bounds is only supported up to {} or for powers of 10",
+            multiple_of, MAX_REMAINDER_RULES
+        ));
+    }
+
+    let name = format!("xgrammar_multiple_of_{}", placeholders.len());
+    let mut alternatives = Vec::new();
+    if zero {
+        alternatives.push("\"0\"".to_string());
+    }
+    if positive {
+        alternatives.push(format!("{}_positive", name));
+    }
+    if negative {
+        alternatives.push(format!("\"-\" {}_positive", name));
+    }
+    let mut rules = format!("{} ::= {}\n", name, alternatives.join(" | "));
+    if is_power_of_10 {
+        let zeros = multiple_of.to_string()[1..].to_string();
+        rules += &format!("{}_positive ::= [1-9] [0-9]* \"{}\"\n", name, zeros);
+    } else {
+        // `<name>_r<r>` matches the remaining digits of a number whose leading digits have
+        // the remainder `r`.
+        let digits = |remainder: u64, first: u32| -> String {
+            let mut by_target: Vec<(u64, String)> = Vec::new();
+            for digit in first..10 {
+                let target = (remainder * 10 + digit as u64) % multiple_of;
+                let digit = char::from_digit(digit, 10).unwrap();
+                match by_target.iter_mut().find(|(t, _)| *t == target) {
+                    Some((_, class)) => class.push(digit),
+                    None => by_target.push((target, digit.to_string())),
+                }
+            }
+            by_target
+                .iter()
+                .map(|(target, class)| {
+                    format!("[{}] {}_r{}", class, name, target)
+                })
+                .collect::<Vec<_>>()
+                .join(" | ")
+        };
+        rules += &format!("{}_positive ::= {}\n", name, digits(0, 1));
+        for remainder in 0..multiple_of {
+            let empty = if remainder == 0 {
+                "\"\" | "
+            } else {
+                ""
+            };
+            rules += &format!(
+                "{}_r{} ::= {}{}\n",
+                name,
+                remainder,
+                empty,
+                digits(remainder, 0)
+            );
+        }
+    }
+    let placeholder = Value::from(name.as_str());
+    placeholders.push((name, rules));
+    let mut replacement = Map::new();
+    replacement.insert("const".to_string(), placeholder);
+    Ok(Some(replacement))
+}
@@ -0,0 +1,134 @@
+//! Options of the JSON schema conversion, beyond the format parameters of
+//! `Grammar::from_json_schema`.
+
+use std::collections::HashMap;
+
+use super::{
+    json_schema_defaults, json_schema_formats::apply_custom_formats,
+    json_schema_refs::bundle_external_refs,
+    json_schema_required::require_all_properties,
+};
+use crate::JsonSchemaError;
+
+/// Options of `Grammar::from_json_schema_with_options` and
+/// `GrammarCompiler::compile_json_schema_with_options`.
+///
+/// The format fields are the parameters of `Grammar::from_json_schema`, and default to the
+/// same values as in the Python API. The other fields rewrite the schema before the
+/// conversion, and by default leave it unchanged.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JsonSchemaOptions {
+    /// Whether to allow any whitespace. If true, `indent` and `separators` are ignored.
+    pub any_whitespace: bool,
+    /// The number of spaces for indentation. If `None`, the output is in one line.
+    pub indent: Option<i32>,
+    /// The comma and colon separators, e.g. `(", ".into(), ": ".into())`. If `None`, the
+    /// separators depend on `indent`, see `Grammar::from_json_schema`.
+    pub separators: Option<(String, String)>,
+    /// Whether to disallow the properties and items not specified in the schema.
+    pub strict_mode: bool,
+    /// The maximum number of whitespace characters between elements. If `None`, there is
+    /// no limit.
+    pub max_whitespace_cnt: Option<i32>,
+    /// Whether to print the converted EBNF, for debugging purposes.
+    pub print_converted_ebnf: bool,
+    /// Maps a base URI (the part of a `$ref` before `#`, matched verbatim) to the schema
+    /// document it refers to.
+    ///
+    /// The converter only resolves local references such as `#/$defs/Foo`. When `refs` is
+    /// not empty, every externally referenced document is first inlined into the root
+    /// `$defs`, so that `{"$ref": "common.json#/$defs/Id"}` works when `refs` contains the
+    /// key `common.json`. The referenced documents may refer to each other and to
+    /// themselves. A `$ref` whose base is not in `refs` is then an error.
+    pub refs: HashMap<String, String>,
+    /// Maps a custom `format` name to the regex the strings of that format must match.
+    ///
+    /// The standard formats (`email`, `date`, `time`, `date-time`, `duration`, `ipv4`,
+    /// `ipv6`, `hostname`, `uuid`, `uri`, `uri-reference`, `uri-template`, `json-pointer`
+    /// and `relative-json-pointer`) are always converted to the matching regex. Each format
+    /// in `formats` is converted to its regex as if the schema used `"pattern"` instead, and
+    /// overrides a standard format of the same name. Other formats are converted as plain
+    /// strings, see [`Grammar::unknown_json_schema_formats`] to report them.
+    ///
+    /// [`Grammar::unknown_json_schema_formats`]: crate::Grammar::unknown_json_schema_formats
+    pub formats: HashMap<String, String>,
+    /// Whether to only accept the `default` value where one is given.
+    ///
+    /// The converter ignores `default`. With `force_defaults`, every subschema that has a
+    /// `default` is compiled as a `const` of that default, so e.g. configuration-like fields
+    /// can only take their default value. `required` is not changed: an optional property
+    /// may still be omitted, but when present it must equal its default.
+    pub force_defaults: bool,
+    /// Whether every declared property is required.
+    ///
+    /// `required` is enforced whether or not `strict_mode` is set: non-strict mode only
+    /// allows additional properties, after the declared ones. With
+    /// `require_all_properties`, every object subschema is compiled as if all of its
+    /// `properties` were listed in `required`, e.g. for schemas written without `required`
+    /// whose properties must all be generated.
+    pub require_all_properties: bool,
+    /// Whether whitespace may follow the JSON value.
+    ///
+    /// By default the grammar ends with the JSON value, so a whitespace token generated
+    /// before the stop token is rejected. With `trailing_whitespace`, any number of spaces,
+    /// newlines, carriage returns and tabs may follow the value before the stop token, at
+    /// most `max_whitespace_cnt` of them if it is set.
+    pub trailing_whitespace: bool,
+}
+
+impl Default for JsonSchemaOptions {
+    fn default() -> Self {
+        Self {
+            any_whitespace: true,
+            indent: None,
+            separators: None,
+            strict_mode: true,
+            max_whitespace_cnt: None,
+            print_converted_ebnf: false,
+            refs: HashMap::new(),
+            formats: HashMap::new(),
+            force_defaults: false,
+            require_all_properties: false,
+            trailing_whitespace: false,
+        }
+    }
+}
+
+impl JsonSchemaOptions {
+    // The separators in the form `Grammar::from_json_schema` takes them.
+    pub(crate) fn separators(&self) -> Option<(&str, &str)> {
+        self.separators
+            .as_ref()
+            .map(|(comma, colon)| (comma.as_str(), colon.as_str()))
+    }
+
+    // The schema with the rewrites of these options applied, or `None` if they leave it
+    // unchanged.
+    pub(crate) fn rewrite_schema(
+        &self,
+        schema: &str,
+    ) -> Result<Option<String>, JsonSchemaError> {
+        let mut rewritten: Option<String> = None;
+        if !self.refs.is_empty() {
+            rewritten = Some(bundle_external_refs(schema, &self.refs)?);
+        }
+        if !self.formats.is_empty() {
+            let schema = rewritten.as_deref().unwrap_or(schema);
+            rewritten = Some(apply_custom_formats(schema, &self.formats)?.0);
+        }
+        if self.force_defaults {
+            let schema = rewritten.as_deref().unwrap_or(schema);
+            if let Some(pinned) = json_schema_defaults::force_defaults(schema)?
+            {
+                rewritten = Some(pinned);
+            }
+        }
+        if self.require_all_properties {
+            let schema = rewritten.as_deref().unwrap_or(schema);
+            if let Some(required) = require_all_properties(schema) {
+                rewritten = Some(required);
+            }
+        }
+        Ok(rewritten)
+    }
+}
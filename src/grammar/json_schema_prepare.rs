@@ -0,0 +1,431 @@
+//! The checks and rewrites applied to every JSON schema before it is passed to the converter.
+
+use serde_json::{Map, Value};
+
+use super::{
+    json_schema_contains::rewrite_contains,
+    json_schema_exclusive_bounds::rewrite_exclusive_bounds,
+    json_schema_multiple_of::{MultipleOfPlaceholders, rewrite_multiple_of},
+};
+use crate::{Grammar, JsonSchemaError};
+
+/// Keywords whose value is a subschema.
+pub(super) const SCHEMA_KEYWORDS: &[&str] = &[
+    "items",
+    "additionalItems",
+    "unevaluatedItems",
+    "contains",
+    "additionalProperties",
+    "unevaluatedProperties",
+    "propertyNames",
+    "not",
+    "if",
+    "then",
+    "else",
+];
+
+/// Keywords whose value is an array of subschemas.
+pub(super) const SCHEMA_ARRAY_KEYWORDS: &[&str] =
+    &["prefixItems", "allOf", "anyOf", "oneOf"];
+
+/// Keywords whose value is an object mapping names to subschemas.
+pub(super) const SCHEMA_MAP_KEYWORDS: &[&str] = &[
+    "properties",
+    "patternProperties",
+    "dependentSchemas",
+    "$defs",
+    "definitions",
+];
+
+/// A JSON schema checked and rewritten by `prepare_json_schema`.
+pub(crate) struct PreparedJsonSchema {
+    // The rewritten schema, or `None` if the schema is passed to the converter unchanged.
+    rewritten: Option<String>,
+    multiple_of: MultipleOfPlaceholders,
+}
+
+impl PreparedJsonSchema {
+    /// The schema to pass to the converter, given `schema`, the prepared schema.
+    pub(crate) fn schema<'a>(
+        &'a self,
+        schema: &'a str,
+    ) -> &'a str {
+        self.rewritten.as_deref().unwrap_or(schema)
+    }
+
+    /// Whether the EBNF of the converted schema must be rewritten with `rewrite_ebnf`, i.e.
+    /// whether the grammar differs from the one the converter builds.
+    pub(crate) fn rewrites_ebnf(&self) -> bool {
+        !self.multiple_of.is_empty()
+    }
+
+    /// Rewrite `ebnf`, the EBNF of the converted schema, see `rewrites_ebnf`.
+    pub(crate) fn rewrite_ebnf(
+        &self,
+        ebnf: &str,
+    ) -> String {
+        self.multiple_of.substitute(ebnf)
+    }
+}
+
+/// Check `schema` for contradictory bounds and for constructs the converter would silently
+/// misconvert, then rewrite its exclusive bounds (see
+/// `json_schema_exclusive_bounds::rewrite_exclusive_bounds`), its `contains` keywords (see
+/// `json_schema_contains::rewrite_contains`) and its `multipleOf` keywords (see
+/// `json_schema_multiple_of::rewrite_multiple_of`). The schema is parsed once, and a schema
+/// that is not valid JSON is passed to the converter unchanged, which reports the error.
+///
+/// # Errors
+///
+/// - When a subschema has contradictory bounds (see `check_contradictions`).
+/// - When a `pattern` or a `patternProperties` key is not a valid regex (see
+///   `check_regexes`).
+/// - When an object schema combines `additionalProperties` given as a schema with
+///   `patternProperties` or `propertyNames`.
+/// - When a number schema has an exclusive bound the grammar cannot enforce.
+/// - When the schema uses an unsupported form of `contains` or `multipleOf`.
+pub(crate) fn prepare_json_schema(
+    schema: &str
+) -> Result<PreparedJsonSchema, JsonSchemaError> {
+    let mut prepared = PreparedJsonSchema {
+        rewritten: None,
+        multiple_of: MultipleOfPlaceholders::default(),
+    };
+    let Ok(mut root) = serde_json::from_str::<Value>(schema) else {
+        return Ok(prepared);
+    };
+    walk(&root, &mut String::new(), &mut |obj, path| {
+        check_bounds(obj, path)?;
+        check_patterns(obj, path)?;
+        check_additional_properties(obj, path)
+    })?;
+    let mut changed = false;
+    walk_mut(&mut root, &mut String::new(), &mut |obj, path| {
+        changed |= rewrite_exclusive_bounds(obj, path)?;
+        changed |= rewrite_contains(obj, path)?;
+        changed |= rewrite_multiple_of(obj, path, &mut prepared.multiple_of)?;
+        Ok(())
+    })?;
+    prepared.rewritten = changed.then(|| root.to_string());
+    Ok(prepared)
+}
+
+/// Check every subschema of `schema` for bounds that no instance can satisfy: `minItems`
+/// greater than `maxItems` or than the number of `prefixItems` when `items` is `false`,
+/// `minLength` greater than `maxLength`, `minimum` greater than `maximum`, and
+/// `minProperties` greater than `maxProperties`.
+///
+/// # Errors
+///
+/// - `JsonSchemaError::InvalidJson` when `schema` is not valid JSON.
+/// - `JsonSchemaError::ContradictorySchema` for the first contradiction found.
+pub(crate) fn check_contradictions(
+    schema: &str
+) -> Result<(), JsonSchemaError> {
+    let root: Value = serde_json::from_str(schema)
+        .map_err(|e| JsonSchemaError::InvalidJson(e.to_string()))?;
+    walk(&root, &mut String::new(), &mut check_bounds)
+}
+
+/// The keywords of JSON schema drafts 4 to 2020-12 that are not subschema keywords, see
+/// `SCHEMA_KEYWORDS`, `SCHEMA_ARRAY_KEYWORDS` and `SCHEMA_MAP_KEYWORDS`.
+const OTHER_KEYWORDS: &[&str] = &[
+    // Core
+    "$schema",
+    "$id",
+    "id",
+    "$ref",
+    "$anchor",
+    "$dynamicRef",
+    "$dynamicAnchor",
+    "$recursiveRef",
+    "$recursiveAnchor",
+    "$vocabulary",
+    "$comment",
+    "dependencies",
+    // Validation
+    "type",
+    "enum",
+    "const",
+    "multipleOf",
+    "maximum",
+    "exclusiveMaximum",
+    "minimum",
+    "exclusiveMinimum",
+    "maxLength",
+    "minLength",
+    "pattern",
+    "maxItems",
+    "minItems",
+    "uniqueItems",
+    "maxContains",
+    "minContains",
+    "maxProperties",
+    "minProperties",
+    "required",
+    "dependentRequired",
+    // Format and content
+    "format",
+    "contentEncoding",
+    "contentMediaType",
+    "contentSchema",
+    // Annotations
+    "title",
+    "description",
+    "default",
+    "deprecated",
+    "readOnly",
+    "writeOnly",
+    "examples",
+];
+
+/// Check every subschema of `schema` for keywords that no JSON schema draft defines, e.g. the
+/// typo `porperties`, which the converter would silently ignore. Keywords starting with `x-`
+/// are extensions and are allowed. The values of `properties` and the other keywords mapping
+/// names to subschemas are names, not keywords, so they are not checked.
+///
+/// # Errors
+///
+/// One message per unknown keyword, with the JSON pointer of its subschema, or the parse error
+/// when `schema` is not valid JSON.
+pub(crate) fn check_keywords(schema: &str) -> Result<(), Vec<String>> {
+    let root: Value = serde_json::from_str(schema).map_err(|e| {
+        vec![JsonSchemaError::InvalidJson(e.to_string()).to_string()]
+    })?;
+    let mut unknown = Vec::new();
+    let _ = walk(&root, &mut String::new(), &mut |obj, path| {
+        for keyword in obj.keys().map(String::as_str) {
+            let known = SCHEMA_KEYWORDS.contains(&keyword)
+                || SCHEMA_ARRAY_KEYWORDS.contains(&keyword)
+                || SCHEMA_MAP_KEYWORDS.contains(&keyword)
+                || OTHER_KEYWORDS.contains(&keyword)
+                || keyword.starts_with("x-");
+            if !known {
+                unknown.push(format!(
+                    "Unknown JSON schema keyword \"{}\" (at \"{}\")",
+                    keyword, path
+                ));
+            }
+        }
+        Ok::<(), ()>(())
+    });
+    if unknown.is_empty() {
+        Ok(())
+    } else {
+        Err(unknown)
+    }
+}
+
+/// Check that the `pattern` of every subschema, including those of `propertyNames`, and the
+/// keys of every `patternProperties` are regexes the converter can parse.
+///
+/// # Errors
+///
+/// - `JsonSchemaError::InvalidJson` when `schema` is not valid JSON.
+/// - `JsonSchemaError::InvalidSchemaRegex` for the first invalid regex found.
+pub(crate) fn check_regexes(schema: &str) -> Result<(), JsonSchemaError> {
+    let root: Value = serde_json::from_str(schema)
+        .map_err(|e| JsonSchemaError::InvalidJson(e.to_string()))?;
+    walk(&root, &mut String::new(), &mut check_patterns)
+}
+
+/// Call `visit` on every subschema of `value` with its JSON pointer, depth first.
+fn walk<E>(
+    value: &Value,
+    path: &mut String,
+    visit: &mut impl FnMut(&Map<String, Value>, &str) -> Result<(), E>,
+) -> Result<(), E> {
+    let Value::Object(obj) = value else {
+        return Ok(());
+    };
+    visit(obj, path)?;
+    for (keyword, child) in obj {
+        let len = path.len();
+        push_pointer_token(path, keyword);
+        if SCHEMA_KEYWORDS.contains(&keyword.as_str()) {
+            walk(child, path, visit)?;
+        } else if SCHEMA_ARRAY_KEYWORDS.contains(&keyword.as_str()) {
+            if let Value::Array(items) = child {
+                for (i, item) in items.iter().enumerate() {
+                    let len = path.len();
+                    push_pointer_token(path, &i.to_string());
+                    walk(item, path, visit)?;
+                    path.truncate(len);
+                }
+            }
+        } else if SCHEMA_MAP_KEYWORDS.contains(&keyword.as_str()) {
+            if let Value::Object(entries) = child {
+                for (name, entry) in entries {
+                    let len = path.len();
+                    push_pointer_token(path, name);
+                    walk(entry, path, visit)?;
+                    path.truncate(len);
+                }
+            }
+        }
+        path.truncate(len);
+    }
+    Ok(())
+}
+
+/// Call `visit` on every subschema of `value` with its JSON pointer, depth first, like
+/// `walk`. The subschemas of an object are walked after `visit` rewrote it.
+pub(super) fn walk_mut<E>(
+    value: &mut Value,
+    path: &mut String,
+    visit: &mut impl FnMut(&mut Map<String, Value>, &str) -> Result<(), E>,
+) -> Result<(), E> {
+    let Value::Object(obj) = value else {
+        return Ok(());
+    };
+    visit(obj, path)?;
+    for (keyword, child) in obj.iter_mut() {
+        let len = path.len();
+        push_pointer_token(path, keyword);
+        if SCHEMA_KEYWORDS.contains(&keyword.as_str()) {
+            walk_mut(child, path, visit)?;
+        } else if SCHEMA_ARRAY_KEYWORDS.contains(&keyword.as_str()) {
+            if let Value::Array(items) = child {
+                for (i, item) in items.iter_mut().enumerate() {
+                    let len = path.len();
+                    push_pointer_token(path, &i.to_string());
+                    walk_mut(item, path, visit)?;
+                    path.truncate(len);
+                }
+            }
+        } else if SCHEMA_MAP_KEYWORDS.contains(&keyword.as_str()) {
+            if let Value::Object(entries) = child {
+                for (name, entry) in entries.iter_mut() {
+                    let len = path.len();
+                    push_pointer_token(path, name);
+                    walk_mut(entry, path, visit)?;
+                    path.truncate(len);
+                }
+            }
+        }
+        path.truncate(len);
+    }
+    Ok(())
+}
+
+/// Append `/token` to the JSON pointer `path`, escaping `~` and `/` in the token.
+pub(super) fn push_pointer_token(
+    path: &mut String,
+    token: &str,
+) {
+    path.push('/');
+    path.push_str(&token.replace('~', "~0").replace('/', "~1"));
+}
+
+fn check_bounds(
+    obj: &Map<String, Value>,
+    path: &str,
+) -> Result<(), JsonSchemaError> {
+    let contradiction = |detail: String| {
+        Err(JsonSchemaError::ContradictorySchema {
+            detail: format!("{} (at \"{}\")", detail, path),
+        })
+    };
+    for (min_keyword, max_keyword) in [
+        ("minItems", "maxItems"),
+        ("minLength", "maxLength"),
+        ("minimum", "maximum"),
+        ("minProperties", "maxProperties"),
+    ] {
+        let (Some(min), Some(max)) = (
+            obj.get(min_keyword).filter(|v| v.is_number()),
+            obj.get(max_keyword).filter(|v| v.is_number()),
+        ) else {
+            continue;
+        };
+        if min.as_f64() > max.as_f64() {
+            return contradiction(format!(
+                "{} is greater than {}: {} > {}",
+                min_keyword, max_keyword, min, max
+            ));
+        }
+    }
+    if let (Some(min_items), Some(Value::Bool(false))) =
+        (obj.get("minItems").and_then(Value::as_f64), obj.get("items"))
+    {
+        let prefix_items = match obj.get("prefixItems") {
+            Some(Value::Array(items)) => items.len(),
+            _ => 0,
+        };
+        if min_items > prefix_items as f64 {
+            return contradiction(format!(
+                "minItems is greater than the number of prefixItems, but additional items are \
+                 not allowed: {} > {}",
+                obj["minItems"], prefix_items
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn check_patterns(
+    obj: &Map<String, Value>,
+    path: &str,
+) -> Result<(), JsonSchemaError> {
+    let check = |pattern: &str, keyword_path: String| match Grammar::from_regex(
+        pattern, false,
+    ) {
+        Ok(_) => Ok(()),
+        Err(message) => Err(JsonSchemaError::InvalidSchemaRegex {
+            pattern: pattern.to_string(),
+            detail: format!(
+                "{} (at \"{}\")",
+                strip_log_prefix(&message),
+                keyword_path
+            ),
+        }),
+    };
+    if let Some(Value::String(pattern)) = obj.get("pattern") {
+        check(pattern, format!("{}/pattern", path))?;
+    }
+    if let Some(Value::Object(pattern_properties)) =
+        obj.get("patternProperties")
+    {
+        for pattern in pattern_properties.keys() {
+            check(pattern, format!("{}/patternProperties", path))?;
+        }
+    }
+    Ok(())
+}
+
+/// The message of a C++ error without the time and source location the logger prepends,
+/// e.g. `[12:00:00] cpp/regex_converter.cc:75: `.
+fn strip_log_prefix(message: &str) -> &str {
+    message
+        .split_once(".cc:")
+        .and_then(|(_, rest)| rest.split_once(": "))
+        .map_or(message, |(_, message)| message)
+        .trim_end()
+}
+
+/// With `patternProperties` or `propertyNames`, the converter only constrains the property
+/// names, and accepts any value for the properties they do not cover. A typed
+/// `additionalProperties` would be silently dropped, so it is rejected.
+fn check_additional_properties(
+    obj: &Map<String, Value>,
+    path: &str,
+) -> Result<(), JsonSchemaError> {
+    let typed = match obj.get("additionalProperties") {
+        Some(Value::Object(schema)) => !schema.is_empty(),
+        _ => false,
+    };
+    if !typed {
+        return Ok(());
+    }
+    for keyword in ["patternProperties", "propertyNames"] {
+        if obj.contains_key(keyword) {
+            return Err(JsonSchemaError::Unsupported(format!(
+                "Unsupported JSON schema at \"{}\": \"additionalProperties\" with a schema \
+                 cannot be combined with \"{}\", as the grammar would not constrain the \
+                 values of the additional properties",
+                path, keyword
+            )));
+        }
+    }
+    Ok(())
+}
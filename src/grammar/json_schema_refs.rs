@@ -0,0 +1,164 @@
+//! Bundling of external `$ref` targets into a single JSON schema document.
+//!
+//! The JSON schema converter only resolves local references (`#` and `#/...`). To support
+//! references to sibling documents, every referenced document is copied under the root
+//! `$defs` and the references are rewritten to point at the copy.
+
+use std::collections::{HashMap, VecDeque};
+
+use serde_json::{Map, Value};
+
+use crate::JsonSchemaError;
+
+const EXTERNAL_DEFS_PREFIX: &str = "__external_ref_";
+
+/// Inline the documents referenced by external `$ref`s of `schema` from `refs`.
+///
+/// A `$ref` of the form `<base>#<fragment>` is looked up by `<base>` in `refs` (an
+/// exact string match) and rewritten to `#/$defs/<key><fragment>`. References whose base
+/// equals the root `$id` are treated as local. Referenced documents are processed
+/// recursively, and their local references are rewritten to stay inside their copy.
+pub(crate) fn bundle_external_refs(
+    schema: &str,
+    refs: &HashMap<String, String>,
+) -> Result<String, JsonSchemaError> {
+    let mut root: Value = serde_json::from_str(schema).map_err(|e| {
+        JsonSchemaError::InvalidJson(format!("Invalid JSON schema: {}", e))
+    })?;
+    let root_id = root.get("$id").and_then(Value::as_str).map(str::to_string);
+
+    let mut bundler = Bundler {
+        refs,
+        root_id,
+        keys: HashMap::new(),
+        queue: VecDeque::new(),
+        unresolved: Vec::new(),
+    };
+    bundler.rewrite(&mut root, None);
+
+    let mut bundled = Vec::new();
+    while let Some((base, key)) = bundler.queue.pop_front() {
+        let mut document: Value =
+            serde_json::from_str(&refs[&base]).map_err(|e| {
+                JsonSchemaError::InvalidJson(format!(
+                    "Invalid JSON schema for $ref base `{}`: {}",
+                    base, e
+                ))
+            })?;
+        bundler.rewrite(&mut document, Some(&key));
+        bundled.push((key, document));
+    }
+
+    if !bundler.unresolved.is_empty() {
+        return Err(JsonSchemaError::Invalid(format!(
+            "Unresolved $ref: {}",
+            bundler.unresolved.join(", ")
+        )));
+    }
+    if bundled.is_empty() {
+        return Ok(schema.to_string());
+    }
+
+    let root_obj = root.as_object_mut().ok_or_else(|| {
+        JsonSchemaError::Invalid(
+            "The root of a schema with external $ref must be an object"
+                .to_string(),
+        )
+    })?;
+    let defs = root_obj
+        .entry("$defs")
+        .or_insert_with(|| Value::Object(Map::new()))
+        .as_object_mut()
+        .ok_or_else(|| {
+            JsonSchemaError::Invalid("`$defs` must be an object".to_string())
+        })?;
+    for (key, document) in bundled {
+        if defs.contains_key(&key) {
+            return Err(JsonSchemaError::Invalid(format!(
+                "`$defs` already contains the key `{}`",
+                key
+            )));
+        }
+        defs.insert(key, document);
+    }
+    Ok(root.to_string())
+}
+
+struct Bundler<'a> {
+    refs: &'a HashMap<String, String>,
+    root_id: Option<String>,
+    /// Base URI -> key under the root `$defs`.
+    keys: HashMap<String, String>,
+    /// Documents that still need to be rewritten and inlined.
+    queue: VecDeque<(String, String)>,
+    unresolved: Vec<String>,
+}
+
+impl Bundler<'_> {
+    /// Rewrite all `$ref`s in `value`. `current_key` is the `$defs` key of the document
+    /// being rewritten, or `None` for the root document.
+    fn rewrite(
+        &mut self,
+        value: &mut Value,
+        current_key: Option<&str>,
+    ) {
+        match value {
+            Value::Object(obj) => {
+                if let Some(Value::String(reference)) = obj.get_mut("$ref") {
+                    if let Some(new_reference) =
+                        self.resolve(reference, current_key)
+                    {
+                        *reference = new_reference;
+                    }
+                }
+                for (name, child) in obj.iter_mut() {
+                    // Enum and const values are data, not subschemas.
+                    if name != "$ref" && name != "enum" && name != "const" {
+                        self.rewrite(child, current_key);
+                    }
+                }
+            },
+            Value::Array(items) => {
+                for item in items {
+                    self.rewrite(item, current_key);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    fn resolve(
+        &mut self,
+        reference: &str,
+        current_key: Option<&str>,
+    ) -> Option<String> {
+        let (base, fragment) = match reference.find('#') {
+            Some(pos) => (&reference[..pos], &reference[pos + 1..]),
+            None => (reference, ""),
+        };
+        let is_local = base.is_empty()
+            || (current_key.is_none() && self.root_id.as_deref() == Some(base));
+        if is_local {
+            return current_key
+                .map(|key| format!("#/$defs/{}{}", key, fragment))
+                .or_else(|| Some(format!("#{}", fragment)));
+        }
+        if !self.refs.contains_key(base) {
+            if !self.unresolved.iter().any(|r| r == reference) {
+                self.unresolved.push(reference.to_string());
+            }
+            return None;
+        }
+        let key = match self.keys.get(base) {
+            Some(key) => key.clone(),
+            None => {
+                let key =
+                    format!("{}{}", EXTERNAL_DEFS_PREFIX, self.keys.len());
+                self.keys.insert(base.to_string(), key.clone());
+                self.queue.push_back((base.to_string(), key.clone()));
+                key
+            },
+        };
+        Some(format!("#/$defs/{}{}", key, fragment))
+    }
+}
@@ -0,0 +1,53 @@
+//! Making every declared property of the JSON schema objects required.
+//!
+//! The converter enforces `required` in strict and non-strict mode alike; non-strict mode only
+//! allows additional properties after the declared ones. Schemas written without `required`
+//! therefore accept objects with any declared property omitted.
+
+use serde_json::{Map, Value};
+
+use super::json_schema_prepare::walk_mut;
+
+/// Rewrite every subschema of `schema` that has `properties` so that all of its properties
+/// are in `required`. The names already in `required` are kept first, in their order.
+///
+/// # Returns
+///
+/// The rewritten schema, or `None` if every property is already required or the schema is not
+/// valid JSON (the converter then reports the error).
+pub(crate) fn require_all_properties(schema: &str) -> Option<String> {
+    if !schema.contains("\"properties\"") {
+        return None;
+    }
+    let mut root = serde_json::from_str::<Value>(schema).ok()?;
+    let mut changed = false;
+    let _ = walk_mut(&mut root, &mut String::new(), &mut |obj, _| {
+        changed |= require_properties(obj);
+        Ok::<(), ()>(())
+    });
+    changed.then(|| root.to_string())
+}
+
+/// Add the properties of `obj` missing from its `required`. Returns whether `obj` changed.
+fn require_properties(obj: &mut Map<String, Value>) -> bool {
+    let Some(Value::Object(properties)) = obj.get("properties") else {
+        return false;
+    };
+    let mut required = match obj.get("required") {
+        Some(Value::Array(required)) => required.clone(),
+        // An invalid `required` is left for the converter to report.
+        Some(_) => return false,
+        None => Vec::new(),
+    };
+    let len = required.len();
+    for name in properties.keys() {
+        if !required.iter().any(|required| required == name) {
+            required.push(Value::String(name.clone()));
+        }
+    }
+    if required.len() == len {
+        return false;
+    }
+    obj.insert("required".to_string(), Value::Array(required));
+    true
+}
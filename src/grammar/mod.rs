@@ -1,7 +1,24 @@
 //! This module provides classes representing grammars.
 
+mod concat_markers;
+mod ebnf_format;
+pub(crate) mod ebnf_literal;
 pub mod grammar;
+mod json_schema_contains;
+mod json_schema_defaults;
+mod json_schema_exclusive_bounds;
+mod json_schema_formats;
+mod json_schema_multiple_of;
+mod json_schema_options;
+pub(crate) mod json_schema_prepare;
+mod json_schema_refs;
+mod json_schema_required;
+mod regex_flavor;
 pub mod structural_tag_item;
 
+pub use concat_markers::{Markers, PartTracker};
+pub use ebnf_format::EbnfFormatOptions;
 pub use grammar::Grammar;
+pub use json_schema_options::JsonSchemaOptions;
+pub use regex_flavor::{RegexFlavor, RegexMatchMode};
 pub use structural_tag_item::StructuralTagItem;
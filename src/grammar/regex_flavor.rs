@@ -0,0 +1,219 @@
+//! Regex dialects accepted by `Grammar::from_regex_with_flavor`.
+//!
+//! The regex converter implements the ECMAScript dialect. Patterns of the other dialects are
+//! rewritten to it before conversion where the constructs have an equivalent, and rejected
+//! with an error naming the construct otherwise.
+
+use std::borrow::Cow;
+
+/// The dialect of a regex passed to `Grammar::from_regex_with_flavor`.
+///
+/// In every flavor, `\d`, `\w` and `\s` are the ASCII classes `[0-9]`, `[a-zA-Z0-9_]` and
+/// `[\f\n\r\t\v\u0020\u00a0]`; the Unicode classes of Rust's `regex` crate are not
+/// reproduced.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum RegexFlavor {
+    /// ECMAScript (JavaScript) regexes, the dialect of the converter. The pattern is passed
+    /// through unchanged, like `Grammar::from_regex`.
+    #[default]
+    Ecma,
+    /// Perl-compatible regexes (PCRE, Python `re`).
+    Pcre,
+    /// Regexes of Rust's `regex` crate.
+    RustRegex,
+}
+
+/// How much of the output a regex passed to `Grammar::from_regex_with_match_mode` matches.
+///
+/// The converter ignores the `^` and `$` anchors, so the mode alone decides where the match
+/// ends.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum RegexMatchMode {
+    /// The regex matches the whole output, like `Grammar::from_regex`.
+    #[default]
+    FullMatch,
+    /// The regex matches a prefix of the output, and any text may follow it, e.g. to detect
+    /// a trigger at the start of the output.
+    Prefix,
+}
+
+/// Rewrite `regex` of the given flavor to the ECMAScript dialect of the converter:
+///
+/// - Named groups `(?P<name>...)`, `(?<name>...)` and `(?'name'...)` become non-capturing
+///   groups, as the converter ignores group names.
+/// - `\A` at the start becomes `^`, and `\z` or `\Z` at the end becomes `$`.
+/// - `\x{...}` becomes `\u{...}`, `\a` becomes `\x07` and `\e` becomes `\x1B`.
+/// - PCRE comments `(?#...)` are removed.
+///
+/// Constructs the converter would reject or misread, such as inline flags, atomic groups,
+/// possessive quantifiers and unknown letter escapes, are reported as errors.
+///
+/// # Errors
+///
+/// When the regex uses a construct of its flavor that has no ECMAScript equivalent.
+pub(crate) fn normalize_regex(
+    regex: &str,
+    flavor: RegexFlavor,
+) -> Result<Cow<'_, str>, String> {
+    if flavor == RegexFlavor::Ecma {
+        return Ok(Cow::Borrowed(regex));
+    }
+    let chars: Vec<char> = regex.chars().collect();
+    let unsupported = |construct: &str, description: &str, pos: usize| {
+        format!(
+            "Unsupported regex construct `{}` at position {}: {}",
+            construct,
+            pos + 1,
+            description
+        )
+    };
+    let mut out = String::with_capacity(regex.len());
+    let mut in_class = false;
+    let mut after_quantifier = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let was_quantifier = std::mem::take(&mut after_quantifier);
+        match c {
+            '\\' if i + 1 < chars.len() => {
+                let escaped = chars[i + 1];
+                match escaped {
+                    'x' if chars.get(i + 2) == Some(&'{') => {
+                        let Some(len) =
+                            chars[i + 3..].iter().position(|&c| c == '}')
+                        else {
+                            return Err(unsupported(
+                                "\\x{",
+                                "the escape is not closed",
+                                i,
+                            ));
+                        };
+                        out.push_str("\\u{");
+                        out.extend(&chars[i + 3..i + 3 + len]);
+                        out.push('}');
+                        i += 4 + len;
+                        continue;
+                    },
+                    'A' if !in_class && i == 0 => out.push('^'),
+                    'z' | 'Z' if !in_class && i + 2 == chars.len() => {
+                        out.push('$')
+                    },
+                    'A' | 'z' | 'Z' => {
+                        return Err(unsupported(
+                            &format!("\\{}", escaped),
+                            "the anchor is only supported at the start (\\A) or the \
+                             end (\\z, \\Z) of the regex",
+                            i,
+                        ));
+                    },
+                    'a' => out.push_str("\\x07"),
+                    'e' => out.push_str("\\x1B"),
+                    'd' | 'D' | 'w' | 'W' | 's' | 'S' | 'n' | 'r' | 't'
+                    | 'f' | 'v' | 'x' | 'u' | 'c' | 'b' | 'B' | 'p' | 'P'
+                    | 'k' => {
+                        out.push('\\');
+                        out.push(escaped);
+                    },
+                    _ if escaped.is_ascii_alphabetic() => {
+                        return Err(unsupported(
+                            &format!("\\{}", escaped),
+                            "the escape sequence has no equivalent in the regex \
+                             converter",
+                            i,
+                        ));
+                    },
+                    _ => {
+                        out.push('\\');
+                        out.push(escaped);
+                    },
+                }
+                i += 2;
+                continue;
+            },
+            '[' if !in_class => in_class = true,
+            ']' if in_class => in_class = false,
+            '(' if !in_class && chars.get(i + 1) == Some(&'?') => {
+                let rest = &chars[i + 2..];
+                let name_start = match rest {
+                    ['P', '<', ..] => Some(2),
+                    ['<', next, ..] if *next != '=' && *next != '!' => Some(1),
+                    ['\'', ..] => Some(1),
+                    _ => None,
+                };
+                if let Some(name_start) = name_start {
+                    let close = if rest[0] == '\'' {
+                        '\''
+                    } else {
+                        '>'
+                    };
+                    let Some(len) =
+                        rest[name_start..].iter().position(|&c| c == close)
+                    else {
+                        return Err(unsupported(
+                            "(?<",
+                            "the group name is not closed",
+                            i,
+                        ));
+                    };
+                    out.push_str("(?:");
+                    i += 2 + name_start + len + 1;
+                    continue;
+                }
+                match rest.first() {
+                    Some(':' | '=' | '!' | '<') => {},
+                    Some('#') => {
+                        let Some(len) = rest.iter().position(|&c| c == ')')
+                        else {
+                            return Err(unsupported(
+                                "(?#",
+                                "the comment is not closed",
+                                i,
+                            ));
+                        };
+                        i += 2 + len + 1;
+                        continue;
+                    },
+                    Some('P') if rest.get(1) == Some(&'=') => {
+                        return Err(unsupported(
+                            "(?P=",
+                            "backreferences are not supported",
+                            i,
+                        ));
+                    },
+                    Some('>') => {
+                        return Err(unsupported(
+                            "(?>",
+                            "atomic groups are not supported",
+                            i,
+                        ));
+                    },
+                    _ => {
+                        let end = rest
+                            .iter()
+                            .position(|&c| c == ')' || c == ':')
+                            .map_or(rest.len(), |end| end + 1);
+                        let construct: String =
+                            ['(', '?'].iter().chain(&rest[..end]).collect();
+                        return Err(unsupported(
+                            &construct,
+                            "inline flags are not supported",
+                            i,
+                        ));
+                    },
+                }
+            },
+            '+' if !in_class && was_quantifier => {
+                return Err(unsupported(
+                    &format!("{}+", chars[i - 1]),
+                    "possessive quantifiers are not supported",
+                    i - 1,
+                ));
+            },
+            '*' | '+' | '?' | '}' if !in_class => after_quantifier = true,
+            _ => {},
+        }
+        out.push(c);
+        i += 1;
+    }
+    Ok(Cow::Owned(out))
+}
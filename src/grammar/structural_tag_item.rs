@@ -1,7 +1,11 @@
 /// Deprecated. Definition of a structural tag item.
 ///
 /// See :meth:`xgrammar.Grammar.from_structural_tag` for more details.
+///
+/// With the `serde` feature, the item (de)serializes as an object with the string fields
+/// `begin`, `schema` and `end`; the schema is the JSON schema document as a string.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StructuralTagItem {
     /// The begin tag.
     pub begin: String,
@@ -12,6 +16,8 @@ pub struct StructuralTagItem {
 }
 
 impl StructuralTagItem {
+    /// Construct a structural tag item without checking the schema. An invalid schema is
+    /// reported when the item is compiled; use `try_new` to check it at construction.
     pub fn new(
         begin: impl Into<String>,
         schema: impl Into<String>,
@@ -23,4 +29,51 @@ impl StructuralTagItem {
             end: end.into(),
         }
     }
+
+    /// Construct a structural tag item, checking that `schema` is a valid JSON schema
+    /// document, i.e. a JSON object or boolean. The schema keywords themselves are checked
+    /// when the item is compiled.
+    ///
+    /// # Parameters
+    ///
+    /// - `begin`: The begin tag.
+    /// - `schema`: The JSON schema of the content between the tags.
+    /// - `end`: The end tag.
+    ///
+    /// # Errors
+    ///
+    /// - When `schema` is not valid JSON. The error gives the line and column of the problem.
+    /// - When `schema` is valid JSON but neither an object nor a boolean.
+    pub fn try_new(
+        begin: impl Into<String>,
+        schema: impl Into<String>,
+        end: impl Into<String>,
+    ) -> Result<Self, String> {
+        let item = Self::new(begin, schema, end);
+        item.validate()?;
+        Ok(item)
+    }
+
+    /// Check that the schema of the item is a JSON object or boolean.
+    ///
+    /// # Errors
+    ///
+    /// - When the schema is not valid JSON, or is neither an object nor a boolean.
+    pub fn validate(&self) -> Result<(), String> {
+        let schema: serde_json::Value = serde_json::from_str(&self.schema)
+            .map_err(|e| {
+                format!(
+                    "Invalid JSON in the schema of the structural tag {:?}: {}",
+                    self.begin, e
+                )
+            })?;
+        if !schema.is_object() && !schema.is_boolean() {
+            return Err(format!(
+                "The schema of the structural tag {:?} must be a JSON object or boolean, \
+                 got: {}",
+                self.begin, schema
+            ));
+        }
+        Ok(())
+    }
 }
@@ -121,10 +121,6 @@ mod ffi {
             input_str: &CxxString,
             debug_print: bool,
         ) -> bool;
-        pub fn Rollback(
-            self: Pin<&mut GrammarMatcher>,
-            num_tokens: i32,
-        );
         pub fn IsTerminated(self: &GrammarMatcher) -> bool;
         pub fn IsCompleted(self: &GrammarMatcher) -> bool;
         pub fn Reset(self: Pin<&mut GrammarMatcher>);
@@ -209,6 +205,13 @@ mod ffi {
             error_out: *mut CxxString,
         ) -> UniquePtr<TokenizerInfo>;
 
+        pub unsafe fn tokenizer_info_deserialize_bytes_or_error(
+            data: *const u8,
+            len: usize,
+            error_kind: *mut i32,
+            error_out: *mut CxxString,
+        ) -> UniquePtr<TokenizerInfo>;
+
         pub unsafe fn detect_metadata_from_hf(
             backend_str: &CxxString,
             metadata_out: *mut CxxString,
@@ -283,6 +286,20 @@ mod ffi {
 
         pub fn grammar_serialize_json(self_: &Grammar) -> UniquePtr<CxxString>;
 
+        pub fn grammar_is_empty_language(self_: &Grammar) -> bool;
+
+        pub fn grammar_num_rules(self_: &Grammar) -> i32;
+
+        pub fn grammar_rule_names(
+            self_: &Grammar
+        ) -> UniquePtr<CxxVector<CxxString>>;
+
+        pub unsafe fn grammar_validate(
+            self_: &Grammar,
+            unreachable_out: Pin<&mut CxxVector<CxxString>>,
+            error_out: *mut CxxString,
+        ) -> bool;
+
         // cxx_utils/compiled_grammar.hpp
 
         pub unsafe fn compiled_grammar_deserialize_json_or_error(
@@ -300,6 +317,19 @@ mod ffi {
             self_: &CompiledGrammar
         ) -> UniquePtr<TokenizerInfo>;
 
+        pub fn compiled_grammar_clone(
+            self_: &CompiledGrammar
+        ) -> UniquePtr<CompiledGrammar>;
+
+        pub fn compiled_grammar_num_cached_states(
+            self_: &CompiledGrammar
+        ) -> usize;
+
+        pub fn compiled_grammar_memory_breakdown(
+            self_: &CompiledGrammar,
+            rule_names_out: Pin<&mut CxxVector<CxxString>>,
+        ) -> UniquePtr<CxxVector<u64>>;
+
         pub fn compiled_grammar_serialize_json(
             self_: &CompiledGrammar
         ) -> UniquePtr<CxxString>;
@@ -314,15 +344,33 @@ mod ffi {
             error_out: *mut CxxString,
         ) -> UniquePtr<GrammarCompiler>;
 
-        pub fn tokenizer_info_from_vocab_and_metadata(
+        pub fn grammar_compiler_share(
+            compiler: &GrammarCompiler
+        ) -> UniquePtr<GrammarCompiler>;
+
+        pub unsafe fn tokenizer_info_from_vocab_and_metadata(
             encodec_vocab: &CxxVector<CxxString>,
             metadata: &CxxString,
+            error_out: *mut CxxString,
+        ) -> UniquePtr<TokenizerInfo>;
+
+        pub fn tokenizer_info_clone(
+            self_: &TokenizerInfo
         ) -> UniquePtr<TokenizerInfo>;
 
+        pub fn tokenizer_info_shares_impl(
+            self_: &TokenizerInfo,
+            other: &TokenizerInfo,
+        ) -> bool;
+
         pub fn tokenizer_info_serialize_json(
             self_: &TokenizerInfo
         ) -> UniquePtr<CxxString>;
 
+        pub fn tokenizer_info_serialize_bytes(
+            self_: &TokenizerInfo
+        ) -> UniquePtr<CxxVector<u8>>;
+
         pub fn tokenizer_info_dump_metadata(
             self_: &TokenizerInfo
         ) -> UniquePtr<CxxString>;
@@ -342,6 +390,27 @@ mod ffi {
             error_out: *mut CxxString,
         ) -> UniquePtr<CompiledGrammar>;
 
+        pub fn compiler_compile_json_schema_batch(
+            compiler: Pin<&mut GrammarCompiler>,
+            schemas: &CxxVector<CxxString>,
+            any_whitespace: bool,
+            has_indent: bool,
+            indent: i32,
+            has_separators: bool,
+            separator_comma: &CxxString,
+            separator_colon: &CxxString,
+            strict_mode: bool,
+            has_max_whitespace_cnt: bool,
+            max_whitespace_cnt: i32,
+            max_threads: i32,
+            errors_out: Pin<&mut CxxVector<CxxString>>,
+        ) -> UniquePtr<CxxVector<CompiledGrammar>>;
+
+        pub fn compiled_grammar_vec_take(
+            vec: Pin<&mut CxxVector<CompiledGrammar>>,
+            index: usize,
+        ) -> UniquePtr<CompiledGrammar>;
+
         pub unsafe fn compiler_compile_builtin_json(
             compiler: Pin<&mut GrammarCompiler>,
             error_out: *mut CxxString,
@@ -386,6 +455,12 @@ mod ffi {
             self_: Pin<&mut GrammarMatcher>
         ) -> UniquePtr<CxxString>;
 
+        pub unsafe fn grammar_matcher_rollback(
+            self_: Pin<&mut GrammarMatcher>,
+            num_tokens: i32,
+            error_out: *mut CxxString,
+        ) -> bool;
+
         pub fn grammar_matcher_debug_print_internal_state(
             self_: &GrammarMatcher
         ) -> UniquePtr<CxxString>;
@@ -397,6 +472,40 @@ mod ffi {
             debug_print: bool,
         ) -> bool;
 
+        pub unsafe fn grammar_matcher_try_fill_next_token_bitmask(
+            self_: Pin<&mut GrammarMatcher>,
+            bitmask_r: *mut DLTensor,
+            index: i32,
+            debug_print: bool,
+            need_apply: &mut bool,
+            error_out: *mut CxxString,
+        ) -> bool;
+
+        pub unsafe fn token_bitmask_clear_tokens(
+            bitmask_r: *mut DLTensor,
+            index: i32,
+            vocab_size: i32,
+            token_ids_ptr: *const i32,
+            token_ids_len: usize,
+            changed: &mut bool,
+            error_out: *mut CxxString,
+        ) -> bool;
+
+        pub unsafe fn token_bitmask_count_tokens(
+            bitmask: *const DLTensor,
+            index: i32,
+            vocab_size: i32,
+            count: &mut usize,
+            error_out: *mut CxxString,
+        ) -> bool;
+
+        pub unsafe fn token_bitmask_accepted_tokens(
+            bitmask: *const DLTensor,
+            index: i32,
+            vocab_size: i32,
+            error_out: *mut CxxString,
+        ) -> UniquePtr<CxxVector<i32>>;
+
         pub fn grammar_matcher_fork(
             self_: &GrammarMatcher
         ) -> UniquePtr<GrammarMatcher>;
@@ -478,6 +587,21 @@ mod ffi {
             byte_offset: u64,
         ) -> UniquePtr<DLTensor>;
 
+        pub fn tensor_has_data(tensor: &DLTensor) -> bool;
+        pub fn tensor_device_type(tensor: &DLTensor) -> i32;
+        pub fn tensor_dtype(tensor: &DLTensor) -> DLDataType;
+        pub fn tensor_ndim(tensor: &DLTensor) -> i32;
+        pub unsafe fn tensor_shape(
+            tensor: &DLTensor,
+            dim: i32,
+        ) -> i64;
+        pub fn tensor_has_strides(tensor: &DLTensor) -> bool;
+        pub unsafe fn tensor_stride(
+            tensor: &DLTensor,
+            dim: i32,
+        ) -> i64;
+        pub fn tensor_byte_offset(tensor: &DLTensor) -> u64;
+
     }
 
     // This block is unsafe because some functions in it are declared safe.
@@ -513,6 +637,13 @@ mod ffi {
             index: i32,
         ) -> UniquePtr<CxxVector<i32>>;
 
+        pub unsafe fn get_masked_tokens_all_rows(
+            bitmask_r: *const DLTensor,
+            vocab_size: i32,
+            batch_size: i32,
+            error_out: *mut CxxString,
+        ) -> UniquePtr<CxxVector<i32>>;
+
         pub type SingleTokenResult;
         pub unsafe fn is_single_token_bitmask(
             bitmask_r: *const DLTensor,
@@ -579,27 +710,45 @@ pub use ffi::GetBitmaskSize as get_bitmask_size;
 pub use ffi::c_void;
 mod compiler;
 mod config;
-mod dlpack;
+pub mod dlpack;
 mod error;
 mod grammar;
 mod matcher;
+pub mod prelude;
 mod tokenizer_info;
 mod utils;
 
 pub mod testing;
 
-pub use compiler::{CompiledGrammar, GrammarCompiler};
+pub use compiler::{
+    CacheEntryInfo, Compilable, CompileStats, CompiledGrammar, GrammarCompiler,
+    MemoryBreakdown,
+};
 pub use config::{
-    get_max_recursion_depth, get_serialization_version, set_max_recursion_depth,
+    get_max_recursion_depth, get_serialization_version, get_serialized_version,
+    set_max_recursion_depth, swap_max_recursion_depth,
 };
 pub use cxx::UniquePtr as CxxUniquePtr;
-pub use dlpack::{DLDataTypeCode, DLDevice, DLDeviceType};
-pub use error::{DeserializeError, StructuralTagError};
-pub use grammar::{Grammar, StructuralTagItem};
+pub use dlpack::{
+    DLDataTypeCode, DLDevice, DLDeviceType, DLTensorBuilder, OwnedDLTensor,
+};
+pub use error::{
+    AcceptRejectReason, DeserializeError, JsonSchemaError, StructuralTagError,
+};
+pub use grammar::{
+    EbnfFormatOptions, Grammar, JsonSchemaOptions, Markers, PartTracker,
+    RegexFlavor, RegexMatchMode, StructuralTagItem,
+};
 pub use matcher::{
-    BatchGrammarMatcher, GrammarMatcher, allocate_token_bitmask,
-    apply_token_bitmask_inplace_cpu, get_bitmask_shape, reset_token_bitmask,
+    AcceptedStep, AcceptedTokens, AlignedBitmask, BatchGrammarMatcher,
+    BatchStepResult, BitmaskDLTensor, GrammarMatcher, MatcherDebugState,
+    MatcherRuleState, accepted_tokens_iter, allocate_token_bitmask,
+    allocate_token_bitmask_aligned, apply_token_bitmask_inplace_cpu,
+    apply_token_bitmask_with_fill_value, bitmask_dltensor, get_bitmask_shape,
+    is_token_accepted, reject_all_token_bitmask, reset_token_bitmask,
+    try_allocate_token_bitmask,
 };
 pub use tokenizer_info::{
-    HfMetadata, TokenizerInfo, VocabType, detect_metadata_from_hf,
+    HfMetadata, TokenizerInfo, VocabDiagnostics, VocabType,
+    detect_metadata_from_hf,
 };
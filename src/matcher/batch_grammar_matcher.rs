@@ -4,8 +4,32 @@ use crate::{CxxUniquePtr, DLTensor, ffi, utils::bytes_as_c_char_ptr};
 /// A batch version of `GrammarMatcher` that can fill the next token bitmask for multiple
 /// matchers in parallel. It utilizes multiple threads to speed up the computation. It is
 /// especially useful when the batch size is large.
+///
+/// Only [`BatchGrammarMatcher::batch_fill_next_token_bitmask`] is parallel: with
+/// `max_threads > 1` every matcher becomes one task on an internal thread pool, and
+/// each task writes only its own bitmask row. With `max_threads == 1` the rows are
+/// filled serially on the calling thread. The accept and rollback helpers are always
+/// serial, since they are cheap compared to the bitmask generation.
+///
+/// For continuous batching, the matchers can also be kept in the batch matcher itself with
+/// [`BatchGrammarMatcher::push`] and [`BatchGrammarMatcher::remove`], each in a slot that is
+/// its row in the bitmask filled by [`BatchGrammarMatcher::fill_next_token_bitmask`].
 pub struct BatchGrammarMatcher {
     inner: CxxUniquePtr<ffi::BatchGrammarMatcher>,
+    slots: Vec<Option<GrammarMatcher>>,
+}
+
+/// The result of [`BatchGrammarMatcher::accept_token`], with one entry per slot.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BatchStepResult {
+    /// Whether the matcher in each slot accepted its token. `false` for the empty slots.
+    pub accepted: Vec<bool>,
+    /// Whether the matcher in each slot is terminated after the step, e.g. to remove the
+    /// finished sequences from the batch. This includes the matchers that were already
+    /// terminated before the step, and, for the matchers created with
+    /// `terminate_without_stop_token`, the ones whose input is complete. `false` for the empty
+    /// slots.
+    pub terminated: Vec<bool>,
 }
 
 impl BatchGrammarMatcher {
@@ -13,12 +37,15 @@ impl BatchGrammarMatcher {
     ///
     /// # Parameters
     ///
-    /// - `max_threads`: The maximum number of threads to use for parallel processing. If set
-    ///   to -1, the max_threads will be set to `std::thread::hardware_concurrency() / 2`.
+    /// - `max_threads`: The maximum number of threads to use for parallel processing. It is
+    ///   capped at `std::thread::hardware_concurrency()`. If set to a negative value (e.g.
+    ///   -1), the max_threads will be set to `std::thread::hardware_concurrency() / 2`.
+    ///   1 disables the thread pool.
     ///
     /// # Errors
     ///
-    /// Returns an error if the batch grammar matcher cannot be constructed.
+    /// Returns an error if `max_threads` is 0 or the batch grammar matcher cannot be
+    /// constructed.
     pub fn new(max_threads: i32) -> Result<Self, String> {
         cxx::let_cxx_string!(error_out_cxx = "");
         let ffi_pin = unsafe {
@@ -32,6 +59,7 @@ impl BatchGrammarMatcher {
         }
         Ok(Self {
             inner: ffi_pin,
+            slots: Vec::new(),
         })
     }
 
@@ -46,6 +74,11 @@ impl BatchGrammarMatcher {
 
     /// Fill the next token bitmask for multiple matchers.
     ///
+    /// The rows are filled concurrently on a thread pool of up to `max_threads` threads
+    /// (see [`BatchGrammarMatcher::new`]); the call returns once every row is written.
+    /// The thread pool is created per call, so the speedup shows when the per-row work
+    /// dominates, i.e. for large vocabularies and batch sizes.
+    ///
     /// # Parameters
     ///
     /// - `matchers`: The list of matchers to fill the bitmask for.
@@ -58,6 +91,9 @@ impl BatchGrammarMatcher {
     /// - `debug_print`: Whether to print information about generated bitmask.
     ///   Helpful for debugging.
     ///
+    /// The tokens banned with `GrammarMatcher::set_banned_tokens`, or over the length limit of
+    /// `GrammarMatcher::set_max_length`, are masked in the row of their matcher.
+    ///
     /// # Panics
     ///
     /// If the bitmask is invalid (not on CPU, not int32, shape mismatch).
@@ -68,35 +104,193 @@ impl BatchGrammarMatcher {
         indices: Option<&[i32]>,
         debug_print: bool,
     ) {
-        let mut ffi_matcher_vec = ffi::new_grammar_matcher_vector();
-        {
-            let mut vec_pin = ffi_matcher_vec.pin_mut();
-            ffi::grammar_matcher_vec_reserve(vec_pin.as_mut(), matchers.len());
-            for matcher in matchers {
-                ffi::grammar_matcher_vec_push(
-                    vec_pin.as_mut(),
-                    matcher.ffi_ref(),
-                );
-            }
-        }
+        fill_rows(
+            &mut self.inner,
+            matchers.iter(),
+            bitmask,
+            indices,
+            debug_print,
+        );
+    }
 
-        let (has_indices, indices_ptr, indices_len) = match indices {
-            Some(slice) if !slice.is_empty() => {
-                (true, slice.as_ptr(), slice.len())
+    /// Add a matcher to the batch, e.g. when a sequence enters a continuously batched
+    /// generation.
+    ///
+    /// # Returns
+    ///
+    /// The slot of the matcher, i.e. its row in the bitmask filled by
+    /// [`BatchGrammarMatcher::fill_next_token_bitmask`]. The lowest slot freed by
+    /// [`BatchGrammarMatcher::remove`] is reused, so the number of slots stays at the largest
+    /// number of matchers in the batch at once.
+    pub fn push(
+        &mut self,
+        matcher: GrammarMatcher,
+    ) -> usize {
+        match self.slots.iter().position(Option::is_none) {
+            Some(index) => {
+                self.slots[index] = Some(matcher);
+                index
             },
-            _ => (false, std::ptr::null(), 0usize),
-        };
+            None => {
+                self.slots.push(Some(matcher));
+                self.slots.len() - 1
+            },
+        }
+    }
 
-        unsafe {
-            ffi::batch_matcher_batch_fill_next_token_bitmask(
-                self.inner.as_mut().expect("BatchGrammarMatcher inner is null"),
-                ffi_matcher_vec.as_mut().unwrap().get_unchecked_mut(),
-                bitmask.as_mut_ptr(),
-                has_indices,
-                indices_ptr,
-                indices_len,
-                debug_print,
-            );
+    /// Remove the matcher in slot `index` from the batch, e.g. when its sequence finishes.
+    ///
+    /// # Returns
+    ///
+    /// The removed matcher, or `None` if the slot is empty or out of range.
+    pub fn remove(
+        &mut self,
+        index: usize,
+    ) -> Option<GrammarMatcher> {
+        let matcher = self.slots.get_mut(index)?.take();
+        while matches!(self.slots.last(), Some(None)) {
+            self.slots.pop();
+        }
+        matcher
+    }
+
+    /// The matcher in slot `index`, or `None` if the slot is empty or out of range.
+    pub fn get(
+        &self,
+        index: usize,
+    ) -> Option<&GrammarMatcher> {
+        self.slots.get(index)?.as_ref()
+    }
+
+    /// Mutable access to the matcher in slot `index`, e.g. to accept the token sampled for
+    /// its row. `None` if the slot is empty or out of range.
+    pub fn get_mut(
+        &mut self,
+        index: usize,
+    ) -> Option<&mut GrammarMatcher> {
+        self.slots.get_mut(index)?.as_mut()
+    }
+
+    /// The occupied slots with their matchers, in slot order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &GrammarMatcher)> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| Some((index, slot.as_ref()?)))
+    }
+
+    /// The number of matchers in the batch.
+    pub fn len(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.is_some()).count()
+    }
+
+    /// Whether the batch holds no matcher.
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// The number of slots, i.e. one past the highest occupied slot. The bitmask passed to
+    /// [`BatchGrammarMatcher::fill_next_token_bitmask`] needs at least this many rows.
+    pub fn num_slots(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Fill the next token bitmask for the matchers added with
+    /// [`BatchGrammarMatcher::push`], writing the row of each occupied slot. The rows of the
+    /// empty slots are left unchanged. The rows are filled in parallel as in
+    /// [`BatchGrammarMatcher::batch_fill_next_token_bitmask`].
+    ///
+    /// # Parameters
+    ///
+    /// - `bitmask`: Must be a 2-dimensional int32 tensor with shape
+    ///   `(bitmask_batch_size, bitmask_size)`, where `bitmask_batch_size` is at least
+    ///   [`BatchGrammarMatcher::num_slots`].
+    /// - `debug_print`: Whether to print information about generated bitmask.
+    ///
+    /// # Panics
+    ///
+    /// If the bitmask is invalid (not on CPU, not int32, shape mismatch).
+    pub fn fill_next_token_bitmask(
+        &mut self,
+        bitmask: &mut CxxUniquePtr<DLTensor>,
+        debug_print: bool,
+    ) {
+        let (indices, matchers): (Vec<i32>, Vec<&GrammarMatcher>) = self
+            .slots
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| Some((index as i32, slot.as_ref()?)))
+            .unzip();
+        if matchers.is_empty() {
+            return;
+        }
+        fill_rows(
+            &mut self.inner,
+            matchers.into_iter(),
+            bitmask,
+            Some(&indices),
+            debug_print,
+        );
+    }
+
+    /// Accept the sampled token of each matcher added with [`BatchGrammarMatcher::push`],
+    /// e.g. after sampling from the bitmask filled by
+    /// [`BatchGrammarMatcher::fill_next_token_bitmask`]. The tokens are accepted as in
+    /// [`BatchGrammarMatcher::batch_accept_token`].
+    ///
+    /// # Parameters
+    ///
+    /// - `tokens`: The token of each slot. The tokens of the empty slots are ignored.
+    ///
+    /// # Returns
+    ///
+    /// Whether each slot accepted its token and is terminated after the step, see
+    /// `BatchStepResult`.
+    ///
+    /// # Panics
+    ///
+    /// If the length of `tokens` differs from [`BatchGrammarMatcher::num_slots`].
+    pub fn accept_token(
+        &mut self,
+        tokens: &[i32],
+    ) -> BatchStepResult {
+        assert_eq!(
+            tokens.len(),
+            self.slots.len(),
+            "tokens must have one token per slot"
+        );
+        let (matchers, occupied_tokens): (Vec<&GrammarMatcher>, Vec<i32>) =
+            self.slots
+                .iter()
+                .zip(tokens)
+                .filter_map(|(slot, &token_id)| {
+                    Some((slot.as_ref()?, token_id))
+                })
+                .unzip();
+        let mut occupied_accepted =
+            accept_tokens(matchers.into_iter(), &occupied_tokens, false)
+                .into_vec()
+                .into_iter();
+        let accepted = self
+            .slots
+            .iter()
+            .map(|slot| {
+                slot.is_some()
+                    && occupied_accepted
+                        .next()
+                        .expect("one result per occupied slot")
+            })
+            .collect();
+        let terminated = self
+            .slots
+            .iter()
+            .map(|slot| {
+                slot.as_ref().is_some_and(GrammarMatcher::is_terminated)
+            })
+            .collect();
+        BatchStepResult {
+            accepted,
+            terminated,
         }
     }
 
@@ -128,28 +322,7 @@ impl BatchGrammarMatcher {
             "matchers and tokens must have the same length"
         );
 
-        let mut ffi_matcher_vec = ffi::new_grammar_matcher_vector();
-        {
-            let mut vec_pin = ffi_matcher_vec.pin_mut();
-            ffi::grammar_matcher_vec_reserve(vec_pin.as_mut(), matchers.len());
-            for matcher in matchers {
-                ffi::grammar_matcher_vec_push(
-                    vec_pin.as_mut(),
-                    matcher.ffi_ref(),
-                );
-            }
-        }
-
-        let result = unsafe {
-            ffi::batch_accept_token(
-                ffi_matcher_vec.as_mut().unwrap().get_unchecked_mut(),
-                tokens.as_ptr(),
-                tokens.len(),
-                debug_print,
-            )
-        };
-
-        result.iter().map(|&b| b != 0).collect::<Vec<_>>().into_boxed_slice()
+        accept_tokens(matchers.iter(), tokens, debug_print)
     }
 
     /// Accept a batch of strings for multiple matchers.
@@ -180,17 +353,7 @@ impl BatchGrammarMatcher {
             "matchers and strings must have the same length"
         );
 
-        let mut ffi_matcher_vec = ffi::new_grammar_matcher_vector();
-        {
-            let mut vec_pin = ffi_matcher_vec.pin_mut();
-            ffi::grammar_matcher_vec_reserve(vec_pin.as_mut(), matchers.len());
-            for matcher in matchers {
-                ffi::grammar_matcher_vec_push(
-                    vec_pin.as_mut(),
-                    matcher.ffi_ref(),
-                );
-            }
-        }
+        let mut ffi_matcher_vec = ffi_matcher_vector(matchers.iter());
 
         let mut cxx_strings = ffi::new_string_vector();
         {
@@ -216,7 +379,15 @@ impl BatchGrammarMatcher {
             )
         };
 
-        result.iter().map(|&b| b != 0).collect::<Vec<_>>().into_boxed_slice()
+        let accepted: Box<[bool]> = result.iter().map(|&b| b != 0).collect();
+        for ((matcher, string), &accepted) in
+            matchers.iter().zip(strings).zip(accepted.iter())
+        {
+            if accepted {
+                matcher.record_bytes(string.as_ref().as_bytes());
+            }
+        }
+        accepted
     }
 
     /// Roll back each matcher by the corresponding number of tokens. Panics if the slice lengths
@@ -231,17 +402,7 @@ impl BatchGrammarMatcher {
             "matchers and num_tokens must have the same length"
         );
 
-        let mut ffi_matcher_vec = ffi::new_grammar_matcher_vector();
-        {
-            let mut vec_pin = ffi_matcher_vec.pin_mut();
-            ffi::grammar_matcher_vec_reserve(vec_pin.as_mut(), matchers.len());
-            for matcher in matchers {
-                ffi::grammar_matcher_vec_push(
-                    vec_pin.as_mut(),
-                    matcher.ffi_ref(),
-                );
-            }
-        }
+        let mut ffi_matcher_vec = ffi_matcher_vector(matchers.iter());
 
         unsafe {
             ffi::batch_rollback(
@@ -250,5 +411,92 @@ impl BatchGrammarMatcher {
                 num_tokens.len(),
             );
         }
+        // The C++ side stops at the first matcher that cannot roll back, and a negative
+        // count rolls back nothing.
+        for (matcher, &num_tokens) in matchers.iter().zip(num_tokens) {
+            let num_tokens = usize::try_from(num_tokens).unwrap_or(0);
+            if num_tokens > matcher.num_accepted_steps() {
+                break;
+            }
+            matcher.record_rollback(num_tokens);
+        }
+    }
+}
+
+fn ffi_matcher_vector<'a>(
+    matchers: impl ExactSizeIterator<Item = &'a GrammarMatcher>
+) -> CxxUniquePtr<cxx::CxxVector<ffi::GrammarMatcher>> {
+    let mut ffi_matcher_vec = ffi::new_grammar_matcher_vector();
+    {
+        let mut vec_pin = ffi_matcher_vec.pin_mut();
+        ffi::grammar_matcher_vec_reserve(vec_pin.as_mut(), matchers.len());
+        for matcher in matchers {
+            ffi::grammar_matcher_vec_push(vec_pin.as_mut(), matcher.ffi_ref());
+        }
+    }
+    ffi_matcher_vec
+}
+
+// Accept `tokens[i]` for `matchers[i]`, recording the accepted steps.
+fn accept_tokens<'a>(
+    matchers: impl ExactSizeIterator<Item = &'a GrammarMatcher> + Clone,
+    tokens: &[i32],
+    debug_print: bool,
+) -> Box<[bool]> {
+    let mut ffi_matcher_vec = ffi_matcher_vector(matchers.clone());
+
+    let result = unsafe {
+        ffi::batch_accept_token(
+            ffi_matcher_vec.as_mut().unwrap().get_unchecked_mut(),
+            tokens.as_ptr(),
+            tokens.len(),
+            debug_print,
+        )
+    };
+
+    let accepted: Box<[bool]> = result.iter().map(|&b| b != 0).collect();
+    for ((matcher, &token_id), &accepted) in
+        matchers.zip(tokens).zip(accepted.iter())
+    {
+        if accepted {
+            matcher.record_token(token_id);
+        }
+    }
+    accepted
+}
+
+// Fill the bitmask rows `indices` (or `0..matchers.len()`) for `matchers`.
+fn fill_rows<'a>(
+    inner: &mut CxxUniquePtr<ffi::BatchGrammarMatcher>,
+    matchers: impl ExactSizeIterator<Item = &'a GrammarMatcher> + Clone,
+    bitmask: &mut CxxUniquePtr<DLTensor>,
+    indices: Option<&[i32]>,
+    debug_print: bool,
+) {
+    let mut ffi_matcher_vec = ffi_matcher_vector(matchers.clone());
+
+    let (has_indices, indices_ptr, indices_len) = match indices {
+        Some(slice) if !slice.is_empty() => (true, slice.as_ptr(), slice.len()),
+        _ => (false, std::ptr::null(), 0usize),
+    };
+
+    unsafe {
+        ffi::batch_matcher_batch_fill_next_token_bitmask(
+            inner.as_mut().expect("BatchGrammarMatcher inner is null"),
+            ffi_matcher_vec.as_mut().unwrap().get_unchecked_mut(),
+            bitmask.as_mut_ptr(),
+            has_indices,
+            indices_ptr,
+            indices_len,
+            debug_print,
+        );
+    }
+    for (i, matcher) in matchers.enumerate() {
+        let index = indices
+            .filter(|slice| !slice.is_empty())
+            .map_or(i as i32, |slice| slice[i]);
+        matcher
+            .clear_restricted_tokens_in_bitmask(bitmask, index)
+            .unwrap_or_else(|err| panic!("{}", err));
     }
 }
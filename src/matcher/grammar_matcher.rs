@@ -1,6 +1,19 @@
-use std::pin::Pin;
+use std::{
+    cell::{Cell, Ref, RefCell},
+    collections::{HashMap, HashSet},
+    pin::Pin,
+    rc::Rc,
+};
 
-use crate::{CxxUniquePtr, DLTensor, compiler::CompiledGrammar, ffi};
+use crate::{
+    AcceptRejectReason, CxxUniquePtr, DLTensor, TokenizerInfo,
+    compiler::CompiledGrammar,
+    ffi,
+    matcher::{
+        allocate_token_bitmask, bitmask_dltensor, check_bitmask,
+        get_bitmask_shape,
+    },
+};
 
 /// Match the output of the LLM to the specified grammar, then generate the mask for the next
 /// token. This is the core class in the grammar-guided generation.
@@ -20,8 +33,126 @@ use crate::{CxxUniquePtr, DLTensor, compiler::CompiledGrammar, ffi};
 pub struct GrammarMatcher {
     inner: CxxUniquePtr<ffi::GrammarMatcher>,
     stored_stop_token_ids: Box<[i32]>,
+    tokenizer_info: Rc<TokenizerInfo>,
+    terminate_without_stop_token: bool,
+    banned_token_ids: Box<[i32]>,
+    // The maximum number of tokens to accept, see `set_max_length`.
+    max_tokens: Option<usize>,
+    // The maximum number of steps to rollback, `None` if unlimited. See `new`.
+    max_rollback_tokens: Option<usize>,
+    // The number of steps accepted since the last reset, i.e. the length of the rollback
+    // history of the C++ matcher, which does not expose it. See `num_accepted_steps`. A
+    // `Cell` because the batch functions of `BatchGrammarMatcher` advance matchers through
+    // shared references.
+    num_steps: Cell<usize>,
+    // The number of those steps that are tokens, for `set_max_length`, and the indices of the
+    // steps that are strings, to update it on rollback.
+    num_tokens: Cell<usize>,
+    string_steps: RefCell<Vec<usize>>,
+    // The stop token, if it is the last accepted step. See `last_stop_token`.
+    stop_token: Cell<Option<i32>>,
+    // The steps accepted since the last reset, for `save_state`. Only recorded with
+    // `with_state_tracking`.
+    accepted_steps: Option<RefCell<Vec<AcceptedStep>>>,
 }
 
+/// One step accepted by a `GrammarMatcher`, see `GrammarMatcher::accepted_steps`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AcceptedStep {
+    /// A token accepted by `accept_token` or `BatchGrammarMatcher::batch_accept_token`.
+    Token(i32),
+    /// A string accepted by `accept_string`, `accept_bytes`, `accept_string_prefix` or
+    /// `BatchGrammarMatcher::batch_accept_string`.
+    Bytes(Vec<u8>),
+}
+
+/// A structured view of the internal state of a `GrammarMatcher`, see
+/// `GrammarMatcher::internal_state`. Like `debug_print_internal_state`, this is meant for
+/// debugging and its contents are subject to change.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatcherDebugState {
+    /// The number of steps in the parser history, including the initial step. This is the
+    /// size of the stack that rollbacks pop from.
+    pub num_history_steps: usize,
+    /// The scannable parser states of the latest step, i.e. the rule positions the matcher
+    /// can currently continue from.
+    pub states: Vec<MatcherRuleState>,
+    /// Whether the accepted input forms a complete valid string.
+    pub is_completed: bool,
+    /// Whether the matcher has terminated.
+    pub is_terminated: bool,
+    /// Whether a stop token can be accepted now.
+    pub stop_token_allowed: bool,
+}
+
+/// One Earley parser state of a `GrammarMatcher`: a position inside a rule of the grammar.
+/// The ids refer to the rules and expressions of the compiled grammar.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MatcherRuleState {
+    /// The id of the rule.
+    pub rule_id: i32,
+    /// The id of the sequence inside the rule being matched.
+    pub sequence_id: i32,
+    /// The index of the element inside the sequence.
+    pub element_id: i32,
+    /// The input position where matching of the rule started.
+    pub rule_start_pos: i32,
+    /// The position inside the element, e.g. the byte inside a string literal.
+    pub sub_element_id: i32,
+    /// The number of repetitions matched so far for a repeated element.
+    pub repeat_count: i32,
+    /// The partially matched UTF-8 codepoint, if any.
+    pub partial_codepoint: i32,
+}
+
+impl MatcherDebugState {
+    // Parses the output of `debug_print_internal_state`, which looks like
+    // "There are N steps in history. Last step: [\nParserState(rule_id=0, ...), \n]".
+    fn parse(text: &str) -> (usize, Vec<MatcherRuleState>) {
+        let num_history_steps = text
+            .strip_prefix("There are ")
+            .and_then(|rest| rest.split_once(' '))
+            .and_then(|(count, _)| count.parse().ok())
+            .unwrap_or(0);
+        let states = text
+            .lines()
+            .filter_map(|line| {
+                line.trim()
+                    .strip_prefix("ParserState(")?
+                    .trim_end_matches(',')
+                    .strip_suffix(')')
+            })
+            .map(|fields| {
+                let mut state = MatcherRuleState::default();
+                for field in fields.split(", ") {
+                    let Some((key, value)) = field.split_once('=') else {
+                        continue;
+                    };
+                    let Ok(value) = value.parse() else {
+                        continue;
+                    };
+                    match key {
+                        "rule_id" => state.rule_id = value,
+                        "sequence_id" => state.sequence_id = value,
+                        "element_id" => state.element_id = value,
+                        "rule_start_pos" => state.rule_start_pos = value,
+                        "sub_element_id" => state.sub_element_id = value,
+                        "repeat_count" => state.repeat_count = value,
+                        "partial_codepoint" => state.partial_codepoint = value,
+                        _ => {},
+                    }
+                }
+                state
+            })
+            .collect();
+        (num_history_steps, states)
+    }
+}
+
+/// The magic and format version at the start of the data produced by `save_state`.
+const STATE_MAGIC: &[u8; 4] = b"XGMS";
+const STATE_FORMAT_VERSION: u8 = 1;
+
 impl GrammarMatcher {
     /// Construct the grammar matcher.
     ///
@@ -32,9 +163,10 @@ impl GrammarMatcher {
     ///   the grammar.
     /// - `terminate_without_stop_token`: Whether to terminate the matcher without accepting
     ///   a stop token.
-    /// - `max_rollback_tokens`: Deprecated. You don't need to set it and it's always unlimited
-    ///   (-1). The new Earley parser significantly reduces the number of states, so we can
-    ///   allow unlimited rollback.
+    /// - `max_rollback_tokens`: The maximum number of steps that can be rolled back, or -1
+    ///   for unlimited. You don't need to set it: the new Earley parser significantly reduces
+    ///   the number of states, so the C++ matcher keeps its whole history. The limit only
+    ///   bounds `rollback` and `num_accepted_steps`.
     ///
     /// # Errors
     ///
@@ -45,9 +177,10 @@ impl GrammarMatcher {
         terminate_without_stop_token: bool,
         max_rollback_tokens: i32,
     ) -> Result<Self, String> {
+        let tokenizer_info = Rc::new(compiled_grammar.tokenizer_info());
         let stored_stop_token_ids: Box<[i32]> = match override_stop_tokens {
             Some(slice) => slice.to_vec().into_boxed_slice(),
-            None => compiled_grammar.tokenizer_info().stop_token_ids(),
+            None => tokenizer_info.stop_token_ids(),
         };
         let (has_override, ptr, len) = match override_stop_tokens {
             Some(slice) if !slice.is_empty() => {
@@ -74,9 +207,59 @@ impl GrammarMatcher {
         Ok(Self {
             inner: unique_ptr,
             stored_stop_token_ids,
+            tokenizer_info,
+            terminate_without_stop_token,
+            banned_token_ids: Box::new([]),
+            max_tokens: None,
+            max_rollback_tokens: usize::try_from(max_rollback_tokens).ok(),
+            num_steps: Cell::new(0),
+            num_tokens: Cell::new(0),
+            string_steps: RefCell::new(Vec::new()),
+            stop_token: Cell::new(None),
+            accepted_steps: None,
         })
     }
 
+    /// Construct the grammar matcher with the default options of the Python binding: the stop
+    /// tokens of the tokenizer info, termination only after accepting a stop token, and
+    /// unlimited rollback. Equivalent to `GrammarMatcher::new(compiled_grammar, None, false,
+    /// -1)`; use `new` to change these options.
+    ///
+    /// # Parameters
+    ///
+    /// - `compiled_grammar`: The initialization context for the grammar matcher.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the grammar matcher cannot be constructed.
+    pub fn from_compiled(
+        compiled_grammar: &CompiledGrammar
+    ) -> Result<Self, String> {
+        Self::new(compiled_grammar, None, false, -1)
+    }
+
+    /// Record the steps the matcher accepts, for `accepted_steps`, `accepted_token_history`
+    /// and `save_state`. Recording is off by default, as it keeps a copy of every accepted
+    /// token and string for the lifetime of the generation, which `fork` copies as well.
+    ///
+    /// # Returns
+    ///
+    /// The matcher, recording the steps it accepts from now on.
+    ///
+    /// # Panics
+    ///
+    /// If the matcher has accepted steps since its construction or its last reset, as they
+    /// would be missing from the record.
+    pub fn with_state_tracking(mut self) -> Self {
+        assert_eq!(
+            self.num_steps.get(),
+            0,
+            "State tracking must be enabled before the matcher accepts any step"
+        );
+        self.accepted_steps = Some(RefCell::default());
+        self
+    }
+
     /// Accept one token and update the state of the matcher.
     ///
     /// In the following cases, the matcher will not accept the token and return false:
@@ -100,10 +283,44 @@ impl GrammarMatcher {
         &mut self,
         token_id: i32,
     ) -> bool {
-        self.inner
-            .as_mut()
-            .expect("GrammarMatcher inner is null")
-            .AcceptToken(token_id, false)
+        self.accept_token_with_debug(token_id, false)
+    }
+
+    /// Accept one token and update the state of the matcher, reporting why the token was
+    /// rejected. It behaves like `accept_token`, but the rejection cases listed there are
+    /// distinguished, and the checks done on the Rust side do not log a warning.
+    ///
+    /// # Parameters
+    ///
+    /// - `token_id`: The id of the token to accept.
+    ///
+    /// # Errors
+    ///
+    /// The reason of the rejection. The state of the matcher is unchanged in that case.
+    pub fn try_accept_token(
+        &mut self,
+        token_id: i32,
+    ) -> Result<(), AcceptRejectReason> {
+        // Same order of checks as the C++ matcher. A stop token can only have been accepted
+        // when the matcher does not terminate without one.
+        if !self.terminate_without_stop_token && self.is_terminated() {
+            return Err(AcceptRejectReason::AlreadyTerminated);
+        }
+        if usize::try_from(token_id)
+            .map_or(true, |id| id >= self.tokenizer_info.vocab_size())
+        {
+            return Err(AcceptRejectReason::OutOfRange);
+        }
+        if !self.stored_stop_token_ids.contains(&token_id)
+            && self.tokenizer_info.special_token_ids().contains(&token_id)
+        {
+            return Err(AcceptRejectReason::SpecialToken);
+        }
+        if self.accept_token(token_id) {
+            Ok(())
+        } else {
+            Err(AcceptRejectReason::GrammarMismatch)
+        }
     }
 
     /// Accept one token with optional debug printing.
@@ -122,10 +339,63 @@ impl GrammarMatcher {
         token_id: i32,
         debug_print: bool,
     ) -> bool {
-        self.inner
+        let accepted = self
+            .inner
             .as_mut()
             .expect("GrammarMatcher inner is null")
-            .AcceptToken(token_id, debug_print)
+            .AcceptToken(token_id, debug_print);
+        if accepted {
+            self.record_token(token_id);
+        }
+        accepted
+    }
+
+    /// Accept one token like `accept_token`, passing the debug trace to `sink` instead of
+    /// printing it, e.g. to forward it to a logging framework.
+    ///
+    /// `sink` is called with the token and the matcher state before accepting it, then with
+    /// whether the token was accepted, and why not otherwise. The C++ matcher logs nothing
+    /// for the rejections reported here.
+    ///
+    /// # Parameters
+    ///
+    /// - `token_id`: The id of the token to accept.
+    /// - `sink`: Receives each line of the trace.
+    ///
+    /// # Returns
+    ///
+    /// Whether the token is accepted.
+    pub fn accept_token_traced(
+        &mut self,
+        token_id: i32,
+        sink: &mut dyn FnMut(&str),
+    ) -> bool {
+        let decoded_vocab = self.tokenizer_info.ffi_ref().GetDecodedVocab();
+        let token = usize::try_from(token_id)
+            .ok()
+            .and_then(|id| decoded_vocab.get(id))
+            .map_or_else(String::new, |token| {
+                token.to_string_lossy().escape_debug().to_string()
+            });
+        sink(&format!(
+            "Accepting token id {}, string: \"{}\", current state:\n{}",
+            token_id,
+            token,
+            self.debug_print_internal_state()
+        ));
+        match self.try_accept_token(token_id) {
+            Ok(()) => {
+                sink(&format!("Token id {} is accepted.", token_id));
+                true
+            },
+            Err(reason) => {
+                sink(&format!(
+                    "Token id {} is rejected: {}.",
+                    token_id, reason
+                ));
+                false
+            },
+        }
     }
 
     /// Accept a string and update the state of the matcher. The whole string is considered
@@ -146,11 +416,7 @@ impl GrammarMatcher {
         input: &str,
         debug_print: bool,
     ) -> bool {
-        cxx::let_cxx_string!(input_cxx = input);
-        self.inner
-            .as_mut()
-            .expect("GrammarMatcher inner is null")
-            .AcceptString(&input_cxx, debug_print)
+        self.accept_bytes(input.as_bytes(), debug_print)
     }
 
     pub fn accept_bytes(
@@ -159,10 +425,185 @@ impl GrammarMatcher {
         debug_print: bool,
     ) -> bool {
         cxx::let_cxx_string!(input_cxx = input);
-        self.inner
+        let accepted = self
+            .inner
             .as_mut()
             .expect("GrammarMatcher inner is null")
-            .AcceptString(&input_cxx, debug_print)
+            .AcceptString(&input_cxx, debug_print);
+        if accepted {
+            self.record_bytes(input);
+        }
+        accepted
+    }
+
+    /// Accept a string like `accept_string`, passing the debug trace to `sink` instead of
+    /// printing it, e.g. to forward it to a logging framework.
+    ///
+    /// The trace has the same steps as the output of `accept_string` with `debug_print`:
+    /// the matcher state before the string, the state after each accepted byte, and the
+    /// position of the rejected byte or the acceptance of the whole string. The bytes are
+    /// replayed on a fork of the matcher to observe the states, so tracing costs one partial
+    /// match per byte.
+    ///
+    /// # Parameters
+    ///
+    /// - `input`: The string to be accepted.
+    /// - `sink`: Receives each step of the trace.
+    ///
+    /// # Returns
+    ///
+    /// Whether the string is accepted.
+    pub fn accept_string_traced(
+        &mut self,
+        input: &str,
+        sink: &mut dyn FnMut(&str),
+    ) -> bool {
+        let escaped = input.escape_debug().to_string();
+        if !self.terminate_without_stop_token && self.is_terminated() {
+            sink(&format!(
+                "The matcher has terminated after accepting the stop token, but is trying to \
+                 accept new string \"{}\".",
+                escaped
+            ));
+            return false;
+        }
+        sink(&format!(
+            "Trying to accept string \"{}\". Current state:\n{}",
+            escaped,
+            self.debug_print_internal_state()
+        ));
+        let mut fork = self.fork_untracked();
+        for (position, &byte) in input.as_bytes().iter().enumerate() {
+            let char_escaped = std::ascii::escape_default(byte).to_string();
+            if !fork.accept_bytes(&[byte], false) {
+                sink(&format!(
+                    "String \"{}\" is rejected at position {}, char {}",
+                    escaped, position, char_escaped
+                ));
+                return false;
+            }
+            sink(&format!(
+                "Char {} is accepted. Current state:\n{}",
+                char_escaped,
+                fork.debug_print_internal_state()
+            ));
+        }
+        let accepted = self.accept_string(input, false);
+        if accepted {
+            sink(&format!("String \"{}\" is accepted.", escaped));
+        }
+        accepted
+    }
+
+    /// Accept the longest prefix of a string that the grammar allows, and update the state of
+    /// the matcher to the end of that prefix. The accepted prefix is considered as one step in
+    /// rollback; nothing is accepted if the prefix is empty.
+    ///
+    /// The prefix is found by trying prefixes of `input` on forks of the matcher, so a
+    /// rejected string costs a logarithmic number of forks and partial matches.
+    ///
+    /// # Parameters
+    ///
+    /// - `input`: The string to be accepted.
+    ///
+    /// # Returns
+    ///
+    /// The length in bytes of the accepted prefix. It equals `input.len()` if the whole
+    /// string is accepted, and otherwise is the byte offset of the first rejected byte. The
+    /// offset may fall inside a multi-byte character if the grammar only rejects a later byte
+    /// of it.
+    pub fn accept_string_prefix(
+        &mut self,
+        input: &str,
+    ) -> usize {
+        if input.is_empty() {
+            return 0;
+        }
+        if self.accept_string(input, false) {
+            return input.len();
+        }
+        let input = input.as_bytes();
+        let accepted = self.longest_accepted_prefix(input);
+        if accepted > 0 {
+            self.accept_bytes(&input[..accepted], false);
+        }
+        accepted
+    }
+
+    /// Accept a string as a whole: either the entire string is accepted as one step in
+    /// rollback, or the matcher is left exactly as it was before the call.
+    ///
+    /// [`Self::accept_string`] also leaves the state unchanged when it rejects a string; this
+    /// variant additionally reports where the string was rejected.
+    ///
+    /// # Parameters
+    ///
+    /// - `input`: The string to be accepted.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the whole string is accepted.
+    ///
+    /// # Errors
+    ///
+    /// The byte offset of the first rejected byte, when the string is rejected. As in
+    /// [`Self::accept_string_prefix`], the offset may fall inside a multi-byte character.
+    pub fn try_accept_string(
+        &mut self,
+        input: &str,
+    ) -> Result<(), usize> {
+        if self.accept_string(input, false) {
+            return Ok(());
+        }
+        Err(self.longest_accepted_prefix(input.as_bytes()))
+    }
+
+    /// Accept a string one extended grapheme cluster at a time, e.g. `👍🏽` or `e\u{301}`, each
+    /// as one step in rollback, so that `rollback(1)` removes the last grapheme as a whole.
+    /// This suits a text editor backed by the matcher, whose cursor moves by graphemes, while
+    /// iterating `chars()` would split such graphemes. Requires the `unicode-segmentation`
+    /// feature.
+    ///
+    /// Accepting stops at the first rejected grapheme. The graphemes before it stay accepted,
+    /// so the number of steps added to `num_accepted_steps` tells how many were accepted.
+    ///
+    /// # Parameters
+    ///
+    /// - `input`: The string to be accepted.
+    ///
+    /// # Returns
+    ///
+    /// Whether every grapheme of the string is accepted.
+    #[cfg(feature = "unicode-segmentation")]
+    pub fn accept_graphemes(
+        &mut self,
+        input: &str,
+    ) -> bool {
+        use unicode_segmentation::UnicodeSegmentation;
+
+        input
+            .graphemes(true)
+            .all(|grapheme| self.accept_string(grapheme, false))
+    }
+
+    /// The length of the longest prefix of `input` the matcher accepts, found on forks of the
+    /// matcher. `input` itself must be rejected.
+    fn longest_accepted_prefix(
+        &self,
+        input: &[u8],
+    ) -> usize {
+        // Acceptance is monotonic: every prefix of an accepted prefix is accepted. Keep
+        // `accepted < rejected`, with prefix lengths `accepted` accepted and `rejected` not.
+        let (mut accepted, mut rejected) = (0, input.len());
+        while rejected - accepted > 1 {
+            let mid = accepted + (rejected - accepted) / 2;
+            if self.fork_untracked().accept_bytes(&input[..mid], false) {
+                accepted = mid;
+            } else {
+                rejected = mid;
+            }
+        }
+        accepted
     }
 
     /// Fill the bitmask for the next token prediction. The input bitmask must be on CPU.
@@ -180,7 +621,9 @@ impl GrammarMatcher {
     /// # Returns
     ///
     /// Whether the bitmask need to be applied (not all-true). An optimization: if false,
-    /// this means the bitmask is already all-true, so no need to apply it.
+    /// this means the bitmask is already all-true, so no need to apply it. Special tokens
+    /// (tokens that decode to an empty string) are always masked, so the result is `true`
+    /// for a vocabulary with special tokens even where the grammar allows any text.
     ///
     /// # Panics
     ///
@@ -191,14 +634,468 @@ impl GrammarMatcher {
         index: i32,
         debug_print: bool,
     ) -> bool {
-        unsafe {
+        let need_apply = unsafe {
             ffi::grammar_matcher_fill_next_token_bitmask(
                 self.inner.as_mut().expect("GrammarMatcher inner is null"),
                 bitmask.as_mut_ptr(),
                 index,
                 debug_print,
             )
+        };
+        let cleared = self
+            .clear_restricted_tokens_in_bitmask(bitmask, index)
+            .unwrap_or_else(|err| panic!("{}", err));
+        need_apply || cleared
+    }
+
+    /// Non-panicking variant of [`Self::fill_next_token_bitmask`] for bitmasks from untrusted
+    /// sources. The tensor is checked before it is filled: it must be on CPU (or in CUDA/ROCm
+    /// host memory), int32, contiguous with no byte offset, of shape (ceil(vocab_size / 32),) or
+    /// (batch_size, ceil(vocab_size / 32)), and `index` must be one of its rows.
+    ///
+    /// # Parameters
+    ///
+    /// - `bitmask`: The bitmask for the next token prediction.
+    /// - `index`: The batch id of the bitmask.
+    /// - `debug_print`: Whether to print information about generated bitmask.
+    ///
+    /// # Returns
+    ///
+    /// Whether the bitmask need to be applied (not all-true), as in
+    /// [`Self::fill_next_token_bitmask`].
+    ///
+    /// # Errors
+    ///
+    /// - When the tensor is null or fails one of the checks; the message names the check and
+    ///   the offending value.
+    /// - When the matcher has already accepted the stop token.
+    pub fn try_fill_next_token_bitmask(
+        &mut self,
+        bitmask: &mut CxxUniquePtr<DLTensor>,
+        index: i32,
+        debug_print: bool,
+    ) -> Result<bool, String> {
+        let tensor = bitmask
+            .as_ref()
+            .ok_or_else(|| "The bitmask tensor has no data".to_string())?;
+        check_bitmask(tensor, index, self.tokenizer_info.vocab_size())?;
+        let mut need_apply = false;
+        cxx::let_cxx_string!(error_out_cxx = "");
+        let filled = unsafe {
+            ffi::grammar_matcher_try_fill_next_token_bitmask(
+                self.inner.as_mut().expect("GrammarMatcher inner is null"),
+                bitmask.as_mut_ptr(),
+                index,
+                debug_print,
+                &mut need_apply,
+                error_out_cxx.as_mut().get_unchecked_mut(),
+            )
+        };
+        if !filled {
+            return Err(error_out_cxx.to_string());
+        }
+        let cleared =
+            self.clear_restricted_tokens_in_bitmask(bitmask, index)?;
+        Ok(need_apply || cleared)
+    }
+
+    /// Variant of [`Self::fill_next_token_bitmask`] for a CPU bitmask slice, e.g. one from
+    /// `allocate_token_bitmask`, without building the `DLTensor`. The batch size is the length
+    /// of the slice divided by ceil(vocab_size / 32).
+    ///
+    /// # Parameters
+    ///
+    /// - `bitmask`: The bitmask, with `batch_size * ceil(vocab_size / 32)` elements.
+    /// - `batch_index`: The row of the bitmask to fill.
+    /// - `vocab_size`: The vocabulary size the bitmask was allocated for.
+    ///
+    /// # Returns
+    ///
+    /// Whether the bitmask need to be applied (not all-true), as in
+    /// [`Self::fill_next_token_bitmask`].
+    ///
+    /// # Panics
+    ///
+    /// - If the length of `bitmask` is not a positive multiple of ceil(vocab_size / 32).
+    /// - If `batch_index` is not less than the batch size.
+    /// - If ceil(vocab_size / 32) differs from the bitmask size of the vocabulary of the
+    ///   matcher's tokenizer info.
+    pub fn fill_next_token_bitmask_slice(
+        &mut self,
+        bitmask: &mut [i32],
+        batch_index: usize,
+        vocab_size: usize,
+    ) -> bool {
+        let (_, bitmask_size) = get_bitmask_shape(1, vocab_size);
+        let (_, expected_bitmask_size) =
+            get_bitmask_shape(1, self.tokenizer_info.vocab_size());
+        assert_eq!(
+            bitmask_size,
+            expected_bitmask_size,
+            "A bitmask for vocab size {} does not fit the matcher's vocab size {}",
+            vocab_size,
+            self.tokenizer_info.vocab_size()
+        );
+        assert!(
+            !bitmask.is_empty() && bitmask.len() % bitmask_size == 0,
+            "The bitmask has {} elements, which is not a positive multiple of {}",
+            bitmask.len(),
+            bitmask_size
+        );
+        let batch_size = bitmask.len() / bitmask_size;
+        assert!(
+            batch_index < batch_size,
+            "Batch index {} is out of the bitmask of batch size {}",
+            batch_index,
+            batch_size
+        );
+        let mut tensor = bitmask_dltensor(bitmask, batch_size, vocab_size)
+            .expect("the batch size is derived from the bitmask length");
+        self.fill_next_token_bitmask(&mut tensor, batch_index as i32, false)
+    }
+
+    /// Variant of [`Self::fill_next_token_bitmask`] that also counts the accepted tokens,
+    /// e.g. to skip sampling when a single token is allowed. The count is a popcount of the
+    /// row just filled, while it is still in cache, instead of a second pass over the bitmask
+    /// with [`crate::accepted_tokens_iter`].
+    ///
+    /// # Parameters
+    ///
+    /// - `bitmask`: The bitmask for the next token prediction.
+    /// - `index`: The batch id of the bitmask.
+    /// - `debug_print`: Whether to print information about generated bitmask.
+    ///
+    /// # Returns
+    ///
+    /// `(need_apply, num_accepted)`: whether the bitmask need to be applied, as in
+    /// [`Self::fill_next_token_bitmask`], and the number of tokens set in row `index`. The
+    /// bits past the vocabulary size in the last element of the row are not counted.
+    ///
+    /// # Panics
+    ///
+    /// If the bitmask is invalid (not on CPU, not int32, shape mismatch, byte offset or
+    /// non-contiguous strides).
+    pub fn fill_next_token_bitmask_with_count(
+        &mut self,
+        bitmask: &mut CxxUniquePtr<DLTensor>,
+        index: i32,
+        debug_print: bool,
+    ) -> (bool, usize) {
+        let need_apply =
+            self.fill_next_token_bitmask(bitmask, index, debug_print);
+        let mut num_accepted = 0;
+        cxx::let_cxx_string!(error_out_cxx = "");
+        let counted = unsafe {
+            ffi::token_bitmask_count_tokens(
+                bitmask.as_mut_ptr(),
+                index,
+                self.tokenizer_info.vocab_size() as i32,
+                &mut num_accepted,
+                error_out_cxx.as_mut().get_unchecked_mut(),
+            )
+        };
+        if !counted {
+            panic!("{}", error_out_cxx);
+        }
+        (need_apply, num_accepted)
+    }
+
+    /// Check whether each of a few candidate tokens is allowed next, e.g. the top-k tokens of
+    /// the model, without filling the bitmask of the whole vocabulary. The result of each
+    /// candidate is its bit in the bitmask `fill_next_token_bitmask` would fill: the banned
+    /// and special tokens are not allowed, and the stop tokens are allowed when the input is
+    /// complete.
+    ///
+    /// Each candidate is accepted and rolled back on a fork of the matcher, so this is cheaper
+    /// than a fill for a small number of candidates only.
+    ///
+    /// This method does not change the matcher state.
+    ///
+    /// # Parameters
+    ///
+    /// - `candidates`: The ids of the tokens to check. Ids out of the vocabulary are not
+    ///   allowed.
+    ///
+    /// # Returns
+    ///
+    /// Whether each candidate is allowed, in the order of `candidates`.
+    pub fn filter_candidates(
+        &self,
+        candidates: &[i32],
+    ) -> Vec<bool> {
+        let stop_token_accepted =
+            !self.terminate_without_stop_token && self.is_terminated();
+        if stop_token_accepted {
+            return vec![false; candidates.len()];
+        }
+        let vocab_size = self.tokenizer_info.vocab_size();
+        let special_token_ids = self.tokenizer_info.special_token_ids_slice();
+        let content_tokens_left =
+            self.content_tokens_left().filter(|&left| left <= 1);
+        let mut fork: Option<GrammarMatcher> = None;
+        let mut limit_fork: Option<GrammarMatcher> = None;
+        candidates
+            .iter()
+            .map(|&token_id| {
+                if !usize::try_from(token_id).is_ok_and(|id| id < vocab_size)
+                    || self.banned_token_ids.contains(&token_id)
+                {
+                    false
+                } else if self.stored_stop_token_ids.contains(&token_id) {
+                    self.is_completed()
+                } else if special_token_ids.contains(&token_id) {
+                    false
+                } else {
+                    let fork =
+                        fork.get_or_insert_with(|| self.fork_untracked());
+                    let accepted = fork.accept_token(token_id);
+                    if accepted {
+                        fork.rollback(1).expect(
+                            "the token just accepted can be rolled back",
+                        );
+                    }
+                    accepted
+                }
+            })
+            .zip(candidates)
+            .map(|(allowed, &token_id)| {
+                allowed
+                    && content_tokens_left.is_none_or(|left| {
+                        self.is_within_length_limit(
+                            token_id,
+                            left,
+                            &mut limit_fork,
+                        )
+                    })
+            })
+            .collect()
+    }
+
+    /// Clear the bits of the banned tokens, and of the tokens over the length limit (see
+    /// `set_max_length`), in row `index` of a bitmask the matcher has just filled. Returns
+    /// whether any of the bits was set, or an error when the bitmask is not a compact int32
+    /// bitmask of the vocabulary with row `index`.
+    pub(crate) fn clear_restricted_tokens_in_bitmask(
+        &self,
+        bitmask: &mut CxxUniquePtr<DLTensor>,
+        index: i32,
+    ) -> Result<bool, String> {
+        let mut cleared = false;
+        if !self.banned_token_ids.is_empty() {
+            cleared |= self.clear_tokens_in_bitmask(
+                bitmask,
+                index,
+                &self.banned_token_ids,
+            )?;
+        }
+        let Some(content_tokens_left) = self.content_tokens_left() else {
+            return Ok(cleared);
+        };
+        if content_tokens_left > 1 {
+            return Ok(cleared);
+        }
+        cxx::let_cxx_string!(error_out_cxx = "");
+        let allowed = unsafe {
+            ffi::token_bitmask_accepted_tokens(
+                bitmask.as_mut_ptr(),
+                index,
+                self.tokenizer_info.vocab_size() as i32,
+                error_out_cxx.as_mut().get_unchecked_mut(),
+            )
+        };
+        if allowed.is_null() {
+            return Err(error_out_cxx.to_string());
+        }
+        let mut fork: Option<GrammarMatcher> = None;
+        let over_limit: Vec<i32> = allowed
+            .iter()
+            .copied()
+            .filter(|&token_id| {
+                !self.is_within_length_limit(
+                    token_id,
+                    content_tokens_left,
+                    &mut fork,
+                )
+            })
+            .collect();
+        if over_limit.is_empty() {
+            return Ok(cleared);
         }
+        self.clear_tokens_in_bitmask(bitmask, index, &over_limit)?;
+        Ok(true)
+    }
+
+    /// Clear the bits of `token_ids` in row `index` of the bitmask. Returns whether any of the
+    /// bits was set.
+    fn clear_tokens_in_bitmask(
+        &self,
+        bitmask: &mut CxxUniquePtr<DLTensor>,
+        index: i32,
+        token_ids: &[i32],
+    ) -> Result<bool, String> {
+        let mut changed = false;
+        cxx::let_cxx_string!(error_out_cxx = "");
+        let cleared = unsafe {
+            ffi::token_bitmask_clear_tokens(
+                bitmask.as_mut_ptr(),
+                index,
+                self.tokenizer_info.vocab_size() as i32,
+                token_ids.as_ptr(),
+                token_ids.len(),
+                &mut changed,
+                error_out_cxx.as_mut().get_unchecked_mut(),
+            )
+        };
+        if !cleared {
+            return Err(error_out_cxx.to_string());
+        }
+        Ok(changed)
+    }
+
+    /// The number of tokens that can still be accepted before the stop token under the length
+    /// limit, or `None` without a limit.
+    fn content_tokens_left(&self) -> Option<usize> {
+        let max_tokens = self.max_tokens?;
+        let num_tokens = self.num_tokens.get();
+        let stop_tokens = usize::from(!self.terminate_without_stop_token);
+        Some(max_tokens.saturating_sub(num_tokens).saturating_sub(stop_tokens))
+    }
+
+    /// Whether `token_id`, allowed by the grammar, is still allowed with `content_tokens_left`
+    /// (0 or 1) tokens left before the stop token: with none left only the stop tokens are,
+    /// and with one left only the tokens that complete the input. `fork` is the fork of the
+    /// matcher the tokens are tried on, created on first use.
+    fn is_within_length_limit(
+        &self,
+        token_id: i32,
+        content_tokens_left: usize,
+        fork: &mut Option<GrammarMatcher>,
+    ) -> bool {
+        if self.stored_stop_token_ids.contains(&token_id) {
+            return true;
+        }
+        if content_tokens_left == 0 {
+            return false;
+        }
+        let fork = fork.get_or_insert_with(|| self.fork_untracked());
+        if !fork.accept_token(token_id) {
+            return false;
+        }
+        let completed = fork.is_completed();
+        fork.rollback(1).expect("the token just accepted can be rolled back");
+        completed
+    }
+
+    /// Ban tokens on top of the grammar: `fill_next_token_bitmask`, `next_token_bitmask` and
+    /// `BatchGrammarMatcher::batch_fill_next_token_bitmask` additionally mask them, without
+    /// recompiling the grammar. The banned tokens replace the previous ones, are kept by
+    /// `reset` and `fork`, and are removed by `clear_banned_tokens`.
+    ///
+    /// Banning does not change which tokens `accept_token` accepts.
+    ///
+    /// # Parameters
+    ///
+    /// - `token_ids`: The ids of the tokens to ban.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error, and keeps the previous banned tokens, if a token id is out of the
+    /// range `[0, vocab_size)` of the tokenizer info.
+    pub fn set_banned_tokens(
+        &mut self,
+        token_ids: &[i32],
+    ) -> Result<(), String> {
+        let vocab_size = self.tokenizer_info.vocab_size();
+        if let Some(token_id) = token_ids
+            .iter()
+            .find(|&&id| !usize::try_from(id).is_ok_and(|id| id < vocab_size))
+        {
+            return Err(format!(
+                "The banned token id {} is out of the vocabulary range [0, {})",
+                token_id, vocab_size
+            ));
+        }
+        self.banned_token_ids = token_ids.into();
+        Ok(())
+    }
+
+    /// Remove the tokens banned by `set_banned_tokens`.
+    pub fn clear_banned_tokens(&mut self) {
+        self.banned_token_ids = Box::new([]);
+    }
+
+    /// The ids of the tokens banned by `set_banned_tokens`.
+    pub fn banned_tokens(&self) -> &[i32] {
+        &self.banned_token_ids
+    }
+
+    /// Limit the output to `max_tokens` tokens, counting the stop token, so that the
+    /// generation is steered toward a grammar-valid end instead of being cut off.
+    ///
+    /// The limit counts the tokens accepted since the last reset, by `accept_token` and the
+    /// batch functions; strings accepted with `accept_string` do not count. It acts on the
+    /// last tokens of the budget, in `fill_next_token_bitmask`, `next_token_bitmask`,
+    /// `filter_candidates` and `BatchGrammarMatcher::batch_fill_next_token_bitmask`:
+    ///
+    /// - When one token is left before the stop token, only the tokens after which the input
+    ///   is complete are allowed, besides the stop tokens. Each token the grammar allows is
+    ///   tried on a fork of the matcher, so this fill costs one accept per allowed token.
+    /// - When no token is left before the stop token, only the stop tokens are allowed.
+    ///
+    /// The limit interacts with the structure of the grammar: earlier tokens are not
+    /// restricted, as the matcher cannot tell how many tokens the rest of the input needs. If
+    /// the grammar cannot be completed in one token when the budget runs out, e.g. with
+    /// several JSON objects left to close, no token is allowed, and the caller has to stop the
+    /// generation. A limit with some margin over the expected length avoids this.
+    ///
+    /// Like the banned tokens, the limit does not change which tokens `accept_token` accepts,
+    /// is kept by `reset` and `fork`, and is removed by `clear_max_length`.
+    ///
+    /// # Parameters
+    ///
+    /// - `max_tokens`: The maximum number of tokens to accept, including the stop token when
+    ///   the matcher terminates with one.
+    pub fn set_max_length(
+        &mut self,
+        max_tokens: usize,
+    ) {
+        self.max_tokens = Some(max_tokens);
+    }
+
+    /// Remove the length limit set by `set_max_length`.
+    pub fn clear_max_length(&mut self) {
+        self.max_tokens = None;
+    }
+
+    /// The length limit set by `set_max_length`, if any.
+    pub fn max_length(&self) -> Option<usize> {
+        self.max_tokens
+    }
+
+    /// Compute the bitmask for the next token prediction and return it as an owned vector of
+    /// `ceil(vocab_size / 32)` elements, where `vocab_size` is the vocabulary size of the
+    /// matcher's tokenizer info. Bit `i % 32` of element `i / 32` is set if token `i` is
+    /// allowed.
+    ///
+    /// This is a convenience wrapper of `fill_next_token_bitmask` that allocates a new buffer
+    /// and builds the tensor on every call. In a decoding loop, prefer allocating the bitmask
+    /// once with `allocate_token_bitmask` and filling it with `fill_next_token_bitmask`,
+    /// which does not allocate.
+    ///
+    /// This method does not change the matcher state.
+    ///
+    /// # Returns
+    ///
+    /// The next token bitmask.
+    pub fn next_token_bitmask(&mut self) -> Vec<i32> {
+        let vocab_size = self.tokenizer_info.vocab_size();
+        let mut bitmask = allocate_token_bitmask(1, vocab_size).into_vec();
+        {
+            let mut tensor = bitmask_dltensor(&mut bitmask, 1, vocab_size)
+                .expect("the bitmask is allocated with the matching shape");
+            self.fill_next_token_bitmask(&mut tensor, 0, false);
+        }
+        bitmask
     }
 
     /// Find the jump-forward string for jump-forward decoding. This is the longest string that
@@ -217,20 +1114,172 @@ impl GrammarMatcher {
         .to_string()
     }
 
+    /// Find the jump-forward string like `find_jump_forward_string`, keeping at most its first
+    /// `max_bytes` bytes, e.g. when only a few tokens are jumped per step.
+    ///
+    /// The string is cut at the last UTF-8 character boundary within `max_bytes`, so it may be
+    /// shorter than `max_bytes` even if the jump-forward string is longer. The cap bounds the
+    /// string copied out of the C++ matcher and returned; the C++ search itself still walks
+    /// the whole forced continuation, as it has no cut-off.
+    ///
+    /// This method does not change the matcher state.
+    ///
+    /// # Parameters
+    ///
+    /// - `max_bytes`: The maximum length of the returned string in bytes.
+    ///
+    /// # Returns
+    ///
+    /// The longest prefix of the jump-forward string that is at most `max_bytes` long and
+    /// does not split a character.
+    pub fn find_jump_forward_string_capped(
+        &mut self,
+        max_bytes: usize,
+    ) -> String {
+        let jump_forward_string = ffi::grammar_matcher_find_jump_forward_string(
+            self.inner.as_mut().expect("GrammarMatcher inner is null"),
+        );
+        let bytes = jump_forward_string.as_bytes();
+        let mut end = bytes.len().min(max_bytes);
+        while end < bytes.len() && end > 0 && bytes[end] & 0xC0 == 0x80 {
+            end -= 1;
+        }
+        String::from_utf8_lossy(&bytes[..end]).into_owned()
+    }
+
+    /// Split the jump-forward string into token ids of the matcher's vocabulary, so that the
+    /// forced continuation can be appended to the output without running the LLM.
+    ///
+    /// The string from `find_jump_forward_string` is tokenized greedily from its start: each
+    /// step takes the longest vocabulary token (by decoded bytes) that is a prefix of the
+    /// remaining string. Special tokens and stop tokens are never used. Tokenization stops at
+    /// the first position where no token fits entirely inside the remaining string, so a
+    /// token that would cross the end of the jump-forward string is never returned; that
+    /// tail, if any, is left for the LLM to decode. Note that the greedy split may differ
+    /// from the tokenization the LLM would produce itself.
+    ///
+    /// This method does not change the matcher state. Accept the returned tokens with
+    /// `accept_token` to advance it. Each step looks up the prefixes of the remaining string,
+    /// at most as long as the longest token, with `TokenizerInfo::token_id_for_bytes`, so the
+    /// cost does not depend on the vocabulary size after the first call.
+    ///
+    /// # Returns
+    ///
+    /// The token ids covering the longest tokenizable prefix of the jump-forward string.
+    pub fn jump_forward_token_ids(&mut self) -> Vec<i32> {
+        let jump_forward_string = ffi::grammar_matcher_find_jump_forward_string(
+            self.inner.as_mut().expect("GrammarMatcher inner is null"),
+        );
+        let jump_forward = jump_forward_string.as_bytes();
+        let max_token_len = self.tokenizer_info.max_token_len();
+
+        let mut token_ids = Vec::new();
+        let mut pos = 0;
+        while pos < jump_forward.len() {
+            let remaining = &jump_forward[pos..];
+            let best = (1..=remaining.len().min(max_token_len)).rev().find_map(
+                |len| {
+                    self.tokenizer_info
+                        .token_id_for_bytes(&remaining[..len])
+                        .filter(|token_id| {
+                            !self.stored_stop_token_ids.contains(token_id)
+                        })
+                        .map(|token_id| (token_id, len))
+                },
+            );
+            match best {
+                Some((token_id, len)) => {
+                    token_ids.push(token_id);
+                    pos += len;
+                },
+                None => break,
+            }
+        }
+        token_ids
+    }
+
     /// Rollback the matcher to a previous state by several tokens.
     ///
+    /// Each accepted token, and each accepted string, counts as one step. If the rollback
+    /// fails, the matcher state is left unchanged.
+    ///
     /// # Parameters
     ///
-    /// - `num_tokens`: The number of tokens to rollback. It cannot exceed the current number
-    ///   of steps, nor can it exceed the specified maximum number of rollback tokens.
+    /// - `num_tokens`: The number of tokens to rollback. Rolling back 0 tokens is a no-op.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `num_tokens` exceeds `num_accepted_steps`, i.e. the number of
+    /// steps accepted since the construction or the last reset of the matcher, at most
+    /// `max_rollback_tokens`.
     pub fn rollback(
         &mut self,
-        num_tokens: i32,
-    ) {
-        self.inner
-            .as_mut()
-            .expect("GrammarMatcher inner is null")
-            .Rollback(num_tokens);
+        num_tokens: usize,
+    ) -> Result<(), String> {
+        let num_accepted_steps = self.num_accepted_steps();
+        if num_tokens > num_accepted_steps {
+            return Err(format!(
+                "Cannot rollback {} tokens: only {} steps can be rolled back",
+                num_tokens, num_accepted_steps
+            ));
+        }
+        let num_tokens_i32 = i32::try_from(num_tokens).map_err(|_| {
+            format!("Cannot rollback {} tokens: too many", num_tokens)
+        })?;
+        cxx::let_cxx_string!(error_out_cxx = "");
+        let success = unsafe {
+            ffi::grammar_matcher_rollback(
+                self.inner.as_mut().expect("GrammarMatcher inner is null"),
+                num_tokens_i32,
+                error_out_cxx.as_mut().get_unchecked_mut(),
+            )
+        };
+        if !success {
+            return Err(error_out_cxx.to_string());
+        }
+        self.record_rollback(num_tokens);
+        Ok(())
+    }
+
+    /// The number of steps accepted since the construction or the last reset of the matcher,
+    /// i.e. the number of steps that can be rolled back. Each accepted token, each accepted
+    /// string, and the accepted stop token count as one step. The count is clamped to
+    /// `max_rollback_tokens` if it is not unlimited.
+    ///
+    /// The count is kept on the Rust side as steps are accepted and rolled back, so this is a
+    /// constant-time query.
+    pub fn num_accepted_steps(&self) -> usize {
+        match self.max_rollback_tokens {
+            Some(limit) => self.num_steps.get().min(limit),
+            None => self.num_steps.get(),
+        }
+    }
+
+    /// Roll the matcher back to the state after `step` accepted steps, e.g. a value of
+    /// `num_accepted_steps` recorded earlier with unlimited rollback. This is a rollback by
+    /// the difference between the number of steps accepted since the construction or the last
+    /// reset of the matcher and `step`.
+    ///
+    /// # Parameters
+    ///
+    /// - `step`: The absolute step count to return to. `0` is equivalent to `reset`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `step` exceeds the number of steps accepted so far, or if the
+    /// rollback exceeds `max_rollback_tokens`. The matcher state is unchanged in that case.
+    pub fn reset_to(
+        &mut self,
+        step: usize,
+    ) -> Result<(), String> {
+        let num_steps = self.num_steps.get();
+        if step > num_steps {
+            return Err(format!(
+                "Cannot reset to step {}: only {} steps have been accepted",
+                step, num_steps
+            ));
+        }
+        self.rollback(num_steps - step)
     }
 
     /// Check if the matcher has terminated. If `terminate_without_stop_token` is false, the
@@ -253,19 +1302,221 @@ impl GrammarMatcher {
         self.inner.as_ref().expect("GrammarMatcher inner is null").IsCompleted()
     }
 
+    /// Whether the generation can end at the current state, without filling a bitmask. This is
+    /// a cheap check to run before emitting EOS.
+    ///
+    /// If the matcher terminates without a stop token, this is `is_completed`. Otherwise, it is
+    /// whether the next bitmask would allow one of the stop tokens: the input is complete, the
+    /// stop token is not accepted yet, and not every stop token is banned.
+    pub fn can_terminate_now(&self) -> bool {
+        if self.terminate_without_stop_token {
+            self.is_completed()
+        } else {
+            self.is_stop_token_allowed()
+        }
+    }
+
+    fn is_stop_token_allowed(&self) -> bool {
+        !self.is_terminated()
+            && self.is_completed()
+            && self
+                .stored_stop_token_ids
+                .iter()
+                .any(|id| !self.banned_token_ids.contains(id))
+    }
+
     /// Reset the matcher to the initial state.
     pub fn reset(&mut self) {
         self.inner.as_mut().expect("GrammarMatcher inner is null").Reset();
+        self.num_steps.set(0);
+        self.num_tokens.set(0);
+        self.string_steps.get_mut().clear();
+        self.stop_token.set(None);
+        if let Some(steps) = &mut self.accepted_steps {
+            steps.get_mut().clear();
+        }
     }
 
     /// Fork the matcher, returning a new matcher with an independent copy of the current state.
+    /// The fork records its steps if the matcher does, see `with_state_tracking`.
     pub fn fork(&self) -> Self {
+        let mut fork = self.fork_untracked();
+        fork.accepted_steps = self.accepted_steps.clone();
+        fork
+    }
+
+    // A fork that does not record its steps, for the short-lived forks of the queries.
+    fn fork_untracked(&self) -> Self {
         let inner = ffi::grammar_matcher_fork(
             self.inner.as_ref().expect("GrammarMatcher inner is null"),
         );
         Self {
             inner,
             stored_stop_token_ids: self.stored_stop_token_ids.clone(),
+            tokenizer_info: Rc::clone(&self.tokenizer_info),
+            terminate_without_stop_token: self.terminate_without_stop_token,
+            banned_token_ids: self.banned_token_ids.clone(),
+            max_tokens: self.max_tokens,
+            max_rollback_tokens: self.max_rollback_tokens,
+            num_steps: self.num_steps.clone(),
+            num_tokens: self.num_tokens.clone(),
+            string_steps: self.string_steps.clone(),
+            stop_token: self.stop_token.clone(),
+            accepted_steps: None,
+        }
+    }
+
+    /// The steps accepted since the construction or the last reset of the matcher, in order.
+    /// Accepting them on a new matcher of the same compiled grammar and options reproduces the
+    /// current state.
+    ///
+    /// # Panics
+    ///
+    /// If the matcher does not record its steps, see `with_state_tracking`.
+    pub fn accepted_steps(&self) -> Vec<AcceptedStep> {
+        self.tracked_steps().clone()
+    }
+
+    /// The tokens accepted since the construction or the last reset of the matcher, in order.
+    /// The strings accepted by `accept_string` and related methods are not included; use
+    /// `accepted_steps` to replay a matcher that accepted strings.
+    ///
+    /// # Panics
+    ///
+    /// If the matcher does not record its steps, see `with_state_tracking`.
+    pub fn accepted_token_history(&self) -> Vec<i32> {
+        self.tracked_steps()
+            .iter()
+            .filter_map(|step| match step {
+                AcceptedStep::Token(token_id) => Some(*token_id),
+                AcceptedStep::Bytes(_) => None,
+            })
+            .collect()
+    }
+
+    /// Save the state of the matcher as opaque bytes, e.g. to continue the generation on
+    /// another node with `restore_state`.
+    ///
+    /// The C++ matcher does not expose its internal state, so the snapshot holds the accepted
+    /// steps (see `accepted_steps`), and `restore_state` replays them. The snapshot does not
+    /// contain the grammar, the options or the banned tokens of the matcher.
+    ///
+    /// # Returns
+    ///
+    /// The snapshot.
+    ///
+    /// # Panics
+    ///
+    /// If the matcher does not record its steps, see `with_state_tracking`.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut out = STATE_MAGIC.to_vec();
+        out.push(STATE_FORMAT_VERSION);
+        for step in self.tracked_steps().iter() {
+            match step {
+                AcceptedStep::Token(token_id) => {
+                    out.push(0);
+                    out.extend_from_slice(&token_id.to_le_bytes());
+                },
+                AcceptedStep::Bytes(bytes) => {
+                    out.push(1);
+                    out.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+                    out.extend_from_slice(bytes);
+                },
+            }
+        }
+        out
+    }
+
+    /// Restore a state saved by `save_state`: reset the matcher, then accept the saved steps.
+    /// The matcher must be constructed from the same compiled grammar, with the same options,
+    /// as the saved one, but does not need to record its steps. The replay takes time linear
+    /// in the length of the saved generation.
+    ///
+    /// # Parameters
+    ///
+    /// - `state`: The snapshot returned by `save_state`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `state` is not a snapshot, or if the matcher rejects one of the
+    /// saved steps, e.g. because its grammar differs. The matcher state is unchanged in that
+    /// case.
+    pub fn restore_state(
+        &mut self,
+        state: &[u8],
+    ) -> Result<(), String> {
+        let steps = decode_state(state)?;
+        let previous = self.fork();
+        self.reset();
+        let rejected = steps.iter().position(|step| match step {
+            AcceptedStep::Token(token_id) => !self.accept_token(*token_id),
+            AcceptedStep::Bytes(bytes) => !self.accept_bytes(bytes, false),
+        });
+        if let Some(rejected) = rejected {
+            *self = previous;
+            return Err(format!(
+                "The matcher rejected step {} of the saved state: {:?}",
+                rejected, steps[rejected]
+            ));
+        }
+        Ok(())
+    }
+
+    fn tracked_steps(&self) -> Ref<'_, Vec<AcceptedStep>> {
+        self.accepted_steps
+            .as_ref()
+            .expect("The matcher does not record its steps, see `with_state_tracking`")
+            .borrow()
+    }
+
+    pub(crate) fn record_token(
+        &self,
+        token_id: i32,
+    ) {
+        self.num_tokens.set(self.num_tokens.get() + 1);
+        self.stop_token.set(
+            self.stored_stop_token_ids.contains(&token_id).then_some(token_id),
+        );
+        self.record_step(|| AcceptedStep::Token(token_id));
+    }
+
+    pub(crate) fn record_bytes(
+        &self,
+        bytes: &[u8],
+    ) {
+        self.string_steps.borrow_mut().push(self.num_steps.get());
+        self.stop_token.set(None);
+        self.record_step(|| AcceptedStep::Bytes(bytes.to_vec()));
+    }
+
+    fn record_step(
+        &self,
+        step: impl FnOnce() -> AcceptedStep,
+    ) {
+        self.num_steps.set(self.num_steps.get() + 1);
+        if let Some(steps) = &self.accepted_steps {
+            steps.borrow_mut().push(step());
+        }
+    }
+
+    pub(crate) fn record_rollback(
+        &self,
+        num_steps: usize,
+    ) {
+        let num_remaining = self.num_steps.get().saturating_sub(num_steps);
+        let mut string_steps = self.string_steps.borrow_mut();
+        let num_remaining_strings =
+            string_steps.partition_point(|&step| step < num_remaining);
+        let num_rolled_back_tokens = (self.num_steps.get() - num_remaining)
+            - (string_steps.len() - num_remaining_strings);
+        string_steps.truncate(num_remaining_strings);
+        self.num_tokens.set(self.num_tokens.get() - num_rolled_back_tokens);
+        self.num_steps.set(num_remaining);
+        if num_steps > 0 {
+            self.stop_token.set(None);
+        }
+        if let Some(steps) = &self.accepted_steps {
+            steps.borrow_mut().truncate(num_remaining);
         }
     }
 
@@ -301,13 +1552,12 @@ impl GrammarMatcher {
 
     /// Get the maximum number of rollback tokens allowed.
     ///
-    /// Deprecated. Now `max_rollback_tokens` is always unlimited (-1).
-    ///
     /// # Returns
     ///
-    /// The maximum number of rollback tokens.
+    /// The `max_rollback_tokens` the matcher was constructed with, or -1 if it is unlimited.
     pub fn max_rollback_tokens(&self) -> i32 {
-        -1
+        // The limit was converted from a non-negative `i32`.
+        self.max_rollback_tokens.map_or(-1, |limit| limit as i32)
     }
 
     /// The ids of the stop tokens used in the matcher. If specified, the provided stop tokens
@@ -320,6 +1570,21 @@ impl GrammarMatcher {
         self.stored_stop_token_ids.clone()
     }
 
+    /// The stop token that terminated the matcher, e.g. to tell a normal EOS from the end of
+    /// a tool call when several stop tokens are given at construction. It follows `rollback`
+    /// and `reset` like `is_terminated`.
+    ///
+    /// # Returns
+    ///
+    /// The id of the accepted stop token, or `None` if the matcher has not terminated or
+    /// terminated without a stop token.
+    pub fn last_stop_token(&self) -> Option<i32> {
+        if !self.is_terminated() {
+            return None;
+        }
+        self.stop_token.get()
+    }
+
     /// Print the internal state of the matcher. This is used for debugging. The
     /// representation of the internal state is subject to change.
     ///
@@ -333,6 +1598,23 @@ impl GrammarMatcher {
         .to_string()
     }
 
+    /// The internal state of the matcher in a structured form: the parser states of the
+    /// latest step, the size of the history and whether a stop token is allowed. This is
+    /// parsed from `debug_print_internal_state`, so it is subject to change as well.
+    pub fn internal_state(&self) -> MatcherDebugState {
+        let (num_history_steps, states) =
+            MatcherDebugState::parse(&self.debug_print_internal_state());
+        let is_completed = self.is_completed();
+        let is_terminated = self.is_terminated();
+        MatcherDebugState {
+            num_history_steps,
+            states,
+            is_completed,
+            is_terminated,
+            stop_token_allowed: self.is_stop_token_allowed(),
+        }
+    }
+
     pub(crate) fn ffi_mut(&mut self) -> Pin<&mut ffi::GrammarMatcher> {
         self.inner.as_mut().expect("GrammarMatcher inner is null")
     }
@@ -345,3 +1627,52 @@ impl GrammarMatcher {
 impl Drop for GrammarMatcher {
     fn drop(&mut self) {}
 }
+
+fn decode_state(state: &[u8]) -> Result<Vec<AcceptedStep>, String> {
+    let invalid = || "Invalid matcher state: the data is truncated".to_string();
+    let Some(rest) = state.strip_prefix(STATE_MAGIC.as_slice()) else {
+        return Err(format!(
+            "Invalid matcher state: the data does not start with {:?}",
+            String::from_utf8_lossy(STATE_MAGIC)
+        ));
+    };
+    let Some((&version, mut rest)) = rest.split_first() else {
+        return Err(invalid());
+    };
+    if version != STATE_FORMAT_VERSION {
+        return Err(format!(
+            "Invalid matcher state: unsupported format version {}, expected {}",
+            version, STATE_FORMAT_VERSION
+        ));
+    }
+    let mut steps = Vec::new();
+    while let Some((&tag, tail)) = rest.split_first() {
+        let (step, tail) = match tag {
+            0 => {
+                let (token_id, tail) =
+                    tail.split_first_chunk::<4>().ok_or_else(invalid)?;
+                (AcceptedStep::Token(i32::from_le_bytes(*token_id)), tail)
+            },
+            1 => {
+                let (len, tail) =
+                    tail.split_first_chunk::<8>().ok_or_else(invalid)?;
+                let len = usize::try_from(u64::from_le_bytes(*len))
+                    .map_err(|_| invalid())?;
+                if tail.len() < len {
+                    return Err(invalid());
+                }
+                let (bytes, tail) = tail.split_at(len);
+                (AcceptedStep::Bytes(bytes.to_vec()), tail)
+            },
+            _ => {
+                return Err(format!(
+                    "Invalid matcher state: unknown step tag {}",
+                    tag
+                ));
+            },
+        };
+        steps.push(step);
+        rest = tail;
+    }
+    Ok(steps)
+}
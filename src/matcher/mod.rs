@@ -1,20 +1,27 @@
 //! Match the output of the LLM to the specified grammar, then generate the mask for the next
 //! token.
 
-use crate::{CxxUniquePtr, DLTensor};
+use std::{
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
+
+use crate::{CxxUniquePtr, DLTensor, c_void, dlpack, ffi};
 
 mod batch_grammar_matcher;
 mod grammar_matcher;
 
-pub use batch_grammar_matcher::BatchGrammarMatcher;
-pub use grammar_matcher::GrammarMatcher;
+pub use batch_grammar_matcher::{BatchGrammarMatcher, BatchStepResult};
+pub use grammar_matcher::{
+    AcceptedStep, GrammarMatcher, MatcherDebugState, MatcherRuleState,
+};
 
 /// Return the shape of the bitmask: (batch_size, ceil(vocab_size / 32)).
 pub fn get_bitmask_shape(
     batch_size: usize,
     vocab_size: usize,
 ) -> (usize, usize) {
-    (batch_size, (vocab_size + 31) / 32)
+    (batch_size, vocab_size.div_ceil(32))
 }
 
 /// Allocate the bitmask for the next token prediction. The bitmask is an int32 tensor on
@@ -37,20 +44,575 @@ pub fn get_bitmask_shape(
 /// -------
 /// bitmask : torch.Tensor
 ///     The shape of the bitmask.
+///
+/// Panics if the size of the bitmask overflows `usize`, see `try_allocate_token_bitmask`.
 pub fn allocate_token_bitmask(
     batch_size: usize,
     vocab_size: usize,
 ) -> Box<[i32]> {
-    let (_, bitmask_size) = get_bitmask_shape(batch_size, vocab_size);
-    let total_size = batch_size * bitmask_size;
+    let total_size = bitmask_len(batch_size, vocab_size)
+        .unwrap_or_else(|error| panic!("{}", error));
     vec![-1i32; total_size].into_boxed_slice()
 }
 
-/// Reset the bitmask to the full mask.
+/// Allocate a bitmask like `allocate_token_bitmask`, but return an error instead of panicking
+/// or aborting when the bitmask is too large, e.g. for a `vocab_size` read from an untrusted
+/// config.
+///
+/// # Parameters
+///
+/// - `batch_size`: The batch size of the bitmask.
+/// - `vocab_size`: The size of the vocabulary.
+///
+/// # Returns
+///
+/// The bitmask with shape (batch_size, ceil(vocab_size / 32)), with all tokens allowed.
+///
+/// # Errors
+///
+/// - When the size of the bitmask in bytes overflows.
+/// - When the memory cannot be allocated.
+pub fn try_allocate_token_bitmask(
+    batch_size: usize,
+    vocab_size: usize,
+) -> Result<Box<[i32]>, String> {
+    let total_size = bitmask_len(batch_size, vocab_size)?;
+    let mut bitmask = Vec::new();
+    bitmask.try_reserve_exact(total_size).map_err(|error| {
+        format!(
+            "Failed to allocate a token bitmask of {} elements: {}",
+            total_size, error
+        )
+    })?;
+    bitmask.resize(total_size, -1i32);
+    Ok(bitmask.into_boxed_slice())
+}
+
+/// The number of int32 elements of a bitmask, checking that its size in bytes fits in
+/// `isize`.
+fn bitmask_len(
+    batch_size: usize,
+    vocab_size: usize,
+) -> Result<usize, String> {
+    let (_, bitmask_size) = get_bitmask_shape(batch_size, vocab_size);
+    batch_size
+        .checked_mul(bitmask_size)
+        .filter(|&len| {
+            len.checked_mul(size_of::<i32>())
+                .is_some_and(|bytes| bytes <= isize::MAX as usize)
+        })
+        .ok_or_else(|| {
+            format!(
+                "The token bitmask for batch_size {} and vocab_size {} is too large",
+                batch_size, vocab_size
+            )
+        })
+}
+
+// Checks that `bitmask` can be filled at row `index` for `vocab_size` tokens: it has data
+// on CPU (or in CUDA/ROCm host memory), is int32 with no byte offset, is contiguous, and has
+// shape (ceil(vocab_size / 32),) with index 0 or (batch_size, ceil(vocab_size / 32)) with
+// index in 0..batch_size. XGrammar itself ignores the byte offset and the strides.
+pub(crate) fn check_bitmask(
+    bitmask: &DLTensor,
+    index: i32,
+    vocab_size: usize,
+) -> Result<(), String> {
+    if !ffi::tensor_has_data(bitmask) {
+        return Err("The bitmask tensor has no data".to_string());
+    }
+    let device_type = ffi::tensor_device_type(bitmask);
+    if device_type != dlpack::DLDeviceType::kDLCPU as i32
+        && device_type != dlpack::DLDeviceType::kDLCUDAHost as i32
+        && device_type != dlpack::DLDeviceType::kDLROCMHost as i32
+    {
+        return Err(format!(
+            "The bitmask must be on CPU (or in CUDA/ROCm host memory), but its device type is {}",
+            device_type
+        ));
+    }
+    let dtype = ffi::tensor_dtype(bitmask);
+    if dtype.code != dlpack::DLDataTypeCode::kDLInt as u8
+        || dtype.bits != 32
+        || dtype.lanes != 1
+    {
+        return Err(format!(
+            "The bitmask must be int32, but its dtype has code {}, {} bits and {} lanes",
+            dtype.code, dtype.bits, dtype.lanes
+        ));
+    }
+    let byte_offset = ffi::tensor_byte_offset(bitmask);
+    if byte_offset != 0 {
+        return Err(format!(
+            "The bitmask must have no byte offset, but has {}",
+            byte_offset
+        ));
+    }
+    let (_, bitmask_size) = get_bitmask_shape(1, vocab_size);
+    let bitmask_size = bitmask_size as i64;
+    let ndim = ffi::tensor_ndim(bitmask);
+    // SAFETY: `ndim` is checked before reading a dimension.
+    let shape = |dim| unsafe { ffi::tensor_shape(bitmask, dim) };
+    match ndim {
+        1 => {
+            if shape(0) != bitmask_size {
+                return Err(format!(
+                    "The 1D bitmask must have shape ({},) for vocab size {}, but has shape ({},)",
+                    bitmask_size,
+                    vocab_size,
+                    shape(0)
+                ));
+            }
+            if index != 0 {
+                return Err(format!(
+                    "The index must be 0 for a 1D bitmask, but is {}",
+                    index
+                ));
+            }
+        },
+        2 => {
+            if shape(1) != bitmask_size {
+                return Err(format!(
+                    "The 2D bitmask must have shape (batch_size, {}) for vocab size {}, but has shape ({}, {})",
+                    bitmask_size,
+                    vocab_size,
+                    shape(0),
+                    shape(1)
+                ));
+            }
+            if index < 0 || i64::from(index) >= shape(0) {
+                return Err(format!(
+                    "The index {} is out of the {} rows of the bitmask",
+                    index,
+                    shape(0)
+                ));
+            }
+        },
+        _ => {
+            return Err(format!(
+                "The bitmask must have 1 or 2 dimensions, but has {}",
+                ndim
+            ));
+        },
+    }
+    if ffi::tensor_has_strides(bitmask) {
+        // SAFETY: the strides array has `ndim` entries.
+        let stride = |dim| unsafe { ffi::tensor_stride(bitmask, dim) };
+        if stride(ndim - 1) != 1 || (ndim == 2 && stride(0) != bitmask_size) {
+            return Err("The bitmask must be contiguous".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// A CPU int32 `DLTensor` of shape (batch_size, ceil(vocab_size / 32)) viewing a bitmask
+/// slice, created by `bitmask_dltensor`. It owns the shape and strides arrays the tensor points
+/// to, and borrows the slice mutably, so the tensor cannot outlive its data.
+///
+/// It dereferences to `CxxUniquePtr<DLTensor>`, so it can be passed wherever the bitmask
+/// tensor is expected, e.g. `matcher.fill_next_token_bitmask(&mut tensor, 0, false)`.
+pub struct BitmaskDLTensor<'a> {
+    tensor: CxxUniquePtr<DLTensor>,
+    // Boxed so that the pointers stored in the tensor stay valid when this struct moves.
+    _shape: Box<[i64; 2]>,
+    _strides: Box<[i64; 2]>,
+    _data: PhantomData<&'a mut [i32]>,
+}
+
+impl Deref for BitmaskDLTensor<'_> {
+    type Target = CxxUniquePtr<DLTensor>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tensor
+    }
+}
+
+impl DerefMut for BitmaskDLTensor<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.tensor
+    }
+}
+
+/// Create the `DLTensor` expected by `fill_next_token_bitmask` and related functions over a
+/// bitmask slice, e.g. one from `allocate_token_bitmask`.
+///
+/// The slice is mutably borrowed for the lifetime of the returned tensor, so it can only be
+/// read again after the tensor is dropped. Do not copy the raw `DLTensor` out of the returned
+/// value: its data, shape and strides pointers dangle once the value is dropped.
+///
+/// # Parameters
+///
+/// - `data`: The bitmask, with `batch_size * ceil(vocab_size / 32)` elements.
+/// - `batch_size`: The batch size of the bitmask.
+/// - `vocab_size`: The size of the vocabulary.
+///
+/// # Returns
+///
+/// The tensor viewing `data`.
+///
+/// # Errors
+///
+/// When the length of `data` does not match the bitmask shape.
+pub fn bitmask_dltensor(
+    data: &mut [i32],
+    batch_size: usize,
+    vocab_size: usize,
+) -> Result<BitmaskDLTensor<'_>, String> {
+    let (_, bitmask_size) = get_bitmask_shape(batch_size, vocab_size);
+    if data.len() != batch_size * bitmask_size {
+        return Err(format!(
+            "The bitmask has {} elements, but a bitmask of batch size {} and vocab size {} \
+             needs {}",
+            data.len(),
+            batch_size,
+            vocab_size,
+            batch_size * bitmask_size
+        ));
+    }
+    // SAFETY: `data` holds the whole shape and stays borrowed by the tensor.
+    Ok(unsafe {
+        bitmask_view(
+            data.as_mut_ptr(),
+            2,
+            [batch_size as i64, bitmask_size as i64],
+            [bitmask_size as i64, 1],
+        )
+    })
+}
+
+// Create a CPU int32 tensor over `data`. The caller guarantees that `data` covers the shape
+// and strides and is borrowed for `'a`.
+unsafe fn bitmask_view<'a>(
+    data: *mut i32,
+    ndim: i32,
+    shape: [i64; 2],
+    strides: [i64; 2],
+) -> BitmaskDLTensor<'a> {
+    let mut shape = Box::new(shape);
+    let mut strides = Box::new(strides);
+    let tensor = unsafe {
+        DLTensor::new(
+            data as *mut c_void,
+            dlpack::cpu_device(),
+            ndim,
+            dlpack::bitmask_dtype(),
+            shape.as_mut_ptr(),
+            strides.as_mut_ptr(),
+            0,
+        )
+    };
+    BitmaskDLTensor {
+        tensor,
+        _shape: shape,
+        _strides: strides,
+        _data: PhantomData,
+    }
+}
+
+/// A CPU bitmask whose rows start at addresses aligned for SIMD, created by
+/// `allocate_token_bitmask_aligned`.
+///
+/// Each row holds the ceil(vocab_size / 32) elements of `allocate_token_bitmask`, followed by
+/// padding up to `row_stride` elements, so that every row starts at a multiple of the
+/// alignment. All elements, including the padding, start as `-1` (all tokens allowed).
+///
+/// # Strides
+///
+/// The row stride is `row_stride()` elements, not ceil(vocab_size / 32), so the bitmask is
+/// not a compact (batch_size, ceil(vocab_size / 32)) tensor. The fill and apply functions
+/// assume compact rows, so the bitmask is only handed out one row at a time: fill a row
+/// through `row_dltensor(i)` with index `0`, which leaves the padding untouched, and read or
+/// apply it through `row(i)`.
+pub struct AlignedBitmask {
+    // Over-allocated by up to one alignment; the bitmask starts at `offset`.
+    buffer: Vec<i32>,
+    offset: usize,
+    batch_size: usize,
+    vocab_size: usize,
+    row_stride: usize,
+}
+
+impl AlignedBitmask {
+    /// The batch size of the bitmask.
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    /// The vocabulary size the bitmask was allocated for.
+    pub fn vocab_size(&self) -> usize {
+        self.vocab_size
+    }
+
+    /// The number of bitmask elements of a row, ceil(vocab_size / 32).
+    pub fn row_len(&self) -> usize {
+        get_bitmask_shape(1, self.vocab_size).1
+    }
+
+    /// The distance between the starts of two rows, in int32 elements. At least `row_len`.
+    pub fn row_stride(&self) -> usize {
+        self.row_stride
+    }
+
+    /// The whole bitmask, `batch_size * row_stride` elements including the padding. It starts
+    /// at an aligned address.
+    pub fn as_slice(&self) -> &[i32] {
+        &self.buffer
+            [self.offset..self.offset + self.batch_size * self.row_stride]
+    }
+
+    /// Mutable variant of [`Self::as_slice`].
+    pub fn as_mut_slice(&mut self) -> &mut [i32] {
+        let len = self.batch_size * self.row_stride;
+        &mut self.buffer[self.offset..self.offset + len]
+    }
+
+    /// Row `index` of the bitmask without its padding, as expected by `is_token_accepted` or
+    /// `apply_token_bitmask_with_fill_value`.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is not less than the batch size.
+    pub fn row(
+        &self,
+        index: usize,
+    ) -> &[i32] {
+        assert!(index < self.batch_size, "Row {} is out of the bitmask", index);
+        let start = index * self.row_stride;
+        &self.as_slice()[start..start + self.row_len()]
+    }
+
+    /// Mutable variant of [`Self::row`].
+    ///
+    /// # Panics
+    ///
+    /// If `index` is not less than the batch size.
+    pub fn row_mut(
+        &mut self,
+        index: usize,
+    ) -> &mut [i32] {
+        assert!(index < self.batch_size, "Row {} is out of the bitmask", index);
+        let start = index * self.row_stride;
+        let row_len = self.row_len();
+        &mut self.as_mut_slice()[start..start + row_len]
+    }
+
+    /// Reset every row to the full mask.
+    pub fn reset(&mut self) {
+        reset_token_bitmask(self.as_mut_slice());
+    }
+
+    /// A (ceil(vocab_size / 32),) tensor over row `index`, to be filled with index `0`, e.g.
+    /// `matcher.fill_next_token_bitmask(&mut bitmask.row_dltensor(i), 0, false)`.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is not less than the batch size.
+    pub fn row_dltensor(
+        &mut self,
+        index: usize,
+    ) -> BitmaskDLTensor<'_> {
+        let row = self.row_mut(index);
+        let row_len = row.len() as i64;
+        // SAFETY: the row holds `row_len` elements.
+        unsafe { bitmask_view(row.as_mut_ptr(), 1, [row_len, 0], [1, 0]) }
+    }
+}
+
+/// Allocate a bitmask like `allocate_token_bitmask`, but with every row padded so that it
+/// starts at a multiple of `align` bytes, e.g. `64` for AVX-512. See `AlignedBitmask` for the
+/// stride semantics.
+///
+/// # Parameters
+///
+/// - `batch_size`: The batch size of the bitmask.
+/// - `vocab_size`: The size of the vocabulary.
+/// - `align`: The alignment of the rows in bytes.
+///
+/// # Returns
+///
+/// The aligned bitmask, with all tokens allowed.
+///
+/// # Panics
+///
+/// - If `align` is not a power of two or is less than the 4-byte size of an element.
+/// - If the size of the bitmask overflows `usize`.
+pub fn allocate_token_bitmask_aligned(
+    batch_size: usize,
+    vocab_size: usize,
+    align: usize,
+) -> AlignedBitmask {
+    assert!(
+        align.is_power_of_two() && align >= size_of::<i32>(),
+        "The alignment must be a power of two of at least 4 bytes, got {}",
+        align
+    );
+    let align_elements = align / size_of::<i32>();
+    let (_, row_len) = get_bitmask_shape(batch_size, vocab_size);
+    let row_stride = row_len.div_ceil(align_elements) * align_elements;
+    let buffer_len = batch_size
+        .checked_mul(row_stride)
+        .and_then(|len| len.checked_add(align_elements - 1))
+        .unwrap_or_else(|| {
+            panic!(
+                "The token bitmask for batch_size {} and vocab_size {} is too large",
+                batch_size, vocab_size
+            )
+        });
+    let buffer = vec![-1i32; buffer_len];
+    let offset = buffer.as_ptr().align_offset(align);
+    AlignedBitmask {
+        buffer,
+        offset,
+        batch_size,
+        vocab_size,
+        row_stride,
+    }
+}
+
+/// Whether a token is allowed by a bitmask, e.g. one from `allocate_token_bitmask` filled by
+/// `fill_next_token_bitmask`. Token `token_id` of row `batch_index` is bit `token_id % 32` of
+/// element `batch_index * ceil(vocab_size / 32) + token_id / 32`; the int32 elements are read
+/// as raw bits, so the sign bit stands for the last token of each element.
+///
+/// # Parameters
+///
+/// - `bitmask`: The bitmask, with shape (batch_size, ceil(vocab_size / 32)).
+/// - `batch_index`: The row of the bitmask.
+/// - `token_id`: The token to test.
+/// - `vocab_size`: The size of the vocabulary the bitmask was allocated for.
+///
+/// # Returns
+///
+/// Whether the bit of the token is set.
+///
+/// # Panics
+///
+/// If `token_id` is not less than `vocab_size`, or the row is out of the bitmask.
+pub fn is_token_accepted(
+    bitmask: &[i32],
+    batch_index: usize,
+    token_id: usize,
+    vocab_size: usize,
+) -> bool {
+    assert!(
+        token_id < vocab_size,
+        "The token id {} is out of the vocabulary of size {}",
+        token_id,
+        vocab_size
+    );
+    let (_, bitmask_size) = get_bitmask_shape(1, vocab_size);
+    let element = bitmask[batch_index * bitmask_size + token_id / 32] as u32;
+    element & (1 << (token_id % 32)) != 0
+}
+
+/// A lazy iterator over the accepted token ids of one row of a bitmask, in increasing order.
+/// Created by `accepted_tokens_iter`.
+///
+/// Unlike `testing::get_masked_tokens_from_bitmask`, it does not allocate, and can stop early.
+/// `count` counts the set bits of the remaining elements instead of visiting every token.
+#[derive(Clone, Debug)]
+pub struct AcceptedTokens<'a> {
+    row: &'a [i32],
+    vocab_size: usize,
+    /// The index of the next element of `row` to load into `bits`.
+    next_element: usize,
+    /// The not yet yielded bits of element `next_element - 1`.
+    bits: u32,
+}
+
+impl AcceptedTokens<'_> {
+    /// The bits of element `element` of the row, without the bits past `vocab_size`.
+    fn element_bits(
+        &self,
+        element: usize,
+    ) -> u32 {
+        let bits = self.row[element] as u32;
+        let valid = self.vocab_size - element * 32;
+        if valid < 32 {
+            bits & ((1 << valid) - 1)
+        } else {
+            bits
+        }
+    }
+}
+
+impl Iterator for AcceptedTokens<'_> {
+    type Item = i32;
+
+    fn next(&mut self) -> Option<i32> {
+        while self.bits == 0 {
+            if self.next_element == self.row.len() {
+                return None;
+            }
+            self.bits = self.element_bits(self.next_element);
+            self.next_element += 1;
+        }
+        let bit = self.bits.trailing_zeros();
+        self.bits &= self.bits - 1;
+        Some(((self.next_element - 1) * 32) as i32 + bit as i32)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let upper = self.bits.count_ones() as usize
+            + self.vocab_size.saturating_sub(self.next_element * 32);
+        (0, Some(upper))
+    }
+
+    fn count(self) -> usize {
+        (self.next_element..self.row.len())
+            .map(|element| self.element_bits(element).count_ones() as usize)
+            .sum::<usize>()
+            + self.bits.count_ones() as usize
+    }
+}
+
+impl std::iter::FusedIterator for AcceptedTokens<'_> {}
+
+/// Iterate over the accepted token ids of row `batch_index` of a bitmask filled by
+/// `fill_next_token_bitmask`, without allocating. The bits past `vocab_size` in the last
+/// element of the row are ignored.
+///
+/// # Parameters
+///
+/// - `bitmask`: The bitmask, with shape (batch_size, ceil(vocab_size / 32)).
+/// - `batch_index`: The row of the bitmask.
+/// - `vocab_size`: The size of the vocabulary the bitmask was allocated for.
+///
+/// # Returns
+///
+/// An iterator yielding the accepted token ids in increasing order.
+///
+/// # Panics
+///
+/// If the row is out of the bitmask.
+pub fn accepted_tokens_iter(
+    bitmask: &[i32],
+    batch_index: usize,
+    vocab_size: usize,
+) -> AcceptedTokens<'_> {
+    let (_, bitmask_size) = get_bitmask_shape(1, vocab_size);
+    let start = batch_index * bitmask_size;
+    AcceptedTokens {
+        row: &bitmask[start..start + bitmask_size],
+        vocab_size,
+        next_element: 0,
+        bits: 0,
+    }
+}
+
+/// Reset the bitmask to the full mask: every element is `-1`, all bits set, so every token
+/// is accepted.
 pub fn reset_token_bitmask(bitmask: &mut [i32]) {
     bitmask.fill(-1i32);
 }
 
+/// Clear the bitmask to the empty mask: every element is `0`, no bit set, so every token is
+/// rejected. This is the starting point to allow tokens selectively, e.g. when composing a
+/// mask from several constraints by OR-ing their rows in.
+pub fn reject_all_token_bitmask(bitmask: &mut [i32]) {
+    bitmask.fill(0);
+}
+
 pub fn apply_token_bitmask_inplace_cpu(
     logits: &mut CxxUniquePtr<DLTensor>,
     bitmask: &DLTensor,
@@ -79,3 +641,51 @@ pub fn apply_token_bitmask_inplace_cpu(
     }
     Ok(())
 }
+
+/// Apply the bitmask of one sequence to its logits on CPU, setting the logits of the masked
+/// tokens to `fill_value`. Unlike `apply_token_bitmask_inplace_cpu`, which always uses
+/// `-inf`, a large finite value such as `-1e9` keeps the softmax free of NaNs when every token
+/// is masked.
+///
+/// # Parameters
+///
+/// - `logits`: The logits of one sequence.
+/// - `bitmask`: The bitmask of the sequence, e.g. one row of `allocate_token_bitmask`.
+/// - `vocab_size`: The number of tokens to mask. Defaults to `min(logits.len(),
+///   bitmask.len() * 32)`; the logits after it are left unchanged.
+/// - `fill_value`: The value of the masked logits, e.g. `f32::NEG_INFINITY` or `-1e9`.
+///
+/// # Returns
+///
+/// The number of allowed tokens. `0` means that every token is masked, i.e. the generation
+/// reached a dead end; the logits are still filled in that case.
+///
+/// # Errors
+///
+/// When `vocab_size` exceeds the length of `logits` or the number of bits of `bitmask`.
+pub fn apply_token_bitmask_with_fill_value(
+    logits: &mut [f32],
+    bitmask: &[i32],
+    vocab_size: Option<usize>,
+    fill_value: f32,
+) -> Result<usize, String> {
+    let vocab_size =
+        vocab_size.unwrap_or_else(|| logits.len().min(bitmask.len() * 32));
+    if vocab_size > logits.len() || vocab_size > bitmask.len() * 32 {
+        return Err(format!(
+            "The vocab size {} exceeds the {} logits or the {} bits of the bitmask",
+            vocab_size,
+            logits.len(),
+            bitmask.len() * 32
+        ));
+    }
+    let mut num_allowed = 0;
+    for (token_id, logit) in logits[..vocab_size].iter_mut().enumerate() {
+        if bitmask[token_id / 32] & (1 << (token_id % 32)) != 0 {
+            num_allowed += 1;
+        } else {
+            *logit = fill_value;
+        }
+    }
+    Ok(num_allowed)
+}
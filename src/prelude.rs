@@ -0,0 +1,16 @@
+//! The everyday types and bitmask helpers of the crate, meant to be glob-imported with
+//! `use xgrammar::prelude::*;`.
+//!
+//! This covers building grammars, compiling them, matching with one or a batch of matchers,
+//! and allocating, filling and applying token bitmasks, including the DLPack types the
+//! bitmask functions take. Everything here is also exported at the crate root.
+
+pub use crate::{
+    BatchGrammarMatcher, CompiledGrammar, CxxUniquePtr, DLDataTypeCode,
+    DLDevice, DLDeviceType, DLTensor, DLTensorBuilder, Grammar,
+    GrammarCompiler, GrammarMatcher, StructuralTagItem, TokenizerInfo,
+    VocabType, accepted_tokens_iter, allocate_token_bitmask,
+    apply_token_bitmask_inplace_cpu, apply_token_bitmask_with_fill_value,
+    bitmask_dltensor, get_bitmask_shape, is_token_accepted,
+    reset_token_bitmask, testing,
+};
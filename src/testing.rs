@@ -1,5 +1,6 @@
 use crate::{
-    CxxUniquePtr, DLTensor, ffi, grammar::Grammar, matcher::GrammarMatcher,
+    CxxUniquePtr, DLTensor, GrammarCompiler, TokenizerInfo, VocabType, ffi,
+    grammar::Grammar, matcher::GrammarMatcher,
 };
 
 /// Convert EBNF to Grammar without normalization.
@@ -120,6 +121,71 @@ pub fn get_masked_tokens_from_bitmask(
     }
 }
 
+/// Get the ids of the rejected tokens for every row of the bitmask in one pass. Mainly for
+/// debug purposes.
+///
+/// # Parameters
+///
+/// - `bitmask`: The rejected token bitmask, a CPU int32 tensor of shape
+///   `(batch_size, ceil(vocab_size / 32))` (or 1D when `batch_size` is 1).
+/// - `vocab_size`: The size of the vocabulary.
+/// - `batch_size`: The expected number of rows in the bitmask.
+///
+/// # Returns
+///
+/// The rejected token ids of each row, indexed by the batch index.
+///
+/// # Errors
+///
+/// Returns an error if the bitmask is not a CPU int32 tensor, its number of rows differs from
+/// `batch_size`, or its rows are too short for `vocab_size`.
+pub fn masked_tokens_all_rows(
+    bitmask: &DLTensor,
+    vocab_size: i32,
+    batch_size: i32,
+) -> Result<Vec<Vec<i32>>, String> {
+    cxx::let_cxx_string!(error_out_cxx = "");
+    let flat = unsafe {
+        ffi::get_masked_tokens_all_rows(
+            bitmask as *const _,
+            vocab_size,
+            batch_size,
+            error_out_cxx.as_mut().get_unchecked_mut(),
+        )
+    };
+    if flat.is_null() {
+        return Err(error_out_cxx.to_string());
+    }
+    // Rows are encoded as `[len_0, ids_0..., len_1, ids_1...]`.
+    let flat = flat.as_slice();
+    let mut rows = Vec::with_capacity(batch_size.max(0) as usize);
+    let mut pos = 0;
+    while pos < flat.len() {
+        let row = usize::try_from(flat[pos])
+            .ok()
+            .and_then(|len| flat.get(pos + 1..pos + 1 + len));
+        let Some(row) = row else {
+            return Err(format!(
+                "Malformed row {} of the masked tokens: length {} at offset {} of {}",
+                rows.len(),
+                flat[pos],
+                pos,
+                flat.len()
+            ));
+        };
+        rows.push(row.to_vec());
+        pos += 1 + row.len();
+    }
+    if rows.len() != batch_size.max(0) as usize {
+        return Err(format!(
+            "Expected {} rows of masked tokens, but got {}",
+            batch_size,
+            rows.len()
+        ));
+    }
+    Ok(rows)
+}
+
 /// Check if the bitmask is a single token bitmask.
 ///
 /// # Parameters
@@ -254,3 +320,57 @@ pub fn print_grammar_fsms(grammar: &Grammar) -> Result<String, String> {
     }
     Ok(result.to_string())
 }
+
+/// Assert that a grammar matches each string of `accepts` completely and rejects each string
+/// of `rejects`. The grammar is compiled once with an empty vocabulary, and each string is
+/// matched from the start with `accept_string`.
+///
+/// # Parameters
+///
+/// - `grammar`: The grammar to test.
+/// - `accepts`: The strings the grammar must match completely.
+/// - `rejects`: The strings the grammar must not match, either because they fail to match or
+///   because they are incomplete.
+///
+/// # Panics
+///
+/// If a string of `accepts` is not matched, or a string of `rejects` is matched. The message
+/// lists every such string, with the grammar in EBNF.
+pub fn assert_accepts(
+    grammar: &Grammar,
+    accepts: &[&str],
+    rejects: &[&str],
+) {
+    let tokenizer_info =
+        TokenizerInfo::new::<&str>(&[], VocabType::RAW, &None, false)
+            .expect("an empty vocabulary is valid");
+    let mut compiler = GrammarCompiler::new(&tokenizer_info, 1, false, -1)
+        .expect("the compiler is created from a valid tokenizer info");
+    let compiled_grammar =
+        compiler.compile_grammar(grammar).unwrap_or_else(|error| {
+            panic!("Failed to compile the grammar: {}", error)
+        });
+    let mut matcher = GrammarMatcher::new(&compiled_grammar, None, true, -1)
+        .expect("a matcher is created from a compiled grammar");
+    let mut is_accepted = |input: &str| {
+        matcher.reset();
+        matcher.accept_string(input, false) && matcher.is_terminated()
+    };
+    let mut failures = Vec::new();
+    for input in accepts {
+        if !is_accepted(input) {
+            failures.push(format!("  not accepted: {:?}", input));
+        }
+    }
+    for input in rejects {
+        if is_accepted(input) {
+            failures.push(format!("  not rejected: {:?}", input));
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "The grammar does not match as expected:\n{}\nGrammar:\n{}",
+        failures.join("\n"),
+        grammar
+    );
+}
@@ -1,3 +1,8 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::OnceLock,
+};
+
 use crate::{
     CxxUniquePtr, DeserializeError, ffi,
     utils::{bytes_as_c_char_ptr, tie_enum_with_ffi},
@@ -17,6 +22,74 @@ pub enum VocabType {
 
 tie_enum_with_ffi!(VocabType, i32, RAW, BYTE_FALLBACK, BYTE_LEVEL);
 
+impl VocabType {
+    /// Every vocabulary type, in the order of their values.
+    pub const ALL: [VocabType; 3] =
+        [VocabType::RAW, VocabType::BYTE_FALLBACK, VocabType::BYTE_LEVEL];
+
+    /// Every vocabulary type, e.g. to list the choices in a configuration UI. Same as
+    /// `VocabType::ALL`.
+    pub fn all() -> &'static [VocabType] {
+        &Self::ALL
+    }
+
+    /// The name of the vocabulary type: `raw`, `byte_fallback` or `byte_level`. This is the
+    /// `Display` output, and what `FromStr` parses.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            VocabType::RAW => "raw",
+            VocabType::BYTE_FALLBACK => "byte_fallback",
+            VocabType::BYTE_LEVEL => "byte_level",
+        }
+    }
+}
+
+/// Formats the vocabulary type as `raw`, `byte_fallback` or `byte_level`.
+impl core::fmt::Display for VocabType {
+    fn fmt(
+        &self,
+        f: &mut core::fmt::Formatter<'_>,
+    ) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Parses `raw`, `byte_fallback` or `byte_level`, ignoring ASCII case.
+impl core::str::FromStr for VocabType {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        VocabType::ALL
+            .into_iter()
+            .find(|vocab_type| name.eq_ignore_ascii_case(vocab_type.as_str()))
+            .ok_or_else(|| {
+                format!(
+                    "unknown vocab_type: {name:?}, expected one of \"raw\", \"byte_fallback\", \"byte_level\""
+                )
+            })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for VocabType {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for VocabType {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D
+    ) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 #[derive(Clone)]
 pub struct HfMetadata {
     pub vocab_type: VocabType,
@@ -60,6 +133,97 @@ pub fn detect_metadata_from_hf(
     })
 }
 
+/// Whether `token` is a ByteFallback byte token, e.g. `<0x1B>`.
+fn is_byte_fallback_token(token: &str) -> bool {
+    token.len() == 6
+        && token.starts_with("<0x")
+        && token.ends_with('>')
+        && token[3..5].bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Whether `token` has the shape of a byte escape, `<0x` or `<0X` followed by hex digits and
+/// `>`, whatever the number of digits.
+fn looks_like_byte_escape(token: &[u8]) -> bool {
+    matches!(
+        token,
+        [b'<', b'0', b'x' | b'X', digits @ .., b'>']
+            if !digits.is_empty() && digits.iter().all(u8::is_ascii_hexdigit)
+    )
+}
+
+/// Whether `token` contains the Byte-Level BPE encoding of a space (`Ġ`) or a newline (`Ċ`).
+fn is_byte_level_token(token: &str) -> bool {
+    token.contains(['\u{0120}', '\u{010A}'])
+}
+
+/// Check that the markers found in `encoded_vocab` agree with `vocab_type`. The markers of
+/// both encodings are counted, and the vocabulary is rejected when the more frequent ones
+/// belong to another type than the declared one.
+fn check_vocab_type<T: AsRef<str>>(
+    encoded_vocab: &[T],
+    vocab_type: &VocabType,
+) -> Result<(), String> {
+    let byte_fallback: Vec<&str> = encoded_vocab
+        .iter()
+        .map(AsRef::as_ref)
+        .filter(|token| is_byte_fallback_token(token))
+        .collect();
+    let byte_level: Vec<&str> = encoded_vocab
+        .iter()
+        .map(AsRef::as_ref)
+        .filter(|token| is_byte_level_token(token))
+        .collect();
+
+    let (likely_type, markers, description) =
+        if byte_fallback.len() > byte_level.len() {
+            (VocabType::BYTE_FALLBACK, byte_fallback, "byte tokens")
+        } else if byte_level.len() > byte_fallback.len() {
+            (
+                VocabType::BYTE_LEVEL,
+                byte_level,
+                "byte-level space or newline markers",
+            )
+        } else {
+            return Ok(());
+        };
+    if likely_type == *vocab_type {
+        return Ok(());
+    }
+    Err(format!(
+        "vocab_type is {} but the vocabulary looks like {}: found {} {} (e.g. {:?})",
+        vocab_type,
+        likely_type,
+        markers.len(),
+        description,
+        markers[0]
+    ))
+}
+
+/// The findings of `TokenizerInfo::diagnose`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VocabDiagnostics {
+    /// The decoded texts shared by more than one token, with the ids of the tokens in
+    /// ascending order. Sorted by the lowest id. Empty tokens are reported in `empty_tokens`
+    /// instead.
+    pub duplicate_tokens: Vec<(Box<[u8]>, Vec<i32>)>,
+    /// The ids from `tokenizer_vocab_size` up to `vocab_size`, which have no token in the
+    /// encoded vocabulary. They are treated as special tokens and never allowed.
+    pub missing_ids: std::ops::Range<i32>,
+    /// The ids of the tokens that decode to nothing, in ascending order. This includes the
+    /// holes of a vocabulary built by id order, which are filled with empty strings. They are
+    /// treated as special tokens and never allowed.
+    pub empty_tokens: Vec<i32>,
+}
+
+impl VocabDiagnostics {
+    /// Whether nothing was found.
+    pub fn is_clean(&self) -> bool {
+        self.duplicate_tokens.is_empty()
+            && self.missing_ids.is_empty()
+            && self.empty_tokens.is_empty()
+    }
+}
+
 /// The tokenizer info contains the vocabulary, the type of the vocabulary, and necessary
 /// information for the grammar-guided generation.
 ///
@@ -74,6 +238,14 @@ pub fn detect_metadata_from_hf(
 /// this information is used to determine the size of the token mask.
 pub struct TokenizerInfo {
     inner: CxxUniquePtr<ffi::TokenizerInfo>,
+    // The lowest non-special id of each decoded token, built by the first `token_id_for_bytes`.
+    token_ids: OnceLock<TokenIds>,
+}
+
+struct TokenIds {
+    by_bytes: HashMap<Box<[u8]>, i32>,
+    // The length of the longest decoded token in `by_bytes`.
+    max_len: usize,
 }
 
 impl TokenizerInfo {
@@ -174,10 +346,48 @@ impl TokenizerInfo {
             return Err(error_out_cxx.to_string());
         }
 
-        let inner = ffi_obj;
-        Ok(Self {
-            inner,
-        })
+        Ok(Self::from_unique_ptr(ffi_obj))
+    }
+
+    /// Construct the tokenizer info like `new_with_vocab_size`, but first check that the
+    /// vocabulary is consistent with `vocab_type`.
+    ///
+    /// The check looks for the markers each encoding leaves in the vocabulary: byte tokens
+    /// such as `<0x1B>` for `BYTE_FALLBACK`, and `Ġ` (space) or `Ċ` (newline) for
+    /// `BYTE_LEVEL`. A vocabulary whose markers point to another type is rejected, as its
+    /// tokens would be decoded incorrectly and every token mask would be wrong.
+    ///
+    /// # Parameters
+    ///
+    /// - `encoded_vocab`: The encoded vocabulary of the tokenizer.
+    /// - `vocab_type`: The type of the vocabulary. See also `VocabType`.
+    /// - `vocab_size`: The size of the vocabulary. If not provided, the vocabulary size will
+    ///   be `encoded_vocab.len()`.
+    /// - `stop_token_ids`: The stop token ids. If `None`, the stop token ids will be auto
+    ///   detected (but may not be correct).
+    /// - `add_prefix_space`: Whether the tokenizer will prepend a space before the text in
+    ///   the tokenization process.
+    ///
+    /// # Errors
+    ///
+    /// - When the vocabulary looks like another `VocabType`. The message names the likely
+    ///   type.
+    /// - When the tokenizer info cannot be constructed.
+    pub fn new_validated<T: AsRef<str>>(
+        encoded_vocab: &[T],
+        vocab_type: VocabType,
+        vocab_size: Option<usize>,
+        stop_token_ids: &StopTokenIds,
+        add_prefix_space: bool,
+    ) -> Result<Self, String> {
+        check_vocab_type(encoded_vocab, &vocab_type)?;
+        Self::new_with_vocab_size(
+            encoded_vocab,
+            vocab_type,
+            vocab_size,
+            stop_token_ids,
+            add_prefix_space,
+        )
     }
 
     /// Construct the tokenizer info from the vocabulary and the metadata string in JSON format.
@@ -185,11 +395,20 @@ impl TokenizerInfo {
     /// # Parameters
     ///
     /// - `encoded_vocab`: The encoded vocabulary of the tokenizer.
-    /// - `metadata`: The metadata string in JSON format.
+    /// - `metadata`: The metadata string in JSON format, as returned by `dump_metadata`.
+    ///
+    /// # Returns
+    ///
+    /// The tokenizer info.
+    ///
+    /// # Errors
+    ///
+    /// When the metadata is not valid JSON, lacks a field or has an invalid value, e.g. an
+    /// unknown `vocab_type`, or the tokenizer info cannot be constructed from it.
     pub fn from_vocab_and_metadata_bytes<I, B>(
         encoded_vocab: I,
         metadata: &str,
-    ) -> Self
+    ) -> Result<Self, String>
     where
         I: IntoIterator<Item = B>,
         B: AsRef<[u8]>,
@@ -210,13 +429,18 @@ impl TokenizerInfo {
         }
 
         cxx::let_cxx_string!(metadata_cxx = metadata);
-        let ffi_ptr = ffi::tokenizer_info_from_vocab_and_metadata(
-            cxx_vec.as_ref().unwrap(),
-            &metadata_cxx,
-        );
-        Self {
-            inner: ffi_ptr,
+        cxx::let_cxx_string!(error_out_cxx = "");
+        let ffi_ptr = unsafe {
+            ffi::tokenizer_info_from_vocab_and_metadata(
+                cxx_vec.as_ref().unwrap(),
+                &metadata_cxx,
+                error_out_cxx.as_mut().get_unchecked_mut(),
+            )
+        };
+        if ffi_ptr.is_null() {
+            return Err(error_out_cxx.to_string());
         }
+        Ok(Self::from_unique_ptr(ffi_ptr))
     }
 
     /// The type of the vocabulary.
@@ -224,7 +448,8 @@ impl TokenizerInfo {
         self.inner.as_ref().expect("UniquePtr was null").GetVocabType().into()
     }
 
-    /// The size of the vocabulary.
+    /// The size of the vocabulary, i.e. the dimension of the token mask. It can be larger than
+    /// `tokenizer_vocab_size` when the model pads its vocabulary.
     pub fn vocab_size(&self) -> usize {
         usize::try_from(
             self.inner.as_ref().expect("UniquePtr was null").GetVocabSize(),
@@ -232,11 +457,48 @@ impl TokenizerInfo {
         .expect("GetVocabSize returned a negative value")
     }
 
+    /// The number of tokens of the tokenizer, i.e. the length of the encoded vocabulary
+    /// passed at construction. The ids from it up to `vocab_size` are padding, and are treated
+    /// as special tokens.
+    pub fn tokenizer_vocab_size(&self) -> usize {
+        self.inner.GetDecodedVocab().len()
+    }
+
     /// Whether the tokenizer will prepend a space before the text in the tokenization process.
     pub fn add_prefix_space(&self) -> bool {
         self.inner.as_ref().expect("UniquePtr was null").GetAddPrefixSpace()
     }
 
+    /// The token that is only the space the tokenizer prepends to the text, e.g. `▁` in
+    /// SentencePiece vocabularies, if `add_prefix_space` is set and the vocabulary has one.
+    ///
+    /// XGrammar does not strip the prepended space: the decoded vocabulary maps `▁` to a
+    /// space, so the matcher sees the first token of a generation, e.g. `▁Hello`, as text
+    /// starting with a space, and `add_prefix_space` does not change the token masks. When
+    /// decoding the first token, strip one leading space from its decoded text to match the
+    /// text the tokenizer would produce; this token decodes to nothing then.
+    ///
+    /// # Returns
+    ///
+    /// The lowest id of a non-special token whose decoded text is a single space, or `None`
+    /// if `add_prefix_space` is false or no such token exists.
+    pub fn prefix_space_token(&self) -> Option<i32> {
+        if !self.add_prefix_space() {
+            return None;
+        }
+        let special_token_ids = self.special_token_ids_slice();
+        self.inner
+            .GetDecodedVocab()
+            .iter()
+            .enumerate()
+            .map(|(token_id, token)| (token_id as i32, token))
+            .find(|(token_id, token)| {
+                token.as_bytes() == b" "
+                    && !special_token_ids.contains(token_id)
+            })
+            .map(|(token_id, _)| token_id)
+    }
+
     /// The decoded vocabulary of the tokenizer. This converts the tokens in the LLM's
     /// vocabulary back to the original format of the input text. E.g. for type ByteFallback,
     /// the token `<0x1B>` is converted back to `\u001b`.
@@ -249,14 +511,124 @@ impl TokenizerInfo {
         result.into_boxed_slice()
     }
 
-    /// The stop token ids.
+    /// Find the token whose decoded text is exactly `bytes`, e.g. to map a jump-forward string
+    /// or a test input to token ids. The lookup map is built from the decoded vocabulary on
+    /// the first call and kept in the tokenizer info, so later calls are O(1).
+    ///
+    /// # Parameters
+    ///
+    /// - `bytes`: The decoded text of the token, e.g. ` Hello` for the token `▁Hello` of a
+    ///   ByteFallback vocabulary.
+    ///
+    /// # Returns
+    ///
+    /// The lowest id of a non-special token decoding to `bytes`, or `None` if there is none.
+    /// Special tokens, including the empty ones, are never returned.
+    pub fn token_id_for_bytes(
+        &self,
+        bytes: &[u8],
+    ) -> Option<i32> {
+        self.token_ids().by_bytes.get(bytes).copied()
+    }
+
+    // The length of the longest decoded text `token_id_for_bytes` can find.
+    pub(crate) fn max_token_len(&self) -> usize {
+        self.token_ids().max_len
+    }
+
+    fn token_ids(&self) -> &TokenIds {
+        self.token_ids.get_or_init(|| {
+            let special_token_ids: HashSet<i32> =
+                self.special_token_ids_slice().iter().copied().collect();
+            let mut by_bytes = HashMap::new();
+            let mut max_len = 0;
+            for (token_id, token) in
+                self.inner.GetDecodedVocab().iter().enumerate()
+            {
+                let token_id = token_id as i32;
+                if !special_token_ids.contains(&token_id) {
+                    max_len = max_len.max(token.as_bytes().len());
+                    by_bytes.entry(token.as_bytes().into()).or_insert(token_id);
+                }
+            }
+            TokenIds {
+                by_bytes,
+                max_len,
+            }
+        })
+    }
+
+    /// Find the tokens that look like byte-fallback escapes (`<0x1B>`) but were not decoded
+    /// to a single byte. This happens when the vocabulary type is not `BYTE_FALLBACK`, or when
+    /// the escape is malformed (e.g. `<0X1B>` or `<0x1B1B>`), and makes the token masks treat
+    /// the escape as literal text.
+    ///
+    /// Only the decoded vocabulary is kept, so an escape with a lowercase last digit (e.g.
+    /// `<0x1b>`), which the decoder turns into a wrong byte, cannot be detected here.
+    ///
+    /// # Returns
+    ///
+    /// The id and the decoded text of each such token, in id order. Empty for a correctly
+    /// configured vocabulary.
+    pub fn verify_byte_fallback(&self) -> Vec<(i32, String)> {
+        self.inner
+            .GetDecodedVocab()
+            .iter()
+            .enumerate()
+            .filter(|(_, token)| looks_like_byte_escape(token.as_bytes()))
+            .map(|(token_id, token)| {
+                (token_id as i32, token.to_string_lossy().into_owned())
+            })
+            .collect()
+    }
+
+    /// Check the vocabulary for the problems that make the token masks silently wrong: tokens
+    /// sharing the same decoded text, ids without a token and tokens that decode to nothing.
+    ///
+    /// Run it when onboarding a new tokenizer. Some findings are expected for a correct
+    /// vocabulary, e.g. ByteFallback vocabularies often have both `<0x20>` and `▁`, which
+    /// decode to a space, and models that pad their vocabulary have ids past
+    /// `tokenizer_vocab_size`.
+    ///
+    /// # Returns
+    ///
+    /// The findings. See `VocabDiagnostics`.
+    pub fn diagnose(&self) -> VocabDiagnostics {
+        let decoded_vocab = self.inner.GetDecodedVocab();
+        let mut empty_tokens = Vec::new();
+        let mut token_ids: HashMap<&[u8], Vec<i32>> = HashMap::new();
+        for (token_id, token) in decoded_vocab.iter().enumerate() {
+            let token_id = token_id as i32;
+            if token.as_bytes().is_empty() {
+                empty_tokens.push(token_id);
+            } else {
+                token_ids.entry(token.as_bytes()).or_default().push(token_id);
+            }
+        }
+        let mut duplicate_tokens: Vec<(Box<[u8]>, Vec<i32>)> = token_ids
+            .into_iter()
+            .filter(|(_, ids)| ids.len() > 1)
+            .map(|(token, ids)| (token.into(), ids))
+            .collect();
+        duplicate_tokens.sort_unstable_by_key(|(_, ids)| ids[0]);
+        let vocab_size = self.vocab_size() as i32;
+        let tokenizer_vocab_size = (decoded_vocab.len() as i32).min(vocab_size);
+        VocabDiagnostics {
+            duplicate_tokens,
+            missing_ids: tokenizer_vocab_size..vocab_size,
+            empty_tokens,
+        }
+    }
+
+    /// The stop token ids, copied into a new box. See also `stop_token_ids_slice`.
     pub fn stop_token_ids(&self) -> Box<[i32]> {
         let cxx_vec = self.inner.GetStopTokenIds();
         cxx_vec.iter().copied().collect::<Vec<_>>().into_boxed_slice()
     }
 
     /// The special token ids. Special tokens include control tokens, reserved tokens,
-    /// padded tokens, etc. Now it is automatically detected from the vocabulary.
+    /// padded tokens, etc. Now it is automatically detected from the vocabulary. The ids are
+    /// copied into a new box; see also `special_token_ids_slice`.
     pub fn special_token_ids(&self) -> Box<[i32]> {
         let cxx_vec = self
             .inner
@@ -266,6 +638,48 @@ impl TokenizerInfo {
         cxx_vec.iter().copied().collect::<Vec<_>>().into_boxed_slice()
     }
 
+    /// The stop token ids, borrowed from the tokenizer info without a copy. Prefer this over
+    /// `stop_token_ids` when the ids are read often.
+    pub fn stop_token_ids_slice(&self) -> &[i32] {
+        self.inner
+            .as_ref()
+            .expect("UniquePtr was null")
+            .GetStopTokenIds()
+            .as_slice()
+    }
+
+    /// The special token ids, borrowed from the tokenizer info without a copy. Prefer this
+    /// over `special_token_ids` when the ids are read often.
+    pub fn special_token_ids_slice(&self) -> &[i32] {
+        self.inner
+            .as_ref()
+            .expect("UniquePtr was null")
+            .GetSpecialTokenIds()
+            .as_slice()
+    }
+
+    // Whether `other` is the same tokenizer: a clone of this tokenizer info, or one with the
+    // same metadata and decoded vocabulary.
+    pub(crate) fn is_same_tokenizer(
+        &self,
+        other: &TokenizerInfo,
+    ) -> bool {
+        if ffi::tokenizer_info_shares_impl(self.ffi_ref(), other.ffi_ref()) {
+            return true;
+        }
+        self.dump_metadata() == other.dump_metadata()
+            && self
+                .inner
+                .GetDecodedVocab()
+                .iter()
+                .map(|token| token.as_bytes())
+                .eq(other
+                    .inner
+                    .GetDecodedVocab()
+                    .iter()
+                    .map(|token| token.as_bytes()))
+    }
+
     /// Dump the metadata of the tokenizer to a JSON string. It can be used to construct the
     /// tokenizer info from the vocabulary and the metadata string.
     pub fn dump_metadata(&self) -> String {
@@ -287,6 +701,16 @@ impl TokenizerInfo {
         .to_string()
     }
 
+    /// Serialize the tokenizer info to an indented JSON string, for reading and diffing. The string
+    /// deserializes to the same tokenizer info as the one from `serialize_json`.
+    ///
+    /// # Returns
+    ///
+    /// The indented JSON string.
+    pub fn serialize_json_pretty(&self) -> String {
+        crate::utils::pretty_json(self.serialize_json())
+    }
+
     /// Deserialize a tokenizer info from a JSON string.
     ///
     /// # Parameters
@@ -319,9 +743,57 @@ impl TokenizerInfo {
                 error_out_cxx.to_string(),
             ));
         }
-        Ok(Self {
-            inner: uptr,
-        })
+        Ok(Self::from_unique_ptr(uptr))
+    }
+
+    /// Serialize the tokenizer info to a compact binary format. It holds the same data as
+    /// `serialize_json`, including the serialization version, but is smaller and much faster
+    /// to load for large vocabularies.
+    ///
+    /// # Returns
+    ///
+    /// The serialized bytes.
+    pub fn serialize_bytes(&self) -> Vec<u8> {
+        ffi::tokenizer_info_serialize_bytes(
+            self.inner.as_ref().expect("UniquePtr was null"),
+        )
+        .as_slice()
+        .to_vec()
+    }
+
+    /// Deserialize a tokenizer info from bytes produced by `serialize_bytes`.
+    ///
+    /// # Parameters
+    ///
+    /// - `bytes`: The serialized bytes.
+    ///
+    /// # Returns
+    ///
+    /// The tokenizer info.
+    ///
+    /// # Errors
+    ///
+    /// - When the bytes are not in the binary format of the tokenizer info, or are truncated.
+    /// - When the data does not follow the serialization format of the tokenizer info.
+    /// - When the embedded serialization version is not the same as the current version.
+    pub fn deserialize_bytes(bytes: &[u8]) -> Result<Self, DeserializeError> {
+        cxx::let_cxx_string!(error_out_cxx = "");
+        let mut error_kind: i32 = 0;
+        let uptr = unsafe {
+            ffi::tokenizer_info_deserialize_bytes_or_error(
+                bytes.as_ptr(),
+                bytes.len(),
+                &mut error_kind,
+                error_out_cxx.as_mut().get_unchecked_mut(),
+            )
+        };
+        if uptr.is_null() {
+            return Err(DeserializeError::from_parts(
+                error_kind,
+                error_out_cxx.to_string(),
+            ));
+        }
+        Ok(Self::from_unique_ptr(uptr))
     }
 
     pub(crate) fn ffi_ref(&self) -> &ffi::TokenizerInfo {
@@ -333,14 +805,35 @@ impl TokenizerInfo {
     ) -> Self {
         Self {
             inner,
+            token_ids: OnceLock::new(),
         }
     }
 }
 
+/// Cloning is cheap: the clone shares the vocabulary and the precomputed tables with the
+/// original, which are immutable after construction.
+impl Clone for TokenizerInfo {
+    fn clone(&self) -> Self {
+        Self::from_unique_ptr(ffi::tokenizer_info_clone(self.ffi_ref()))
+    }
+}
+
 impl Drop for TokenizerInfo {
     fn drop(&mut self) {}
 }
 
+/// The end-of-turn tokens of chat models that are stop tokens in their generation config, but
+/// are not among the stop tokens detected from the vocabulary, see
+/// `TokenizerInfo::from_huggingface_with_chat_stop_tokens`.
+#[cfg(feature = "tokenizers")]
+const CHAT_STOP_TOKENS: &[&str] = &[
+    "<|im_end|>",
+    "<|eom_id|>",
+    "<|END_OF_TURN_TOKEN|>",
+    "<|return|>",
+    "<|call|>",
+];
+
 #[cfg(feature = "tokenizers")]
 impl TokenizerInfo {
     #[inline]
@@ -424,8 +917,9 @@ impl TokenizerInfo {
     /// vocab_size and the tokenizer's vocabulary size. User should pass the model's vocab_size
     /// (could be defined in the model config) here.
     ///
-    /// The stop token ids is by default auto-detected. If there are other stop tokens, you can
-    /// specify them manually.
+    /// The stop token ids is by default auto-detected, including the end-of-turn tokens of chat
+    /// models (see `from_huggingface_with_chat_stop_tokens`). If there are other stop tokens,
+    /// you can specify them manually.
     ///
     /// # Parameters
     ///
@@ -452,17 +946,238 @@ impl TokenizerInfo {
         vocab_size: Option<usize>,
         stop_token_ids: Option<&[i32]>,
     ) -> Result<Self, String> {
-        let backend_str = tokenizer.to_string(false).map_err(|e| {
-            format!("failed to serialize tokenizer backend: {e}")
-        })?;
-        let metadata = detect_metadata_from_hf(&backend_str)?;
+        Self::from_huggingface_with_chat_stop_tokens(
+            tokenizer,
+            vocab_size,
+            stop_token_ids,
+            true,
+        )
+    }
 
-        Self::from_tokenizers_with_options(
+    /// Construct the tokenizer info from a Hugging Face tokenizer like `from_huggingface`,
+    /// choosing whether the end-of-turn tokens of chat models are stop tokens.
+    ///
+    /// The stop tokens detected from the vocabulary are the end-of-sequence tokens, such as
+    /// `</s>` or `<|eot_id|>`. Chat models often end a turn with another token, such as
+    /// `<|im_end|>`, which `tokenizer.json` does not mark as a stop token; generation then
+    /// never stops. When the stop tokens are auto-detected and `detect_chat_stop_tokens` is
+    /// set, the special added tokens `<|im_end|>`, `<|eom_id|>`, `<|END_OF_TURN_TOKEN|>`,
+    /// `<|return|>` and `<|call|>` are stop tokens too. `from_huggingface` sets it.
+    ///
+    /// # Parameters
+    ///
+    /// - `tokenizer`: The tokenizer.
+    /// - `vocab_size`: The vocabulary size defined by the model (not the tokenizer). See
+    ///   `from_huggingface`.
+    /// - `stop_token_ids`: The stop token ids. If `None`, they will be auto-detected.
+    /// - `detect_chat_stop_tokens`: Whether the auto-detected stop tokens include the
+    ///   end-of-turn tokens. Ignored when `stop_token_ids` is given.
+    ///
+    /// # Returns
+    ///
+    /// The tokenizer info.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tokenizer info cannot be constructed.
+    pub fn from_huggingface_with_chat_stop_tokens(
+        tokenizer: &tokenizers::Tokenizer,
+        vocab_size: Option<usize>,
+        stop_token_ids: Option<&[i32]>,
+        detect_chat_stop_tokens: bool,
+    ) -> Result<Self, String> {
+        let metadata = Self::detect_tokenizer_metadata(tokenizer)?;
+
+        let tokenizer_info = Self::from_tokenizers_with_options(
             tokenizer,
-            metadata.vocab_type,
+            metadata.vocab_type.clone(),
             vocab_size,
             stop_token_ids,
             metadata.add_prefix_space,
-        )
+        )?;
+        if stop_token_ids.is_some() || !detect_chat_stop_tokens {
+            return Ok(tokenizer_info);
+        }
+        let chat_stop_ids =
+            Self::chat_stop_token_ids(tokenizer, tokenizer_info.vocab_size());
+        tokenizer_info.with_chat_stop_tokens(&chat_stop_ids, |stop_ids| {
+            Self::from_tokenizers_with_options(
+                tokenizer,
+                metadata.vocab_type,
+                vocab_size,
+                Some(stop_ids),
+                metadata.add_prefix_space,
+            )
+        })
+    }
+
+    /// Add `chat_stop_ids` to the detected stop tokens of `self`. The constructor does not
+    /// detect them, so the tokenizer info is constructed again by `construct` with all the
+    /// stop tokens, if some are missing.
+    fn with_chat_stop_tokens(
+        self,
+        chat_stop_ids: &[i32],
+        construct: impl FnOnce(&[i32]) -> Result<Self, String>,
+    ) -> Result<Self, String> {
+        let mut stop_ids = self.stop_token_ids().to_vec();
+        let num_detected = stop_ids.len();
+        for &id in chat_stop_ids {
+            if !stop_ids.contains(&id) {
+                stop_ids.push(id);
+            }
+        }
+        if stop_ids.len() == num_detected {
+            return Ok(self);
+        }
+        stop_ids.sort_unstable();
+        construct(&stop_ids)
+    }
+
+    /// The ids below `size` of the special added tokens of `tokenizer` that are in
+    /// `CHAT_STOP_TOKENS`, in increasing order.
+    fn chat_stop_token_ids(
+        tokenizer: &tokenizers::Tokenizer,
+        size: usize,
+    ) -> Vec<i32> {
+        let mut ids: Vec<i32> = tokenizer
+            .get_added_tokens_decoder()
+            .into_iter()
+            .filter(|(id, token)| {
+                token.special
+                    && (*id as usize) < size
+                    && CHAT_STOP_TOKENS.contains(&token.content.as_str())
+            })
+            .map(|(id, _)| id as i32)
+            .collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Construct the tokenizer info from a Hugging Face tokenizer like `from_huggingface`, but
+    /// treat the added tokens the tokenizer flags as `special` as special tokens.
+    ///
+    /// `from_huggingface` only treats empty tokens and the padding as special, so special
+    /// tokens such as `<|im_start|>` or reserved tokens stay in the vocabulary as text, and
+    /// are allowed by the token masks wherever their text is. With this constructor they
+    /// are masked out everywhere, except for the stop tokens. Their decoded text is empty, as
+    /// XGrammar identifies special tokens by their empty text.
+    ///
+    /// # Parameters
+    ///
+    /// - `tokenizer`: The tokenizer.
+    /// - `vocab_size`: The vocabulary size defined by the model (not the tokenizer). See
+    ///   `from_huggingface`.
+    /// - `stop_token_ids`: The stop token ids. If `None`, they will be auto-detected, also
+    ///   among the special tokens, including the end-of-turn tokens like
+    ///   `from_huggingface`.
+    ///
+    /// # Returns
+    ///
+    /// The tokenizer info.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tokenizer info cannot be constructed.
+    pub fn from_huggingface_with_special_tokens(
+        tokenizer: &tokenizers::Tokenizer,
+        vocab_size: Option<usize>,
+        stop_token_ids: Option<&[i32]>,
+    ) -> Result<Self, String> {
+        let metadata = Self::detect_tokenizer_metadata(tokenizer)?;
+        let mut ordered = Self::extract_ordered_vocab(tokenizer, vocab_size);
+        let mut special: Vec<(u32, String)> = tokenizer
+            .get_added_tokens_decoder()
+            .into_iter()
+            .filter(|(_, token)| token.special)
+            .map(|(id, token)| (id, token.content))
+            .collect();
+        special.sort_unstable();
+
+        // The stop tokens keep their text. Auto-detection recognizes them by their text, so
+        // it is run on the special tokens before they are cleared.
+        let chat_stop_ids = Self::chat_stop_token_ids(tokenizer, ordered.len());
+        let stop_ids: Vec<i32> = match stop_token_ids {
+            Some(ids) => ids.to_vec(),
+            None => {
+                let contents: Vec<&str> = special
+                    .iter()
+                    .map(|(_, content)| content.as_str())
+                    .collect();
+                Self::new(&contents, VocabType::RAW, &None, false)?
+                    .stop_token_ids()
+                    .iter()
+                    .map(|&i| special[i as usize].0 as i32)
+                    .chain(chat_stop_ids.iter().copied())
+                    .collect()
+            },
+        };
+        for (id, _) in &special {
+            if stop_ids.contains(&(*id as i32)) {
+                continue;
+            }
+            if let Some(token) = ordered.get_mut(*id as usize) {
+                token.clear();
+            }
+        }
+
+        let construct = |stop: &Option<Box<[i32]>>| {
+            Self::new_with_vocab_size(
+                &ordered,
+                metadata.vocab_type.clone(),
+                Some(ordered.len()),
+                stop,
+                metadata.add_prefix_space,
+            )
+        };
+        let stop: Option<Box<[i32]>> =
+            stop_token_ids.map(|s| s.to_vec().into_boxed_slice());
+        let tokenizer_info = construct(&stop)?;
+        if stop_token_ids.is_some() {
+            return Ok(tokenizer_info);
+        }
+        tokenizer_info.with_chat_stop_tokens(&chat_stop_ids, |stop_ids| {
+            construct(&Some(stop_ids.into()))
+        })
+    }
+
+    fn detect_tokenizer_metadata(
+        tokenizer: &tokenizers::Tokenizer
+    ) -> Result<HfMetadata, String> {
+        let backend_str = tokenizer.to_string(false).map_err(|e| {
+            format!("failed to serialize tokenizer backend: {e}")
+        })?;
+        detect_metadata_from_hf(&backend_str)
+    }
+
+    /// Construct the tokenizer info from a local Hugging Face `tokenizer.json` file, e.g. one
+    /// shipped with the application. This is equivalent to loading the file with
+    /// `tokenizers::Tokenizer::from_file` and calling `from_huggingface`, and needs no
+    /// network access.
+    ///
+    /// # Parameters
+    ///
+    /// - `path`: The path of the `tokenizer.json` file.
+    /// - `vocab_size`: The vocabulary size defined by the model (not the tokenizer). See
+    ///   `from_huggingface`.
+    /// - `stop_token_ids`: The stop token ids. If `None`, they will be auto-detected.
+    ///
+    /// # Returns
+    ///
+    /// The tokenizer info.
+    ///
+    /// # Errors
+    ///
+    /// - When the file cannot be read or is not a valid tokenizer.
+    /// - When the tokenizer info cannot be constructed.
+    pub fn from_tokenizer_file(
+        path: &std::path::Path,
+        vocab_size: Option<usize>,
+        stop_token_ids: Option<&[i32]>,
+    ) -> Result<Self, String> {
+        let tokenizer =
+            tokenizers::Tokenizer::from_file(path).map_err(|e| {
+                format!("failed to load tokenizer from {}: {e}", path.display())
+            })?;
+        Self::from_huggingface(&tokenizer, vocab_size, stop_token_ids)
     }
 }
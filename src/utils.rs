@@ -64,3 +64,12 @@ macro_rules! tie_enum_with_ffi {
     };
 }
 pub(crate) use tie_enum_with_ffi;
+
+/// Re-indent the JSON produced by a `serialize_json` method, keeping the order of the keys.
+/// Returns `json` unchanged if it cannot be parsed.
+pub(crate) fn pretty_json(json: String) -> String {
+    serde_json::from_str::<serde_json::Value>(&json)
+        .ok()
+        .and_then(|value| serde_json::to_string_pretty(&value).ok())
+        .unwrap_or(json)
+}
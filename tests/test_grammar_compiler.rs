@@ -2,11 +2,13 @@
 
 mod test_utils;
 use serial_test::serial;
-#[cfg(feature = "hf")]
 use test_utils::*;
+use xgrammar::{
+    CacheEntryInfo, CompiledGrammar, GrammarCompiler, JsonSchemaError,
+    JsonSchemaOptions, RegexMatchMode, TokenizerInfo, VocabType,
+};
 #[cfg(feature = "hf")]
 use xgrammar::{Grammar, GrammarMatcher};
-use xgrammar::{GrammarCompiler, TokenizerInfo, VocabType};
 
 fn get_allow_empty_rule_ids_via_json(
     compiled: &xgrammar::CompiledGrammar
@@ -200,10 +202,8 @@ rule1 ::= [abc]* [def]*
 
     // Empty vocab is fine for this structural property
     let empty_vocab: Vec<&str> = vec![];
-    let tokenizer_info =
-        TokenizerInfo::new(&empty_vocab, VocabType::RAW, &None, false).unwrap();
-    let mut compiler =
-        GrammarCompiler::new(&tokenizer_info, 1, false, -1).unwrap();
+    let tokenizer_info = raw_tokenizer_info(&empty_vocab);
+    let mut compiler = compiler_from_tokenizer(&tokenizer_info, false);
 
     for (ebnf, expected) in cases.iter() {
         let compiled_grammar =
@@ -213,6 +213,510 @@ rule1 ::= [abc]* [def]*
     }
 }
 
+#[test]
+#[serial]
+fn test_compile_json_schema_default_whitespace() {
+    let schema =
+        r#"{"type": "object", "properties": {"a": {"type": "integer"}}}"#;
+    let empty_vocab: Vec<&str> = vec![];
+    let tokenizer_info = raw_tokenizer_info(&empty_vocab);
+    let mut compiler = compiler_from_tokenizer(&tokenizer_info, false);
+
+    let five_args = compiler
+        .compile_json_schema_default_whitespace(
+            schema,
+            false,
+            Some(2),
+            None::<(&str, &str)>,
+            true,
+        )
+        .unwrap();
+    let six_args = compiler
+        .compile_json_schema(
+            schema,
+            false,
+            Some(2),
+            None::<(&str, &str)>,
+            true,
+            None,
+        )
+        .unwrap();
+    assert_eq!(five_args.grammar().to_string(), six_args.grammar().to_string());
+}
+
+#[test]
+#[serial]
+fn test_compile_json_schema_with_options() {
+    let schema = r#"{"type": "object", "properties": {"id": {"$ref": "common.json#/$defs/Id"}}, "required": ["id"]}"#;
+    let vocab = ["</s>", "{", "}", "1"];
+    let tokenizer_info = raw_tokenizer_info(&vocab);
+    let mut compiler = compiler_from_tokenizer(&tokenizer_info, true);
+
+    let mut options = JsonSchemaOptions {
+        any_whitespace: false,
+        ..JsonSchemaOptions::default()
+    };
+    options.refs.insert(
+        "common.json".to_string(),
+        r#"{"$defs": {"Id": {"type": "integer"}}}"#.to_string(),
+    );
+    let compiled =
+        compiler.compile_json_schema_with_options(schema, &options).unwrap();
+    let accepts = |compiled: &CompiledGrammar, instance: &str| {
+        let mut matcher =
+            xgrammar::GrammarMatcher::new(compiled, None, false, -1).unwrap();
+        matcher.accept_string(instance, false) && matcher.can_terminate_now()
+    };
+    assert!(accepts(&compiled, r#"{"id": 1}"#));
+    assert!(!accepts(&compiled, r#"{"id": "1"}"#));
+    assert!(!accepts(&compiled, "{\"id\": 1}\n"));
+    compiler.compile_json_schema_with_options(schema, &options).unwrap();
+    assert!(compiler.last_compile_stats().unwrap().cache_size_unchanged);
+
+    options.trailing_whitespace = true;
+    let trailing =
+        compiler.compile_json_schema_with_options(schema, &options).unwrap();
+    assert!(accepts(&trailing, "{\"id\": 1}\n"));
+    assert!(accepts(&trailing, r#"{"id": 1}"#));
+    options.trailing_whitespace = false;
+
+    let common = options.refs.remove("common.json").unwrap();
+    options.refs.insert("types.json".to_string(), common);
+    assert!(
+        compiler.compile_json_schema_with_options(schema, &options).is_err()
+    );
+
+    let plain =
+        r#"{"type": "object", "properties": {"a": {"type": "integer"}}}"#;
+    let defaults = compiler
+        .compile_json_schema_with_options(plain, &JsonSchemaOptions::default())
+        .unwrap();
+    let positional = compiler
+        .compile_json_schema(
+            plain,
+            true,
+            None,
+            None::<(&str, &str)>,
+            true,
+            None,
+        )
+        .unwrap();
+    assert_eq!(
+        defaults.grammar().to_string(),
+        positional.grammar().to_string()
+    );
+}
+
+#[test]
+#[serial]
+fn test_last_compile_stats() {
+    let vocab = ["</s>", "a", "b", "ab"];
+    let tokenizer_info = raw_tokenizer_info(&vocab);
+    let mut compiler = compiler_from_tokenizer(&tokenizer_info, true);
+    assert_eq!(compiler.last_compile_stats(), None);
+
+    compiler.compile_regex("(ab)+").unwrap();
+    let first = compiler.last_compile_stats().unwrap();
+    assert!(!first.cache_size_unchanged);
+    assert!(first.num_states > 0);
+
+    compiler.compile_regex("(ab)+").unwrap();
+    let second = compiler.last_compile_stats().unwrap();
+    assert!(second.cache_size_unchanged);
+    assert_eq!(second.num_states, first.num_states);
+
+    assert!(compiler.compile_regex("(").is_err());
+    assert_eq!(compiler.last_compile_stats(), Some(second));
+
+    let mut uncached = compiler_from_tokenizer(&tokenizer_info, false);
+    uncached.compile_regex("(ab)+").unwrap();
+    uncached.compile_regex("(ab)+").unwrap();
+    assert!(!uncached.last_compile_stats().unwrap().cache_size_unchanged);
+}
+
+#[test]
+#[serial]
+fn test_compile_regex_with_options() {
+    let vocab = ["</s>", "a", "b", "ab"];
+    let tokenizer_info = raw_tokenizer_info(&vocab);
+    let mut compiler = compiler_from_tokenizer(&tokenizer_info, true);
+
+    let plain = compiler.compile_regex("(ab)+").unwrap();
+    let printed = compiler.compile_regex_with_options("(ab)+", true).unwrap();
+    assert_eq!(plain.grammar().to_string(), printed.grammar().to_string());
+    assert!(compiler.last_compile_stats().unwrap().cache_size_unchanged);
+
+    let quiet = compiler.compile_regex_with_options("a|b", false).unwrap();
+    assert_eq!(
+        quiet.grammar().to_string(),
+        compiler.compile_regex("a|b").unwrap().grammar().to_string()
+    );
+
+    assert!(compiler.compile_regex_with_options("(", true).is_err());
+    assert!(compiler.compile_regex_with_options("(", false).is_err());
+
+    let accepts_after_match = |compiled: &CompiledGrammar| {
+        let mut matcher =
+            xgrammar::GrammarMatcher::new(compiled, None, false, -1).unwrap();
+        assert!(matcher.accept_token(3));
+        matcher.accept_token(1)
+    };
+    let full = compiler
+        .compile_regex_with_match_mode("ab", RegexMatchMode::FullMatch)
+        .unwrap();
+    assert!(!accepts_after_match(&full));
+    let prefix = compiler
+        .compile_regex_with_match_mode("ab", RegexMatchMode::Prefix)
+        .unwrap();
+    assert!(accepts_after_match(&prefix));
+    assert!(
+        compiler
+            .compile_regex_with_match_mode("(", RegexMatchMode::Prefix)
+            .is_err()
+    );
+}
+
+#[test]
+#[serial]
+fn test_cache_entries() {
+    let vocab = ["</s>", "a", "b", "ab", "{", "}", "\"", ":", "1"];
+    let tokenizer_info = raw_tokenizer_info(&vocab);
+    let mut compiler = compiler_from_tokenizer(&tokenizer_info, true);
+    let a = compiler.compile_regex("(ab)+").unwrap().memory_size_bytes();
+    let compile_schema = |compiler: &mut GrammarCompiler, indent| {
+        compiler
+            .compile_json_schema(
+                r#"{"type": "integer"}"#,
+                false,
+                indent,
+                None::<(&str, &str)>,
+                true,
+                None,
+            )
+            .unwrap()
+            .memory_size_bytes()
+    };
+    let b = compile_schema(&mut compiler, None);
+    let c =
+        compiler.compile_builtin_json_grammar().unwrap().memory_size_bytes();
+    compiler.compile_regex("(ab)+").unwrap();
+
+    let entries = compiler.cache_entries();
+    assert_eq!(entries.len(), 3);
+    assert!(
+        entries
+            .windows(2)
+            .all(|w| w[0].memory_size_bytes >= w[1].memory_size_bytes)
+    );
+    let find = |entries: &[CacheEntryInfo], kind| {
+        let mut found = entries.iter().filter(|entry| entry.kind == kind);
+        let entry = found.next().unwrap().clone();
+        assert!(found.next().is_none(), "{kind}");
+        entry
+    };
+    let regex = find(&entries, "regex");
+    assert_eq!(regex.memory_size_bytes, a);
+    let schema = find(&entries, "json_schema");
+    assert_eq!(schema.memory_size_bytes, b);
+    assert_eq!(find(&entries, "builtin_json").memory_size_bytes, c);
+    assert_ne!(regex.key_hash, schema.key_hash);
+
+    // The format options are part of the key of a JSON schema.
+    compile_schema(&mut compiler, Some(2));
+    let entries = compiler.cache_entries();
+    assert_eq!(entries.len(), 4);
+    let schemas: Vec<u64> = entries
+        .iter()
+        .filter(|entry| entry.kind == "json_schema")
+        .map(|entry| entry.key_hash)
+        .collect();
+    assert_eq!(schemas.len(), 2);
+    assert!(schemas.contains(&schema.key_hash));
+
+    // The keys of equal inputs are equal across compilers.
+    let mut other = compiler_from_tokenizer(&tokenizer_info, true);
+    other.compile_regex("(ab)+").unwrap();
+    assert_eq!(other.cache_entries(), vec![regex]);
+
+    compiler.clear_cache();
+    assert!(compiler.cache_entries().is_empty());
+
+    // Under a limit, the entries the cache evicts are dropped. With no room at all, each
+    // new grammar evicts the others, and compiling the remaining one again is a cache hit.
+    let mut limited =
+        GrammarCompiler::new(&tokenizer_info, 1, true, 0).unwrap();
+    limited.compile_regex("(ab)+").unwrap();
+    limited.compile_regex("b+").unwrap();
+    let c = limited.compile_regex("a|b").unwrap().memory_size_bytes();
+    let entries = limited.cache_entries();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].memory_size_bytes, c);
+    limited.compile_regex("a|b").unwrap();
+    assert!(limited.last_compile_stats().unwrap().cache_size_unchanged);
+    limited.compile_regex("(ab)+").unwrap();
+    assert_eq!(limited.cache_entries().len(), 1);
+    assert_ne!(limited.cache_entries()[0].key_hash, entries[0].key_hash);
+
+    // With room for one grammar in the two thirds of the limit the grammar-level cache gets,
+    // inserting a grammar evicts the least recently used ones until one is left.
+    let limit = 3 * a.div_ceil(2);
+    let mut limited =
+        GrammarCompiler::new(&tokenizer_info, 1, true, limit as isize).unwrap();
+    limited.compile_regex("(ab)+").unwrap();
+    let ab = limited.cache_entries()[0].key_hash;
+    limited.compile_regex("a|b").unwrap();
+    let entries = limited.cache_entries();
+    assert_eq!(entries.len(), 2);
+    let a_or_b =
+        entries.iter().find(|entry| entry.key_hash != ab).unwrap().key_hash;
+    limited.compile_regex("(ab)+").unwrap();
+    limited.compile_regex("b+").unwrap();
+    let entries = limited.cache_entries();
+    assert_eq!(entries.len(), 2);
+    assert!(entries.iter().all(|entry| entry.key_hash != a_or_b));
+    limited.compile_regex("(ab)+").unwrap();
+    assert!(limited.last_compile_stats().unwrap().cache_size_unchanged);
+
+    let mut uncached = compiler_from_tokenizer(&tokenizer_info, false);
+    uncached.compile_regex("(ab)+").unwrap();
+    assert!(uncached.cache_entries().is_empty());
+}
+
+#[test]
+#[serial]
+fn test_memory_breakdown() {
+    let vocab = ["</s>", "a", "b", "ab", "c", "1", "12", "\""];
+    let tokenizer_info = raw_tokenizer_info(&vocab);
+    let mut compiler = compiler_from_tokenizer(&tokenizer_info, false);
+    let compiled = compiler
+        .compile_grammar_from_ebnf(
+            r#"root ::= "a" word | number
+word ::= [a-c]+
+number ::= [0-9]+
+"#,
+            "root",
+        )
+        .unwrap();
+
+    let breakdown = compiled.memory_breakdown();
+    assert_eq!(breakdown.total_bytes(), compiled.memory_size_bytes());
+    assert!(breakdown.grammar_bytes > 0);
+    assert!(breakdown.token_mask_cache_bytes > 0);
+
+    let names: Vec<&str> = breakdown
+        .token_mask_cache_bytes_per_rule
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect();
+    assert_eq!(&names[..3], &["root", "word", "number"]);
+    let per_rule_sum: usize = breakdown
+        .token_mask_cache_bytes_per_rule
+        .iter()
+        .map(|(_, bytes)| bytes)
+        .sum();
+    assert_eq!(per_rule_sum, breakdown.token_mask_cache_bytes);
+}
+
+#[test]
+#[serial]
+fn test_verify_byte_fallback() {
+    let vocab =
+        ["</s>", "<0x1B>", "<0x0A>", "\u{2581}a", "<0X1B>", "<0x1B1B>", "<0x>"];
+    let tokenizer_info =
+        TokenizerInfo::new(&vocab, VocabType::BYTE_FALLBACK, &None, false)
+            .unwrap();
+    assert_eq!(
+        tokenizer_info.verify_byte_fallback(),
+        vec![(4, "<0X1B>".to_string()), (5, "<0x1B1B>".to_string())]
+    );
+
+    let tokenizer_info = raw_tokenizer_info(&vocab[..4]);
+    assert_eq!(
+        tokenizer_info.verify_byte_fallback(),
+        vec![(1, "<0x1B>".to_string()), (2, "<0x0A>".to_string())]
+    );
+
+    let tokenizer_info =
+        TokenizerInfo::new(&vocab[..4], VocabType::BYTE_FALLBACK, &None, false)
+            .unwrap();
+    assert!(tokenizer_info.verify_byte_fallback().is_empty());
+}
+
+#[test]
+#[serial]
+fn test_compile_json_schema_batch() {
+    let vocab = ["</s>", "{", "}", "\"", "a", "1", ":", ",", " "];
+    let tokenizer_info = raw_tokenizer_info(&vocab);
+    let schemas = [
+        r#"{"type": "object", "properties": {"a": {"type": "integer"}}}"#,
+        r#"{"type": "array", "contains": {"type": "string"}}"#,
+        r#"{"type": "string"}"#,
+        "not json",
+        r#"{"type": "integer"}"#,
+        r#"{"type": "object", "properties": {"multipleOf": {"type": "integer"}}}"#,
+        r#"{"type": "integer", "multipleOf": 3}"#,
+    ];
+    for max_threads in [1, 4] {
+        let mut compiler =
+            GrammarCompiler::new(&tokenizer_info, max_threads, true, -1)
+                .unwrap();
+        let results = compiler.compile_json_schema_batch(
+            &schemas,
+            false,
+            None,
+            None::<(&str, &str)>,
+            true,
+            None,
+        );
+        assert_eq!(results.len(), schemas.len());
+        assert!(matches!(results[1], Err(JsonSchemaError::Unsupported(_))));
+        assert!(matches!(results[3], Err(JsonSchemaError::Invalid(_))));
+        // The schemas are compiled in the batch, as JSON schemas, including the one with a
+        // property named `multipleOf`; only the `multipleOf` schema is compiled as a grammar.
+        let kinds: Vec<&str> =
+            compiler.cache_entries().iter().map(|entry| entry.kind).collect();
+        assert_eq!(
+            kinds.iter().filter(|&&kind| kind == "json_schema").count(),
+            4
+        );
+        assert_eq!(kinds.iter().filter(|&&kind| kind == "grammar").count(), 1);
+        for i in [0, 2, 4, 5, 6] {
+            let expected = compiler
+                .compile_json_schema(
+                    schemas[i],
+                    false,
+                    None,
+                    None::<(&str, &str)>,
+                    true,
+                    None,
+                )
+                .unwrap();
+            assert_eq!(
+                results[i].as_ref().unwrap().grammar().to_string_ebnf(),
+                expected.grammar().to_string_ebnf(),
+                "schema {i}"
+            );
+        }
+    }
+    let mut compiler =
+        GrammarCompiler::new(&tokenizer_info, 4, false, -1).unwrap();
+    assert!(
+        compiler
+            .compile_json_schema_batch(
+                &[],
+                false,
+                None,
+                None::<(&str, &str)>,
+                true,
+                None,
+            )
+            .is_empty()
+    );
+}
+
+#[test]
+#[serial]
+fn test_compiled_grammar_debug() {
+    let vocab = ["</s>", "a", "b"];
+    let tokenizer_info = raw_tokenizer_info(&vocab);
+    let mut compiler = compiler_from_tokenizer(&tokenizer_info, false);
+    let compiled = compiler
+        .compile_grammar_from_ebnf(r#"root ::= "a" "b"*"#, "root")
+        .unwrap();
+
+    let debug = format!("{:?}", compiled);
+    assert!(debug.starts_with("CompiledGrammar { grammar: root ::= "));
+    assert!(debug.contains(compiled.grammar().to_string_ebnf().trim_end()));
+    assert!(debug.contains("vocab_size: 3"));
+    assert!(debug.contains(&format!(
+        "memory_size_bytes: {} }}",
+        compiled.memory_size_bytes()
+    )));
+
+    let deserialized = CompiledGrammar::deserialize_json(
+        &compiled.serialize_json(),
+        &tokenizer_info,
+    )
+    .unwrap();
+    let grammar_end = debug.find(", vocab_size").unwrap();
+    assert!(format!("{:?}", deserialized).starts_with(&debug[..grammar_end]));
+}
+
+#[test]
+#[serial]
+fn test_tokenizer_info_clone() {
+    let vocab = ["</s>", "a", "b", "ab"];
+    let tokenizer_info =
+        TokenizerInfo::new(&vocab, VocabType::BYTE_FALLBACK, &None, true)
+            .unwrap();
+    let cloned = tokenizer_info.clone();
+    drop(tokenizer_info);
+
+    assert_eq!(cloned.vocab_type(), VocabType::BYTE_FALLBACK);
+    assert_eq!(cloned.vocab_size(), vocab.len());
+    assert!(cloned.add_prefix_space());
+    assert_eq!(&*cloned.stop_token_ids(), &[0]);
+
+    for max_threads in [1, 4] {
+        let mut compiler =
+            GrammarCompiler::new(&cloned.clone(), max_threads, true, -1)
+                .unwrap();
+        compiler.compile_regex("(ab)+").unwrap();
+    }
+}
+
+#[test]
+#[serial]
+fn test_tokenizer_vocab_size() {
+    let vocab = ["</s>", "a", "b", "ab"];
+    let tokenizer_info = TokenizerInfo::new_with_vocab_size(
+        &vocab,
+        VocabType::RAW,
+        Some(8),
+        &None,
+        false,
+    )
+    .unwrap();
+    assert_eq!(tokenizer_info.vocab_size(), 8);
+    assert_eq!(tokenizer_info.tokenizer_vocab_size(), vocab.len());
+    assert_eq!(&*tokenizer_info.special_token_ids(), &[4, 5, 6, 7]);
+
+    let tokenizer_info = raw_tokenizer_info(&vocab);
+    assert_eq!(tokenizer_info.vocab_size(), vocab.len());
+    assert_eq!(tokenizer_info.tokenizer_vocab_size(), vocab.len());
+}
+
+#[test]
+#[serial]
+fn test_token_ids_slices() {
+    let vocab = ["", "</s>", "a", "<|end|>"];
+    let tokenizer_info = TokenizerInfo::new(
+        &vocab,
+        VocabType::RAW,
+        &Some(vec![1, 3].into()),
+        false,
+    )
+    .unwrap();
+    assert_eq!(tokenizer_info.stop_token_ids_slice(), &[1, 3]);
+    assert_eq!(
+        tokenizer_info.stop_token_ids_slice(),
+        &*tokenizer_info.stop_token_ids()
+    );
+    assert_eq!(tokenizer_info.special_token_ids_slice(), &[0]);
+    assert_eq!(
+        tokenizer_info.special_token_ids_slice(),
+        &*tokenizer_info.special_token_ids()
+    );
+
+    // The slices borrow the same data on every call.
+    assert_eq!(
+        tokenizer_info.stop_token_ids_slice().as_ptr(),
+        tokenizer_info.stop_token_ids_slice().as_ptr()
+    );
+    let cloned = tokenizer_info.clone();
+    assert_eq!(cloned.stop_token_ids_slice(), &[1, 3]);
+}
+
 #[test]
 #[serial]
 #[cfg(feature = "hf")]
@@ -380,3 +884,361 @@ fn test_grammar_compiler_cache_limited() {
     grammar_compiler.clear_cache();
     assert_eq!(grammar_compiler.get_cache_size_bytes(), 0);
 }
+
+#[test]
+#[serial]
+fn test_compile_ebnf() {
+    let vocab = ["</s>", "a", "b"];
+    let tokenizer_info = raw_tokenizer_info(&vocab);
+    let mut compiler = compiler_from_tokenizer(&tokenizer_info, false);
+    let ebnf = r#"root ::= "a" "b"*"#;
+    let compiled = compiler.compile_ebnf(ebnf).unwrap();
+    let explicit = compiler.compile_grammar_from_ebnf(ebnf, "root").unwrap();
+    assert_eq!(
+        compiled.grammar().to_string_ebnf(),
+        explicit.grammar().to_string_ebnf()
+    );
+
+    assert!(compiler.compile_ebnf(r#"main ::= "a""#).is_err());
+}
+
+#[test]
+#[serial]
+fn test_compiled_grammar_shared_across_threads() {
+    let vocab = ["</s>", "a", "b"];
+    let tokenizer_info = raw_tokenizer_info(&vocab);
+    let mut compiler = compiler_from_tokenizer(&tokenizer_info, false);
+    let compiled = std::sync::Arc::new(
+        compiler.compile_ebnf(r#"root ::= "a" "b"*"#).unwrap(),
+    );
+
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let compiled = std::sync::Arc::clone(&compiled);
+            std::thread::spawn(move || {
+                let mut matcher =
+                    xgrammar::GrammarMatcher::new(&compiled, None, true, -1)
+                        .unwrap();
+                matcher.accept_string("abb", false) && matcher.is_completed()
+            })
+        })
+        .collect();
+    for handle in handles {
+        assert!(handle.join().unwrap());
+    }
+}
+
+// The memory usage of the process, on Linux with glibc. The allocated bytes count the live
+// `malloc` allocations, which back both the Rust and the C++ objects.
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+mod memory {
+    use std::ffi::{c_int, c_long};
+
+    // `struct mallinfo2` from `<malloc.h>`.
+    #[repr(C)]
+    struct MallInfo2 {
+        arena: usize,
+        ordblks: usize,
+        smblks: usize,
+        hblks: usize,
+        hblkhd: usize,
+        usmblks: usize,
+        fsmblks: usize,
+        uordblks: usize,
+        fordblks: usize,
+        keepcost: usize,
+    }
+
+    // `_SC_PAGESIZE` from `<unistd.h>`.
+    const SC_PAGESIZE: c_int = 30;
+
+    unsafe extern "C" {
+        fn mallinfo2() -> MallInfo2;
+        fn sysconf(name: c_int) -> c_long;
+    }
+
+    // The bytes of the live allocations, from the heap and from `mmap`.
+    pub fn allocated_bytes() -> usize {
+        let info = unsafe { mallinfo2() };
+        info.uordblks + info.hblkhd
+    }
+
+    // The resident set size in bytes, from `/proc/self/statm`.
+    pub fn resident_bytes() -> usize {
+        let statm = std::fs::read_to_string("/proc/self/statm").unwrap();
+        let pages: usize =
+            statm.split_whitespace().nth(1).unwrap().parse().unwrap();
+        let page_size = unsafe { sysconf(SC_PAGESIZE) };
+        pages * usize::try_from(page_size).unwrap()
+    }
+}
+
+// `mallinfo2` needs glibc 2.33 or later, and the test compiles 11k grammars, so it only runs
+// on request: `cargo test --test test_grammar_compiler -- --ignored`.
+#[test]
+#[serial]
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+#[ignore = "slow, and needs glibc 2.33 or later"]
+fn test_compile_drop_memory_bounded() {
+    let vocab = ["</s>", "a", "b", "ab", "1", "12"];
+    let tokenizer_info = raw_tokenizer_info(&vocab);
+    let mut compiler = compiler_from_tokenizer(&tokenizer_info, false);
+    let mut compile_and_drop = |n: usize| {
+        for i in 0..n {
+            let compiled = compiler
+                .compile_ebnf(&format!(r#"root ::= "a" [0-9]* "{}""#, i))
+                .unwrap();
+            let matcher =
+                xgrammar::GrammarMatcher::new(&compiled, None, true, -1)
+                    .unwrap();
+            drop(compiled);
+            drop(matcher);
+        }
+    };
+
+    // Warm up the allocator before measuring.
+    compile_and_drop(1_000);
+    let allocated = memory::allocated_bytes();
+    let resident = memory::resident_bytes();
+    compile_and_drop(10_000);
+    // Leaking even a few bytes per grammar would exceed the bound on the live allocations.
+    let allocated_growth = memory::allocated_bytes().saturating_sub(allocated);
+    assert!(
+        allocated_growth < 64 << 10,
+        "live allocations grew by {allocated_growth} bytes"
+    );
+    let resident_growth = memory::resident_bytes().saturating_sub(resident);
+    assert!(
+        resident_growth < 16 << 20,
+        "resident memory grew by {resident_growth} bytes"
+    );
+}
+
+#[test]
+#[serial]
+fn test_prefix_space_token() {
+    let vocab = ["<unk>", "<s>", "</s>", "a", "\u{2581}Hello", "\u{2581}"];
+    let tokenizer_info =
+        TokenizerInfo::new(&vocab, VocabType::BYTE_FALLBACK, &None, true)
+            .unwrap();
+    assert_eq!(tokenizer_info.prefix_space_token(), Some(5));
+    assert_eq!(&*tokenizer_info.decoded_vocab()[4], b" Hello");
+
+    let tokenizer_info =
+        TokenizerInfo::new(&vocab, VocabType::BYTE_FALLBACK, &None, false)
+            .unwrap();
+    assert_eq!(tokenizer_info.prefix_space_token(), None);
+
+    let vocab = ["<unk>", "<s>", "</s>", "a"];
+    let tokenizer_info =
+        TokenizerInfo::new(&vocab, VocabType::BYTE_FALLBACK, &None, true)
+            .unwrap();
+    assert_eq!(tokenizer_info.prefix_space_token(), None);
+}
+
+#[test]
+#[serial]
+fn test_diagnose_vocab() {
+    let vocab = ["<s>", "a", "", "<0x20>", "\u{2581}", "b", "a"];
+    let tokenizer_info = TokenizerInfo::new_with_vocab_size(
+        &vocab,
+        VocabType::BYTE_FALLBACK,
+        Some(10),
+        &None,
+        false,
+    )
+    .unwrap();
+    let diagnostics = tokenizer_info.diagnose();
+    assert_eq!(
+        diagnostics.duplicate_tokens,
+        vec![
+            (b"a".as_slice().into(), vec![1, 6]),
+            (b" ".as_slice().into(), vec![3, 4]),
+        ]
+    );
+    assert_eq!(diagnostics.missing_ids, 7..10);
+    assert_eq!(diagnostics.empty_tokens, vec![2]);
+    assert!(!diagnostics.is_clean());
+
+    let vocab = ["<s>", "a", "b"];
+    let tokenizer_info = raw_tokenizer_info(&vocab);
+    assert!(tokenizer_info.diagnose().is_clean());
+}
+
+#[test]
+#[serial]
+fn test_token_id_for_bytes() {
+    let vocab =
+        ["<unk>", "</s>", "a", "\u{2581}Hello", "<0x20>", "\u{2581}", "a", ""];
+    let tokenizer_info =
+        TokenizerInfo::new(&vocab, VocabType::BYTE_FALLBACK, &None, false)
+            .unwrap();
+    assert_eq!(tokenizer_info.token_id_for_bytes(b"a"), Some(2));
+    assert_eq!(tokenizer_info.token_id_for_bytes(b" Hello"), Some(3));
+    assert_eq!(tokenizer_info.token_id_for_bytes(b" "), Some(4));
+    assert_eq!(tokenizer_info.token_id_for_bytes(b"</s>"), Some(1));
+    assert_eq!(tokenizer_info.token_id_for_bytes(b""), None);
+    assert_eq!(tokenizer_info.token_id_for_bytes(b"Hello"), None);
+    assert_eq!(tokenizer_info.clone().token_id_for_bytes(b"a"), Some(2));
+}
+
+#[test]
+#[serial]
+fn test_compile_on_calling_thread() {
+    let vocab = ["</s>", "a", "b", "ab", "{", "}", "\"", ":", ","];
+    let tokenizer_info = raw_tokenizer_info(&vocab);
+    let schema =
+        r#"{"type": "object", "properties": {"a": {"type": "string"}}}"#;
+    let compile = |max_threads| {
+        let mut compiler =
+            GrammarCompiler::new(&tokenizer_info, max_threads, false, -1)
+                .unwrap();
+        let compiled = compiler
+            .compile_json_schema(
+                schema,
+                false,
+                None,
+                None::<(&str, &str)>,
+                true,
+                None,
+            )
+            .unwrap();
+        let batch = compiler.compile_json_schema_batch(
+            &[schema, schema],
+            false,
+            None,
+            None::<(&str, &str)>,
+            true,
+            None,
+        );
+        assert!(batch.iter().all(Result::is_ok));
+        compiled.serialize_json()
+    };
+    let parallel = compile(4);
+    assert_eq!(compile(1), parallel);
+    assert_eq!(compile(0), parallel);
+}
+
+#[test]
+#[serial]
+fn test_compile_json_schema_with_timeout() {
+    let vocab = ["</s>", "a", "b", "ab", "{", "}", "\"", ":", ","];
+    let tokenizer_info = raw_tokenizer_info(&vocab);
+    let mut compiler = compiler_from_tokenizer(&tokenizer_info, true);
+    let compile = |compiler: &mut GrammarCompiler, schema: &str, timeout| {
+        compiler.compile_json_schema_with_timeout(
+            schema,
+            false,
+            None,
+            None::<(&str, &str)>,
+            true,
+            None,
+            timeout,
+        )
+    };
+
+    let schema =
+        r#"{"type": "object", "properties": {"a": {"type": "string"}}}"#;
+    let compiled =
+        compile(&mut compiler, schema, std::time::Duration::from_secs(60))
+            .unwrap();
+    let expected = compiler
+        .compile_json_schema(
+            schema,
+            false,
+            None,
+            None::<(&str, &str)>,
+            true,
+            None,
+        )
+        .unwrap();
+    assert_eq!(compiled.serialize_json(), expected.serialize_json());
+    assert!(compiler.last_compile_stats().unwrap().cache_size_unchanged);
+    assert_eq!(compiler.cache_entries().len(), 1);
+
+    assert!(
+        compile(&mut compiler, "{", std::time::Duration::from_secs(60))
+            .is_err()
+    );
+
+    // Schemas that take long enough to compile to time out reliably.
+    let vocab: Vec<String> =
+        (0..3000).map(|i| format!("t{}\"{{:,", i)).collect();
+    let tokenizer_info = raw_tokenizer_info(&vocab);
+    let mut compiler = compiler_from_tokenizer(&tokenizer_info, true);
+    let slow_schema = |name: usize| {
+        let properties: Vec<String> = (0..100)
+            .map(|i| {
+                format!(
+                    r#""{}_{}": {{"type": "string", "pattern": "^[a-z]{{2,9}}[0-9]+$"}}"#,
+                    name, i
+                )
+            })
+            .collect();
+        format!(
+            r#"{{"type": "object", "properties": {{{}}}}}"#,
+            properties.join(",")
+        )
+    };
+    for name in 0..4 {
+        let schema = slow_schema(name);
+        assert_eq!(
+            compile(&mut compiler, &schema, std::time::Duration::ZERO)
+                .unwrap_err(),
+            JsonSchemaError::Timeout
+        );
+    }
+    assert!(compiler.cache_entries().is_empty());
+    // The abandoned compilations are still running.
+    let schema = slow_schema(4);
+    let err =
+        compile(&mut compiler, &schema, std::time::Duration::from_secs(60))
+            .unwrap_err();
+    assert!(err.to_string().contains("still running"), "{err}");
+}
+
+#[test]
+#[serial]
+fn test_compile_compilable() {
+    fn compile_any(
+        compiler: &mut GrammarCompiler,
+        input: impl xgrammar::Compilable,
+    ) -> CompiledGrammar {
+        compiler.compile(input).unwrap()
+    }
+
+    let vocab = ["</s>", "a", "b", "ab"];
+    let tokenizer_info = raw_tokenizer_info(&vocab);
+    let mut compiler = compiler_from_tokenizer(&tokenizer_info, false);
+    let grammar =
+        xgrammar::Grammar::from_ebnf(r#"root ::= "a" "b"*"#, "root").unwrap();
+    let compiled = compile_any(&mut compiler, &grammar);
+    let passed_through = compile_any(&mut compiler, &compiled);
+    assert_eq!(passed_through.serialize_json(), compiled.serialize_json());
+    assert_eq!(
+        compiled.clone().grammar().to_string_ebnf(),
+        grammar.to_string_ebnf()
+    );
+
+    let mut matcher =
+        xgrammar::GrammarMatcher::new(&passed_through, None, false, -1)
+            .unwrap();
+    drop(compiled);
+    assert!(matcher.accept_token(3));
+    assert!(matcher.accept_token(0));
+
+    // A tokenizer info with the same vocabulary is the same tokenizer.
+    let equal_info = raw_tokenizer_info(&vocab);
+    let mut equal_compiler = compiler_from_tokenizer(&equal_info, false);
+    assert!(equal_compiler.compile(&passed_through).is_ok());
+
+    let larger_info = raw_tokenizer_info(&["</s>", "a", "b", "ab", "ba"]);
+    let mut larger_compiler = compiler_from_tokenizer(&larger_info, false);
+    let err = larger_compiler.compile(&passed_through).unwrap_err();
+    assert!(err.contains("vocabulary of size 4"), "{}", err);
+
+    let other_info = raw_tokenizer_info(&["</s>", "a", "b", "ba"]);
+    let mut other_compiler = compiler_from_tokenizer(&other_info, false);
+    let err = other_compiler.compile(&passed_through).unwrap_err();
+    assert!(err.contains("different tokenizer"), "{}", err);
+}
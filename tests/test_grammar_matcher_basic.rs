@@ -4,14 +4,13 @@ mod test_utils;
 
 use serial_test::serial;
 use test_utils::*;
-#[cfg(feature = "hf")]
 use xgrammar::{
-    BatchGrammarMatcher, GrammarCompiler, GrammarMatcher,
+    AcceptRejectReason, AcceptedStep, Grammar, TokenizerInfo, VocabType,
     allocate_token_bitmask,
 };
-use xgrammar::{Grammar, TokenizerInfo, VocabType};
-
 #[cfg(feature = "hf")]
+use xgrammar::{BatchGrammarMatcher, GrammarCompiler, GrammarMatcher};
+
 fn get_masked_tokens_from_bitmask(
     bitmask: &[i32],
     vocab_size: usize,
@@ -95,6 +94,128 @@ fn test_debug_print_internal_state() {
     }
 }
 
+#[test]
+#[serial]
+#[cfg(feature = "unicode-segmentation")]
+fn test_accept_graphemes() {
+    let grammar =
+        Grammar::from_ebnf(r#"root ::= "\"" [^"]* "\"""#, "root").unwrap();
+    let mut matcher = matcher_from_grammar(&grammar);
+    assert!(matcher.accept_graphemes("\"\u{1F44D}\u{1F3FD}e\u{301}"));
+    assert_eq!(matcher.num_accepted_steps(), 3);
+    matcher.rollback(1).unwrap();
+    matcher.rollback(1).unwrap();
+    assert!(matcher.accept_graphemes("ok\""));
+    assert!(matcher.is_completed());
+
+    matcher.reset();
+    assert!(!matcher.accept_graphemes("\"a\"b"));
+    assert_eq!(matcher.num_accepted_steps(), 3);
+}
+
+#[test]
+#[serial]
+fn test_internal_state() {
+    let grammar = Grammar::from_ebnf("root ::= \"ab\"", "root").unwrap();
+    let vocab = vec!["a", "b", "</s>"];
+    let stop_ids: Option<Box<[i32]>> = Some(vec![2].into());
+    let tokenizer_info =
+        TokenizerInfo::new(&vocab, VocabType::RAW, &stop_ids, false).unwrap();
+    let mut matcher = matcher_from_grammar_with_tokenizer_and_rollback(
+        &grammar,
+        &tokenizer_info,
+        -1,
+    );
+
+    let initial = matcher.internal_state();
+    assert!(!initial.states.is_empty());
+    assert!(initial.states.iter().all(|state| state.rule_id == 0));
+    assert!(!initial.is_completed);
+    assert!(!initial.stop_token_allowed);
+
+    assert!(matcher.accept_string("a", false));
+    let state = matcher.internal_state();
+    assert_eq!(state.num_history_steps, initial.num_history_steps + 1);
+    assert_ne!(state.states, initial.states);
+    assert!(!state.stop_token_allowed);
+
+    assert!(matcher.accept_string("b", false));
+    let state = matcher.internal_state();
+    assert!(state.is_completed);
+    assert!(!state.is_terminated);
+    assert!(state.stop_token_allowed);
+
+    assert!(matcher.accept_token(2));
+    let state = matcher.internal_state();
+    assert!(state.is_terminated);
+    assert!(!state.stop_token_allowed);
+}
+
+#[test]
+#[serial]
+fn test_can_terminate_now() {
+    let grammar = Grammar::from_ebnf("root ::= \"a\" \"b\"*", "root").unwrap();
+    let vocab = vec!["a", "b", "</s>"];
+    let stop_ids: Option<Box<[i32]>> = Some(vec![2].into());
+    let tokenizer_info =
+        TokenizerInfo::new(&vocab, VocabType::RAW, &stop_ids, false).unwrap();
+    let mut matcher = matcher_from_grammar_with_tokenizer_and_rollback(
+        &grammar,
+        &tokenizer_info,
+        -1,
+    );
+
+    assert!(!matcher.can_terminate_now());
+    assert!(matcher.accept_token(0));
+    assert!(matcher.can_terminate_now());
+    assert!(matcher.accept_token(1));
+    assert!(matcher.can_terminate_now());
+    assert!(is_token_accepted_helper(2, &matcher.next_token_bitmask()));
+
+    matcher.set_banned_tokens(&[2]).unwrap();
+    assert!(!matcher.can_terminate_now());
+    matcher.clear_banned_tokens();
+    assert!(matcher.can_terminate_now());
+
+    assert!(matcher.accept_token(2));
+    assert!(!matcher.can_terminate_now());
+
+    let mut matcher =
+        matcher_from_grammar_with_tokenizer(&grammar, &tokenizer_info);
+    assert!(!matcher.can_terminate_now());
+    assert!(matcher.accept_token(0));
+    assert!(matcher.can_terminate_now());
+}
+
+#[test]
+#[serial]
+fn test_prelude() {
+    use xgrammar::prelude::*;
+
+    let vocab = ["a", "b", "</s>"];
+    let stop_ids: Option<Box<[i32]>> = Some(vec![2].into());
+    let tokenizer_info =
+        TokenizerInfo::new(&vocab, VocabType::RAW, &stop_ids, false).unwrap();
+    let mut compiler =
+        GrammarCompiler::new(&tokenizer_info, 1, false, -1).unwrap();
+    let grammar = Grammar::from_ebnf("root ::= \"a\"+", "root").unwrap();
+    let compiled_grammar = compiler.compile_grammar(&grammar).unwrap();
+    let mut matcher =
+        GrammarMatcher::new(&compiled_grammar, None, false, -1).unwrap();
+
+    let mut bitmask = allocate_token_bitmask(1, vocab.len());
+    matcher.fill_next_token_bitmask(
+        &mut bitmask_dltensor(&mut bitmask, 1, vocab.len()).unwrap(),
+        0,
+        false,
+    );
+    let accepted: Vec<i32> =
+        accepted_tokens_iter(&bitmask, 0, vocab.len()).collect();
+    assert_eq!(accepted, [0]);
+    assert!(matcher.accept_token(0));
+    assert!(testing::regex_to_ebnf("a+", false).is_ok());
+}
+
 #[test]
 #[serial]
 #[cfg(feature = "hf")]
@@ -293,7 +414,7 @@ fn test_rollback() {
         5,
     );
 
-    assert_eq!(matcher.max_rollback_tokens(), -1);
+    assert_eq!(matcher.max_rollback_tokens(), 5);
 
     let input_ids_splitted: Vec<(i32, i32)> =
         input_ids.chunks(2).map(|chunk| (chunk[0], chunk[1])).collect();
@@ -306,7 +427,7 @@ fn test_rollback() {
             get_next_token_bitmask_helper(&mut matcher, vocab.len());
         assert!(matcher.accept_token(i_2));
 
-        matcher.rollback(2);
+        matcher.rollback(2).unwrap();
 
         let bitmask1_after =
             get_next_token_bitmask_helper(&mut matcher, vocab.len());
@@ -471,12 +592,136 @@ fn test_termination() {
 
     assert!(!matcher.accept_token(0));
 
-    matcher.rollback(2);
+    matcher.rollback(2).unwrap();
 
     assert!(!matcher.is_terminated());
     assert!(matcher.accept_token(input_ids[input_ids.len() - 2]));
 }
 
+#[test]
+#[serial]
+fn test_rollback_more_than_accepted() {
+    let grammar = Grammar::from_ebnf(r#"root ::= "abc""#, "root").unwrap();
+    let mut matcher = matcher_from_grammar(&grammar);
+
+    assert!(matcher.rollback(1).is_err());
+    assert!(matcher.rollback(0).is_ok());
+
+    assert!(matcher.accept_string("a", false));
+    assert!(matcher.accept_string("b", false));
+    assert!(matcher.rollback(3).is_err());
+    // A failed rollback leaves the state unchanged.
+    assert!(matcher.accept_string("c", false));
+    assert!(matcher.is_terminated());
+
+    assert!(matcher.rollback(3).is_ok());
+    assert!(matcher.rollback(1).is_err());
+    assert!(matcher.accept_string("abc", false));
+}
+
+#[test]
+#[serial]
+fn test_rollback_limit() {
+    let grammar = Grammar::from_ebnf(r#"root ::= [a-z]+"#, "root").unwrap();
+    let tokenizer_info =
+        TokenizerInfo::new(&Vec::<&str>::new(), VocabType::RAW, &None, false)
+            .unwrap();
+    let mut matcher = matcher_from_grammar_with_tokenizer_and_rollback(
+        &grammar,
+        &tokenizer_info,
+        2,
+    );
+    assert_eq!(matcher.max_rollback_tokens(), 2);
+
+    for (i, s) in ["a", "b", "c", "d"].iter().enumerate() {
+        assert!(matcher.accept_string(s, false));
+        assert_eq!(matcher.num_accepted_steps(), (i + 1).min(2));
+    }
+    assert!(matcher.rollback(3).is_err());
+    assert!(matcher.reset_to(1).is_err());
+    assert_eq!(matcher.num_accepted_steps(), 2);
+
+    matcher.rollback(2).unwrap();
+    assert_eq!(matcher.num_accepted_steps(), 2);
+    matcher.reset_to(1).unwrap();
+    assert_eq!(matcher.num_accepted_steps(), 1);
+    assert!(matcher.rollback(2).is_err());
+    matcher.reset();
+    assert_eq!(matcher.num_accepted_steps(), 0);
+}
+
+#[test]
+#[serial]
+fn test_accept_string_prefix() {
+    let grammar =
+        Grammar::from_ebnf(r#"root ::= "ab" [0-9]+ "é" "c""#, "root").unwrap();
+    let cases: &[(&str, usize)] = &[
+        ("ab12éc", 7),
+        ("ab12x", 4),
+        ("x", 0),
+        ("ab", 2),
+        ("ab12ëc", 5),
+        ("", 0),
+    ];
+    for (input, expected) in cases {
+        let mut matcher = matcher_from_grammar(&grammar);
+        assert_eq!(matcher.accept_string_prefix(input), *expected, "{input}");
+        let steps = if *expected > 0 {
+            1
+        } else {
+            0
+        };
+        assert_eq!(matcher.num_accepted_steps(), steps, "{input}");
+    }
+
+    let mut matcher = matcher_from_grammar(&grammar);
+    assert_eq!(matcher.accept_string_prefix("ab1?"), 3);
+    assert!(matcher.accept_string("2éc", false));
+    assert!(matcher.is_completed());
+}
+
+#[test]
+#[serial]
+fn test_try_accept_string() {
+    let grammar =
+        Grammar::from_ebnf(r#"root ::= "ab" [0-9]+ "é" "c""#, "root").unwrap();
+    let mut matcher = matcher_from_grammar(&grammar);
+    assert_eq!(matcher.try_accept_string("ab1"), Ok(()));
+    assert_eq!(matcher.try_accept_string("23x"), Err(2));
+    assert_eq!(matcher.try_accept_string("ëc"), Err(1));
+    assert_eq!(matcher.num_accepted_steps(), 1);
+    assert_eq!(matcher.try_accept_string("2éc"), Ok(()));
+    assert!(matcher.is_completed());
+    assert_eq!(matcher.num_accepted_steps(), 2);
+}
+
+#[test]
+#[serial]
+fn test_reset_to() {
+    let grammar =
+        Grammar::from_ebnf(r#"root ::= [a-z]+ "." [0-9]*"#, "root").unwrap();
+    let mut matcher = matcher_from_grammar(&grammar);
+    assert_eq!(matcher.num_accepted_steps(), 0);
+
+    let input = ["ab", "c", "d", ".", "1", "2", "3"];
+    for (i, s) in input.iter().enumerate() {
+        assert!(matcher.accept_string(s, false));
+        assert_eq!(matcher.num_accepted_steps(), i + 1);
+    }
+
+    assert!(matcher.reset_to(8).is_err());
+    assert_eq!(matcher.num_accepted_steps(), 7);
+
+    matcher.reset_to(3).unwrap();
+    assert_eq!(matcher.num_accepted_steps(), 3);
+    assert!(!matcher.accept_string("1", false));
+    assert!(matcher.accept_string("x.", false));
+
+    matcher.reset_to(0).unwrap();
+    assert_eq!(matcher.num_accepted_steps(), 0);
+    assert!(!matcher.accept_string(".", false));
+}
+
 #[test]
 #[serial]
 fn test_get_jump_forward_string() {
@@ -493,6 +738,625 @@ sub_rule ::= "b"
     assert_eq!(matcher.find_jump_forward_string(), "bb");
 }
 
+#[test]
+#[serial]
+fn test_find_jump_forward_string_capped() {
+    let grammar =
+        Grammar::from_ebnf(r#"root ::= "ab\u00e9cd" [a-z]"#, "root").unwrap();
+    let tokenizer_info =
+        TokenizerInfo::new::<&str>(&[], VocabType::RAW, &None, false).unwrap();
+    let mut matcher =
+        matcher_from_grammar_with_tokenizer(&grammar, &tokenizer_info);
+    assert_eq!(matcher.find_jump_forward_string(), "ab\u{e9}cd");
+    assert_eq!(matcher.find_jump_forward_string_capped(2), "ab");
+    // "\u{e9}" takes two bytes and is not split.
+    assert_eq!(matcher.find_jump_forward_string_capped(3), "ab");
+    assert_eq!(matcher.find_jump_forward_string_capped(4), "ab\u{e9}");
+    assert_eq!(matcher.find_jump_forward_string_capped(0), "");
+    assert_eq!(matcher.find_jump_forward_string_capped(100), "ab\u{e9}cd");
+    assert!(matcher.accept_string("ab", false));
+    assert_eq!(matcher.find_jump_forward_string_capped(3), "\u{e9}c");
+}
+
+#[test]
+#[serial]
+fn test_fill_next_token_bitmask_outside_tag() {
+    let grammar = Grammar::from_ebnf(
+        "root ::= TagDispatch((\"<t>\", body))\nbody ::= \"x\" \"</t>\"",
+        "root",
+    )
+    .unwrap();
+    let vocab = vec!["a", "b", "<", "<t>", "x", "</t>", "</s>"];
+    let stop_ids: Option<Box<[i32]>> = Some(vec![6].into());
+    let tokenizer_info =
+        TokenizerInfo::new(&vocab, VocabType::RAW, &stop_ids, false).unwrap();
+    let mut matcher = matcher_from_grammar_with_tokenizer_and_rollback(
+        &grammar,
+        &tokenizer_info,
+        -1,
+    );
+    let need_apply = |matcher: &mut xgrammar::GrammarMatcher| {
+        let mut bitmask = allocate_token_bitmask(1, vocab.len());
+        let (mut tensor, _shape, _strides) =
+            create_bitmask_dltensor(&mut bitmask, 1, vocab.len());
+        matcher.fill_next_token_bitmask(&mut tensor, 0, false)
+    };
+
+    // Outside of the tag, any text is allowed.
+    assert!(!need_apply(&mut matcher));
+    for token in [0, 1, 2, 4] {
+        assert!(matcher.accept_token(token));
+        assert!(!need_apply(&mut matcher));
+    }
+
+    // Inside of the tag, only the body is allowed.
+    assert!(matcher.accept_token(3));
+    assert!(need_apply(&mut matcher));
+    assert!(matcher.accept_token(4));
+    assert!(matcher.accept_token(5));
+    assert!(!need_apply(&mut matcher));
+}
+
+#[test]
+#[serial]
+fn test_jump_forward_token_ids() {
+    let grammar =
+        Grammar::from_ebnf(r#"root ::= "hello world" [a-z]"#, "root").unwrap();
+
+    let vocab = ["</s>", "hel", "hello", " ", " wor", "ld", "l", "x"];
+    let tokenizer_info = TokenizerInfo::new(
+        &vocab,
+        VocabType::RAW,
+        &Some(vec![0].into()),
+        false,
+    )
+    .unwrap();
+    let mut matcher =
+        matcher_from_grammar_with_tokenizer(&grammar, &tokenizer_info);
+    assert_eq!(matcher.jump_forward_token_ids(), vec![2, 4, 5]);
+    for token_id in matcher.jump_forward_token_ids() {
+        assert!(matcher.accept_token(token_id));
+    }
+    assert_eq!(matcher.find_jump_forward_string(), "");
+    assert!(matcher.jump_forward_token_ids().is_empty());
+    assert!(matcher.accept_token(7));
+
+    // The tail "ld" cannot be covered by whole tokens and is left for decoding.
+    let vocab = ["</s>", "hello", " wor", "lda"];
+    let tokenizer_info = TokenizerInfo::new(
+        &vocab,
+        VocabType::RAW,
+        &Some(vec![0].into()),
+        false,
+    )
+    .unwrap();
+    let mut matcher =
+        matcher_from_grammar_with_tokenizer(&grammar, &tokenizer_info);
+    assert_eq!(matcher.jump_forward_token_ids(), vec![1, 2]);
+}
+
+#[test]
+#[serial]
+fn test_next_token_bitmask() {
+    let grammar = Grammar::from_ebnf(r#"root ::= "a" "b"*"#, "root").unwrap();
+    let vocab: Vec<String> = ["", "</s>", "a", "b", "ab"]
+        .into_iter()
+        .map(String::from)
+        .chain((0..35).map(|i| format!("t{}", i)))
+        .collect();
+    let tokenizer_info = TokenizerInfo::new(
+        &vocab,
+        VocabType::RAW,
+        &Some(vec![1].into()),
+        false,
+    )
+    .unwrap();
+    let mut matcher =
+        matcher_from_grammar_with_tokenizer(&grammar, &tokenizer_info);
+
+    let bitmask = matcher.next_token_bitmask();
+    assert_eq!(bitmask.len(), 2);
+    let allowed: Vec<usize> = (0..vocab.len())
+        .filter(|&i| bitmask[i / 32] & (1 << (i % 32)) != 0)
+        .collect();
+    assert_eq!(allowed, vec![2, 4]);
+
+    assert!(matcher.accept_token(2));
+    let bitmask = matcher.next_token_bitmask();
+    let mut expected = allocate_token_bitmask(1, vocab.len());
+    let (mut tensor, _shape, _strides) =
+        create_bitmask_dltensor(&mut expected, 1, vocab.len());
+    matcher.fill_next_token_bitmask(&mut tensor, 0, false);
+    assert_eq!(bitmask, expected.to_vec());
+    assert_eq!(
+        get_masked_tokens_from_bitmask(&bitmask, vocab.len()).len(),
+        vocab.len() - 2
+    );
+}
+
+#[test]
+#[serial]
+fn test_from_compiled() {
+    let grammar = Grammar::from_ebnf(r#"root ::= "a""#, "root").unwrap();
+    let vocab = ["", "</s>", "a", "b"];
+    let tokenizer_info = TokenizerInfo::new(
+        &vocab,
+        VocabType::RAW,
+        &Some(vec![1].into()),
+        false,
+    )
+    .unwrap();
+    let mut compiler =
+        xgrammar::GrammarCompiler::new(&tokenizer_info, 1, false, -1).unwrap();
+    let compiled_grammar = compiler.compile_grammar(&grammar).unwrap();
+    let mut matcher =
+        xgrammar::GrammarMatcher::from_compiled(&compiled_grammar).unwrap();
+
+    assert_eq!(&*matcher.stop_token_ids(), &[1]);
+    assert_eq!(matcher.max_rollback_tokens(), -1);
+    assert!(matcher.accept_token(2));
+    assert!(!matcher.is_terminated());
+    assert!(matcher.accept_token(1));
+    assert!(matcher.is_terminated());
+    matcher.rollback(2).unwrap();
+    assert!(!matcher.is_terminated());
+}
+
+#[test]
+#[serial]
+fn test_banned_tokens() {
+    let grammar =
+        Grammar::from_ebnf(r#"root ::= ("a" | "b" | "ab") "c""#, "root")
+            .unwrap();
+    let vocab = ["", "</s>", "a", "b", "ab", "c"];
+    let tokenizer_info = TokenizerInfo::new(
+        &vocab,
+        VocabType::RAW,
+        &Some(vec![1].into()),
+        false,
+    )
+    .unwrap();
+    let mut matcher =
+        matcher_from_grammar_with_tokenizer(&grammar, &tokenizer_info);
+    let allowed = |bitmask: &[i32]| -> Vec<usize> {
+        (0..vocab.len())
+            .filter(|&i| bitmask[i / 32] & (1 << (i % 32)) != 0)
+            .collect()
+    };
+    assert_eq!(allowed(&matcher.next_token_bitmask()), vec![2, 3, 4]);
+
+    matcher.set_banned_tokens(&[4, 5]).unwrap();
+    assert_eq!(matcher.banned_tokens(), &[4, 5]);
+    assert_eq!(allowed(&matcher.next_token_bitmask()), vec![2, 3]);
+
+    let mut bitmask = allocate_token_bitmask(2, vocab.len());
+    let (mut tensor, _shape, _strides) =
+        create_bitmask_dltensor(&mut bitmask, 2, vocab.len());
+    assert!(matcher.fill_next_token_bitmask(&mut tensor, 1, false));
+    drop(tensor);
+    assert_eq!(allowed(&bitmask[..1]), vec![0, 1, 2, 3, 4, 5]);
+    assert_eq!(allowed(&bitmask[1..]), vec![2, 3]);
+
+    assert!(matcher.accept_token(2));
+    assert_eq!(allowed(&matcher.next_token_bitmask()), vec![3]);
+    matcher.reset();
+    assert_eq!(matcher.banned_tokens(), &[4, 5]);
+    assert_eq!(allowed(&matcher.next_token_bitmask()), vec![2, 3]);
+    assert_eq!(allowed(&matcher.fork().next_token_bitmask()), vec![2, 3]);
+
+    let mut batch_matcher = xgrammar::BatchGrammarMatcher::new(1).unwrap();
+    let other = matcher_from_grammar_with_tokenizer(&grammar, &tokenizer_info);
+    let mut bitmask = allocate_token_bitmask(3, vocab.len());
+    let (mut tensor, _shape, _strides) =
+        create_bitmask_dltensor(&mut bitmask, 3, vocab.len());
+    batch_matcher.batch_fill_next_token_bitmask(
+        &[other, matcher.fork()],
+        &mut tensor,
+        Some(&[2, 0]),
+        false,
+    );
+    drop(tensor);
+    assert_eq!(allowed(&bitmask[..1]), vec![2, 3]);
+    assert_eq!(allowed(&bitmask[2..]), vec![2, 3, 4]);
+
+    assert!(matcher.set_banned_tokens(&[6]).is_err());
+    assert!(matcher.set_banned_tokens(&[-1]).is_err());
+    assert_eq!(matcher.banned_tokens(), &[4, 5]);
+
+    matcher.clear_banned_tokens();
+    assert_eq!(allowed(&matcher.next_token_bitmask()), vec![2, 3, 4]);
+}
+
+#[test]
+#[serial]
+fn test_fill_next_token_bitmask_with_count() {
+    let grammar =
+        Grammar::from_ebnf(r#"root ::= ("a" | "b" | "ab") "c""#, "root")
+            .unwrap();
+    let vocab = ["", "</s>", "a", "b", "ab", "c"];
+    let tokenizer_info = TokenizerInfo::new(
+        &vocab,
+        VocabType::RAW,
+        &Some(vec![1].into()),
+        false,
+    )
+    .unwrap();
+    let mut matcher =
+        matcher_from_grammar_with_tokenizer(&grammar, &tokenizer_info);
+    let mut bitmask = allocate_token_bitmask(2, vocab.len());
+    let (mut tensor, _shape, _strides) =
+        create_bitmask_dltensor(&mut bitmask, 2, vocab.len());
+
+    assert_eq!(
+        matcher.fill_next_token_bitmask_with_count(&mut tensor, 1, false),
+        (true, 3)
+    );
+    matcher.set_banned_tokens(&[4]).unwrap();
+    assert_eq!(
+        matcher.fill_next_token_bitmask_with_count(&mut tensor, 1, false),
+        (true, 2)
+    );
+    assert!(matcher.accept_token(3));
+    assert_eq!(
+        matcher.fill_next_token_bitmask_with_count(&mut tensor, 0, false),
+        (true, 1)
+    );
+    drop(tensor);
+    assert_eq!(bitmask[0] & 0b111111, 1 << 5);
+}
+
+#[test]
+#[serial]
+fn test_fill_next_token_bitmask_slice() {
+    let grammar =
+        Grammar::from_ebnf(r#"root ::= ("a" | "b" | "ab") "c""#, "root")
+            .unwrap();
+    let vocab = ["", "</s>", "a", "b", "ab", "c"];
+    let tokenizer_info = TokenizerInfo::new(
+        &vocab,
+        VocabType::RAW,
+        &Some(vec![1].into()),
+        false,
+    )
+    .unwrap();
+    let mut matcher =
+        matcher_from_grammar_with_tokenizer(&grammar, &tokenizer_info);
+    let mut bitmask = allocate_token_bitmask(2, vocab.len());
+
+    assert!(matcher.fill_next_token_bitmask_slice(
+        &mut bitmask,
+        1,
+        vocab.len()
+    ));
+    assert_eq!(bitmask[1] & 0b111111, 0b11100);
+    assert_eq!(bitmask[0], -1);
+    assert!(matcher.accept_token(4));
+    assert!(matcher.fill_next_token_bitmask_slice(
+        &mut bitmask,
+        0,
+        vocab.len()
+    ));
+    assert_eq!(bitmask[0] & 0b111111, 1 << 5);
+
+    let panic = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        matcher.fill_next_token_bitmask_slice(&mut bitmask, 2, vocab.len());
+    }))
+    .unwrap_err();
+    let message = panic.downcast_ref::<String>().unwrap();
+    assert!(message.contains("out of the bitmask"), "{message}");
+}
+
+#[test]
+#[serial]
+fn test_filter_candidates() {
+    let grammar =
+        Grammar::from_ebnf(r#"root ::= ("a" | "b" | "ab") "c"?"#, "root")
+            .unwrap();
+    let vocab = ["", "</s>", "a", "b", "ab", "c", "bc"];
+    let tokenizer_info = TokenizerInfo::new(
+        &vocab,
+        VocabType::RAW,
+        &Some(vec![1].into()),
+        false,
+    )
+    .unwrap();
+    let mut matcher =
+        matcher_from_grammar_with_tokenizer(&grammar, &tokenizer_info);
+    let all: Vec<i32> = (0..vocab.len() as i32).collect();
+    let from_bitmask = |matcher: &mut xgrammar::GrammarMatcher| -> Vec<bool> {
+        let bitmask = matcher.next_token_bitmask();
+        all.iter().map(|&i| bitmask[0] & (1 << i) != 0).collect()
+    };
+
+    assert_eq!(
+        matcher.filter_candidates(&all),
+        [false, false, true, true, true, false, true]
+    );
+    assert_eq!(matcher.filter_candidates(&all), from_bitmask(&mut matcher));
+    assert_eq!(
+        matcher.filter_candidates(&[6, 7, -1, 2]),
+        [true, false, false, true]
+    );
+    assert_eq!(matcher.num_accepted_steps(), 0);
+
+    matcher.set_banned_tokens(&[6]).unwrap();
+    assert!(matcher.accept_token(3));
+    assert_eq!(matcher.filter_candidates(&all), from_bitmask(&mut matcher));
+    assert_eq!(
+        matcher.filter_candidates(&all),
+        [false, true, false, false, false, true, false]
+    );
+}
+
+#[test]
+#[serial]
+fn test_set_max_length() {
+    let vocab = ["</s>", "a", "b", "ab", "aa"];
+    let tokenizer_info = TokenizerInfo::new(
+        &vocab,
+        VocabType::RAW,
+        &Some(vec![0].into()),
+        false,
+    )
+    .unwrap();
+    let grammar = Grammar::from_ebnf(r#"root ::= "a"* "b""#, "root").unwrap();
+    let mut compiler =
+        xgrammar::GrammarCompiler::new(&tokenizer_info, 1, false, -1).unwrap();
+    let compiled = compiler.compile_grammar(&grammar).unwrap();
+    let allowed = |matcher: &mut xgrammar::GrammarMatcher| {
+        let bitmask = matcher.next_token_bitmask();
+        let from_bitmask: Vec<i32> =
+            (0..5).filter(|&i| bitmask[0] & (1 << i) != 0).collect();
+        let candidates: Vec<i32> = (0..5).collect();
+        let from_candidates: Vec<i32> = matcher
+            .filter_candidates(&candidates)
+            .iter()
+            .zip(&candidates)
+            .filter_map(|(&allowed, &i)| allowed.then_some(i))
+            .collect();
+        assert_eq!(from_bitmask, from_candidates);
+        from_bitmask
+    };
+
+    let mut matcher =
+        xgrammar::GrammarMatcher::new(&compiled, None, false, -1).unwrap();
+    matcher.set_max_length(3);
+    assert_eq!(matcher.max_length(), Some(3));
+    assert_eq!(allowed(&mut matcher), [1, 2, 3, 4]);
+    assert!(matcher.accept_token(1));
+    assert_eq!(allowed(&mut matcher), [2, 3]);
+    assert!(matcher.accept_token(4));
+    assert!(allowed(&mut matcher).is_empty());
+    matcher.rollback(1).unwrap();
+    assert!(matcher.accept_token(2));
+    assert_eq!(allowed(&mut matcher), [0]);
+    assert!(matcher.accept_token(0));
+
+    matcher.reset();
+    assert_eq!(matcher.max_length(), Some(3));
+    assert!(matcher.accept_string("aaa", false));
+    assert!(matcher.accept_token(1));
+    assert_eq!(allowed(&mut matcher), [2, 3]);
+    matcher.rollback(2).unwrap();
+    assert!(matcher.accept_string("a", false));
+    assert_eq!(allowed(&mut matcher), [1, 2, 3, 4]);
+    assert!(matcher.accept_token(1));
+    assert_eq!(allowed(&mut matcher), [2, 3]);
+    matcher.clear_max_length();
+    assert_eq!(allowed(&mut matcher), [1, 2, 3, 4]);
+
+    let mut matcher =
+        xgrammar::GrammarMatcher::new(&compiled, None, true, -1).unwrap();
+    matcher.set_max_length(2);
+    assert_eq!(allowed(&mut matcher), [1, 2, 3, 4]);
+    assert!(matcher.accept_token(4));
+    assert_eq!(allowed(&mut matcher), [2, 3]);
+
+    // The tokens past the first 32 are in the second element of the bitmask row.
+    let mut vocab: Vec<String> = (0..32).map(|i| format!("<{i}>")).collect();
+    vocab.extend(["a", "b", "ab"].map(String::from));
+    let tokenizer_info = TokenizerInfo::new(
+        &vocab,
+        VocabType::RAW,
+        &Some(vec![0].into()),
+        false,
+    )
+    .unwrap();
+    let mut compiler =
+        xgrammar::GrammarCompiler::new(&tokenizer_info, 1, false, -1).unwrap();
+    let compiled = compiler.compile_grammar(&grammar).unwrap();
+    let mut matcher =
+        xgrammar::GrammarMatcher::new(&compiled, None, true, -1).unwrap();
+    assert_eq!(matcher.next_token_bitmask()[1] & 0b111, 0b111);
+    matcher.set_max_length(1);
+    assert_eq!(matcher.next_token_bitmask()[1] & 0b111, 0b110);
+}
+
+#[test]
+#[serial]
+fn test_last_stop_token() {
+    let vocab = ["</s>", "<|call_end|>", "a", "b"];
+    let tokenizer_info =
+        TokenizerInfo::new(&vocab, VocabType::RAW, &None, false).unwrap();
+    let grammar = Grammar::from_ebnf(r#"root ::= "a" "b"*"#, "root").unwrap();
+    let mut compiler =
+        xgrammar::GrammarCompiler::new(&tokenizer_info, 1, false, -1).unwrap();
+    let compiled = compiler.compile_grammar(&grammar).unwrap();
+    let mut matcher =
+        xgrammar::GrammarMatcher::new(&compiled, Some(&[0, 1]), false, -1)
+            .unwrap();
+
+    assert_eq!(matcher.last_stop_token(), None);
+    assert!(matcher.accept_token(2));
+    assert_eq!(matcher.last_stop_token(), None);
+    assert!(matcher.accept_token(1));
+    assert_eq!(matcher.last_stop_token(), Some(1));
+    matcher.rollback(1).unwrap();
+    assert_eq!(matcher.last_stop_token(), None);
+    assert!(matcher.accept_token(0));
+    assert_eq!(matcher.last_stop_token(), Some(0));
+    matcher.reset();
+    assert_eq!(matcher.last_stop_token(), None);
+
+    let mut matcher =
+        xgrammar::GrammarMatcher::new(&compiled, Some(&[0, 1]), true, -1)
+            .unwrap();
+    assert!(matcher.accept_string("a", false));
+    assert!(matcher.is_terminated());
+    assert_eq!(matcher.last_stop_token(), None);
+}
+
+#[test]
+#[serial]
+fn test_save_restore_state() {
+    let grammar =
+        Grammar::from_ebnf(r#"root ::= "a"+ "bc" "d"*"#, "root").unwrap();
+    let vocab = ["", "</s>", "a", "b", "c", "d", "bc"];
+    let tokenizer_info = TokenizerInfo::new(
+        &vocab,
+        VocabType::RAW,
+        &Some(vec![1].into()),
+        false,
+    )
+    .unwrap();
+    let mut matcher = matcher_from_grammar_with_tokenizer_and_rollback(
+        &grammar,
+        &tokenizer_info,
+        -1,
+    )
+    .with_state_tracking();
+    assert!(matcher.accept_token(2));
+    assert!(!matcher.accept_token(5));
+    assert!(matcher.accept_string("ab", false));
+    assert!(matcher.accept_token(4));
+    assert!(matcher.accept_token(5));
+    assert_eq!(
+        matcher.accepted_steps(),
+        vec![
+            AcceptedStep::Token(2),
+            AcceptedStep::Bytes(b"ab".to_vec()),
+            AcceptedStep::Token(4),
+            AcceptedStep::Token(5),
+        ]
+    );
+    assert_eq!(matcher.accepted_token_history(), vec![2, 4, 5]);
+    matcher.rollback(1).unwrap();
+    assert_eq!(matcher.accepted_token_history(), vec![2, 4]);
+
+    let state = matcher.save_state();
+    let mut restored = matcher_from_grammar_with_tokenizer_and_rollback(
+        &grammar,
+        &tokenizer_info,
+        -1,
+    )
+    .with_state_tracking();
+    restored.restore_state(&state).unwrap();
+    assert_eq!(restored.accepted_steps(), matcher.accepted_steps());
+    assert_eq!(restored.num_accepted_steps(), 3);
+    assert_eq!(restored.next_token_bitmask(), matcher.next_token_bitmask());
+    assert!(restored.accept_token(1));
+    assert!(restored.is_terminated());
+
+    // Restoring does not need the steps to be recorded.
+    let mut untracked = matcher_from_grammar_with_tokenizer_and_rollback(
+        &grammar,
+        &tokenizer_info,
+        -1,
+    );
+    untracked.restore_state(&state).unwrap();
+    assert_eq!(untracked.num_accepted_steps(), 3);
+    assert_eq!(untracked.next_token_bitmask(), matcher.next_token_bitmask());
+
+    // A state the grammar rejects leaves the matcher unchanged.
+    let other = Grammar::from_ebnf(r#"root ::= "b" "c""#, "root").unwrap();
+    let mut other_matcher =
+        matcher_from_grammar_with_tokenizer(&other, &tokenizer_info)
+            .with_state_tracking();
+    assert!(other_matcher.accept_token(3));
+    let err = other_matcher.restore_state(&state).unwrap_err();
+    assert!(err.contains("step 0"), "{err}");
+    assert_eq!(other_matcher.accepted_token_history(), vec![3]);
+    assert!(other_matcher.accept_token(4));
+
+    assert!(restored.restore_state(b"XGMS").is_err());
+    assert!(restored.restore_state(&state[..state.len() - 1]).is_err());
+    assert!(restored.restore_state(b"not a state").is_err());
+
+    let forked = matcher.fork();
+    assert_eq!(forked.accepted_steps(), matcher.accepted_steps());
+    matcher.reset();
+    assert!(matcher.accepted_steps().is_empty());
+    assert_eq!(forked.accepted_token_history(), vec![2, 4]);
+
+    let matchers = [matcher.fork(), matcher.fork()];
+    let accepted = xgrammar::BatchGrammarMatcher::batch_accept_token(
+        &matchers,
+        &[2, 3],
+        false,
+    );
+    assert_eq!(&*accepted, &[true, false]);
+    xgrammar::BatchGrammarMatcher::batch_accept_string(
+        &matchers,
+        &["a", "a"],
+        false,
+    );
+    assert_eq!(
+        matchers[0].accepted_steps(),
+        vec![AcceptedStep::Token(2), AcceptedStep::Bytes(b"a".to_vec())]
+    );
+    xgrammar::BatchGrammarMatcher::batch_rollback(&matchers, &[1, 1]);
+    assert_eq!(matchers[0].accepted_token_history(), vec![2]);
+    assert!(matchers[1].accepted_steps().is_empty());
+}
+
+#[test]
+#[serial]
+fn test_try_accept_token() {
+    let grammar = Grammar::from_ebnf(r#"root ::= "a""#, "root").unwrap();
+    let vocab = ["", "</s>", "a", "b"];
+    let tokenizer_info = TokenizerInfo::new(
+        &vocab,
+        VocabType::RAW,
+        &Some(vec![1].into()),
+        false,
+    )
+    .unwrap();
+    assert_eq!(&*tokenizer_info.special_token_ids(), &[0]);
+    let mut matcher = matcher_from_grammar_with_tokenizer_and_rollback(
+        &grammar,
+        &tokenizer_info,
+        -1,
+    );
+
+    assert_eq!(
+        matcher.try_accept_token(4),
+        Err(AcceptRejectReason::OutOfRange)
+    );
+    assert_eq!(
+        matcher.try_accept_token(-1),
+        Err(AcceptRejectReason::OutOfRange)
+    );
+    assert_eq!(
+        matcher.try_accept_token(0),
+        Err(AcceptRejectReason::SpecialToken)
+    );
+    assert_eq!(
+        matcher.try_accept_token(3),
+        Err(AcceptRejectReason::GrammarMismatch)
+    );
+    assert_eq!(
+        matcher.try_accept_token(1),
+        Err(AcceptRejectReason::GrammarMismatch)
+    );
+    assert_eq!(matcher.try_accept_token(2), Ok(()));
+    assert_eq!(matcher.try_accept_token(1), Ok(()));
+    assert!(matcher.is_terminated());
+    assert_eq!(
+        matcher.try_accept_token(2),
+        Err(AcceptRejectReason::AlreadyTerminated)
+    );
+}
+
 #[test]
 #[serial]
 fn test_vocab_size() {
@@ -1000,3 +1864,154 @@ fn test_batch_fill_next_token_bitmask_pressure_shuffled() {
         );
     }
 }
+
+#[test]
+#[serial]
+fn test_batch_grammar_matcher_max_threads() {
+    assert!(xgrammar::BatchGrammarMatcher::new_auto().is_ok());
+    assert!(xgrammar::BatchGrammarMatcher::new(1).is_ok());
+    assert!(xgrammar::BatchGrammarMatcher::new(4).is_ok());
+    assert!(xgrammar::BatchGrammarMatcher::new(0).is_err());
+}
+
+#[test]
+#[serial]
+fn test_batch_grammar_matcher_slots() {
+    let vocab = ["</s>", "a", "b", "1"];
+    let tokenizer_info =
+        TokenizerInfo::new(&vocab, VocabType::RAW, &None, false).unwrap();
+    let matcher = |ebnf: &str| {
+        matcher_from_grammar_with_tokenizer(
+            &Grammar::from_ebnf(ebnf, "root").unwrap(),
+            &tokenizer_info,
+        )
+    };
+
+    let mut batch_matcher = xgrammar::BatchGrammarMatcher::new(2).unwrap();
+    assert!(batch_matcher.is_empty());
+    assert_eq!(batch_matcher.push(matcher(r#"root ::= "a""#)), 0);
+    assert_eq!(batch_matcher.push(matcher(r#"root ::= "b""#)), 1);
+    assert_eq!(batch_matcher.push(matcher(r#"root ::= [0-9]+"#)), 2);
+    assert!(batch_matcher.remove(1).is_some());
+    assert!(batch_matcher.remove(1).is_none());
+    assert!(batch_matcher.get(1).is_none());
+    assert_eq!(batch_matcher.len(), 2);
+    assert_eq!(batch_matcher.num_slots(), 3);
+    assert_eq!(
+        batch_matcher.iter().map(|(index, _)| index).collect::<Vec<_>>(),
+        vec![0, 2]
+    );
+
+    let batch_size = batch_matcher.num_slots();
+    let mut token_bitmask =
+        allocate_token_bitmask(batch_size, tokenizer_info.vocab_size());
+    token_bitmask.fill(0);
+    let (mut tensor, _shape, _strides) = create_bitmask_dltensor(
+        &mut token_bitmask,
+        batch_size,
+        tokenizer_info.vocab_size(),
+    );
+    batch_matcher.fill_next_token_bitmask(&mut tensor, false);
+    let accepted = |row: &[i32]| -> Vec<usize> {
+        let masked = get_masked_tokens_from_bitmask(row, vocab.len());
+        (0..vocab.len()).filter(|id| !masked.contains(id)).collect()
+    };
+    assert_eq!(accepted(&token_bitmask[0..1]), vec![1]);
+    assert_eq!(token_bitmask[1], 0);
+    assert_eq!(accepted(&token_bitmask[2..3]), vec![3]);
+
+    assert!(batch_matcher.get_mut(2).unwrap().accept_token(3));
+    assert_eq!(batch_matcher.push(matcher(r#"root ::= "b""#)), 1);
+    batch_matcher.fill_next_token_bitmask(&mut tensor, false);
+    assert_eq!(accepted(&token_bitmask[1..2]), vec![2]);
+    assert_eq!(accepted(&token_bitmask[2..3]), vec![0, 3]);
+
+    assert!(batch_matcher.remove(2).is_some());
+    assert_eq!(batch_matcher.num_slots(), 2);
+    assert!(batch_matcher.remove(0).is_some());
+    assert!(batch_matcher.remove(1).is_some());
+    assert!(batch_matcher.is_empty());
+    assert_eq!(batch_matcher.num_slots(), 0);
+}
+
+#[test]
+#[serial]
+fn test_batch_grammar_matcher_accept_token() {
+    let vocab = ["</s>", "a", "b", "1"];
+    let tokenizer_info =
+        TokenizerInfo::new(&vocab, VocabType::RAW, &None, false).unwrap();
+    let matcher = |ebnf: &str| {
+        matcher_from_grammar_with_tokenizer_and_rollback(
+            &Grammar::from_ebnf(ebnf, "root").unwrap(),
+            &tokenizer_info,
+            -1,
+        )
+        .with_state_tracking()
+    };
+    let mut batch_matcher = xgrammar::BatchGrammarMatcher::new(1).unwrap();
+    batch_matcher.push(matcher(r#"root ::= "a""#));
+    batch_matcher.push(matcher(r#"root ::= "b""#));
+    batch_matcher.push(matcher(r#"root ::= [0-9]+"#));
+    batch_matcher.remove(1);
+
+    let result = batch_matcher.accept_token(&[1, 2, 3]);
+    assert_eq!(result.accepted, vec![true, false, true]);
+    assert_eq!(result.terminated, vec![false, false, false]);
+
+    let result = batch_matcher.accept_token(&[0, 0, 1]);
+    assert_eq!(result.accepted, vec![true, false, false]);
+    assert_eq!(result.terminated, vec![true, false, false]);
+    assert_eq!(batch_matcher.get(0).unwrap().accepted_token_history(), [1, 0]);
+    assert_eq!(batch_matcher.get(2).unwrap().accepted_token_history(), [3]);
+}
+
+#[test]
+#[serial]
+fn test_accept_traced() {
+    let vocab = ["</s>", "a", "b", "ab"];
+    let tokenizer_info =
+        TokenizerInfo::new(&vocab, VocabType::RAW, &None, false).unwrap();
+    let grammar = Grammar::from_ebnf(r#"root ::= "ab" "b"*"#, "root").unwrap();
+    let mut matcher =
+        matcher_from_grammar_with_tokenizer(&grammar, &tokenizer_info);
+
+    let mut trace = Vec::new();
+    assert!(
+        !matcher.accept_string_traced("ba", &mut |line| trace
+            .push(line.to_string()))
+    );
+    assert_eq!(trace.len(), 2);
+    assert!(trace[0].starts_with("Trying to accept string \"ba\"."));
+    assert!(trace[0].contains("ParserState"));
+    assert_eq!(trace[1], "String \"ba\" is rejected at position 0, char b");
+    assert_eq!(matcher.num_accepted_steps(), 0);
+
+    trace.clear();
+    assert!(
+        matcher.accept_string_traced("ab", &mut |line| trace
+            .push(line.to_string()))
+    );
+    assert_eq!(trace.len(), 4);
+    assert!(trace[1].starts_with("Char a is accepted. Current state:\n"));
+    assert!(trace[2].starts_with("Char b is accepted."));
+    assert_eq!(trace[3], "String \"ab\" is accepted.");
+    assert_eq!(matcher.num_accepted_steps(), 1);
+
+    trace.clear();
+    assert!(
+        matcher
+            .accept_token_traced(2, &mut |line| trace.push(line.to_string()))
+    );
+    assert!(trace[0].starts_with("Accepting token id 2, string: \"b\""));
+    assert_eq!(trace[1], "Token id 2 is accepted.");
+
+    trace.clear();
+    assert!(
+        !matcher
+            .accept_token_traced(9, &mut |line| trace.push(line.to_string()))
+    );
+    assert_eq!(
+        trace[1],
+        "Token id 9 is rejected: the token id is out of range."
+    );
+}
@@ -33,6 +33,59 @@ fn test_json_accept() {
     }
 }
 
+#[test]
+#[serial]
+fn test_compact_json_grammar() {
+    let compact = Grammar::builtin_json_grammar_with_options(false);
+    let builtin = Grammar::builtin_json_grammar();
+    // Without whitespace, the compact grammar accepts what the builtin grammar accepts.
+    for s in [
+        "{}",
+        "[]",
+        r#"{"a":[1,-2.5e3,true,null],"b":{"c":"x y"}}"#,
+        r#"[{"a":1},"s",[]]"#,
+        r#"["\"\\\/\b\f\n\r\t\u00e9","é",0,-0.5E+3,1e-2]"#,
+        r#"[[[{"":{}}]]]"#,
+    ] {
+        assert!(is_grammar_accept_string(&compact, s), "{}", s);
+        assert!(is_grammar_accept_string(&builtin, s), "{}", s);
+    }
+    for s in [
+        "1",
+        r#""s""#,
+        "[01]",
+        "[1.]",
+        "[.5]",
+        "[+1]",
+        r#"["\x"]"#,
+        "[\"\u{1}\"]",
+        r#"["\u00e"]"#,
+        "[1,]",
+        r#"{"a"}"#,
+        "[True]",
+    ] {
+        assert!(!is_grammar_accept_string(&compact, s), "{}", s);
+        assert!(!is_grammar_accept_string(&builtin, s), "{}", s);
+    }
+    for s in [
+        r#"{"a": 1}"#,
+        r#"{ "a":1}"#,
+        r#"[1, 2]"#,
+        "[1,2]\n",
+        "[\n]",
+        r#"{"a":1 }"#,
+    ] {
+        assert!(!is_grammar_accept_string(&compact, s), "{}", s);
+    }
+
+    let with_whitespace = Grammar::builtin_json_grammar_with_options(true);
+    assert_eq!(
+        with_whitespace.to_string_ebnf(),
+        Grammar::builtin_json_grammar().to_string_ebnf()
+    );
+    assert!(is_grammar_accept_string(&with_whitespace, r#"{"a": 1}"#));
+}
+
 #[test]
 #[serial]
 fn test_json_refuse() {
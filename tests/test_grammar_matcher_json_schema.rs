@@ -539,7 +539,9 @@ fn test_64bit_limit_validation() {
             match result {
                 Ok(_) => panic!("expected schema error"),
                 Err(err) => assert!(
-                    err.to_lowercase().contains(&error_pattern.to_lowercase()),
+                    err.to_string()
+                        .to_lowercase()
+                        .contains(&error_pattern.to_lowercase()),
                     "expected error containing '{}', got '{}'",
                     error_pattern,
                     err
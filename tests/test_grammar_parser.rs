@@ -1,7 +1,10 @@
 mod test_utils;
 
 use serial_test::serial;
-use xgrammar::{Grammar, testing::ebnf_to_grammar_no_normalization};
+use xgrammar::{
+    EbnfFormatOptions, Grammar,
+    testing::{assert_accepts, ebnf_to_grammar_no_normalization},
+};
 
 #[test]
 #[serial]
@@ -530,3 +533,223 @@ root_repeat_1 ::= (([0-9]*)) (=([0-9]* [0-9]* [0-9]* [0-9]* [0-9]* [0-9]* [0-9]*
     assert!(test_utils::is_grammar_accept_string(&grammar, "12345"));
     let _ = expected_grammar;
 }
+
+#[test]
+#[serial]
+fn test_validate() {
+    let grammar = Grammar::from_ebnf(
+        r#"root ::= "a" rule_b | "c"
+rule_b ::= [0-9]+ (=("x"))
+"#,
+        "root",
+    )
+    .unwrap();
+    assert!(grammar.validate().is_ok());
+
+    assert!(Grammar::builtin_json_grammar().validate().is_ok());
+
+    let union = Grammar::union(&[
+        Grammar::from_ebnf(r#"root ::= "x""#, "root").unwrap(),
+        Grammar::from_regex("[a-z]+", false).unwrap(),
+    ]);
+    assert!(union.validate().is_ok());
+
+    let roundtrip =
+        Grammar::deserialize_json(&grammar.serialize_json()).unwrap();
+    assert!(roundtrip.validate().is_ok());
+
+    let unreachable = Grammar::from_ebnf(
+        "root ::= \"a\" rule_b\nrule_b ::= \"b\"\nrule_c ::= \"c\" rule_b\n",
+        "root",
+    )
+    .unwrap();
+    assert!(unreachable.validate().is_ok());
+    assert_eq!(unreachable.unreachable_rules().unwrap(), ["rule_c"]);
+    assert!(grammar.unreachable_rules().unwrap().is_empty());
+}
+
+#[test]
+#[serial]
+fn test_from_ebnf_bytes() {
+    let ebnf = r#"root ::= "\xff" [\x00-\x1f] "é"
+"#;
+    let from_bytes = Grammar::from_ebnf_bytes(ebnf.as_bytes(), "root").unwrap();
+    let from_str = Grammar::from_ebnf(ebnf, "root").unwrap();
+    assert_eq!(from_bytes.to_string(), from_str.to_string());
+
+    let err =
+        Grammar::from_ebnf_bytes(b"root ::= \"\xe9\"\n", "root").err().unwrap();
+    assert!(err.contains("Invalid UTF8"), "{}", err);
+
+    let err =
+        Grammar::from_ebnf_bytes(b"root ::= \"a\"\n", "main").err().unwrap();
+    assert!(err.contains("main"), "{}", err);
+}
+
+#[test]
+#[serial]
+fn test_is_empty_language() {
+    let non_empty = [
+        r#"root ::= "a" root | """#,
+        "root ::= a*\na ::= \"x\" a",
+        "root ::= [^a]",
+    ];
+    for ebnf in non_empty {
+        let grammar = Grammar::from_ebnf(ebnf, "root").unwrap();
+        assert!(!grammar.is_empty_language(), "{}", ebnf);
+    }
+    assert!(!Grammar::builtin_json_grammar().is_empty_language());
+
+    let empty = [
+        r#"root ::= "a" root"#,
+        "root ::= a\na ::= \"x\" a | \"y\" a",
+        "root ::= a+\na ::= \"x\" a",
+        "root ::= \"x\" b\nb ::= [a-z] b | c\nc ::= \"q\" c",
+    ];
+    for ebnf in empty {
+        let grammar = Grammar::from_ebnf(ebnf, "root").unwrap();
+        assert!(grammar.is_empty_language(), "{}", ebnf);
+    }
+}
+
+#[test]
+#[serial]
+fn test_from_ebnf_auto_root() {
+    let gbnf = "# A GBNF grammar\nstart ::= greeting \" \" name\n\
+                greeting ::= \"hello\" | \"hi\"\nname ::= [a-z]+\n";
+    let (grammar, root) = Grammar::from_ebnf_auto_root(gbnf).unwrap();
+    assert_eq!(root, "start");
+    assert!(test_utils::is_grammar_accept_string(&grammar, "hi bob"));
+    assert!(!test_utils::is_grammar_accept_string(&grammar, "hi"));
+
+    let with_root = "item ::= \"x\"\n  root ::= item+\n";
+    let (grammar, root) = Grammar::from_ebnf_auto_root(with_root).unwrap();
+    assert_eq!(root, "root");
+    assert!(test_utils::is_grammar_accept_string(&grammar, "xxx"));
+
+    assert_eq!(
+        Grammar::from_ebnf(gbnf, "start").unwrap().to_string(),
+        Grammar::from_ebnf_auto_root(gbnf).unwrap().0.to_string()
+    );
+    assert!(Grammar::from_ebnf_auto_root("# only a comment\n").is_err());
+    assert!(Grammar::from_ebnf_auto_root("start ::= undefined_rule").is_err());
+}
+
+#[test]
+#[serial]
+fn test_literal_ci() {
+    let accepts = test_utils::is_grammar_accept_string;
+    let grammar = Grammar::literal_ci("true");
+    for input in ["true", "TRUE", "True", "tRuE"] {
+        assert!(accepts(&grammar, input), "{input}");
+    }
+    assert!(!accepts(&grammar, "tru"));
+    assert!(!accepts(&grammar, "truee"));
+
+    let grammar = Grammar::literal_ci("Café-\"[x]\\\n1");
+    assert!(accepts(&grammar, "CAFÉ-\"[X]\\\n1"));
+    assert!(accepts(&grammar, "café-\"[x]\\\n1"));
+    assert!(!accepts(&grammar, "cafe-\"[x]\\\n1"));
+
+    // Case mappings that change the length are not applied.
+    let grammar = Grammar::literal_ci("straße");
+    assert!(accepts(&grammar, "STRAßE"));
+    assert!(!accepts(&grammar, "STRASSE"));
+
+    assert!(accepts(&Grammar::literal_ci(""), ""));
+    let grammar = Grammar::concat(&[
+        Grammar::literal_ci("null"),
+        Grammar::from_ebnf("root ::= [0-9]", "root").unwrap(),
+    ]);
+    assert!(accepts(&grammar, "NuLL7"));
+}
+
+#[test]
+#[serial]
+fn test_rule_count_and_names() {
+    let grammar = Grammar::from_ebnf(
+        "root ::= \"a\" rule_b\nrule_b ::= [0-9]+ (\"x\" | \"y\")*",
+        "root",
+    )
+    .unwrap();
+    let names = grammar.rule_names();
+    assert_eq!(names.len(), grammar.rule_count());
+    assert_eq!(&names[..2], &["root", "rule_b"]);
+    let ebnf = grammar.to_string();
+    let mut ebnf_names: Vec<&str> = ebnf
+        .lines()
+        .filter_map(|line| line.split_once(" ::= ").map(|(name, _)| name))
+        .collect();
+    ebnf_names.sort_unstable();
+    let mut sorted_names: Vec<&str> =
+        names.iter().map(String::as_str).collect();
+    sorted_names.sort_unstable();
+    assert_eq!(sorted_names, ebnf_names);
+
+    let json = Grammar::builtin_json_grammar();
+    assert!(json.rule_count() > grammar.rule_count());
+}
+
+#[test]
+#[serial]
+fn test_from_str() {
+    let ebnf = r#"root ::= "a" rule_b
+rule_b ::= [0-9]+
+"#;
+    let grammar: Grammar = ebnf.parse().unwrap();
+    assert_eq!(
+        grammar.to_string(),
+        Grammar::from_ebnf(ebnf, "root").unwrap().to_string()
+    );
+    assert!(Grammar::try_from(ebnf).is_ok());
+
+    assert!("main ::= \"a\"".parse::<Grammar>().is_err());
+    assert!(Grammar::try_from("root ::= undefined_rule").is_err());
+}
+
+#[test]
+#[serial]
+fn test_to_ebnf_pretty() {
+    let grammar = Grammar::builtin_json_grammar();
+    let options = EbnfFormatOptions::default();
+    let pretty = grammar.to_ebnf_pretty(&options);
+    assert!(pretty.lines().count() > grammar.to_string().lines().count());
+    assert!(pretty.lines().any(|line| line.starts_with("  | ")));
+
+    // The printed grammar is already normalized, so compare against a reparse of it.
+    let expected = Grammar::from_ebnf(&grammar.to_string(), "root").unwrap();
+    let reparsed = Grammar::from_ebnf(&pretty, "root").unwrap();
+    assert_eq!(reparsed.to_string(), expected.to_string());
+
+    let grammar = Grammar::from_ebnf(
+        r#"root ::= "a" | "b" rule_b
+rule_b ::= ("c" | "d")* "a b | c" [|()]{1, 3} (="e")
+"#,
+        "root",
+    )
+    .unwrap();
+    let compact = grammar.to_ebnf_pretty(&EbnfFormatOptions {
+        max_line_width: 20,
+        alternatives_on_separate_lines: false,
+    });
+    assert!(!compact.lines().any(|line| line.starts_with("  | ")));
+    let expected = Grammar::from_ebnf(&grammar.to_string(), "root").unwrap();
+    let reparsed = Grammar::from_ebnf(&compact, "root").unwrap();
+    assert_eq!(reparsed.to_string(), expected.to_string());
+}
+
+#[test]
+#[serial]
+fn test_assert_accepts() {
+    let grammar = Grammar::from_ebnf(r#"root ::= "a" "b"*"#, "root").unwrap();
+    assert_accepts(&grammar, &["a", "ab", "abbb"], &["", "b", "aba"]);
+
+    let panic = std::panic::catch_unwind(|| {
+        assert_accepts(&grammar, &["a", "ba"], &["ab"]);
+    })
+    .unwrap_err();
+    let message = panic.downcast_ref::<String>().unwrap();
+    assert!(message.contains("not accepted: \"ba\""), "{message}");
+    assert!(message.contains("not rejected: \"ab\""), "{message}");
+    assert!(!message.contains("not accepted: \"a\""), "{message}");
+}
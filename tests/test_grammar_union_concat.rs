@@ -95,6 +95,123 @@ r3 ::= ("" | ("abc"))
     assert_eq!(concat.to_string(), expected);
 }
 
+#[test]
+#[serial]
+fn test_grammar_try_union_concat() {
+    let sources = ["a", "b", "c"];
+    let grammars = || {
+        sources.iter().map(|s| {
+            Grammar::from_ebnf(&format!(r#"root ::= "{}""#, s), "root").unwrap()
+        })
+    };
+
+    let union = Grammar::try_union(grammars()).unwrap();
+    let slice_union = Grammar::union(&grammars().collect::<Vec<_>>());
+    assert_eq!(union.to_string(), slice_union.to_string());
+
+    let concat = Grammar::try_concat(grammars().filter(|_| true)).unwrap();
+    let slice_concat = Grammar::concat(&grammars().collect::<Vec<_>>());
+    assert_eq!(concat.to_string(), slice_concat.to_string());
+
+    let Err(err) = Grammar::try_union(grammars().filter(|_| false)) else {
+        panic!("try_union should fail on an empty iterator");
+    };
+    assert!(err.contains("requires at least one grammar"));
+    let Err(err) = Grammar::try_concat(std::iter::empty()) else {
+        panic!("try_concat should fail on an empty iterator");
+    };
+    assert!(err.contains("requires at least one grammar"));
+}
+
+#[test]
+#[serial]
+fn test_grammar_concat_with_markers() {
+    let prefix =
+        Grammar::from_ebnf(r#"root ::= "call " [a-z_]+"#, "root").unwrap();
+    let body = Grammar::builtin_json_grammar();
+    let suffix = Grammar::from_ebnf(r#"root ::= ";"*"#, "root").unwrap();
+    let (grammar, markers) = Grammar::concat_with_markers(&[
+        ("prefix", &prefix),
+        ("body", &body),
+        ("suffix", &suffix),
+    ])
+    .unwrap();
+    assert_eq!(markers.names(), ["prefix", "body", "suffix"]);
+
+    let matched = r#"call get_time{"tz": "UTC"};;"#;
+    assert!(test_utils::is_grammar_accept_string(&grammar, matched));
+    assert_eq!(
+        markers.part_ends(matched).unwrap(),
+        [("prefix", 13), ("body", 26), ("suffix", 28)]
+    );
+    assert_eq!(
+        markers.part_ends(r#"call f[]"#).unwrap(),
+        [("prefix", 6), ("body", 8), ("suffix", 8)]
+    );
+
+    assert!(markers.part_ends(r#"call get_time"#).is_err());
+    assert!(markers.part_ends(r#"call f[]x"#).is_err());
+
+    let mut tracker = markers.tracker().unwrap();
+    for chunk in ["cal", "l get", "_time{\"tz", "\": \"UTC\"}", ";"] {
+        assert!(tracker.accept_bytes(chunk.as_bytes()));
+    }
+    assert_eq!(
+        tracker.part_ends().unwrap(),
+        [("prefix", 13), ("body", 26), ("suffix", 27)]
+    );
+    assert!(!tracker.accept_bytes(b"x"));
+    assert!(tracker.part_ends().is_err());
+
+    // Ambiguous boundaries resolve to the earliest end of each part.
+    let (_, markers) = Grammar::concat_with_markers(&[
+        ("a", &Grammar::from_ebnf(r#"root ::= "a"*"#, "root").unwrap()),
+        ("b", &Grammar::from_ebnf(r#"root ::= "a"* "b""#, "root").unwrap()),
+        ("c", &Grammar::from_ebnf(r#"root ::= "b"*"#, "root").unwrap()),
+    ])
+    .unwrap();
+    assert_eq!(
+        markers.part_ends("aaabb").unwrap(),
+        [("a", 0), ("b", 4), ("c", 5)]
+    );
+    assert_eq!(markers.part_ends("b").unwrap(), [("a", 0), ("b", 1), ("c", 1)]);
+
+    let Err(err) = Grammar::concat_with_markers(&[]) else {
+        panic!("concat_with_markers should fail without parts");
+    };
+    assert!(err.contains("requires at least one grammar"), "{}", err);
+}
+
+#[test]
+#[serial]
+fn test_grammar_union_concat_refs() {
+    let make_grammars = |sources: &[&str]| -> Vec<Grammar> {
+        sources
+            .iter()
+            .map(|s| {
+                Grammar::from_ebnf(&format!(r#"root ::= "{}""#, s), "root")
+                    .unwrap()
+            })
+            .collect()
+    };
+    let grammars = make_grammars(&["a", "b", "c"]);
+    let refs: Vec<&Grammar> = grammars.iter().rev().collect();
+    let reversed = make_grammars(&["c", "b", "a"]);
+
+    assert_eq!(
+        Grammar::union_refs(&refs).to_string(),
+        Grammar::union(&reversed).to_string()
+    );
+    assert_eq!(
+        Grammar::concat_refs(&refs).to_string(),
+        Grammar::concat(&reversed).to_string()
+    );
+    assert_eq!(
+        Grammar::concat_refs(&[&grammars[0]]).to_string(),
+        Grammar::concat(&grammars[..1]).to_string()
+    );
+}
+
 #[test]
 #[serial]
 fn test_grammar_union_with_stag() {
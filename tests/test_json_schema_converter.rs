@@ -6,7 +6,7 @@ use serde_json::{Value, json};
 use serial_test::serial;
 use test_utils::*;
 use xgrammar::{
-    Grammar,
+    Grammar, JsonSchemaError, JsonSchemaOptions,
     testing::{
         generate_float_range_regex, generate_range_regex, json_schema_to_ebnf,
     },
@@ -419,7 +419,7 @@ fn test_array_schema_error_cases() {
         match result {
             Ok(_) => panic!("expected error for schema"),
             Err(err) => assert!(
-                err.contains(err_message),
+                err.to_string().contains(err_message),
                 "expected error containing '{}', got '{}'",
                 err_message,
                 err
@@ -759,6 +759,149 @@ fn test_complex_restrictions() {
     assert!(is_grammar_accept_string(&grammar, r#"100"#));
 }
 
+/// Test integer multipleOf, enumerated between bounds or matched by a grammar
+#[test]
+#[serial]
+fn test_integer_multiple_of() {
+    let from_schema = |schema: &str| {
+        Grammar::from_json_schema(
+            schema,
+            false,
+            None,
+            None::<(&str, &str)>,
+            true,
+            None,
+            false,
+        )
+    };
+    let accepts = |schema: &str, instances: &[&str]| -> Vec<bool> {
+        let grammar = from_schema(schema).unwrap();
+        instances
+            .iter()
+            .map(|instance| is_grammar_accept_string(&grammar, instance))
+            .collect()
+    };
+
+    let schema = r#"{"type":"integer","multipleOf":3}"#;
+    assert_eq!(
+        accepts(schema, &["0", "3", "-12", "1002", "1", "-7", "1000", "03"]),
+        [true, true, true, true, false, false, false, false]
+    );
+    let schema = r#"{"type":"integer","multipleOf":100,"minimum":0}"#;
+    assert_eq!(
+        accepts(schema, &["0", "100", "2300", "-100", "10", "150"]),
+        [true, true, true, false, false, false]
+    );
+    let schema = r#"{"type":"integer","multipleOf":5,"exclusiveMinimum":0,"maximum":20}"#;
+    assert_eq!(
+        accepts(schema, &["5", "20", "0", "25", "7"]),
+        [true, true, false, false, false]
+    );
+    let schema = r#"{"type":"object","properties":{"qty":{"type":"integer","multipleOf":5}},"required":["qty"]}"#;
+    assert_eq!(
+        accepts(schema, &[r#"{"qty": 15}"#, r#"{"qty": 16}"#]),
+        [true, false]
+    );
+    let schema = r#"{"type":"integer","multipleOf":1}"#;
+    assert_eq!(accepts(schema, &["7"]), [true]);
+
+    for schema in [
+        r#"{"type":"number","multipleOf":5}"#,
+        r#"{"type":"integer","multipleOf":0.5}"#,
+        r#"{"type":"integer","multipleOf":101}"#,
+        r#"{"type":"integer","multipleOf":5,"minimum":100}"#,
+        r#"{"type":"integer","multipleOf":5,"minimum":1,"maximum":4}"#,
+    ] {
+        assert!(from_schema(schema).is_err(), "{schema}");
+    }
+
+    let vocab = ["</s>", "a"];
+    let tokenizer_info = xgrammar::TokenizerInfo::new(
+        &vocab,
+        xgrammar::VocabType::RAW,
+        &None,
+        false,
+    )
+    .unwrap();
+    let mut compiler =
+        xgrammar::GrammarCompiler::new(&tokenizer_info, 1, false, -1).unwrap();
+    let results = compiler.compile_json_schema_batch(
+        &[r#"{"type":"integer","multipleOf":7}"#, r#"{"type":"integer"}"#],
+        false,
+        None,
+        None::<(&str, &str)>,
+        true,
+        None,
+    );
+    let grammar = results[0].as_ref().unwrap().grammar();
+    assert!(is_grammar_accept_string(&grammar, "14"));
+    assert!(!is_grammar_accept_string(&grammar, "15"));
+    assert!(results[1].is_ok());
+}
+
+/// Test exclusiveMinimum and exclusiveMaximum, exact on integers and rejected on numbers
+#[test]
+#[serial]
+fn test_exclusive_bounds() {
+    let from_schema = |schema: &str| {
+        Grammar::from_json_schema(
+            schema,
+            false,
+            None,
+            None::<(&str, &str)>,
+            true,
+            None,
+            false,
+        )
+    };
+    let accepts = |schema: &str, instances: &[&str]| -> Vec<bool> {
+        let grammar = from_schema(schema).unwrap();
+        instances
+            .iter()
+            .map(|instance| is_grammar_accept_string(&grammar, instance))
+            .collect()
+    };
+
+    let schema = r#"{"type":"integer","exclusiveMinimum":0}"#;
+    assert_eq!(
+        accepts(schema, &["1", "42", "0", "-1"]),
+        [true, true, false, false]
+    );
+    let schema = r#"{"type":"integer","exclusiveMinimum":-5,"minimum":1}"#;
+    assert_eq!(
+        accepts(schema, &["1", "6", "0", "-3"]),
+        [true, true, false, false]
+    );
+    let schema = r#"{"type":"integer","exclusiveMaximum":10,"maximum":3}"#;
+    assert_eq!(
+        accepts(schema, &["3", "-5", "4", "9"]),
+        [true, true, false, false]
+    );
+    let schema =
+        r#"{"type":"integer","exclusiveMinimum":-3,"exclusiveMaximum":3}"#;
+    assert_eq!(
+        accepts(schema, &["-2", "0", "2", "-3", "3"]),
+        [true, true, true, false, false]
+    );
+    let schema = r#"{"type":"object","properties":{"pct":{"type":"integer","exclusiveMinimum":0,"maximum":100}},"required":["pct"]}"#;
+    assert_eq!(
+        accepts(schema, &[r#"{"pct": 100}"#, r#"{"pct": 0}"#]),
+        [true, false]
+    );
+    let schema = r#"{"type":"number","minimum":1,"exclusiveMinimum":0}"#;
+    assert_eq!(accepts(schema, &["1", "0"]), [true, false]);
+
+    for schema in [
+        r#"{"type":"number","exclusiveMinimum":0}"#,
+        r#"{"type":"number","minimum":0,"exclusiveMinimum":0}"#,
+        r#"{"type":["number","null"],"exclusiveMaximum":1.5}"#,
+    ] {
+        let err = from_schema(schema).err().expect(schema);
+        assert!(matches!(err, JsonSchemaError::Unsupported(_)), "{err:?}");
+        assert!(err.to_string().contains("exclusiveM"), "{err}");
+    }
+}
+
 /// Test array with only items keyword
 #[test]
 #[serial]
@@ -1047,6 +1190,7 @@ fn test_object_error_handle() {
             false,
         )
         .map(|_| ())
+        .map_err(|err| err.to_string())
     };
 
     let schema = json!({"type": "object", "properties": "not an object"});
@@ -1715,6 +1859,291 @@ fn test_uuid_format() {
     ));
 }
 
+/// Test custom format mappings next to the standard formats
+#[test]
+#[serial]
+fn test_json_schema_with_custom_formats() {
+    let schema = json!({
+        "type": "object",
+        "properties": {
+            "at": {"type": "string", "format": "date-time"},
+            "sku": {"type": "string", "format": "sku"},
+            "note": {"type": "string", "format": "unknown-format"},
+            "tags": {"type": "array", "items": {"type": "string", "format": "sku"}},
+            "kind": {"enum": [{"format": "sku"}]}
+        },
+        "required": ["at", "sku", "note"]
+    })
+    .to_string();
+    let mut options = JsonSchemaOptions {
+        any_whitespace: false,
+        ..JsonSchemaOptions::default()
+    };
+    options.formats.insert("sku".to_string(), "[A-Z]{3}-[0-9]{4}".to_string());
+
+    let grammar =
+        Grammar::from_json_schema_with_options(&schema, &options).unwrap();
+    assert_eq!(
+        Grammar::unknown_json_schema_formats(&schema, &options.formats)
+            .unwrap(),
+        ["unknown-format"]
+    );
+    let accepted = [
+        r#"{"at": "2024-01-02T03:04:05Z", "sku": "ABC-1234", "note": "x"}"#,
+        r#"{"at": "2024-01-02T03:04:05Z", "sku": "ABC-1234", "note": "", "tags": ["XYZ-0000"]}"#,
+        r#"{"at": "2024-01-02T03:04:05Z", "sku": "ABC-1234", "note": "", "kind": {"format":"sku"}}"#,
+    ];
+    for instance in accepted {
+        assert!(is_grammar_accept_string(&grammar, instance), "{}", instance);
+    }
+    let rejected = [
+        r#"{"at": "2024-01-02", "sku": "ABC-1234", "note": "x"}"#,
+        r#"{"at": "2024-01-02T03:04:05Z", "sku": "abc-1234", "note": "x"}"#,
+        r#"{"at": "2024-01-02T03:04:05Z", "sku": "ABC-1234", "note": "", "tags": ["x"]}"#,
+    ];
+    for instance in rejected {
+        assert!(!is_grammar_accept_string(&grammar, instance), "{}", instance);
+    }
+
+    options.formats.insert("sku".to_string(), "[".to_string());
+    assert!(Grammar::from_json_schema_with_options(&schema, &options).is_err());
+    assert!(
+        Grammar::unknown_json_schema_formats("{", &options.formats).is_err()
+    );
+}
+
+#[test]
+#[serial]
+fn test_additional_properties_schema() {
+    let schema = json!({
+        "type": "object",
+        "properties": {
+            "name": {"type": "string"},
+            "counts": {
+                "type": "object",
+                "additionalProperties": {"type": "integer"}
+            }
+        },
+        "required": ["name", "counts"],
+        "additionalProperties": {"type": "boolean"}
+    })
+    .to_string();
+    for strict_mode in [true, false] {
+        let grammar = Grammar::from_json_schema(
+            &schema,
+            false,
+            None,
+            None::<(&str, &str)>,
+            strict_mode,
+            None,
+            false,
+        )
+        .unwrap();
+        let accepted = [
+            r#"{"name": "a", "counts": {}}"#,
+            r#"{"name": "a", "counts": {"x": 1, "y": -2}, "flag": true}"#,
+        ];
+        for instance in accepted {
+            assert!(
+                is_grammar_accept_string(&grammar, instance),
+                "strict_mode={strict_mode}, {instance}"
+            );
+        }
+        let rejected = [
+            r#"{"name": "a", "counts": {"x": "1"}}"#,
+            r#"{"name": "a", "counts": {"x": 1.5}}"#,
+            r#"{"name": "a", "counts": {}, "flag": 1}"#,
+        ];
+        for instance in rejected {
+            assert!(
+                !is_grammar_accept_string(&grammar, instance),
+                "strict_mode={strict_mode}, {instance}"
+            );
+        }
+    }
+
+    for keyword in ["patternProperties", "propertyNames"] {
+        let constraint = if keyword == "propertyNames" {
+            json!({"pattern": "^[a-z]+$"})
+        } else {
+            json!({"^[a-z]+$": {"type": "string"}})
+        };
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "map": {
+                    "type": "object",
+                    keyword: constraint,
+                    "additionalProperties": {"type": "integer"}
+                }
+            }
+        })
+        .to_string();
+        let Err(err) = Grammar::from_json_schema(
+            &schema,
+            false,
+            None,
+            None::<(&str, &str)>,
+            true,
+            None,
+            false,
+        ) else {
+            panic!(
+                "{} with a typed additionalProperties was accepted",
+                keyword
+            );
+        };
+        let JsonSchemaError::Unsupported(err) = err else {
+            panic!("expected an unsupported schema, got {:?}", err);
+        };
+        assert!(err.contains("/properties/map"), "{}", err);
+        assert!(err.contains(keyword), "{}", err);
+    }
+}
+
+#[test]
+#[serial]
+fn test_force_defaults() {
+    let schema = json!({
+        "type": "object",
+        "properties": {
+            "model": {"type": "string"},
+            "temperature": {"type": "number", "default": 0.5},
+            "mode": {"enum": ["fast", "slow"], "default": "fast"}
+        },
+        "required": ["model", "temperature"]
+    })
+    .to_string();
+    let build = |schema: &str, force_defaults: bool| {
+        let options = JsonSchemaOptions {
+            any_whitespace: false,
+            force_defaults,
+            ..JsonSchemaOptions::default()
+        };
+        Grammar::from_json_schema_with_options(schema, &options)
+    };
+
+    let grammar = build(&schema, true).unwrap();
+    let accepted = [
+        r#"{"model": "a", "temperature": 0.5}"#,
+        r#"{"model": "a", "temperature": 0.5, "mode": "fast"}"#,
+    ];
+    for instance in accepted {
+        assert!(is_grammar_accept_string(&grammar, instance), "{}", instance);
+    }
+    let rejected = [
+        r#"{"model": "a", "temperature": 0.7}"#,
+        r#"{"model": "a", "temperature": 0.5, "mode": "slow"}"#,
+    ];
+    for instance in rejected {
+        assert!(!is_grammar_accept_string(&grammar, instance), "{}", instance);
+    }
+
+    let grammar = build(&schema, false).unwrap();
+    for instance in rejected {
+        assert!(is_grammar_accept_string(&grammar, instance), "{}", instance);
+    }
+
+    let contradicting =
+        json!({"enum": ["fast", "slow"], "default": "medium"}).to_string();
+    assert!(build(&contradicting, false).is_ok());
+    assert!(build(&contradicting, true).is_err());
+}
+
+#[test]
+#[serial]
+fn test_required_non_strict() {
+    let schema = json!({
+        "type": "object",
+        "properties": {"name": {"type": "string"}, "age": {"type": "integer"}},
+        "required": ["name"]
+    })
+    .to_string();
+    for strict_mode in [true, false] {
+        let grammar = Grammar::from_json_schema(
+            &schema,
+            false,
+            None,
+            None::<(&str, &str)>,
+            strict_mode,
+            None,
+            false,
+        )
+        .unwrap();
+        for instance in [r#"{"name": "a"}"#, r#"{"name": "a", "age": 1}"#] {
+            assert!(
+                is_grammar_accept_string(&grammar, instance),
+                "{}",
+                instance
+            );
+        }
+        for instance in [r#"{}"#, r#"{"age": 1}"#, r#"{"extra": 1}"#] {
+            assert!(
+                !is_grammar_accept_string(&grammar, instance),
+                "{}",
+                instance
+            );
+        }
+    }
+}
+
+#[test]
+#[serial]
+fn test_require_all_properties() {
+    let schema = json!({
+        "type": "object",
+        "properties": {
+            "name": {"type": "string"},
+            "tags": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {"key": {"type": "string"}, "value": {"type": "integer"}},
+                    "required": ["value"]
+                }
+            }
+        }
+    })
+    .to_string();
+    let build = |require_all_properties: bool, strict_mode: bool| {
+        let options = JsonSchemaOptions {
+            any_whitespace: false,
+            strict_mode,
+            require_all_properties,
+            ..JsonSchemaOptions::default()
+        };
+        Grammar::from_json_schema_with_options(&schema, &options).unwrap()
+    };
+
+    let complete = r#"{"name": "a", "tags": [{"key": "k", "value": 1}]}"#;
+    let partial = [
+        r#"{}"#,
+        r#"{"name": "a"}"#,
+        r#"{"name": "a", "tags": [{"value": 1}]}"#,
+    ];
+    for strict_mode in [true, false] {
+        let grammar = build(true, strict_mode);
+        assert!(is_grammar_accept_string(&grammar, complete));
+        for instance in partial {
+            assert!(
+                !is_grammar_accept_string(&grammar, instance),
+                "{}",
+                instance
+            );
+        }
+    }
+    assert!(is_grammar_accept_string(
+        &build(true, false),
+        r#"{"name": "a", "tags": [], "extra": 1}"#
+    ));
+
+    let grammar = build(false, true);
+    assert!(is_grammar_accept_string(&grammar, complete));
+    for instance in partial {
+        assert!(is_grammar_accept_string(&grammar, instance), "{}", instance);
+    }
+}
+
 /// Test duration format validation
 #[test]
 #[serial]
@@ -1931,3 +2360,204 @@ root ::= string
         );
     }
 }
+
+#[test]
+#[serial]
+fn test_json_schema_with_external_refs() {
+    let schema = json!({
+        "type": "object",
+        "properties": {
+            "id": {"$ref": "common.json#/$defs/Id"},
+            "owner": {"$ref": "person.json"}
+        },
+        "required": ["id", "owner"]
+    })
+    .to_string();
+    let mut refs = std::collections::HashMap::new();
+    refs.insert(
+        "common.json".to_string(),
+        json!({"$defs": {"Id": {"type": "integer"}}}).to_string(),
+    );
+    refs.insert(
+        "person.json".to_string(),
+        json!({
+            "type": "object",
+            "properties": {
+                "name": {"type": "string"},
+                "id": {"$ref": "common.json#/$defs/Id"},
+                "friend": {"anyOf": [{"$ref": "#"}, {"type": "null"}]}
+            },
+            "required": ["name"]
+        })
+        .to_string(),
+    );
+
+    let mut options = JsonSchemaOptions {
+        any_whitespace: false,
+        refs,
+        ..JsonSchemaOptions::default()
+    };
+    let grammar =
+        Grammar::from_json_schema_with_options(&schema, &options).unwrap();
+    let accepted = [
+        r#"{"id": 1, "owner": {"name": "a"}}"#,
+        r#"{"id": 1, "owner": {"name": "a", "id": 2, "friend": {"name": "b", "friend": null}}}"#,
+    ];
+    for instance in accepted {
+        assert!(is_grammar_accept_string(&grammar, instance), "{}", instance);
+    }
+    let rejected = [
+        r#"{"id": "1", "owner": {"name": "a"}}"#,
+        r#"{"id": 1, "owner": {"id": 2}}"#,
+    ];
+    for instance in rejected {
+        assert!(!is_grammar_accept_string(&grammar, instance), "{}", instance);
+    }
+
+    options.refs.remove("common.json");
+    let err = Grammar::from_json_schema_with_options(&schema, &options)
+        .err()
+        .unwrap();
+    let JsonSchemaError::Invalid(err) = err else {
+        panic!("expected an unresolved $ref, got {:?}", err);
+    };
+    assert!(err.contains("common.json#/$defs/Id"), "{}", err);
+}
+
+#[cfg(feature = "schemars")]
+#[test]
+#[serial]
+fn test_from_schemars() {
+    #[allow(dead_code)]
+    #[derive(schemars::JsonSchema)]
+    struct Person {
+        name: String,
+        age: u32,
+        nickname: Option<String>,
+    }
+
+    let grammar = Grammar::from_schemars::<Person>(
+        false,
+        None,
+        None::<(&str, &str)>,
+        true,
+        None,
+        false,
+    )
+    .unwrap();
+    assert!(is_grammar_accept_string(
+        &grammar,
+        r#"{"name": "a", "age": 3, "nickname": null}"#
+    ));
+    assert!(!is_grammar_accept_string(&grammar, r#"{"name": "a", "age": -3}"#));
+    assert!(!is_grammar_accept_string(&grammar, r#"{"age": 3}"#));
+}
+
+#[test]
+#[serial]
+fn test_trailing_whitespace() {
+    let schema = json!({
+        "type": "object",
+        "properties": {"name": {"type": "string"}},
+        "required": ["name"]
+    })
+    .to_string();
+    let options = |trailing_whitespace, max_whitespace_cnt| JsonSchemaOptions {
+        trailing_whitespace,
+        max_whitespace_cnt,
+        ..JsonSchemaOptions::default()
+    };
+    let from_schema = |trailing_whitespace, max_whitespace_cnt| {
+        Grammar::from_json_schema_with_options(
+            &schema,
+            &options(trailing_whitespace, max_whitespace_cnt),
+        )
+        .unwrap()
+    };
+    xgrammar::testing::assert_accepts(
+        &from_schema(false, None),
+        &[r#"{"name": "John"}"#],
+        &[r#"{"name": "John"} "#, "{\"name\": \"John\"}\n"],
+    );
+    xgrammar::testing::assert_accepts(
+        &from_schema(true, None),
+        &[
+            r#"{"name": "John"}"#,
+            r#"{"name": "John"} "#,
+            "{\"name\": \"John\"} \n\t\n",
+            "{\"name\": \"John\"}\r\n",
+        ],
+        &[r#"{"name": "John"} x"#, r#" {"name": "John"}"#],
+    );
+    xgrammar::testing::assert_accepts(
+        &from_schema(true, Some(2)),
+        &[r#"{"name": "John"}  "#],
+        &[r#"{"name": "John"}   "#],
+    );
+    for max_whitespace_cnt in [0, -1] {
+        let result = Grammar::from_json_schema_with_options(
+            &schema,
+            &options(true, Some(max_whitespace_cnt)),
+        );
+        let Err(JsonSchemaError::Invalid(err)) = result else {
+            panic!("expected an invalid max_whitespace_cnt");
+        };
+        assert!(err.contains("max_whitespace_cnt"), "{}", err);
+    }
+
+    xgrammar::testing::assert_accepts(
+        &Grammar::builtin_json_grammar_with_trailing_whitespace(false),
+        &[r#"{"a": [1, 2]}"#],
+        &["{\"a\": [1, 2]}\n"],
+    );
+    xgrammar::testing::assert_accepts(
+        &Grammar::builtin_json_grammar_with_trailing_whitespace(true),
+        &[r#"{"a": [1, 2]}"#, "{\"a\": [1, 2]}\r\n", "[] "],
+        &["[] x"],
+    );
+}
+
+#[test]
+#[serial]
+fn test_json_schema_ebnf_roundtrip() {
+    let schema = json!({
+        "type": "object",
+        "properties": {
+            "name": {"type": "string"},
+            "count": {"type": "integer", "multipleOf": 5, "minimum": 0}
+        },
+        "required": ["name", "count"]
+    })
+    .to_string();
+    let options = JsonSchemaOptions {
+        any_whitespace: false,
+        ..JsonSchemaOptions::default()
+    };
+    let ebnf = Grammar::from_json_schema_with_options(&schema, &options)
+        .unwrap()
+        .to_string_ebnf();
+    assert!(ebnf.contains("root ::="), "{}", ebnf);
+    let recompiled = Grammar::from_ebnf(&ebnf, "root").unwrap();
+    assert_eq!(recompiled.to_string_ebnf(), ebnf);
+    xgrammar::testing::assert_accepts(
+        &recompiled,
+        &[r#"{"name": "a", "count": 10}"#],
+        &[r#"{"name": "a", "count": 7}"#],
+    );
+
+    let patched = Grammar::from_ebnf(
+        &ebnf.replace(
+            "root ::=",
+            "root ::= \"[\" root_object \"]\"\nroot_object ::=",
+        ),
+        "root",
+    )
+    .unwrap();
+    xgrammar::testing::assert_accepts(
+        &patched,
+        &[r#"[{"name": "a", "count": 10}]"#],
+        &[r#"{"name": "a", "count": 10}"#],
+    );
+
+    assert!(Grammar::from_json_schema_with_options("{", &options).is_err());
+}
@@ -1,5 +1,7 @@
+mod test_utils;
+
 use serial_test::serial;
-use xgrammar::Grammar;
+use xgrammar::{Grammar, JsonSchemaError};
 
 #[test]
 #[serial]
@@ -21,9 +23,263 @@ fn test_from_json_schema_returns_err_instead_of_aborting() {
     .err()
     .expect("expected from_json_schema to return Err for an invalid schema");
 
-    // Message comes from the underlying C++ exception (xgrammar::LogFatalError).
+    assert!(
+        matches!(err, JsonSchemaError::ContradictorySchema { .. }),
+        "{err:?}"
+    );
+    let err = err.to_string();
     assert!(
         err.contains("minItems") || err.contains("prefixItems"),
         "unexpected error message: {err}"
     );
 }
+
+#[test]
+#[serial]
+fn test_json_schema_contains() {
+    let from_schema = |schema: &str| {
+        Grammar::from_json_schema(
+            schema,
+            false,
+            None,
+            None::<(&str, &str)>,
+            true,
+            None,
+            false,
+        )
+    };
+    let unsupported = |schema: &str| match from_schema(schema).err() {
+        Some(JsonSchemaError::Unsupported(message)) => message,
+        other => panic!("expected an unsupported schema, got {other:?}"),
+    };
+    let accepts = |schema: &str, instance: &str| {
+        let grammar = from_schema(schema).unwrap();
+        test_utils::is_grammar_accept_string(&grammar, instance)
+    };
+
+    let schema = r#"{"type":"array","items":{"type":"integer"},"contains":{},"minContains":2,"maxContains":3}"#;
+    assert!(!accepts(schema, "[1]"));
+    assert!(accepts(schema, "[1, 2]"));
+    assert!(accepts(schema, "[1, 2, 3]"));
+    assert!(!accepts(schema, "[1, 2, 3, 4]"));
+
+    let schema =
+        r#"{"type":"array","items":{"type":"integer"},"contains":true}"#;
+    assert!(!accepts(schema, "[]"));
+    assert!(accepts(schema, "[1]"));
+
+    let schema = r#"{"type":"array","items":{"type":"integer"},"contains":{"const":1},"minContains":0}"#;
+    assert!(accepts(schema, "[]"));
+    assert!(accepts(schema, "[2]"));
+
+    let schema = r#"{"type":"object","properties":{"contains":{"type":"array","contains":{"const":1}}}}"#;
+    let err = unsupported(schema);
+    assert!(err.contains("\"/properties/contains/contains\""), "{err}");
+    assert!(err.contains("at least 1"), "{err}");
+
+    let schema = r#"{"type":"array","contains":{"type":"string"},"minContains":0,"maxContains":2}"#;
+    let err = unsupported(schema);
+    assert!(err.contains("between 0 and 2"), "{err}");
+
+    let tokenizer_info = xgrammar::TokenizerInfo::new(
+        &["a"],
+        xgrammar::VocabType::RAW,
+        &None,
+        false,
+    )
+    .unwrap();
+    let mut compiler =
+        xgrammar::GrammarCompiler::new(&tokenizer_info, 1, false, -1).unwrap();
+    let schema = r#"{"type":"array","contains":{"type":"string"}}"#;
+    assert!(matches!(
+        compiler.compile_json_schema(
+            schema,
+            true,
+            None,
+            None::<(&str, &str)>,
+            true,
+            None
+        ),
+        Err(JsonSchemaError::Unsupported(_))
+    ));
+}
+
+#[test]
+#[serial]
+fn test_contradictory_schema() {
+    let contradiction = |schema: &str| {
+        match Grammar::check_json_schema_contradictions(schema) {
+            Err(JsonSchemaError::ContradictorySchema {
+                detail,
+            }) => detail,
+            other => panic!("expected a contradiction, got {other:?}"),
+        }
+    };
+    let detail = contradiction(
+        r#"{"type":"array","prefixItems":[{}],"items":false,"minItems":2}"#,
+    );
+    assert!(detail.contains("prefixItems"), "{detail}");
+    assert!(detail.ends_with("2 > 1 (at \"\")"), "{detail}");
+    let detail = contradiction(
+        r#"{"properties":{"a":{"type":"string","minLength":5,"maxLength":3}}}"#,
+    );
+    assert!(detail.contains("minLength is greater than maxLength: 5 > 3"));
+    assert!(detail.contains("\"/properties/a\""), "{detail}");
+    contradiction(r#"{"type":"number","minimum":1.5,"maximum":1}"#);
+    contradiction(r#"{"type":"object","minProperties":3,"maxProperties":2}"#);
+    contradiction(r#"{"anyOf":[{"type":"array","minItems":2,"maxItems":1}]}"#);
+
+    for schema in [
+        r#"{"type":"array","prefixItems":[{},{}],"items":false,"minItems":2}"#,
+        r#"{"type":"string","minLength":3,"maxLength":3}"#,
+        r#"{"type":"integer","minimum":-1,"maximum":1}"#,
+    ] {
+        assert_eq!(Grammar::check_json_schema_contradictions(schema), Ok(()));
+    }
+    assert!(matches!(
+        Grammar::check_json_schema_contradictions("{"),
+        Err(JsonSchemaError::InvalidJson(_))
+    ));
+
+    let err = Grammar::from_json_schema(
+        r#"{"type":"string","minLength":5,"maxLength":3}"#,
+        true,
+        None,
+        None::<(&str, &str)>,
+        true,
+        None,
+        false,
+    )
+    .err()
+    .unwrap();
+    assert!(
+        matches!(err, JsonSchemaError::ContradictorySchema { .. }),
+        "{err:?}"
+    );
+    assert!(
+        err.to_string().starts_with("Contradictory JSON schema: "),
+        "{err}"
+    );
+}
+
+#[test]
+#[serial]
+fn test_validate_schema_keywords() {
+    let errors = Grammar::validate_schema_keywords(
+        r#"{
+            "type": "object",
+            "porperties": {"a": {"type": "string"}},
+            "properties": {
+                "b": {"type": "array", "itmes": {"type": "integer"}},
+                "maxLenght": {"type": "string", "x-order": 1}
+            },
+            "$defs": {"c": {"anyOf": [{"typ": "null"}]}}
+        }"#,
+    )
+    .unwrap_err();
+    assert_eq!(
+        errors,
+        vec![
+            "Unknown JSON schema keyword \"porperties\" (at \"\")",
+            "Unknown JSON schema keyword \"itmes\" (at \"/properties/b\")",
+            "Unknown JSON schema keyword \"typ\" (at \"/$defs/c/anyOf/0\")",
+        ]
+    );
+
+    assert_eq!(
+        Grammar::validate_schema_keywords(
+            r#"{"$schema": "https://json-schema.org/draft/2020-12/schema",
+                "title": "T", "type": "object",
+                "properties": {"a": {"type": "integer", "minimum": 0}},
+                "required": ["a"], "additionalProperties": false}"#
+        ),
+        Ok(())
+    );
+    assert_eq!(Grammar::validate_schema_keywords("{").unwrap_err().len(), 1);
+}
+
+#[test]
+#[serial]
+fn test_invalid_schema_regex() {
+    let invalid_regex =
+        |schema: &str| match Grammar::check_json_schema_regexes(schema) {
+            Err(JsonSchemaError::InvalidSchemaRegex {
+                pattern,
+                detail,
+            }) => (pattern, detail),
+            other => panic!("expected an invalid regex, got {other:?}"),
+        };
+    let (pattern, detail) = invalid_regex(
+        r#"{"type":"object","patternProperties":{"^a(":{"type":"string"}}}"#,
+    );
+    assert_eq!(pattern, "^a(");
+    assert!(detail.starts_with("Regex parsing error"), "{detail}");
+    assert!(detail.ends_with("(at \"/patternProperties\")"), "{detail}");
+    let (pattern, detail) = invalid_regex(
+        r#"{"properties":{"m":{"type":"object","propertyNames":{"pattern":"[a"}}}}"#,
+    );
+    assert_eq!(pattern, "[a");
+    assert!(
+        detail.ends_with("(at \"/properties/m/propertyNames/pattern\")"),
+        "{detail}"
+    );
+
+    assert_eq!(
+        Grammar::check_json_schema_regexes(
+            r#"{"type":"object","patternProperties":{"^x-[a-z]+$":{}},
+                "propertyNames":{"pattern":"^[a-z-]+$"}}"#
+        ),
+        Ok(())
+    );
+    assert!(matches!(
+        Grammar::check_json_schema_regexes("{"),
+        Err(JsonSchemaError::InvalidJson(_))
+    ));
+
+    let err = Grammar::from_json_schema(
+        r#"{"type":"object","propertyNames":{"pattern":"(a"}}"#,
+        true,
+        None,
+        None::<(&str, &str)>,
+        true,
+        None,
+        false,
+    )
+    .err()
+    .unwrap();
+    assert!(
+        err.to_string()
+            .starts_with("Invalid regex \"(a\" in the JSON schema: "),
+        "{err}"
+    );
+    let JsonSchemaError::InvalidSchemaRegex {
+        pattern,
+        detail,
+    } = err
+    else {
+        panic!("expected an invalid regex, got {err:?}");
+    };
+    assert_eq!(pattern, "(a");
+    assert!(detail.contains("/propertyNames/pattern"), "{detail}");
+
+    let tokenizer_info = xgrammar::TokenizerInfo::new(
+        &["a"],
+        xgrammar::VocabType::RAW,
+        &None,
+        false,
+    )
+    .unwrap();
+    let mut compiler =
+        xgrammar::GrammarCompiler::new(&tokenizer_info, 1, false, -1).unwrap();
+    assert!(matches!(
+        compiler.compile_json_schema(
+            r#"{"type":"string","pattern":"[a"}"#,
+            true,
+            None,
+            None::<(&str, &str)>,
+            true,
+            None
+        ),
+        Err(JsonSchemaError::InvalidSchemaRegex { pattern, .. }) if pattern == "[a"
+    ));
+}
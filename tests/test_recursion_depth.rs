@@ -24,6 +24,17 @@ fn test_recursion_depth_context() {
     assert_eq!(xgrammar::get_max_recursion_depth(), 10_000);
 }
 
+#[test]
+#[serial]
+fn test_swap_recursion_depth() {
+    let default_depth = xgrammar::get_max_recursion_depth();
+    assert_eq!(xgrammar::swap_max_recursion_depth(1000), default_depth);
+    assert_eq!(xgrammar::get_max_recursion_depth(), 1000);
+    assert_eq!(xgrammar::swap_max_recursion_depth(2000), 1000);
+    assert_eq!(xgrammar::swap_max_recursion_depth(default_depth), 2000);
+    assert_eq!(xgrammar::get_max_recursion_depth(), default_depth);
+}
+
 #[test]
 #[serial]
 fn test_recursion_exceed_does_not_crash() {
@@ -2,7 +2,7 @@ mod test_utils;
 
 use serial_test::serial;
 use test_utils::*;
-use xgrammar::{Grammar, testing};
+use xgrammar::{Grammar, RegexFlavor, RegexMatchMode, testing};
 #[cfg(feature = "hf")]
 use xgrammar::{
     GrammarCompiler, GrammarMatcher, TokenizerInfo, allocate_token_bitmask,
@@ -553,3 +553,85 @@ fn test_empty() {
         assert!(!is_grammar_accept_string(&grammar, "a"), "regex={regex}");
     }
 }
+
+#[test]
+#[serial]
+fn test_regex_flavor() {
+    let accepted = [
+        (RegexFlavor::Pcre, r"(?P<year>\d{4})-(?<month>\d\d)", "2024-05"),
+        (RegexFlavor::Pcre, r"(?'word'\w+)(?#comment)!", "ab_1!"),
+        (RegexFlavor::RustRegex, r"\A[a-c]+\z", "abc"),
+        (RegexFlavor::RustRegex, r"\x{48}\x{1F601}", "H😁"),
+        (RegexFlavor::Pcre, r"a\Z", "a"),
+        (RegexFlavor::Pcre, r"(?:a|b)(?<first_name>c)", "bc"),
+    ];
+    for (flavor, regex, input) in accepted {
+        let grammar =
+            Grammar::from_regex_with_flavor(regex, flavor, false).unwrap();
+        assert!(
+            is_grammar_accept_string(&grammar, input),
+            "regex={regex}, input={input}"
+        );
+    }
+
+    let grammar =
+        Grammar::from_regex_with_flavor(r"\d+\s\w", RegexFlavor::Ecma, false)
+            .unwrap();
+    assert_eq!(
+        grammar.to_string(),
+        Grammar::from_regex(r"\d+\s\w", false).unwrap().to_string()
+    );
+
+    let rejected = [
+        (RegexFlavor::Pcre, r"(?i)abc", "`(?i)`"),
+        (RegexFlavor::RustRegex, r"(?s:a.)", "`(?s:`"),
+        (RegexFlavor::Pcre, r"(?>ab)c", "`(?>`"),
+        (RegexFlavor::Pcre, r"a++b", "`++`"),
+        (RegexFlavor::Pcre, r"\Qa.b\E", "`\\Q`"),
+        (RegexFlavor::Pcre, r"a\Ab", "`\\A`"),
+        (RegexFlavor::Pcre, r"(?P<x>a)(?P=x)", "`(?P=`"),
+    ];
+    for (flavor, regex, construct) in rejected {
+        let Err(err) = Grammar::from_regex_with_flavor(regex, flavor, false)
+        else {
+            panic!("regex={regex} should be rejected");
+        };
+        assert!(
+            err.contains(&format!("Unsupported regex construct {}", construct)),
+            "regex={regex}, err={err}"
+        );
+    }
+}
+
+#[test]
+#[serial]
+fn test_regex_match_mode() {
+    let regex = r"<tool>\d+";
+    let full = Grammar::from_regex_with_match_mode(
+        regex,
+        RegexMatchMode::FullMatch,
+        false,
+    )
+    .unwrap();
+    assert_eq!(
+        full.to_string(),
+        Grammar::from_regex(regex, false).unwrap().to_string()
+    );
+    testing::assert_accepts(
+        &full,
+        &["<tool>12"],
+        &["<tool>12 rest", "x<tool>1"],
+    );
+
+    let prefix = Grammar::from_regex_with_match_mode(
+        regex,
+        RegexMatchMode::Prefix,
+        false,
+    )
+    .unwrap();
+    testing::assert_accepts(
+        &prefix,
+        &["<tool>12", "<tool>12 rest", "<tool>1\n{\"a\": 1}"],
+        &["", "<tool>", "x<tool>1", "<tool>x"],
+    );
+}
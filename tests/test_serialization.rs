@@ -5,7 +5,8 @@ use test_utils::is_grammar_accept_string;
 #[cfg(feature = "hf")]
 use test_utils::{create_bitmask_dltensor, make_hf_tokenizer_info};
 use xgrammar::{
-    CompiledGrammar, Grammar, GrammarCompiler, TokenizerInfo, VocabType,
+    CompiledGrammar, DeserializeError, Grammar, GrammarCompiler, TokenizerInfo,
+    VocabType,
 };
 #[cfg(feature = "hf")]
 use xgrammar::{GrammarMatcher, allocate_token_bitmask, testing};
@@ -96,6 +97,33 @@ fn test_serialize_tokenizer_info_roundtrip() {
     }
 }
 
+#[test]
+#[serial]
+fn test_serialize_tokenizer_info_bytes_roundtrip() {
+    let orig = construct_tokenizer_info();
+    let bytes = orig.serialize_bytes();
+    assert!(bytes.len() < orig.serialize_json().len());
+    let recovered =
+        TokenizerInfo::deserialize_bytes(&bytes).expect("deserialize bytes");
+    assert_eq!(orig.serialize_json(), recovered.serialize_json());
+    assert_eq!(recovered.decoded_vocab(), orig.decoded_vocab());
+
+    assert!(matches!(
+        TokenizerInfo::deserialize_bytes(&[]),
+        Err(DeserializeError::Format(_))
+    ));
+    assert!(matches!(
+        TokenizerInfo::deserialize_bytes(&bytes[..bytes.len() - 1]),
+        Err(DeserializeError::Format(_))
+    ));
+    let mut bad_magic = bytes.clone();
+    bad_magic[0] ^= 0xff;
+    assert!(matches!(
+        TokenizerInfo::deserialize_bytes(&bad_magic),
+        Err(DeserializeError::Format(_))
+    ));
+}
+
 #[test]
 #[serial]
 fn test_serialize_compiled_grammar_roundtrip() {
@@ -106,6 +134,28 @@ fn test_serialize_compiled_grammar_roundtrip() {
     assert_eq!(orig_cg.serialize_json(), recovered.serialize_json());
 }
 
+#[test]
+#[serial]
+fn test_serialize_json_pretty_roundtrip() {
+    let grammar = construct_grammar();
+    let pretty = grammar.serialize_json_pretty();
+    assert!(pretty.contains("\n  \""));
+    let recovered = Grammar::deserialize_json(&pretty).unwrap();
+    assert_eq!(grammar.serialize_json(), recovered.serialize_json());
+
+    let tok = construct_tokenizer_info();
+    let pretty = tok.serialize_json_pretty();
+    assert!(pretty.contains('\n'));
+    let recovered = TokenizerInfo::deserialize_json(&pretty).unwrap();
+    assert_eq!(tok.serialize_json(), recovered.serialize_json());
+
+    let (cg, tok) = construct_compiled_grammar();
+    let pretty = cg.serialize_json_pretty();
+    assert!(pretty.contains('\n'));
+    let recovered = CompiledGrammar::deserialize_json(&pretty, &tok).unwrap();
+    assert_eq!(cg.serialize_json(), recovered.serialize_json());
+}
+
 #[test]
 #[serial]
 fn test_serialize_compiled_grammar_functional() {
@@ -140,6 +190,35 @@ fn test_grammar_deserialize_errors() {
     assert!(Grammar::deserialize_json(&v.to_string()).is_err());
 }
 
+#[test]
+#[serial]
+fn test_get_serialized_version() {
+    let grammar = construct_grammar();
+    let serialized = grammar.serialize_json();
+    assert_eq!(
+        xgrammar::get_serialized_version(&serialized).unwrap(),
+        xgrammar::get_serialization_version()
+    );
+
+    let mut v: serde_json::Value = serde_json::from_str(&serialized).unwrap();
+    v["__VERSION__"] = serde_json::json!("v1");
+    let stale = v.to_string();
+    assert_eq!(xgrammar::get_serialized_version(&stale).unwrap(), "v1");
+    assert!(matches!(
+        Grammar::deserialize_json(&stale),
+        Err(DeserializeError::VersionMismatch(_))
+    ));
+
+    assert!(matches!(
+        xgrammar::get_serialized_version("not json"),
+        Err(DeserializeError::InvalidJson(_))
+    ));
+    assert!(matches!(
+        xgrammar::get_serialized_version(r#"{"rules": []}"#),
+        Err(DeserializeError::Format(_))
+    ));
+}
+
 #[test]
 #[serial]
 fn test_compiled_grammar_deserialize_errors() {
@@ -320,3 +399,49 @@ fn test_serialize_grammar_utf8() {
     assert!(is_grammar_accept_string(&grammar, test_str));
     assert!(is_grammar_accept_string(&deserialized, test_str));
 }
+
+#[test]
+#[serial]
+fn test_vocab_type_string_roundtrip() {
+    for vocab_type in
+        [VocabType::RAW, VocabType::BYTE_FALLBACK, VocabType::BYTE_LEVEL]
+    {
+        let name = vocab_type.to_string();
+        assert_eq!(name.parse::<VocabType>().unwrap(), vocab_type);
+        assert_eq!(
+            name.to_uppercase().parse::<VocabType>().unwrap(),
+            vocab_type
+        );
+    }
+    assert_eq!(VocabType::BYTE_LEVEL.to_string(), "byte_level");
+    assert!("byte-level".parse::<VocabType>().is_err());
+}
+
+#[test]
+#[serial]
+fn test_vocab_type_all() {
+    assert_eq!(
+        VocabType::all(),
+        &[VocabType::RAW, VocabType::BYTE_FALLBACK, VocabType::BYTE_LEVEL]
+    );
+    for (value, vocab_type) in VocabType::ALL.iter().enumerate() {
+        assert_eq!(vocab_type.clone() as i32, value as i32);
+        assert_eq!(vocab_type.as_str(), vocab_type.to_string());
+        assert_eq!(
+            vocab_type.as_str().parse::<VocabType>().unwrap(),
+            *vocab_type
+        );
+    }
+    assert_eq!(VocabType::BYTE_FALLBACK.as_str(), "byte_fallback");
+}
+
+#[cfg(feature = "serde")]
+#[test]
+#[serial]
+fn test_vocab_type_serde() {
+    let vocab_type: VocabType =
+        serde_json::from_str(r#""Byte_Fallback""#).unwrap();
+    assert_eq!(vocab_type, VocabType::BYTE_FALLBACK);
+    assert_eq!(serde_json::to_string(&VocabType::RAW).unwrap(), r#""raw""#);
+    assert!(serde_json::from_str::<VocabType>(r#""bpe""#).is_err());
+}
@@ -62,3 +62,165 @@ fn test_empty_tag_dispatch_accepts_any() {
     assert!(m.accept_string("any string", false));
     assert!(m.is_terminated());
 }
+
+#[test]
+#[serial]
+fn test_structural_tag_item_try_new() {
+    let schema = r#"{"type":"object","properties":{"a":{"type":"integer"}}}"#;
+    let item = StructuralTagItem::try_new("<f>", schema, "</f>").unwrap();
+    assert_eq!(item.begin, "<f>");
+    assert_eq!(item.schema, schema);
+    assert_eq!(item.end, "</f>");
+    assert!(StructuralTagItem::try_new("<f>", "true", "</f>").is_ok());
+
+    let err =
+        StructuralTagItem::try_new("<f>", r#"{"type":"object",}"#, "</f>")
+            .unwrap_err();
+    assert!(err.contains("\"<f>\""), "{}", err);
+    assert!(err.contains("line 1 column 18"), "{}", err);
+
+    let err =
+        StructuralTagItem::try_new("<f>", r#""object""#, "</f>").unwrap_err();
+    assert!(err.contains("must be a JSON object or boolean"), "{}", err);
+
+    let item = StructuralTagItem::new("<f>", "{", "</f>");
+    assert!(item.validate().is_err());
+}
+
+#[test]
+#[serial]
+fn test_compile_structural_tag_json() {
+    let structural_tag = r#"{
+        "type": "structural_tag",
+        "format": {
+            "type": "sequence",
+            "elements": [
+                {"type": "const_string", "value": "<answer>"},
+                {"type": "json_schema", "json_schema": {"type": "integer"}},
+                {"type": "const_string", "value": "</answer>"}
+            ]
+        }
+    }"#;
+    let tok = xgrammar::TokenizerInfo::new(
+        &[""],
+        xgrammar::VocabType::RAW,
+        &None,
+        false,
+    )
+    .unwrap();
+    let mut compiler =
+        xgrammar::GrammarCompiler::new(&tok, 1, false, -1).unwrap();
+    let compiled =
+        compiler.compile_structural_tag_json(structural_tag).unwrap();
+    let mut matcher =
+        xgrammar::GrammarMatcher::new(&compiled, None, true, -1).unwrap();
+    assert!(matcher.accept_string("<answer>42</answer>", false));
+    assert!(matcher.is_terminated());
+    matcher.reset();
+    assert!(!matcher.accept_string("<answer>x", false));
+
+    assert!(compiler.compile_structural_tag_json("{").is_err());
+    assert!(
+        compiler
+            .compile_structural_tag_json(r#"{"type": "structural_tag"}"#)
+            .is_err()
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+#[serial]
+fn test_structural_tag_item_serde() {
+    let config = r#"[
+        {"begin": "<function=f>", "schema": "{\"type\": \"integer\"}", "end": "</function>"}
+    ]"#;
+    let tags: Vec<StructuralTagItem> = serde_json::from_str(config).unwrap();
+    assert_eq!(tags.len(), 1);
+    assert_eq!(tags[0].begin, "<function=f>");
+    assert_eq!(tags[0].schema, r#"{"type": "integer"}"#);
+    assert_eq!(tags[0].end, "</function>");
+
+    let tok = xgrammar::TokenizerInfo::new(
+        &[""],
+        xgrammar::VocabType::RAW,
+        &None,
+        false,
+    )
+    .unwrap();
+    let mut compiler =
+        xgrammar::GrammarCompiler::new(&tok, 1, false, -1).unwrap();
+    let compiled_grammar =
+        compiler.compile_structural_tag(&tags, &["<function="]).unwrap();
+    assert!(test_utils::is_grammar_accept_string(
+        &compiled_grammar.grammar(),
+        "<function=f>42</function>"
+    ));
+
+    let json = serde_json::to_string(&tags[0]).unwrap();
+    let round_trip: StructuralTagItem = serde_json::from_str(&json).unwrap();
+    assert_eq!(round_trip.schema, tags[0].schema);
+    assert!(
+        serde_json::from_str::<StructuralTagItem>(r#"{"begin": "<a>"}"#)
+            .is_err()
+    );
+}
+
+#[test]
+#[serial]
+fn test_compile_structural_tag_auto_triggers() {
+    let schema = r#"{"type":"integer"}"#;
+    let tok = xgrammar::TokenizerInfo::new(
+        &[""],
+        xgrammar::VocabType::RAW,
+        &None,
+        false,
+    )
+    .unwrap();
+    let mut compiler =
+        xgrammar::GrammarCompiler::new(&tok, 1, false, -1).unwrap();
+
+    let tags = [
+        StructuralTagItem::new("<function=f>", schema, "</function>"),
+        StructuralTagItem::new("<function=g>", schema, "</function>"),
+        StructuralTagItem::new("<function=g>", schema, "</g>"),
+    ];
+    let grammar =
+        compiler.compile_structural_tag_auto_triggers(&tags).unwrap().grammar();
+    for accepted in [
+        "text",
+        "a <function=f>1</function> b",
+        "<function=g>2</g><function=g>3</function>",
+    ] {
+        assert!(
+            test_utils::is_grammar_accept_string(&grammar, accepted),
+            "{}",
+            accepted
+        );
+    }
+    assert!(!test_utils::is_grammar_accept_string(
+        &grammar,
+        "<function=f>x</function>"
+    ));
+
+    let tags = [
+        StructuralTagItem::new("<call>", schema, "</call>"),
+        StructuralTagItem::new("<call><urgent>", schema, "</call>"),
+    ];
+    let grammar =
+        compiler.compile_structural_tag_auto_triggers(&tags).unwrap().grammar();
+    for accepted in ["<call>1</call>", "x<call><urgent>2</call>"] {
+        assert!(
+            test_utils::is_grammar_accept_string(&grammar, accepted),
+            "{}",
+            accepted
+        );
+    }
+    assert!(
+        compiler
+            .compile_structural_tag(&tags, &["<call>", "<call><urgent>"])
+            .is_err()
+    );
+
+    let tags = [StructuralTagItem::new("", schema, "</call>")];
+    assert!(compiler.compile_structural_tag_auto_triggers(&tags).is_err());
+}
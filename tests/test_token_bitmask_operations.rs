@@ -5,8 +5,12 @@ mod test_utils;
 use serial_test::serial;
 use test_utils::*;
 use xgrammar::{
-    allocate_token_bitmask, apply_token_bitmask_inplace_cpu, get_bitmask_shape,
-    reset_token_bitmask, testing,
+    DLDataTypeCode, DLDeviceType, DLTensorBuilder, Grammar, TokenizerInfo,
+    VocabType, accepted_tokens_iter, allocate_token_bitmask,
+    allocate_token_bitmask_aligned, apply_token_bitmask_inplace_cpu,
+    apply_token_bitmask_with_fill_value, bitmask_dltensor, dlpack,
+    get_bitmask_shape, is_token_accepted, reject_all_token_bitmask,
+    reset_token_bitmask, testing, try_allocate_token_bitmask,
 };
 
 fn pack_bool_masks_to_bitmask_data(
@@ -42,6 +46,49 @@ fn test_allocate_reset_token_bitmask() {
     bitmask.fill(0);
     reset_token_bitmask(&mut bitmask);
     assert!(bitmask.iter().all(|&x| x == -1i32));
+
+    reject_all_token_bitmask(&mut bitmask);
+    assert!(bitmask.iter().all(|&x| x == 0));
+    assert!(!is_token_accepted(&bitmask, 0, 0, vocab_size));
+    assert_eq!(accepted_tokens_iter(&bitmask, 9, vocab_size).next(), None);
+}
+
+#[test]
+#[serial]
+fn test_reject_all_token_bitmask() {
+    let batch_size = 2usize;
+    let vocab_size = 70usize;
+    let mut bitmask = allocate_token_bitmask(batch_size, vocab_size);
+    reject_all_token_bitmask(&mut bitmask);
+    for row in 0..batch_size {
+        for token_id in 0..vocab_size {
+            assert!(
+                !is_token_accepted(&bitmask, row, token_id, vocab_size),
+                "row={row}, token_id={token_id}"
+            );
+        }
+        assert_eq!(accepted_tokens_iter(&bitmask, row, vocab_size).count(), 0);
+    }
+
+    let (bitmask_tensor, _bshape, _bstrides) =
+        create_bitmask_dltensor(&mut bitmask, batch_size, vocab_size);
+    let mut logits: Vec<f32> =
+        (1..=batch_size * vocab_size).map(|x| x as f32).collect();
+    let (mut logits_tensor, _lshape, _lstrides) = create_f32_2d_dltensor(
+        &mut logits,
+        batch_size,
+        vocab_size,
+        vocab_size as i64,
+        1,
+    );
+    apply_token_bitmask_inplace_cpu(
+        &mut logits_tensor,
+        &bitmask_tensor,
+        Some(vocab_size as i32),
+        None,
+    )
+    .unwrap();
+    assert!(logits.iter().all(|&logit| logit == f32::NEG_INFINITY));
 }
 
 #[test]
@@ -83,6 +130,202 @@ fn test_get_masked_tokens_from_bitmask() {
     }
 }
 
+#[test]
+#[serial]
+fn test_masked_tokens_all_rows() {
+    let vocab_size = 32_011usize;
+    let masks: Vec<Vec<bool>> = (0..3)
+        .map(|row| (0..vocab_size).map(|i| i % (row + 2) == 0).collect())
+        .collect();
+    let mut bitmask_data = pack_bool_masks_to_bitmask_data(&masks, vocab_size);
+    let (tensor, _shape, _strides) =
+        create_bitmask_dltensor(&mut bitmask_data, masks.len(), vocab_size);
+
+    let rows =
+        testing::masked_tokens_all_rows(&tensor, vocab_size as i32, 3).unwrap();
+    assert_eq!(rows.len(), 3);
+    for (index, row) in rows.iter().enumerate() {
+        let expected = testing::get_masked_tokens_from_bitmask(
+            &tensor,
+            vocab_size as i32,
+            index as i32,
+        );
+        assert_eq!(&row[..], &*expected, "index={index}");
+    }
+
+    assert!(
+        testing::masked_tokens_all_rows(&tensor, vocab_size as i32, 2).is_err()
+    );
+    assert!(
+        testing::masked_tokens_all_rows(&tensor, vocab_size as i32 + 64, 3)
+            .is_err()
+    );
+}
+
+#[test]
+#[serial]
+fn test_bitmask_dltensor() {
+    let grammar = Grammar::from_ebnf(r#"root ::= "a" | "ab""#, "root").unwrap();
+    let vocab = ["</s>", "a", "b", "ab", "ba"];
+    let tokenizer_info =
+        TokenizerInfo::new(&vocab, VocabType::RAW, &None, false).unwrap();
+    let mut matcher =
+        matcher_from_grammar_with_tokenizer(&grammar, &tokenizer_info);
+
+    let mut bitmask = allocate_token_bitmask(1, vocab.len());
+    {
+        let mut tensor =
+            bitmask_dltensor(&mut bitmask, 1, vocab.len()).unwrap();
+        assert!(matcher.fill_next_token_bitmask(&mut tensor, 0, false));
+        assert_eq!(
+            &*testing::get_masked_tokens_from_bitmask(
+                &tensor,
+                vocab.len() as i32,
+                0
+            ),
+            &[0, 2, 4]
+        );
+    }
+    assert_eq!(bitmask[0] & 0b11111, 0b01010);
+
+    assert!(bitmask_dltensor(&mut bitmask, 2, vocab.len()).is_err());
+    assert!(bitmask_dltensor(&mut bitmask, 1, 33).is_err());
+}
+
+#[test]
+#[serial]
+fn test_dltensor_builder() {
+    let dtype = dlpack::bitmask_dtype();
+    assert_eq!(
+        (dtype.code, dtype.bits, dtype.lanes),
+        (DLDataTypeCode::kDLInt as u8, 32, 1)
+    );
+    let cuda = dlpack::cuda_device(3);
+    assert!(matches!(cuda.device_type, DLDeviceType::kDLCUDA));
+    assert_eq!(cuda.device_id, 3);
+    assert!(matches!(dlpack::cpu_device().device_type, DLDeviceType::kDLCPU));
+
+    let grammar = Grammar::from_ebnf(r#"root ::= "a" | "ab""#, "root").unwrap();
+    let vocab = ["</s>", "a", "b", "ab", "ba"];
+    let tokenizer_info =
+        TokenizerInfo::new(&vocab, VocabType::RAW, &None, false).unwrap();
+    let mut matcher =
+        matcher_from_grammar_with_tokenizer(&grammar, &tokenizer_info);
+    let mut bitmask = allocate_token_bitmask(2, vocab.len());
+    let data = bitmask.as_mut_ptr() as *mut xgrammar::c_void;
+    let mut tensor = unsafe {
+        DLTensorBuilder::bitmask(data, 2, vocab.len()).build().unwrap()
+    };
+    assert!(matcher.fill_next_token_bitmask(&mut tensor, 1, false));
+    assert_eq!(
+        &*testing::get_masked_tokens_from_bitmask(
+            &tensor,
+            vocab.len() as i32,
+            1
+        ),
+        &[0, 2, 4]
+    );
+    drop(tensor);
+    assert_eq!(bitmask[0], -1);
+    assert_eq!(bitmask[1] & 0b11111, 0b01010);
+
+    let builder = DLTensorBuilder::new(data, &[2, 1]).strides(&[1]);
+    assert!(unsafe { builder.build() }.is_err());
+}
+
+#[test]
+#[serial]
+fn test_try_fill_next_token_bitmask() {
+    let grammar = Grammar::from_ebnf(r#"root ::= "a" | "ab""#, "root").unwrap();
+    let vocab = ["</s>", "a", "b", "ab", "ba"];
+    let tokenizer_info = TokenizerInfo::new(
+        &vocab,
+        VocabType::RAW,
+        &Some(vec![0].into()),
+        false,
+    )
+    .unwrap();
+    let mut matcher = matcher_from_grammar_with_tokenizer_and_rollback(
+        &grammar,
+        &tokenizer_info,
+        -1,
+    );
+    let mut bitmask = allocate_token_bitmask(2, vocab.len());
+    let data = bitmask.as_mut_ptr() as *mut xgrammar::c_void;
+    let build = |builder: DLTensorBuilder| unsafe { builder.build().unwrap() };
+
+    let mut tensor = build(DLTensorBuilder::bitmask(data, 2, vocab.len()));
+    assert_eq!(
+        matcher.try_fill_next_token_bitmask(&mut tensor, 1, false),
+        Ok(true)
+    );
+    assert_eq!(
+        &*testing::get_masked_tokens_from_bitmask(
+            &tensor,
+            vocab.len() as i32,
+            1
+        ),
+        &[0, 2, 4]
+    );
+    let err =
+        matcher.try_fill_next_token_bitmask(&mut tensor, 2, false).unwrap_err();
+    assert!(err.contains("index 2"), "{}", err);
+
+    let invalid = [
+        (
+            DLTensorBuilder::bitmask(data, 2, vocab.len())
+                .device(dlpack::cuda_device(0)),
+            "CPU",
+        ),
+        (
+            DLTensorBuilder::bitmask(data, 2, vocab.len()).dtype(
+                xgrammar::DLDataType {
+                    code: DLDataTypeCode::kDLFloat as u8,
+                    bits: 32,
+                    lanes: 1,
+                },
+            ),
+            "int32",
+        ),
+        (DLTensorBuilder::new(data, &[2]), "shape"),
+        (DLTensorBuilder::new(data, &[1, 1, 1]), "dimensions"),
+        (DLTensorBuilder::new(data, &[1, 1]).strides(&[2, 1]), "contiguous"),
+        (
+            DLTensorBuilder::bitmask(data, 1, vocab.len()).byte_offset(4),
+            "byte offset",
+        ),
+    ];
+    for (builder, expected) in invalid {
+        let err = matcher
+            .try_fill_next_token_bitmask(&mut build(builder), 0, false)
+            .unwrap_err();
+        assert!(err.contains(expected), "{}", err);
+    }
+
+    assert!(matcher.accept_string("a", false));
+    assert!(matcher.accept_token(0));
+    let mut tensor = build(DLTensorBuilder::bitmask(data, 2, vocab.len()));
+    assert!(
+        matcher.try_fill_next_token_bitmask(&mut tensor, 0, false).is_err()
+    );
+
+    // The banned tokens are cleared by a shim that checks the bitmask itself.
+    let mut matcher = matcher_from_grammar_with_tokenizer_and_rollback(
+        &grammar,
+        &tokenizer_info,
+        -1,
+    );
+    matcher.set_banned_tokens(&[3]).unwrap();
+    let mut tensor =
+        build(DLTensorBuilder::bitmask(data, 1, vocab.len()).byte_offset(4));
+    let panic = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        matcher.fill_next_token_bitmask(&mut tensor, 0, false);
+    }))
+    .unwrap_err();
+    let message = panic.downcast_ref::<String>().unwrap();
+    assert!(message.contains("byte offset"), "{message}");
+}
+
 #[test]
 #[serial]
 fn test_is_single_token_bitmask() {
@@ -332,6 +575,201 @@ fn test_apply_token_bitmask_inplace_cpu_vocab_size() {
     }
 }
 
+#[test]
+#[serial]
+fn test_is_token_accepted() {
+    let vocab_size = 70usize;
+    let bool_masks: Vec<Vec<bool>> = (0..3)
+        .map(|row| {
+            (0..vocab_size).map(|i| (i + row) % 4 == 0 || i == 31).collect()
+        })
+        .collect();
+    let bitmask = pack_bool_masks_to_bitmask_data(&bool_masks, vocab_size);
+    assert!(bitmask[0] < 0);
+    for (row, mask) in bool_masks.iter().enumerate() {
+        for (token_id, &allowed) in mask.iter().enumerate() {
+            assert_eq!(
+                is_token_accepted(&bitmask, row, token_id, vocab_size),
+                allowed,
+                "row={row}, token_id={token_id}"
+            );
+        }
+    }
+}
+
+#[test]
+#[serial]
+fn test_accepted_tokens_iter() {
+    let vocab_size = 70usize;
+    let bool_masks: Vec<Vec<bool>> = (0..3)
+        .map(|row| {
+            (0..vocab_size).map(|i| (i + row) % 5 == 0 || i == 31).collect()
+        })
+        .collect();
+    let mut bitmask = pack_bool_masks_to_bitmask_data(&bool_masks, vocab_size);
+    // Bits past the vocabulary are ignored.
+    bitmask[2] |= -1 << 6;
+    for (row, mask) in bool_masks.iter().enumerate() {
+        let expected: Vec<i32> =
+            (0..vocab_size as i32).filter(|&i| mask[i as usize]).collect();
+        let actual: Vec<i32> =
+            accepted_tokens_iter(&bitmask, row, vocab_size).collect();
+        assert_eq!(actual, expected, "row={row}");
+        assert_eq!(
+            accepted_tokens_iter(&bitmask, row, vocab_size).count(),
+            expected.len()
+        );
+        let mut iter = accepted_tokens_iter(&bitmask, row, vocab_size);
+        iter.nth(2);
+        assert_eq!(iter.count(), expected.len() - 3);
+    }
+
+    let full = allocate_token_bitmask(1, vocab_size);
+    assert_eq!(accepted_tokens_iter(&full, 0, vocab_size).count(), vocab_size);
+    let empty = vec![0i32; 3];
+    assert_eq!(accepted_tokens_iter(&empty, 0, vocab_size).next(), None);
+}
+
+#[test]
+#[serial]
+fn test_try_allocate_token_bitmask() {
+    let bitmask = try_allocate_token_bitmask(2, 100).unwrap();
+    assert_eq!(&*bitmask, &*allocate_token_bitmask(2, 100));
+    assert_eq!(bitmask.len(), 8);
+
+    assert_eq!(get_bitmask_shape(1, usize::MAX), (1, usize::MAX / 32 + 1));
+    let error = try_allocate_token_bitmask(usize::MAX, 64).unwrap_err();
+    assert!(error.contains("too large"), "{error}");
+    assert!(try_allocate_token_bitmask(1 << 40, 1 << 20).is_err());
+    assert!(try_allocate_token_bitmask(1, usize::MAX).is_err());
+
+    let panic = std::panic::catch_unwind(|| {
+        allocate_token_bitmask(usize::MAX, 64);
+    })
+    .unwrap_err();
+    let message = panic.downcast_ref::<String>().unwrap();
+    assert!(message.contains("too large"), "{message}");
+}
+
+#[test]
+#[serial]
+fn test_allocate_token_bitmask_aligned() {
+    let vocab: Vec<String> = (0..40).map(|i| format!("t{i}")).collect();
+    let tokenizer_info =
+        TokenizerInfo::new(&vocab, VocabType::RAW, &None, false).unwrap();
+    let grammar =
+        Grammar::from_ebnf(r#"root ::= "t1" | "t35""#, "root").unwrap();
+    let mut matcher =
+        matcher_from_grammar_with_tokenizer(&grammar, &tokenizer_info);
+
+    let mut bitmask = allocate_token_bitmask_aligned(3, vocab.len(), 64);
+    assert_eq!(bitmask.row_len(), 2);
+    assert_eq!(bitmask.row_stride(), 16);
+    assert_eq!(bitmask.as_slice().len(), 48);
+    assert_eq!(bitmask.as_slice().as_ptr() as usize % 64, 0);
+    assert!(bitmask.as_slice().iter().all(|&x| x == -1));
+
+    assert!(matcher.fill_next_token_bitmask(
+        &mut bitmask.row_dltensor(1),
+        0,
+        false
+    ));
+    assert_eq!(bitmask.row(1), matcher.next_token_bitmask().as_slice());
+    assert_eq!(bitmask.row(0), [-1, -1]);
+    assert_eq!(bitmask.row(2), [-1, -1]);
+    assert!(bitmask.as_slice()[18..].iter().all(|&x| x == -1));
+
+    let mut logits = vec![0.0f32; 3 * vocab.len()];
+    for (i, row_logits) in logits.chunks_mut(vocab.len()).enumerate() {
+        apply_token_bitmask_with_fill_value(
+            row_logits,
+            bitmask.row(i),
+            Some(vocab.len()),
+            f32::NEG_INFINITY,
+        )
+        .unwrap();
+    }
+    for (i, &logit) in logits.iter().enumerate() {
+        let (row, token) = (i / vocab.len(), i % vocab.len());
+        let allowed = row != 1
+            || is_token_accepted(bitmask.row(1), 0, token, vocab.len());
+        assert_eq!(logit == 0.0, allowed, "row={row}, token={token}");
+    }
+
+    assert_eq!(logits[vocab.len()], f32::NEG_INFINITY);
+
+    bitmask.row_mut(1).fill(0);
+    bitmask.reset();
+    assert!(bitmask.as_slice().iter().all(|&x| x == -1));
+    assert_eq!(
+        allocate_token_bitmask_aligned(2, 64, 4).as_slice(),
+        allocate_token_bitmask(2, 64).as_ref()
+    );
+}
+
+#[test]
+#[serial]
+fn test_apply_token_bitmask_with_fill_value() {
+    let vocab_size = 40usize;
+    let bool_mask: Vec<bool> = (0..vocab_size).map(|i| i % 3 == 0).collect();
+    let bitmask = pack_bool_masks_to_bitmask_data(
+        std::slice::from_ref(&bool_mask),
+        vocab_size,
+    );
+
+    let mut logits = vec![1.0f32; vocab_size];
+    let num_allowed =
+        apply_token_bitmask_with_fill_value(&mut logits, &bitmask, None, -1e9)
+            .unwrap();
+    assert_eq!(num_allowed, 14);
+    for i in 0..vocab_size {
+        let expected = if bool_mask[i] {
+            1.0
+        } else {
+            -1e9
+        };
+        assert_eq!(logits[i], expected, "i={i}");
+    }
+
+    let mut logits = vec![1.0f32; vocab_size];
+    let num_allowed = apply_token_bitmask_with_fill_value(
+        &mut logits,
+        &bitmask,
+        Some(20),
+        f32::NEG_INFINITY,
+    )
+    .unwrap();
+    assert_eq!(num_allowed, 7);
+    assert_eq!(logits[1], f32::NEG_INFINITY);
+    assert_eq!(logits[25], 1.0);
+
+    // Every token masked: a dead end.
+    let mut logits = vec![1.0f32; vocab_size];
+    let num_allowed = apply_token_bitmask_with_fill_value(
+        &mut logits,
+        &[0, 0],
+        Some(vocab_size),
+        -1e9,
+    )
+    .unwrap();
+    assert_eq!(num_allowed, 0);
+    assert!(logits.iter().all(|&logit| logit == -1e9));
+
+    assert!(
+        apply_token_bitmask_with_fill_value(
+            &mut logits,
+            &bitmask,
+            Some(41),
+            0.0
+        )
+        .is_err()
+    );
+    assert!(
+        apply_token_bitmask_with_fill_value(&mut logits, &[-1], Some(33), 0.0)
+            .is_err()
+    );
+}
+
 #[test]
 #[serial]
 fn test_apply_token_bitmask_inplace_cpu_indices_mismatch() {
@@ -396,7 +396,8 @@ fn test_dump_metadata_load() {
         let loaded = xgrammar::TokenizerInfo::from_vocab_and_metadata_bytes(
             ordered.iter().map(|s| s.as_bytes()),
             expected_metadata,
-        );
+        )
+        .unwrap();
         assert_eq!(loaded.decoded_vocab(), tokenizer_info.decoded_vocab());
     }
 }
@@ -410,13 +411,218 @@ fn test_special_token_detection() {
     let tokenizer_info = xgrammar::TokenizerInfo::from_vocab_and_metadata_bytes(
         vocab_dict.iter().map(|s| s.as_bytes()),
         "{\"vocab_type\":1,\"vocab_size\":8,\"add_prefix_space\":true,\"stop_token_ids\":[2]}",
-    );
+    )
+    .unwrap();
     let expected: std::collections::HashSet<i32> = [0].into_iter().collect();
     let got: std::collections::HashSet<i32> =
         tokenizer_info.special_token_ids().into_iter().collect();
     assert_eq!(got, expected);
 }
 
+#[test]
+fn test_from_vocab_and_metadata_errors() {
+    let vocab = ["<s>", "</s>", "a"];
+    let load = |metadata: &str| {
+        xgrammar::TokenizerInfo::from_vocab_and_metadata_bytes(
+            vocab.iter().map(|s| s.as_bytes()),
+            metadata,
+        )
+    };
+    let valid = load(
+        r#"{"vocab_type":0,"vocab_size":3,"add_prefix_space":false,"stop_token_ids":[1]}"#,
+    )
+    .unwrap();
+    assert_eq!(valid.stop_token_ids_slice(), [1]);
+
+    for metadata in [
+        "not json",
+        r#"{"vocab_type":7,"vocab_size":3,"add_prefix_space":false,"stop_token_ids":[1]}"#,
+        r#"{"vocab_size":3,"add_prefix_space":false,"stop_token_ids":[1]}"#,
+    ] {
+        assert!(load(metadata).is_err(), "{metadata}");
+    }
+}
+
+#[test]
+#[serial]
+fn test_new_validated_vocab_type() {
+    let byte_level =
+        ["<s>", "</s>", "a", "\u{0120}a", "\u{0120}b", "b\u{010A}"];
+    let byte_fallback = ["<s>", "</s>", "<0x0A>", "<0x1B>", "\u{2581}a", "b"];
+    let raw = ["<s>", "</s>", "a", " a", "b\n"];
+
+    let err = xgrammar::TokenizerInfo::new_validated(
+        &byte_level,
+        xgrammar::VocabType::RAW,
+        None,
+        &None,
+        false,
+    )
+    .err()
+    .unwrap();
+    assert!(err.contains("looks like byte_level"), "{}", err);
+
+    let err = xgrammar::TokenizerInfo::new_validated(
+        &byte_fallback,
+        xgrammar::VocabType::BYTE_LEVEL,
+        None,
+        &None,
+        false,
+    )
+    .err()
+    .unwrap();
+    assert!(err.contains("looks like byte_fallback"), "{}", err);
+
+    for (vocab, vocab_type) in [
+        (&byte_level[..], xgrammar::VocabType::BYTE_LEVEL),
+        (&byte_fallback[..], xgrammar::VocabType::BYTE_FALLBACK),
+        (&raw[..], xgrammar::VocabType::RAW),
+        (&raw[..], xgrammar::VocabType::BYTE_LEVEL),
+    ] {
+        let tokenizer_info = xgrammar::TokenizerInfo::new_validated(
+            vocab,
+            vocab_type.clone(),
+            Some(8),
+            &None,
+            false,
+        )
+        .unwrap();
+        assert_eq!(tokenizer_info.vocab_type(), vocab_type);
+        assert_eq!(tokenizer_info.vocab_size(), 8);
+    }
+}
+
+#[test]
+#[serial]
+fn test_from_tokenizer_file() {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/data/tiny_byte_level_tokenizer.json");
+    let tokenizer_info =
+        xgrammar::TokenizerInfo::from_tokenizer_file(&path, None, None)
+            .unwrap();
+    assert_eq!(tokenizer_info.vocab_type(), xgrammar::VocabType::BYTE_LEVEL);
+    assert_eq!(tokenizer_info.vocab_size(), 11);
+    assert_eq!(&*tokenizer_info.stop_token_ids(), &[0]);
+    let decoded = tokenizer_info.decoded_vocab();
+    assert_eq!(&*decoded[4], b" a");
+    assert_eq!(&*decoded[10], b"\n");
+
+    let tokenizer = tokenizers::Tokenizer::from_file(&path).unwrap();
+    let from_hf = xgrammar::TokenizerInfo::from_huggingface(
+        &tokenizer,
+        Some(16),
+        Some(&[7]),
+    )
+    .unwrap();
+    let from_file = xgrammar::TokenizerInfo::from_tokenizer_file(
+        &path,
+        Some(16),
+        Some(&[7]),
+    )
+    .unwrap();
+    assert_eq!(from_file.dump_metadata(), from_hf.dump_metadata());
+    assert_eq!(from_file.decoded_vocab(), from_hf.decoded_vocab());
+
+    let err = xgrammar::TokenizerInfo::from_tokenizer_file(
+        std::path::Path::new("does/not/exist/tokenizer.json"),
+        None,
+        None,
+    )
+    .err()
+    .unwrap();
+    assert!(err.contains("does/not/exist/tokenizer.json"), "{}", err);
+}
+
+#[test]
+#[serial]
+fn test_from_huggingface_with_special_tokens() {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/data/tiny_byte_level_tokenizer.json");
+    let mut tokenizer = tokenizers::Tokenizer::from_file(&path).unwrap();
+    tokenizer.add_special_tokens(&[
+        tokenizers::AddedToken::from("[TOOL]", true),
+        tokenizers::AddedToken::from("<|eot_id|>", true),
+    ]);
+    tokenizer.add_tokens(&[tokenizers::AddedToken::from("<x>", false)]);
+    let vocab = tokenizer.get_vocab(true);
+    let (tool, eot, x) = (vocab["[TOOL]"], vocab["<|eot_id|>"], vocab["<x>"]);
+
+    let plain =
+        xgrammar::TokenizerInfo::from_huggingface(&tokenizer, None, None)
+            .unwrap();
+    assert!(!plain.special_token_ids().contains(&(tool as i32)));
+
+    let tokenizer_info =
+        xgrammar::TokenizerInfo::from_huggingface_with_special_tokens(
+            &tokenizer, None, None,
+        )
+        .unwrap();
+    assert_eq!(tokenizer_info.vocab_size(), plain.vocab_size());
+    assert_eq!(&*tokenizer_info.special_token_ids(), &[tool as i32]);
+    assert_eq!(&*tokenizer_info.stop_token_ids(), &[0, eot as i32]);
+    let decoded = tokenizer_info.decoded_vocab();
+    assert!(decoded[tool as usize].is_empty());
+    assert_eq!(&*decoded[eot as usize], b"<|eot_id|>");
+    assert_eq!(&*decoded[x as usize], b"<x>");
+
+    let tokenizer_info =
+        xgrammar::TokenizerInfo::from_huggingface_with_special_tokens(
+            &tokenizer,
+            None,
+            Some(&[tool as i32]),
+        )
+        .unwrap();
+    assert_eq!(&*tokenizer_info.stop_token_ids(), &[tool as i32]);
+    let mut special = tokenizer_info.special_token_ids().to_vec();
+    special.sort_unstable();
+    assert_eq!(special, [0, eot as i32]);
+}
+
+#[test]
+#[serial]
+fn test_from_huggingface_chat_stop_tokens() {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/data/tiny_byte_level_tokenizer.json");
+    let mut tokenizer = tokenizers::Tokenizer::from_file(&path).unwrap();
+    tokenizer.add_special_tokens(&[
+        tokenizers::AddedToken::from("<|im_start|>", true),
+        tokenizers::AddedToken::from("<|im_end|>", true),
+    ]);
+    tokenizer.add_tokens(&[tokenizers::AddedToken::from("<|call|>", false)]);
+    let vocab = tokenizer.get_vocab(true);
+    let (im_start, im_end) = (vocab["<|im_start|>"], vocab["<|im_end|>"]);
+
+    let tokenizer_info =
+        xgrammar::TokenizerInfo::from_huggingface(&tokenizer, None, None)
+            .unwrap();
+    assert_eq!(&*tokenizer_info.stop_token_ids(), &[0, im_end as i32]);
+    assert_eq!(tokenizer_info.vocab_size(), vocab.len());
+
+    let without =
+        xgrammar::TokenizerInfo::from_huggingface_with_chat_stop_tokens(
+            &tokenizer, None, None, false,
+        )
+        .unwrap();
+    assert_eq!(&*without.stop_token_ids(), &[0]);
+    let overridden =
+        xgrammar::TokenizerInfo::from_huggingface_with_chat_stop_tokens(
+            &tokenizer,
+            None,
+            Some(&[im_start as i32]),
+            true,
+        )
+        .unwrap();
+    assert_eq!(&*overridden.stop_token_ids(), &[im_start as i32]);
+
+    let tokenizer_info =
+        xgrammar::TokenizerInfo::from_huggingface_with_special_tokens(
+            &tokenizer, None, None,
+        )
+        .unwrap();
+    assert_eq!(&*tokenizer_info.stop_token_ids(), &[0, im_end as i32]);
+    assert_eq!(&*tokenizer_info.special_token_ids(), &[im_start as i32]);
+}
+
 // ---------- 9. test_customize_stop_token_ids ----------
 
 #[test]
@@ -108,6 +108,21 @@ pub fn make_hf_tokenizer_info(model_id: &str) -> TokenizerInfo {
     TokenizerInfo::from_huggingface(&tokenizer, None, None).unwrap()
 }
 
+/// Create a RAW TokenizerInfo from a vocabulary, with the stop tokens detected from it
+#[allow(dead_code)]
+pub fn raw_tokenizer_info<T: AsRef<str>>(vocab: &[T]) -> TokenizerInfo {
+    TokenizerInfo::new(vocab, VocabType::RAW, &None, false).unwrap()
+}
+
+/// Create a single-threaded GrammarCompiler with an unlimited cache
+#[allow(dead_code)]
+pub fn compiler_from_tokenizer(
+    tokenizer_info: &TokenizerInfo,
+    cache_enabled: bool,
+) -> GrammarCompiler {
+    GrammarCompiler::new(tokenizer_info, 1, cache_enabled, -1).unwrap()
+}
+
 /// Create a GrammarMatcher from a Grammar with minimal tokenizer info
 pub fn matcher_from_grammar(grammar: &Grammar) -> GrammarMatcher {
     let empty_vocab: Vec<&str> = vec![];